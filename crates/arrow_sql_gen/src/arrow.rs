@@ -13,18 +13,73 @@ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 See the License for the specific language governing permissions and
 limitations under the License.
 */
+use std::sync::Arc;
+
 use arrow::{
     array::{
-        ArrayBuilder, BinaryBuilder, BooleanBuilder, Date32Builder, Date64Builder,
-        Decimal128Builder, FixedSizeBinaryBuilder, Float32Builder, Float64Builder, Int16Builder,
-        Int32Builder, Int64Builder, Int8Builder, LargeBinaryBuilder, LargeStringBuilder,
-        ListBuilder, NullBuilder, StringBuilder, StructBuilder, Time64NanosecondBuilder,
+        make_builder, ArrayBuilder, ArrayRef, AsArray, BinaryBuilder, BooleanBuilder,
+        Date32Builder, Date64Builder, Decimal128Builder, Decimal256Builder, FixedSizeBinaryBuilder,
+        Float32Builder, Float64Builder, GenericStringArray, Int16Builder, Int32Builder,
+        Int64Builder, Int8Builder, IntervalMonthDayNanoBuilder, LargeBinaryBuilder,
+        LargeStringBuilder, ListBuilder, MapBuilder, NullBuilder, OffsetSizeTrait, StringBuilder,
+        StringDictionaryBuilder, StructBuilder, Time64MicrosecondBuilder, Time64NanosecondBuilder,
         TimestampMicrosecondBuilder, TimestampMillisecondBuilder, TimestampNanosecondBuilder,
         TimestampSecondBuilder, UInt16Builder, UInt32Builder, UInt64Builder, UInt8Builder,
     },
-    datatypes::{DataType, TimeUnit},
+    datatypes::{DataType, FieldRef, Int32Type, IntervalUnit, TimeUnit},
 };
 
+/// How to read the empty strings and NULLs of text columns, for sources that use both for a missing value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyStringHandling {
+    /// Keep empty strings and NULLs as they are.
+    #[default]
+    Preserve,
+    /// Read empty strings as NULL.
+    EmptyAsNull,
+    /// Read NULLs as empty strings.
+    NullAsEmpty,
+}
+
+impl EmptyStringHandling {
+    /// Parses the value of an `empty_string_handling` option, one of `preserve`, `empty_as_null` or `null_as_empty`
+    /// in any case. `None` if it is none of them.
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "preserve" => Some(Self::Preserve),
+            "empty_as_null" => Some(Self::EmptyAsNull),
+            "null_as_empty" => Some(Self::NullAsEmpty),
+            _ => None,
+        }
+    }
+}
+
+/// Applies `handling` to a `Utf8` or `LargeUtf8` `array`. Arrays of other types are returned as they are.
+#[must_use]
+pub fn coerce_empty_strings(array: ArrayRef, handling: EmptyStringHandling) -> ArrayRef {
+    match (handling, array.data_type()) {
+        (EmptyStringHandling::Preserve, _) => array,
+        (_, DataType::Utf8) => Arc::new(coerce_strings(array.as_string::<i32>(), handling)),
+        (_, DataType::LargeUtf8) => Arc::new(coerce_strings(array.as_string::<i64>(), handling)),
+        _ => array,
+    }
+}
+
+fn coerce_strings<O: OffsetSizeTrait>(
+    strings: &GenericStringArray<O>,
+    handling: EmptyStringHandling,
+) -> GenericStringArray<O> {
+    strings
+        .iter()
+        .map(|value| match handling {
+            EmptyStringHandling::Preserve => value,
+            EmptyStringHandling::EmptyAsNull => value.filter(|value| !value.is_empty()),
+            EmptyStringHandling::NullAsEmpty => Some(value.unwrap_or_default()),
+        })
+        .collect()
+}
+
 pub fn map_data_type_to_array_builder_optional(
     data_type: Option<&DataType>,
 ) -> Option<Box<dyn ArrayBuilder>> {
@@ -56,6 +111,11 @@ pub fn map_data_type_to_array_builder(data_type: &DataType) -> Box<dyn ArrayBuil
                 .with_precision_and_scale(*precision, *scale)
                 .unwrap_or_default(),
         ),
+        DataType::Decimal256(precision, scale) => Box::new(
+            Decimal256Builder::new()
+                .with_precision_and_scale(*precision, *scale)
+                .unwrap_or_default(),
+        ),
         DataType::Timestamp(time_unit, time_zone) => match time_unit {
             TimeUnit::Microsecond => {
                 Box::new(TimestampMicrosecondBuilder::new().with_timezone_opt(time_zone.clone()))
@@ -72,8 +132,11 @@ pub fn map_data_type_to_array_builder(data_type: &DataType) -> Box<dyn ArrayBuil
         },
         DataType::Date32 => Box::new(Date32Builder::new()),
         DataType::Date64 => Box::new(Date64Builder::new()),
-        // For time format, always use nanosecond
         DataType::Time64(TimeUnit::Nanosecond) => Box::new(Time64NanosecondBuilder::new()),
+        DataType::Time64(TimeUnit::Microsecond) => Box::new(Time64MicrosecondBuilder::new()),
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            Box::new(IntervalMonthDayNanoBuilder::new())
+        }
         DataType::FixedSizeBinary(s) => Box::new(FixedSizeBinaryBuilder::new(*s)),
         // We can't recursively call map_data_type_to_array_builder here because downcasting will not work if the
         // values_builder is boxed.
@@ -86,8 +149,34 @@ pub fn map_data_type_to_array_builder(data_type: &DataType) -> Box<dyn ArrayBuil
             DataType::Float64 => Box::new(ListBuilder::new(Float64Builder::new())),
             DataType::Utf8 => Box::new(ListBuilder::new(StringBuilder::new())),
             DataType::Boolean => Box::new(ListBuilder::new(BooleanBuilder::new())),
+            DataType::Binary => Box::new(ListBuilder::new(BinaryBuilder::new())),
+            DataType::Date32 => Box::new(ListBuilder::new(Date32Builder::new())),
+            DataType::Timestamp(TimeUnit::Millisecond, time_zone) => Box::new(ListBuilder::new(
+                TimestampMillisecondBuilder::new().with_timezone_opt(time_zone.clone()),
+            )),
+            DataType::Decimal128(precision, scale) => Box::new(ListBuilder::new(
+                Decimal128Builder::new()
+                    .with_precision_and_scale(*precision, *scale)
+                    .unwrap_or_default(),
+            )),
+            DataType::Map(entries, _) if is_string_map(entries) => Box::new(ListBuilder::new(
+                MapBuilder::new(None, StringBuilder::new(), StringBuilder::new()),
+            )),
+            // Nested lists, i.e. of multi-dimensional Postgres arrays, have boxed values builders at every level,
+            // which are downcast as `ListBuilder<Box<dyn ArrayBuilder>>` and then to the innermost builder
+            DataType::List(_) => make_builder(data_type, 0),
             _ => unimplemented!("Unsupported list value data type {:?}", data_type),
         },
+        DataType::Dictionary(key_type, value_type)
+            if **key_type == DataType::Int32 && **value_type == DataType::Utf8 =>
+        {
+            Box::new(StringDictionaryBuilder::<Int32Type>::new())
+        }
+        DataType::Map(entries, _) if is_string_map(entries) => Box::new(MapBuilder::new(
+            None,
+            StringBuilder::new(),
+            StringBuilder::new(),
+        )),
         DataType::Null => Box::new(NullBuilder::new()),
         DataType::Struct(fields) => {
             let mut field_builders = Vec::with_capacity(fields.len());
@@ -99,3 +188,16 @@ pub fn map_data_type_to_array_builder(data_type: &DataType) -> Box<dyn ArrayBuil
         _ => unimplemented!("Unsupported data type {:?}", data_type),
     }
 }
+
+/// Whether the `entries` of a map are string keys and values, i.e. of a Postgres `hstore`.
+fn is_string_map(entries: &FieldRef) -> bool {
+    match entries.data_type() {
+        DataType::Struct(fields) => {
+            fields.len() == 2
+                && fields
+                    .iter()
+                    .all(|field| *field.data_type() == DataType::Utf8)
+        }
+        _ => false,
+    }
+}
@@ -15,6 +15,7 @@ limitations under the License.
 */
 
 mod arrow;
+pub mod literal;
 #[cfg(feature = "clickhouse")]
 pub mod clickhouse;
 #[cfg(feature = "mysql")]
@@ -25,4 +26,5 @@ pub mod postgres;
 pub mod sqlite;
 pub mod statement;
 
+pub use crate::arrow::EmptyStringHandling;
 pub use sea_query;
@@ -0,0 +1,270 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow::{
+    array::{Array, AsArray},
+    datatypes::{
+        DataType, Date32Type, Date64Type, Decimal128Type, Float32Type, Float64Type, Int16Type,
+        Int32Type, Int64Type, Int8Type, TimeUnit, TimestampMicrosecondType,
+        TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type,
+        UInt32Type, UInt64Type, UInt8Type,
+    },
+};
+use bigdecimal_0_3_0::BigDecimal;
+use sea_query::{QueryBuilder, Value};
+use snafu::prelude::*;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Index {index} is out of bounds for an array of length {len}"))]
+    IndexOutOfBounds { index: usize, len: usize },
+
+    #[snafu(display("Unsupported data type for a SQL literal: {data_type}"))]
+    UnsupportedDataType { data_type: DataType },
+
+    #[snafu(display("Non-finite float {value} can't be written as a SQL literal"))]
+    NonFiniteFloat { value: f64 },
+
+    #[snafu(display("Value is outside of the range of SQL dates and timestamps: {source}"))]
+    DateTimeOutOfRange { source: time::error::ComponentRange },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Encodes the value at `index` of `array` as a SQL literal of the `query_builder` dialect, quoting and escaping
+/// strings as the dialect requires.
+///
+/// Prefer binding values as parameters; this is for the places where a literal is unavoidable, i.e. when pushing
+/// filter values into generated SQL. Timestamps are written as their UTC date and time, without an offset, like
+/// the values of an [`InsertBuilder`](crate::statement::InsertBuilder).
+///
+/// # Errors
+///
+/// Returns an error if `index` is out of bounds, the array's data type isn't supported, or the value has no SQL
+/// representation, i.e. a NaN or infinite float.
+pub fn arrow_value_to_sql_literal<T: QueryBuilder>(
+    array: &dyn Array,
+    index: usize,
+    query_builder: &T,
+) -> Result<String> {
+    ensure!(
+        index < array.len(),
+        IndexOutOfBoundsSnafu {
+            index,
+            len: array.len()
+        }
+    );
+    if array.is_null(index) {
+        return Ok("NULL".to_string());
+    }
+
+    let value: Value = match array.data_type() {
+        DataType::Null => return Ok("NULL".to_string()),
+        DataType::Boolean => array.as_boolean().value(index).into(),
+        DataType::Int8 => array.as_primitive::<Int8Type>().value(index).into(),
+        DataType::Int16 => array.as_primitive::<Int16Type>().value(index).into(),
+        DataType::Int32 => array.as_primitive::<Int32Type>().value(index).into(),
+        DataType::Int64 => array.as_primitive::<Int64Type>().value(index).into(),
+        DataType::UInt8 => array.as_primitive::<UInt8Type>().value(index).into(),
+        DataType::UInt16 => array.as_primitive::<UInt16Type>().value(index).into(),
+        DataType::UInt32 => array.as_primitive::<UInt32Type>().value(index).into(),
+        DataType::UInt64 => array.as_primitive::<UInt64Type>().value(index).into(),
+        DataType::Float32 => {
+            let value = array.as_primitive::<Float32Type>().value(index);
+            finite_float(f64::from(value))?;
+            value.into()
+        }
+        DataType::Float64 => finite_float(array.as_primitive::<Float64Type>().value(index))?.into(),
+        DataType::Decimal128(_, scale) => BigDecimal::new(
+            array.as_primitive::<Decimal128Type>().value(index).into(),
+            i64::from(*scale),
+        )
+        .into(),
+        DataType::Utf8 => array.as_string::<i32>().value(index).into(),
+        DataType::LargeUtf8 => array.as_string::<i64>().value(index).into(),
+        DataType::Date32 => {
+            let days = i64::from(array.as_primitive::<Date32Type>().value(index));
+            OffsetDateTime::from_unix_timestamp(days * 86_400)
+                .context(DateTimeOutOfRangeSnafu)?
+                .date()
+                .into()
+        }
+        DataType::Date64 => {
+            let millis = i128::from(array.as_primitive::<Date64Type>().value(index));
+            OffsetDateTime::from_unix_timestamp_nanos(millis * 1_000_000)
+                .context(DateTimeOutOfRangeSnafu)?
+                .date()
+                .into()
+        }
+        DataType::Timestamp(unit, _) => {
+            let nanos = match unit {
+                TimeUnit::Second => {
+                    i128::from(array.as_primitive::<TimestampSecondType>().value(index))
+                        * 1_000_000_000
+                }
+                TimeUnit::Millisecond => {
+                    i128::from(
+                        array
+                            .as_primitive::<TimestampMillisecondType>()
+                            .value(index),
+                    ) * 1_000_000
+                }
+                TimeUnit::Microsecond => {
+                    i128::from(
+                        array
+                            .as_primitive::<TimestampMicrosecondType>()
+                            .value(index),
+                    ) * 1_000
+                }
+                TimeUnit::Nanosecond => {
+                    i128::from(array.as_primitive::<TimestampNanosecondType>().value(index))
+                }
+            };
+            let timestamp = OffsetDateTime::from_unix_timestamp_nanos(nanos)
+                .context(DateTimeOutOfRangeSnafu)?;
+            PrimitiveDateTime::new(timestamp.date(), timestamp.time()).into()
+        }
+        data_type => UnsupportedDataTypeSnafu {
+            data_type: data_type.clone(),
+        }
+        .fail()?,
+    };
+
+    Ok(query_builder.value_to_string(&value))
+}
+
+fn finite_float(value: f64) -> Result<f64> {
+    ensure!(value.is_finite(), NonFiniteFloatSnafu { value });
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{
+        ArrayRef, BooleanArray, Date32Array, Date64Array, Decimal128Array, Float32Array,
+        Float64Array, Int64Array, LargeStringArray, NullArray, StringArray,
+        TimestampMicrosecondArray, TimestampSecondArray, UInt64Array,
+    };
+    use sea_query::{MysqlQueryBuilder, PostgresQueryBuilder, SqliteQueryBuilder};
+
+    use super::*;
+
+    /// Encodes `array[index]` as a literal of the Postgres, `SQLite` and `MySQL` dialects.
+    fn literals(array: &ArrayRef, index: usize) -> [String; 3] {
+        [
+            arrow_value_to_sql_literal(array, index, &PostgresQueryBuilder),
+            arrow_value_to_sql_literal(array, index, &SqliteQueryBuilder),
+            arrow_value_to_sql_literal(array, index, &MysqlQueryBuilder),
+        ]
+        .map(|literal| literal.expect("value is encoded"))
+    }
+
+    #[test]
+    fn test_strings() {
+        let array: ArrayRef = Arc::new(StringArray::from(vec!["plain", "it's", "back\\slash"]));
+
+        assert_eq!(literals(&array, 0), ["'plain'", "'plain'", "'plain'"]);
+        assert_eq!(literals(&array, 1), ["E'it\\'s'", "'it''s'", "'it\\'s'"]);
+        assert_eq!(
+            literals(&array, 2),
+            ["E'back\\\\slash'", "'back\\slash'", "'back\\\\slash'"]
+        );
+
+        let array: ArrayRef = Arc::new(LargeStringArray::from(vec!["it's"]));
+        assert_eq!(literals(&array, 0), ["E'it\\'s'", "'it''s'", "'it\\'s'"]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let array: ArrayRef = Arc::new(Int64Array::from(vec![i64::MIN, 42]));
+        assert_eq!(literals(&array, 0), ["-9223372036854775808"; 3]);
+        assert_eq!(literals(&array, 1), ["42"; 3]);
+
+        let array: ArrayRef = Arc::new(UInt64Array::from(vec![u64::MAX]));
+        assert_eq!(literals(&array, 0), ["18446744073709551615"; 3]);
+
+        let array: ArrayRef = Arc::new(Float32Array::from(vec![1.5]));
+        assert_eq!(literals(&array, 0), ["1.5"; 3]);
+
+        let array: ArrayRef = Arc::new(Float64Array::from(vec![-0.25]));
+        assert_eq!(literals(&array, 0), ["-0.25"; 3]);
+
+        let array: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![12_345])
+                .with_precision_and_scale(10, 2)
+                .expect("valid precision and scale"),
+        );
+        assert_eq!(literals(&array, 0), ["123.45"; 3]);
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        let array: ArrayRef = Arc::new(Float64Array::from(vec![f64::NAN, f64::INFINITY]));
+        for index in 0..2 {
+            assert!(matches!(
+                arrow_value_to_sql_literal(&array, index, &PostgresQueryBuilder),
+                Err(Error::NonFiniteFloat { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_bools_and_nulls() {
+        let array: ArrayRef = Arc::new(BooleanArray::from(vec![Some(true), Some(false), None]));
+        assert_eq!(literals(&array, 0), ["TRUE"; 3]);
+        assert_eq!(literals(&array, 1), ["FALSE"; 3]);
+        assert_eq!(literals(&array, 2), ["NULL"; 3]);
+
+        let array: ArrayRef = Arc::new(NullArray::new(1));
+        assert_eq!(literals(&array, 0), ["NULL"; 3]);
+    }
+
+    #[test]
+    fn test_dates_and_timestamps() {
+        // 2024-01-15
+        let array: ArrayRef = Arc::new(Date32Array::from(vec![19_737]));
+        assert_eq!(literals(&array, 0), ["'2024-01-15'"; 3]);
+
+        let array: ArrayRef = Arc::new(Date64Array::from(vec![1_705_276_800_000]));
+        assert_eq!(literals(&array, 0), ["'2024-01-15'"; 3]);
+
+        let array: ArrayRef = Arc::new(TimestampSecondArray::from(vec![1_705_312_800]));
+        assert_eq!(literals(&array, 0), ["'2024-01-15 10:00:00.000000'"; 3]);
+
+        let array: ArrayRef = Arc::new(
+            TimestampMicrosecondArray::from(vec![1_705_312_800_123_456]).with_timezone("UTC"),
+        );
+        assert_eq!(literals(&array, 0), ["'2024-01-15 10:00:00.123456'"; 3]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let array: ArrayRef = Arc::new(Int64Array::from(vec![1]));
+        assert!(matches!(
+            arrow_value_to_sql_literal(&array, 1, &PostgresQueryBuilder),
+            Err(Error::IndexOutOfBounds { index: 1, len: 1 })
+        ));
+
+        let array: ArrayRef = Arc::new(arrow::array::BinaryArray::from(vec![b"bytes".as_ref()]));
+        assert!(matches!(
+            arrow_value_to_sql_literal(&array, 0, &PostgresQueryBuilder),
+            Err(Error::UnsupportedDataType { .. })
+        ));
+    }
+}
@@ -14,32 +14,76 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::{HashMap, HashSet};
 use std::convert;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 
-use crate::arrow::map_data_type_to_array_builder_optional;
+use crate::arrow::{
+    coerce_empty_strings, map_data_type_to_array_builder, map_data_type_to_array_builder_optional,
+    EmptyStringHandling,
+};
 use crate::statement::map_data_type_to_column_type;
+use array::ArrayType;
 use arrow::array::{
-    ArrayBuilder, ArrayRef, BinaryBuilder, BooleanBuilder, Date32Builder, Decimal128Builder,
-    Float32Builder, Float64Builder, Int16Builder, Int32Builder, Int64Builder, Int8Builder,
-    LargeBinaryBuilder, LargeStringBuilder, ListBuilder, RecordBatch, RecordBatchOptions,
-    StringBuilder, StructBuilder, TimestampMillisecondBuilder, UInt32Builder,
+    ArrayBuilder, ArrayRef, AsArray, BinaryBuilder, BooleanBuilder, Date32Builder,
+    Decimal128Builder, Decimal256Builder, Float32Builder, Float64Builder, Int16Builder,
+    Int32Builder, Int64Builder, Int8Builder, IntervalMonthDayNanoBuilder, LargeBinaryBuilder,
+    LargeStringBuilder, ListBuilder, MapBuilder, PrimitiveArray, PrimitiveBuilder, RecordBatch,
+    RecordBatchOptions, StringBuilder, StringDictionaryBuilder, StructBuilder,
+    Time64MicrosecondBuilder, TimestampMicrosecondBuilder, TimestampMillisecondBuilder,
+    UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use arrow::compute::{cast, cast_with_options, CastOptions};
+use arrow::datatypes::{
+    i256, validate_decimal_precision_and_scale, ArrowPrimitiveType, ArrowTimestampType, DataType,
+    Date32Type, Decimal128Type, Decimal256Type, Field, Fields, Int32Type, Int64Type,
+    IntervalMonthDayNanoType, IntervalUnit, Schema, TimeUnit, TimestampMicrosecondType,
+    TimestampMillisecondType, DECIMAL128_MAX_PRECISION, DECIMAL256_MAX_PRECISION,
 };
-use arrow::datatypes::{DataType, Date32Type, Field, Schema, TimeUnit};
+use arrow::temporal_conversions::as_datetime;
 use bigdecimal::num_bigint::BigInt;
 use bigdecimal::num_bigint::Sign;
 use bigdecimal::BigDecimal;
 use bigdecimal::ToPrimitive;
+use byteorder::{BigEndian, ReadBytesExt};
+use chrono::{Offset, TimeZone, Timelike};
+use chrono_tz::Tz;
 use composite::CompositeType;
+use range::RangeType;
 use sea_query::{Alias, ColumnType, SeaRc};
 use snafu::prelude::*;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio_postgres::types::FromSql;
 use tokio_postgres::types::Kind;
 use tokio_postgres::{types::Type, Column, Row};
 
+pub mod array;
 pub mod builder;
 pub mod composite;
+pub mod range;
+
+// Range bounds carry no typmod, so `numrange` bounds are always read with this scale.
+const NUMRANGE_SCALE: i8 = 10;
+
+// Scale used for the schema of a `numeric` column declared without precision and scale.
+const UNCONSTRAINED_NUMERIC_SCALE: i8 = 10;
+
+// `money` is sent as an integer count of the smallest currency unit. Its scale depends on the `lc_monetary` setting
+// of the server, which isn't available here, so the 2 fractional digits of the common locales are assumed.
+const MONEY_SCALE: i8 = 2;
+
+// `server_version_num` of Postgres 13, which added `xid8`.
+const POSTGRES_13: u32 = 130_000;
+
+/// Field metadata key of the Postgres type name of a column, see `ArrowConversionOptions::with_type_metadata`.
+pub const PG_TYPE_METADATA_KEY: &str = "pg_type";
+
+/// Field metadata key of the Postgres type OID of a column, see `ArrowConversionOptions::with_type_metadata`.
+pub const PG_OID_METADATA_KEY: &str = "pg_oid";
+
+/// Field metadata key of the Postgres type modifier of a column, see `ArrowConversionOptions::with_type_metadata`.
+pub const PG_TYPMOD_METADATA_KEY: &str = "pg_typmod";
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -62,6 +106,11 @@ pub enum Error {
         source: <u128 as convert::TryInto<i64>>::Error,
     },
 
+    #[snafu(display("Integer overflow when converting i128 to i64: {source}"))]
+    FailedToConvertI128toI64 {
+        source: <i128 as convert::TryInto<i64>>::Error,
+    },
+
     #[snafu(display("Failed to get a row value for {pg_type}: {source}"))]
     FailedToGetRowValue {
         pg_type: Type,
@@ -74,12 +123,57 @@ pub enum Error {
         source: composite::Error,
     },
 
+    #[snafu(display("The composite type {pg_type} has no field {field_name}"))]
+    NoCompositeField { pg_type: Type, field_name: String },
+
+    #[snafu(display("Failed to get a range row value for {pg_type}: {source}"))]
+    FailedToGetRangeRowValue { pg_type: Type, source: range::Error },
+
+    #[snafu(display("Failed to get an array row value for {pg_type}: {source}"))]
+    FailedToGetArrayRowValue { pg_type: Type, source: array::Error },
+
+    #[snafu(display("The {pg_type} value of column {column_name} has {dimensions} dimensions, but the column is read with {expected}. Configure the dimensions of the column with `ArrowConversionOptions::with_array_dimensions`."))]
+    UnexpectedArrayDimensions {
+        pg_type: Type,
+        column_name: String,
+        dimensions: usize,
+        expected: usize,
+    },
+
+    #[snafu(display("Failed to append an hstore value: {source}"))]
+    FailedToAppendHstoreValue { source: arrow::error::ArrowError },
+
+    #[snafu(display("Arrays of type {pg_type} can't be read with more than one dimension"))]
+    UnsupportedNestedArrayType { pg_type: Type },
+
+    #[snafu(display("Range type {pg_type} has unsupported element type {element_type}"))]
+    UnsupportedRangeElementType { pg_type: Type, element_type: Type },
+
     #[snafu(display("Failed to parse raw Postgres Bytes as BigDecimal: {:?}", bytes))]
     FailedToParseBigDecimalFromPostgres { bytes: Vec<u8> },
 
+    #[snafu(display(
+        "Postgres NUMERIC value {value} cannot be represented as a Decimal. Filter it out or cast the column to float8 in the query."
+    ))]
+    UnsupportedNumericSpecialValue { value: &'static str },
+
     #[snafu(display("Cannot represent BigDecimal as i128: {big_decimal}"))]
     FailedToConvertBigDecimalToI128 { big_decimal: BigDecimal },
 
+    #[snafu(display("Cannot represent BigDecimal as i256: {big_decimal}"))]
+    FailedToConvertBigDecimalToI256 { big_decimal: BigDecimal },
+
+    #[snafu(display("Failed to rescale NUMERIC values to {data_type}: {source}"))]
+    FailedToRescaleNumeric {
+        data_type: DataType,
+        source: arrow::error::ArrowError,
+    },
+
+    #[snafu(display(
+        "Interval of {microseconds} microseconds overflows when converted to nanoseconds"
+    ))]
+    FailedToConvertIntervalToNanoseconds { microseconds: i64 },
+
     #[snafu(display("Failed to find field {column_name} in schema"))]
     FailedToFindFieldInSchema { column_name: String },
 
@@ -88,6 +182,16 @@ pub enum Error {
 
     #[snafu(display("No column name for index: {index}"))]
     NoColumnNameForIndex { index: usize },
+
+    #[snafu(display("The query returned more than one column named {column_name}"))]
+    DuplicateColumnName { column_name: String },
+
+    #[snafu(display("Postgres type {pg_type} requires server version {min_version} or later, but the server is version {server_version}"))]
+    UnsupportedTypeForServerVersion {
+        pg_type: Type,
+        min_version: u32,
+        server_version: u32,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -138,6 +242,35 @@ macro_rules! handle_primitive_array_type {
     }};
 }
 
+/// Like `handle_primitive_array_type`, but converts each non-null element with `$convert` before appending it.
+/// Elements that `$convert` maps to `None` are appended as nulls.
+macro_rules! handle_converted_array_type {
+    ($type:expr, $builder:expr, $row:expr, $i:expr, $list_builder:ty, $value_type:ty, $convert:expr) => {{
+        let Some(builder) = $builder else {
+            return NoBuilderForIndexSnafu { index: $i }.fail();
+        };
+        let Some(builder) = builder.as_any_mut().downcast_mut::<$list_builder>() else {
+            return FailedToDowncastBuilderSnafu {
+                postgres_type: format!("{:?}", $type),
+            }
+            .fail();
+        };
+        let v: Option<Vec<Option<$value_type>>> = $row
+            .try_get($i)
+            .context(FailedToGetRowValueSnafu { pg_type: $type })?;
+        match v {
+            Some(v) => {
+                let v = v
+                    .into_iter()
+                    .map(|element| element.map($convert).transpose().map(Option::flatten))
+                    .collect::<Result<Vec<_>>>()?;
+                builder.append_value(v);
+            }
+            None => builder.append_null(),
+        }
+    }};
+}
+
 macro_rules! handle_composite_type {
     ($BuilderType:ty, $ValueType:ty, $pg_type:expr, $composite_type:expr, $builder:expr, $idx:expr, $field_name:expr) => {{
         let Some(field_builder) = $builder.field_builder::<$BuilderType>($idx) else {
@@ -146,12 +279,16 @@ macro_rules! handle_composite_type {
             }
             .fail();
         };
+        // The fields of a null composite are appended as nulls
         let v: Option<$ValueType> =
-            $composite_type
-                .try_get($field_name)
-                .context(FailedToGetCompositeRowValueSnafu {
-                    pg_type: $pg_type.clone(),
-                })?;
+            match $composite_type {
+                Some(composite_type) => composite_type.try_get($field_name).context(
+                    FailedToGetCompositeRowValueSnafu {
+                        pg_type: $pg_type.clone(),
+                    },
+                )?,
+                None => None,
+            };
         match v {
             Some(v) => field_builder.append_value(v),
             None => field_builder.append_null(),
@@ -180,27 +317,505 @@ macro_rules! handle_composite_types {
     }
 }
 
+/// How to handle a query returning several columns with the same name, i.e. `SELECT a.id, b.id FROM ...`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateColumnNames {
+    /// Keep the duplicate names as they are.
+    #[default]
+    Preserve,
+    /// Suffix repeated names with a counter, i.e. `id`, `id_1`.
+    Disambiguate,
+    /// Fail with `Error::DuplicateColumnName`.
+    Error,
+}
+
+/// How to read the elements of an array type without native support, see
+/// `ArrowConversionOptions::with_array_element_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayElementType {
+    /// Reads each element as UTF-8 text, giving a `List(Utf8)` column.
+    Utf8,
+    /// Reads each element as raw bytes, giving a `List(Binary)` column.
+    Binary,
+}
+
+impl ArrayElementType {
+    fn data_type(self) -> DataType {
+        match self {
+            ArrayElementType::Utf8 => DataType::Utf8,
+            ArrayElementType::Binary => DataType::Binary,
+        }
+    }
+}
+
+/// Options controlling how Postgres columns are converted to Arrow.
+#[derive(Debug, Clone, Default)]
+pub struct ArrowConversionOptions {
+    exploded_interval_columns: HashSet<String>,
+    duplicate_column_names: DuplicateColumnNames,
+    structured_network_addresses: bool,
+    bool_as_int: bool,
+    array_element_types: HashMap<String, ArrayElementType>,
+    array_dimensions: HashMap<String, usize>,
+    empty_strings: EmptyStringHandling,
+    timestamptz_offset_time_zone: Option<Tz>,
+    server_version_num: Option<u32>,
+    default_numeric_precision: Option<u8>,
+    default_numeric_scale: Option<u8>,
+    type_metadata: bool,
+    not_null_columns: HashSet<(u32, i16)>,
+}
+
+impl ArrowConversionOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the `interval` column `column_name` as three columns, `{column_name}_months: Int32`,
+    /// `{column_name}_days: Int32` and `{column_name}_micros: Int64`, instead of a single
+    /// `Interval(MonthDayNano)` column.
+    #[must_use]
+    pub fn with_exploded_interval_column(mut self, column_name: impl Into<String>) -> Self {
+        self.exploded_interval_columns.insert(column_name.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_duplicate_column_names(
+        mut self,
+        duplicate_column_names: DuplicateColumnNames,
+    ) -> Self {
+        self.duplicate_column_names = duplicate_column_names;
+        self
+    }
+
+    /// Reads `inet` and `cidr` columns as `Struct{addr: Binary, prefix_len: UInt8, family: UInt8}` instead of
+    /// their `Utf8` text representation. `addr` holds the 4 or 16 address bytes, and `family` is 4 or 6.
+    #[must_use]
+    pub fn with_structured_network_addresses(mut self, structured_network_addresses: bool) -> Self {
+        self.structured_network_addresses = structured_network_addresses;
+        self
+    }
+
+    /// Reads `bool` columns as `Int8` with 1 for true and 0 for false, instead of `Boolean`.
+    #[must_use]
+    pub fn with_bool_as_int(mut self, bool_as_int: bool) -> Self {
+        self.bool_as_int = bool_as_int;
+        self
+    }
+
+    /// Reads arrays of the Postgres type named `element_type_name`, i.e. `semver` for a `semver[]` column, as a list
+    /// of `element_type`. The elements are read from their binary representation, which for text-like types such
+    /// as `citext` is the text itself. Takes precedence over the built-in mapping of the array type.
+    #[must_use]
+    pub fn with_array_element_type(
+        mut self,
+        element_type_name: impl Into<String>,
+        element_type: ArrayElementType,
+    ) -> Self {
+        self.array_element_types
+            .insert(element_type_name.into(), element_type);
+        self
+    }
+
+    /// Reads the array column `column_name` as `dimensions` levels of nested lists, i.e. `List(List(Int32))` for an
+    /// `int[][]` column read with 2 dimensions, instead of a flat list. Postgres doesn't enforce the declared
+    /// dimensions of an array column, so they have to be configured here. Values with any other number of dimensions
+    /// fail with `Error::UnexpectedArrayDimensions`, except for empty arrays, which are read as empty lists.
+    #[must_use]
+    pub fn with_array_dimensions(
+        mut self,
+        column_name: impl Into<String>,
+        dimensions: usize,
+    ) -> Self {
+        self.array_dimensions.insert(column_name.into(), dimensions);
+        self
+    }
+
+    /// Reads the empty strings and NULLs of `text`, `varchar`, `char` and `name` columns with `empty_strings`, i.e. as
+    /// NULL for sources that write a missing value as either. The default keeps them as they are.
+    #[must_use]
+    pub fn with_empty_strings(mut self, empty_strings: EmptyStringHandling) -> Self {
+        self.empty_strings = empty_strings;
+        self
+    }
+
+    /// Adds a `{column_name}_offset_seconds: Int32` column after each `timestamptz` column, holding the UTC offset of
+    /// `session_time_zone` at each instant.
+    ///
+    /// Postgres stores a `timestamptz` as a UTC instant and discards the offset it was written with, so this is the
+    /// offset the session displays the value with, which only matches the original one if the value was written
+    /// from the same time zone. `session_time_zone` should be the session's `TimeZone` setting, see `SHOW TIME ZONE`.
+    #[must_use]
+    pub fn with_timestamptz_offset_columns(mut self, session_time_zone: Tz) -> Self {
+        self.timestamptz_offset_time_zone = Some(session_time_zone);
+        self
+    }
+
+    /// Sets the `server_version_num` of the server the rows are read from, i.e. `130004` for 13.4. Types that don't
+    /// exist on that version are rejected, instead of being read with the handling of a newer server.
+    #[must_use]
+    pub fn with_server_version_num(mut self, server_version_num: u32) -> Self {
+        self.server_version_num = Some(server_version_num);
+        self
+    }
+
+    /// Reads unconstrained `numeric` columns, i.e. declared without a precision and scale, with `precision` digits
+    /// instead of 38. Precisions above 38 are read as `Decimal256`. See `with_default_numeric_scale`.
+    #[must_use]
+    pub fn with_default_numeric_precision(mut self, precision: u8) -> Self {
+        self.default_numeric_precision = Some(precision);
+        self
+    }
+
+    /// Reads unconstrained `numeric` columns with `scale` fractional digits, instead of a scale of 10 in the schema
+    /// and the scale of the first row in the rows.
+    ///
+    /// Once a default precision or scale is set, the rows of unconstrained columns are read with the same type as
+    /// their schema, so that the schema can be resolved without reading any data. Values with more fractional digits
+    /// than the scale are truncated to it, instead of widening the scale of the column.
+    #[must_use]
+    pub fn with_default_numeric_scale(mut self, scale: u8) -> Self {
+        self.default_numeric_scale = Some(scale);
+        self
+    }
+
+    /// Attaches the Postgres type of each column to the metadata of its field, under `PG_TYPE_METADATA_KEY`
+    /// (the type name, i.e. `int4` or `_text` for a `text[]`), `PG_OID_METADATA_KEY` and `PG_TYPMOD_METADATA_KEY`
+    /// (`-1` for a column declared without a modifier). Consumers can then tell apart columns that are read with
+    /// the same Arrow type, i.e. `json` and `text`, or recover the declared length of a `varchar(n)`.
+    ///
+    /// The columns added by `with_exploded_interval_column` and `with_timestamptz_offset_columns` don't get any.
+    #[must_use]
+    pub fn with_type_metadata(mut self, type_metadata: bool) -> Self {
+        self.type_metadata = type_metadata;
+        self
+    }
+
+    /// Reads the columns whose `(table_oid, column_id)` is in `not_null_columns` as not nullable, like
+    /// `columns_to_schema_with_not_null_columns` does, so that the rows match the schema of their table. A column that
+    /// holds NULLs anyway, i.e. on the nullable side of an outer join, stays nullable.
+    #[must_use]
+    #[allow(clippy::implicit_hasher)]
+    pub fn with_not_null_columns(mut self, not_null_columns: HashSet<(u32, i16)>) -> Self {
+        self.not_null_columns = not_null_columns;
+        self
+    }
+
+    /// Adds the metadata of the Postgres type of a column to its field, if `with_type_metadata` is set.
+    fn attach_type_metadata(&self, field: Field, column_type: &Type, type_modifier: i32) -> Field {
+        if self.type_metadata {
+            field.with_metadata(type_metadata(column_type, type_modifier))
+        } else {
+            field
+        }
+    }
+
+    /// The type of unconstrained `numeric` columns, if a default precision or scale is set.
+    fn default_numeric_data_type(&self) -> Option<DataType> {
+        if self.default_numeric_precision.is_none() && self.default_numeric_scale.is_none() {
+            return None;
+        }
+
+        let precision = self
+            .default_numeric_precision
+            .unwrap_or(DECIMAL128_MAX_PRECISION)
+            .clamp(1, DECIMAL256_MAX_PRECISION);
+        let scale = self
+            .default_numeric_scale
+            .map_or(UNCONSTRAINED_NUMERIC_SCALE, |scale| {
+                i8::try_from(scale.min(precision)).unwrap_or(i8::MAX)
+            });
+        if precision <= DECIMAL128_MAX_PRECISION {
+            Some(DataType::Decimal128(precision, scale))
+        } else {
+            Some(DataType::Decimal256(precision, scale))
+        }
+    }
+
+    fn array_element_type(&self, column_type: &Type) -> Option<ArrayElementType> {
+        match column_type.kind() {
+            Kind::Array(element_type) => self.array_element_types.get(element_type.name()).copied(),
+            _ => None,
+        }
+    }
+
+    /// The number of dimensions the array column `column_name` is read with, if it's read as nested lists.
+    fn nested_array_dimensions(&self, column_name: &str, column_type: &Type) -> Option<usize> {
+        if !matches!(column_type.kind(), Kind::Array(_)) {
+            return None;
+        }
+        self.array_dimensions
+            .get(column_name)
+            .copied()
+            .filter(|dimensions| *dimensions > 1)
+    }
+
+    /// Only text columns are coerced, not the text representation of other types, i.e. a NULL `json` isn't read as
+    /// an empty string.
+    fn coerce_empty_strings(&self, column_type: &Type, array: ArrayRef) -> ArrayRef {
+        match *column_type {
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+                coerce_empty_strings(array, self.empty_strings)
+            }
+            _ => array,
+        }
+    }
+
+    fn explodes_interval(&self, field: &Field) -> bool {
+        *field.data_type() == DataType::Interval(IntervalUnit::MonthDayNano)
+            && self.exploded_interval_columns.contains(field.name())
+    }
+
+    /// Only `timestamptz` columns are read as timestamps with a time zone.
+    fn adds_timestamptz_offset(&self, field: &Field) -> bool {
+        self.timestamptz_offset_time_zone.is_some()
+            && matches!(field.data_type(), DataType::Timestamp(_, Some(_)))
+    }
+
+    /// Fails for types that were added after the server version, if it is known.
+    fn check_server_version(&self, column_type: &Type) -> Result<()> {
+        let (Some(server_version), Some(min_version)) =
+            (self.server_version_num, min_server_version_num(column_type))
+        else {
+            return Ok(());
+        };
+        ensure!(
+            server_version >= min_version,
+            UnsupportedTypeForServerVersionSnafu {
+                pg_type: column_type.clone(),
+                min_version,
+                server_version,
+            }
+        );
+        Ok(())
+    }
+}
+
+/// Wraps the `List` data type of a one-dimensional array in another `List` for each of its further `dimensions`.
+fn nest_array_data_type(data_type: DataType, dimensions: Option<usize>) -> DataType {
+    (1..dimensions.unwrap_or(1)).fold(data_type, |data_type, _| {
+        DataType::List(Arc::new(Field::new("item", data_type, true)))
+    })
+}
+
+/// Appends `value` to the builder of a column read as `dimensions` levels of nested lists, see
+/// `ArrowConversionOptions::with_array_dimensions`.
+fn append_nested_array(
+    builder: &mut dyn ArrayBuilder,
+    pg_type: &Type,
+    column_name: &str,
+    dimensions: usize,
+    value: Option<ArrayType>,
+) -> Result<()> {
+    let Some(value) = value else {
+        nested_list_builder(builder, pg_type)?.append_null();
+        return Ok(());
+    };
+    ensure!(
+        value.dimensions().is_empty() || value.dimensions().len() == dimensions,
+        UnexpectedArrayDimensionsSnafu {
+            pg_type: pg_type.clone(),
+            column_name,
+            dimensions: value.dimensions().len(),
+            expected: dimensions,
+        }
+    );
+
+    match *value.element_type() {
+        Type::INT2 => append_nested_array_elements::<i16, Int16Builder>(builder, pg_type, &value),
+        Type::INT4 => append_nested_array_elements::<i32, Int32Builder>(builder, pg_type, &value),
+        Type::INT8 => append_nested_array_elements::<i64, Int64Builder>(builder, pg_type, &value),
+        Type::FLOAT4 => {
+            append_nested_array_elements::<f32, Float32Builder>(builder, pg_type, &value)
+        }
+        Type::FLOAT8 => {
+            append_nested_array_elements::<f64, Float64Builder>(builder, pg_type, &value)
+        }
+        Type::BOOL => {
+            append_nested_array_elements::<bool, BooleanBuilder>(builder, pg_type, &value)
+        }
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+            append_nested_array_elements::<&str, StringBuilder>(builder, pg_type, &value)
+        }
+        Type::BYTEA => {
+            append_nested_array_elements::<&[u8], BinaryBuilder>(builder, pg_type, &value)
+        }
+        _ => UnsupportedNestedArrayTypeSnafu {
+            pg_type: pg_type.clone(),
+        }
+        .fail(),
+    }
+}
+
+/// Decodes the elements of `value` as `T`, and appends them to the nested list builder `builder`, whose innermost
+/// builder is a `B`.
+fn append_nested_array_elements<'a, T, B>(
+    builder: &mut dyn ArrayBuilder,
+    pg_type: &Type,
+    value: &'a ArrayType<'a>,
+) -> Result<()>
+where
+    T: FromSql<'a>,
+    B: ArrayBuilder + Extend<Option<T>>,
+{
+    let mut elements = value
+        .elements::<T>()
+        .context(FailedToGetArrayRowValueSnafu {
+            pg_type: pg_type.clone(),
+        })?
+        .into_iter();
+    append_nested_list(
+        builder,
+        pg_type,
+        value.dimensions(),
+        &mut |values_builder, len| {
+            let Some(values_builder) = values_builder.as_any_mut().downcast_mut::<B>() else {
+                return FailedToDowncastBuilderSnafu {
+                    postgres_type: format!("{pg_type:?}"),
+                }
+                .fail();
+            };
+            values_builder.extend(elements.by_ref().take(len));
+            Ok(())
+        },
+    )
+}
+
+/// Appends a list of `dimensions[0]` sub-arrays to the list builder `builder`, recursing into its values builder for
+/// the inner dimensions. The elements of the innermost dimension are appended with `append_elements`, which is
+/// called with the innermost values builder and the number of elements.
+fn append_nested_list(
+    builder: &mut dyn ArrayBuilder,
+    pg_type: &Type,
+    dimensions: &[usize],
+    append_elements: &mut impl FnMut(&mut dyn ArrayBuilder, usize) -> Result<()>,
+) -> Result<()> {
+    let list = nested_list_builder(builder, pg_type)?;
+    match dimensions {
+        [] => {}
+        [len] => append_elements(list.values().as_mut(), *len)?,
+        [len, inner @ ..] => {
+            for _ in 0..*len {
+                append_nested_list(list.values().as_mut(), pg_type, inner, append_elements)?;
+            }
+        }
+    }
+    list.append(true);
+    Ok(())
+}
+
+/// Nested lists are built with boxed values builders, see `map_data_type_to_array_builder`.
+fn nested_list_builder<'b>(
+    builder: &'b mut dyn ArrayBuilder,
+    pg_type: &Type,
+) -> Result<&'b mut ListBuilder<Box<dyn ArrayBuilder>>> {
+    builder
+        .as_any_mut()
+        .downcast_mut::<ListBuilder<Box<dyn ArrayBuilder>>>()
+        .context(FailedToDowncastBuilderSnafu {
+            postgres_type: format!("{pg_type:?}"),
+        })
+}
+
+/// The first `server_version_num` that has `column_type`, for the types that don't exist on all supported versions.
+fn min_server_version_num(column_type: &Type) -> Option<u32> {
+    match *column_type {
+        Type::XID8 => Some(POSTGRES_13),
+        _ => None,
+    }
+}
+
 /// Converts Postgres Columns to Arrow Data Types
 ///
 /// # Errors
 ///
 /// Returns an error if the Postgres column type is not supported
 pub fn columns_to_schema(cols: &[Column]) -> Result<Arc<Schema>> {
+    columns_to_schema_with_options(cols, &ArrowConversionOptions::default())
+}
+
+/// Converts Postgres Columns to Arrow Data Types, applying the given `ArrowConversionOptions`.
+///
+/// # Errors
+///
+/// Returns an error if the Postgres column type is not supported
+pub fn columns_to_schema_with_options(
+    cols: &[Column],
+    options: &ArrowConversionOptions,
+) -> Result<Arc<Schema>> {
+    columns_to_schema_with_not_null_columns(cols, options, &HashSet::new())
+}
+
+/// Converts Postgres Columns to Arrow Data Types, applying the given `ArrowConversionOptions`. Columns whose
+/// `(table_oid, column_id)` is in `not_null_columns`, i.e. the `(attrelid, attnum)` of a `pg_attribute` row with
+/// `attnotnull` set, are not nullable. All other columns are nullable.
+///
+/// # Errors
+///
+/// Returns an error if the Postgres column type is not supported
+#[allow(clippy::implicit_hasher)]
+pub fn columns_to_schema_with_not_null_columns(
+    cols: &[Column],
+    options: &ArrowConversionOptions,
+    not_null_columns: &HashSet<(u32, i16)>,
+) -> Result<Arc<Schema>> {
     let mut arrow_fields: Vec<Option<Field>> = Vec::new();
 
     for column in cols {
         let column_name = column.name();
         let column_type = column.type_();
-        let data_type = map_column_type_to_data_type(column_type);
+        options.check_server_version(column_type)?;
+        check_range_element_type(column_type)?;
+        // Without rows to inspect, the precision and scale of a NUMERIC column come from its declaration
+        let data_type = match *column_type {
+            Type::NUMERIC => Some(numeric_type_modifier_to_data_type(
+                column.type_modifier(),
+                options,
+            )),
+            _ => map_column_type_to_data_type_with_modifier(
+                column_type,
+                column.type_modifier(),
+                options,
+            ),
+        };
+        let data_type = data_type.map(|data_type| {
+            nest_array_data_type(
+                data_type,
+                options.nested_array_dimensions(column_name, column_type),
+            )
+        });
+        let nullable = is_nullable_column(column, not_null_columns);
         match &data_type {
             Some(data_type) => {
-                arrow_fields.push(Some(Field::new(column_name, data_type.clone(), true)));
+                let field = Field::new(column_name, data_type.clone(), nullable);
+                arrow_fields.push(Some(options.attach_type_metadata(
+                    field,
+                    column_type,
+                    column.type_modifier(),
+                )));
             }
             None => arrow_fields.push(None),
         }
     }
 
-    let arrow_fields = arrow_fields.into_iter().flatten().collect::<Vec<Field>>();
+    let arrow_fields = arrow_fields
+        .into_iter()
+        .flatten()
+        .flat_map(|field| {
+            if options.explodes_interval(&field) {
+                exploded_interval_fields(field.name()).to_vec()
+            } else if options.adds_timestamptz_offset(&field) {
+                let offset_field = timestamptz_offset_field(field.name());
+                vec![field, offset_field]
+            } else {
+                vec![field]
+            }
+        })
+        .collect::<Vec<Field>>();
+    let arrow_fields =
+        resolve_duplicate_column_names(arrow_fields, options.duplicate_column_names)?;
 
     Ok(Arc::new(Schema::new(arrow_fields)))
 }
@@ -211,34 +826,202 @@ pub fn columns_to_schema(cols: &[Column]) -> Result<Arc<Schema>> {
 /// # Errors
 ///
 /// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
-#[allow(clippy::too_many_lines)]
 pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
+    rows_to_arrow_with_options(rows, &ArrowConversionOptions::default())
+}
+
+/// Converts Postgres `Row`s to an Arrow `RecordBatch`, applying the given `ArrowConversionOptions`.
+///
+/// # Errors
+///
+/// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
+pub fn rows_to_arrow_with_options(
+    rows: &[Row],
+    options: &ArrowConversionOptions,
+) -> Result<RecordBatch> {
+    let columns = convert_columns(rows, options, |_| true).map_err(|(_, e)| e)?;
+    assemble_record_batch(rows.len(), columns, options)
+}
+
+/// Like `rows_to_arrow`, but converts the columns in parallel on the rayon thread pool, which speeds up the
+/// conversion of wide result sets.
+///
+/// # Errors
+///
+/// Returns the same error as `rows_to_arrow` if there is a failure in converting the rows to a `RecordBatch`.
+#[cfg(feature = "rayon")]
+pub fn rows_to_arrow_parallel(rows: &[Row]) -> Result<RecordBatch> {
+    rows_to_arrow_parallel_with_options(rows, &ArrowConversionOptions::default())
+}
+
+/// Like `rows_to_arrow_parallel`, applying the given `ArrowConversionOptions`.
+///
+/// # Errors
+///
+/// Returns the same error as `rows_to_arrow_with_options` if there is a failure in converting the rows to a
+/// `RecordBatch`.
+#[cfg(feature = "rayon")]
+pub fn rows_to_arrow_parallel_with_options(
+    rows: &[Row],
+    options: &ArrowConversionOptions,
+) -> Result<RecordBatch> {
+    use rayon::prelude::*;
+
+    let column_count = rows.first().map_or(0, |row| row.columns().len());
+    // Columns are dealt round-robin, so that neighbouring wide columns end up in different partitions
+    let partitions = rayon::current_num_threads().clamp(1, column_count.max(1));
+    let converted = (0..partitions)
+        .into_par_iter()
+        .map(|partition| convert_columns(rows, options, |i| i % partitions == partition))
+        .collect::<Vec<_>>();
+
+    let mut columns = Vec::with_capacity(column_count);
+    let mut first_error: Option<(ValuePosition, Error)> = None;
+    for partition in converted {
+        match partition {
+            Ok(partition_columns) => columns.extend(partition_columns),
+            // The serial conversion stops at the first value that fails in row order, report that same one
+            Err((position, e)) => match &first_error {
+                Some((first_position, _)) if *first_position < position => {}
+                _ => first_error = Some((position, e)),
+            },
+        }
+    }
+    if let Some((_, e)) = first_error {
+        return Err(e);
+    }
+
+    columns.sort_unstable_by_key(|(i, _, _)| *i);
+    assemble_record_batch(rows.len(), columns, options)
+}
+
+/// Whether `column` is nullable, which it is unless its `(table_oid, column_id)` is in `not_null_columns`.
+fn is_nullable_column(column: &Column, not_null_columns: &HashSet<(u32, i16)>) -> bool {
+    match (column.table_oid(), column.column_id()) {
+        (Some(table_oid), Some(column_id)) => !not_null_columns.contains(&(table_oid, column_id)),
+        _ => true,
+    }
+}
+
+/// The `(row, column)` index of a value.
+type ValuePosition = (usize, usize);
+
+/// A converted column with its index, and its field and array unless its type isn't supported.
+type ConvertedColumn = (usize, Option<Field>, Option<ArrayRef>);
+
+/// Converts the columns of `rows` whose index is `selected`. On failure, the position of the value that couldn't
+/// be converted is returned with the error.
+fn convert_columns(
+    rows: &[Row],
+    options: &ArrowConversionOptions,
+    selected: impl Fn(usize) -> bool,
+) -> std::result::Result<Vec<ConvertedColumn>, (ValuePosition, Error)> {
     let mut arrow_fields: Vec<Option<Field>> = Vec::new();
     let mut arrow_columns_builders: Vec<Option<Box<dyn ArrayBuilder>>> = Vec::new();
     let mut postgres_types: Vec<Type> = Vec::new();
+    let mut type_modifiers: Vec<i32> = Vec::new();
     let mut column_names: Vec<String> = Vec::new();
+    let mut nullable_columns: Vec<bool> = Vec::new();
 
     if !rows.is_empty() {
         let row = &rows[0];
-        for column in row.columns() {
+        for (i, column) in row.columns().iter().enumerate() {
             let column_name = column.name();
             let column_type = column.type_();
-            let data_type = map_column_type_to_data_type(column_type);
+            options
+                .check_server_version(column_type)
+                .and_then(|()| check_range_element_type(column_type))
+                .map_err(|e| ((0, i), e))?;
+            let data_type = map_column_type_to_data_type_with_modifier(
+                column_type,
+                column.type_modifier(),
+                options,
+            )
+            .map(|data_type| {
+                nest_array_data_type(
+                    data_type,
+                    options.nested_array_dimensions(column_name, column_type),
+                )
+            });
+            let nullable = is_nullable_column(column, &options.not_null_columns);
             match &data_type {
                 Some(data_type) => {
-                    arrow_fields.push(Some(Field::new(column_name, data_type.clone(), true)));
+                    arrow_fields.push(Some(Field::new(column_name, data_type.clone(), nullable)));
                 }
                 None => arrow_fields.push(None),
             }
-            arrow_columns_builders
-                .push(map_data_type_to_array_builder_optional(data_type.as_ref()));
+            arrow_columns_builders.push(if selected(arrow_columns_builders.len()) {
+                map_data_type_to_array_builder_optional(data_type.as_ref())
+            } else {
+                None
+            });
             postgres_types.push(column_type.clone());
+            type_modifiers.push(column.type_modifier());
             column_names.push(column_name.to_string());
+            nullable_columns.push(nullable);
         }
     }
 
-    for row in rows {
+    let mut position = (0, 0);
+    append_rows(
+        rows,
+        &postgres_types,
+        &column_names,
+        &mut arrow_fields,
+        &mut arrow_columns_builders,
+        options,
+        &selected,
+        &mut position,
+    )
+    .map_err(|e| (position, e))?;
+
+    Ok(arrow_fields
+        .into_iter()
+        .zip(arrow_columns_builders)
+        .enumerate()
+        .filter(|(i, _)| selected(*i))
+        .map(|(i, (field, builder))| {
+            let column =
+                builder.map(|mut b| options.coerce_empty_strings(&postgres_types[i], b.finish()));
+            // Attached last, as `append_rows` replaces the field of some columns, i.e. NUMERIC. A NOT NULL column
+            // can still hold NULLs in a query result, i.e. on the nullable side of an outer join.
+            let nullable = nullable_columns[i]
+                || column
+                    .as_ref()
+                    .is_some_and(|column| column.null_count() > 0);
+            let field = field.map(|field| {
+                options.attach_type_metadata(
+                    field.with_nullable(nullable),
+                    &postgres_types[i],
+                    type_modifiers[i],
+                )
+            });
+            (i, field, column)
+        })
+        .collect())
+}
+
+/// Appends the values of the `selected` columns of `rows` to their builders. `position` is kept at the value being
+/// converted, so that it points at the failing one when an error is returned.
+#[allow(clippy::too_many_lines)]
+#[allow(clippy::too_many_arguments)]
+fn append_rows(
+    rows: &[Row],
+    postgres_types: &[Type],
+    column_names: &[String],
+    arrow_fields: &mut [Option<Field>],
+    arrow_columns_builders: &mut [Option<Box<dyn ArrayBuilder>>],
+    options: &ArrowConversionOptions,
+    selected: &impl Fn(usize) -> bool,
+    position: &mut ValuePosition,
+) -> Result<()> {
+    for (row_index, row) in rows.iter().enumerate() {
         for (i, postgres_type) in postgres_types.iter().enumerate() {
+            if !selected(i) {
+                continue;
+            }
+            *position = (row_index, i);
+
             let Some(builder) = arrow_columns_builders.get_mut(i) else {
                 return NoBuilderForIndexSnafu { index: i }.fail();
             };
@@ -247,6 +1030,44 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                 return NoArrowFieldForIndexSnafu { index: i }.fail();
             };
 
+            let Some(column_name) = column_names.get(i) else {
+                return NoColumnNameForIndexSnafu { index: i }.fail();
+            };
+            if let Some(dimensions) = options.nested_array_dimensions(column_name, postgres_type) {
+                let Some(builder) = builder else {
+                    return NoBuilderForIndexSnafu { index: i }.fail();
+                };
+                let v: Option<ArrayType> = row.try_get(i).context(FailedToGetRowValueSnafu {
+                    pg_type: postgres_type.clone(),
+                })?;
+                append_nested_array(builder.as_mut(), postgres_type, column_name, dimensions, v)?;
+                continue;
+            }
+
+            if let Some(element_type) = options.array_element_type(postgres_type) {
+                match element_type {
+                    ArrayElementType::Utf8 => handle_converted_array_type!(
+                        postgres_type.clone(),
+                        builder,
+                        row,
+                        i,
+                        ListBuilder<StringBuilder>,
+                        UntypedTextFromSql,
+                        |v: UntypedTextFromSql| Ok(Some(v.0))
+                    ),
+                    ArrayElementType::Binary => handle_converted_array_type!(
+                        postgres_type.clone(),
+                        builder,
+                        row,
+                        i,
+                        ListBuilder<BinaryBuilder>,
+                        UntypedBytesFromSql,
+                        |v: UntypedBytesFromSql| Ok(Some(v.0))
+                    ),
+                }
+                continue;
+            }
+
             match *postgres_type {
                 Type::INT2 => {
                     handle_primitive_type!(builder, Type::INT2, Int16Builder, i16, row, i);
@@ -257,6 +1078,30 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                 Type::INT8 => {
                     handle_primitive_type!(builder, Type::INT8, Int64Builder, i64, row, i);
                 }
+                Type::OID => {
+                    handle_primitive_type!(builder, Type::OID, UInt32Builder, u32, row, i);
+                }
+                Type::XID8 => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder.as_any_mut().downcast_mut::<UInt64Builder>() else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v = row
+                        .try_get::<usize, Option<Xid8FromSql>>(i)
+                        .with_context(|_| FailedToGetRowValueSnafu {
+                            pg_type: Type::XID8,
+                        })?;
+
+                    match v {
+                        Some(v) => builder.append_value(v.0),
+                        None => builder.append_null(),
+                    }
+                }
                 Type::FLOAT4 => {
                     handle_primitive_type!(builder, Type::FLOAT4, Float32Builder, f32, row, i);
                 }
@@ -269,6 +1114,9 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                 Type::VARCHAR => {
                     handle_primitive_type!(builder, Type::VARCHAR, StringBuilder, &str, row, i);
                 }
+                Type::BYTEA => {
+                    handle_primitive_type!(builder, Type::BYTEA, BinaryBuilder, Vec<u8>, row, i);
+                }
                 Type::BPCHAR => {
                     let Some(builder) = builder else {
                         return NoBuilderForIndexSnafu { index: i }.fail();
@@ -289,96 +1137,92 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                     }
                 }
                 Type::BOOL => {
-                    handle_primitive_type!(builder, Type::BOOL, BooleanBuilder, bool, row, i);
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let v = row.try_get::<usize, Option<bool>>(i).context(
+                        FailedToGetRowValueSnafu {
+                            pg_type: Type::BOOL,
+                        },
+                    )?;
+
+                    append_bool_value(builder.as_mut(), v)?;
                 }
                 Type::NUMERIC => {
                     let v: Option<BigDecimalFromSql> =
                         row.try_get(i).context(FailedToGetRowValueSnafu {
                             pg_type: Type::NUMERIC,
                         })?;
-                    let scale = {
-                        if let Some(v) = &v {
-                            v.scale()
-                        } else {
-                            0
-                        }
-                    };
 
+                    // Without a declared scale, the Arrow type is picked from the first row, and widened when a later
+                    // value has a larger scale or doesn't fit in Decimal128
                     let dec_builder = builder.get_or_insert_with(|| {
-                        Box::new(
-                            Decimal128Builder::new()
-                                .with_precision_and_scale(38, scale.try_into().unwrap_or_default())
-                                .unwrap_or_default(),
-                        )
+                        map_data_type_to_array_builder(&numeric_data_type(v.as_ref()))
                     });
 
-                    let Some(dec_builder) =
-                        dec_builder.as_any_mut().downcast_mut::<Decimal128Builder>()
-                    else {
-                        return FailedToDowncastBuilderSnafu {
-                            postgres_type: format!("{postgres_type}"),
-                        }
-                        .fail();
-                    };
-
                     if arrow_field.is_none() {
                         let Some(field_name) = column_names.get(i) else {
                             return NoColumnNameForIndexSnafu { index: i }.fail();
                         };
-                        let new_arrow_field = Field::new(
-                            field_name,
-                            DataType::Decimal128(38, scale.try_into().unwrap_or_default()),
-                            true,
-                        );
+                        let new_arrow_field =
+                            Field::new(field_name, numeric_data_type(v.as_ref()), true);
 
                         *arrow_field = Some(new_arrow_field);
                     }
 
-                    let Some(v) = v else {
-                        dec_builder.append_null();
-                        continue;
-                    };
-
-                    let Some(v_i128) = v.to_decimal_128() else {
-                        return FailedToConvertBigDecimalToI128Snafu {
-                            big_decimal: v.inner,
-                        }
-                        .fail();
+                    let Some(field) = arrow_field.as_mut() else {
+                        return NoArrowFieldForIndexSnafu { index: i }.fail();
                     };
-                    dec_builder.append_value(v_i128);
+                    append_numeric_value(
+                        dec_builder,
+                        field,
+                        v,
+                        options.default_numeric_data_type().is_none(),
+                    )?;
                 }
                 ref pg_type @ (Type::TIMESTAMP | Type::TIMESTAMPTZ) => {
                     let Some(builder) = builder else {
                         return NoBuilderForIndexSnafu { index: i }.fail();
                     };
-                    let Some(builder) = builder
-                        .as_any_mut()
-                        .downcast_mut::<TimestampMillisecondBuilder>()
-                    else {
-                        return FailedToDowncastBuilderSnafu {
-                            postgres_type: format!("{postgres_type}"),
-                        }
-                        .fail();
-                    };
                     let v = row
                         .try_get::<usize, Option<SystemTime>>(i)
                         .with_context(|_| FailedToGetRowValueSnafu {
                             pg_type: pg_type.clone(),
                         })?;
 
-                    match v {
-                        Some(v) => {
-                            if let Ok(v) = v.duration_since(UNIX_EPOCH) {
-                                let timestamp: i64 = v
-                                    .as_millis()
-                                    .try_into()
-                                    .context(FailedToConvertU128toI64Snafu)?;
-                                builder.append_value(timestamp);
-                            }
+                    let builder = builder.as_any_mut();
+                    if let Some(builder) = builder.downcast_mut::<TimestampMillisecondBuilder>() {
+                        append_timestamp_value(builder, v)?;
+                    } else if let Some(builder) =
+                        builder.downcast_mut::<TimestampMicrosecondBuilder>()
+                    {
+                        append_timestamp_value(builder, v)?;
+                    } else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
                         }
-                        None => builder.append_null(),
+                        .fail();
                     }
                 }
+                Type::MONEY => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder.as_any_mut().downcast_mut::<Decimal128Builder>()
+                    else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v = row.try_get::<usize, Option<MoneyFromSql>>(i).context(
+                        FailedToGetRowValueSnafu {
+                            pg_type: Type::MONEY,
+                        },
+                    )?;
+
+                    builder.append_option(v.map(|v| i128::from(v.0)));
+                }
                 Type::DATE => {
                     let Some(builder) = builder else {
                         return NoBuilderForIndexSnafu { index: i }.fail();
@@ -400,6 +1244,54 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                         None => builder.append_null(),
                     }
                 }
+                Type::TIME => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder
+                        .as_any_mut()
+                        .downcast_mut::<Time64MicrosecondBuilder>()
+                    else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v = row.try_get::<usize, Option<chrono::NaiveTime>>(i).context(
+                        FailedToGetRowValueSnafu {
+                            pg_type: Type::TIME,
+                        },
+                    )?;
+
+                    match v {
+                        Some(v) => builder.append_value(time_to_microseconds(v)),
+                        None => builder.append_null(),
+                    }
+                }
+                Type::INTERVAL => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder
+                        .as_any_mut()
+                        .downcast_mut::<IntervalMonthDayNanoBuilder>()
+                    else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v = row.try_get::<usize, Option<IntervalFromSql>>(i).context(
+                        FailedToGetRowValueSnafu {
+                            pg_type: Type::INTERVAL,
+                        },
+                    )?;
+
+                    match v {
+                        Some(v) => builder.append_value(v.to_month_day_nano()?),
+                        None => builder.append_null(),
+                    }
+                }
                 Type::UUID => {
                     let Some(builder) = builder else {
                         return NoBuilderForIndexSnafu { index: i }.fail();
@@ -421,6 +1313,73 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                         None => builder.append_null(),
                     }
                 }
+                ref pg_type @ (Type::JSON | Type::JSONB) => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder.as_any_mut().downcast_mut::<StringBuilder>() else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v = row
+                        .try_get::<usize, Option<serde_json::Value>>(i)
+                        .with_context(|_| FailedToGetRowValueSnafu {
+                            pg_type: pg_type.clone(),
+                        })?;
+
+                    match v {
+                        Some(v) => builder.append_value(v.to_string()),
+                        None => builder.append_null(),
+                    }
+                }
+                ref pg_type @ (Type::INET | Type::CIDR) => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let v = row
+                        .try_get::<usize, Option<InetFromSql>>(i)
+                        .with_context(|_| FailedToGetRowValueSnafu {
+                            pg_type: pg_type.clone(),
+                        })?;
+
+                    let builder = builder.as_any_mut();
+                    if let Some(builder) = builder.downcast_mut::<StringBuilder>() {
+                        match v {
+                            Some(v) => builder.append_value(v.to_string()),
+                            None => builder.append_null(),
+                        }
+                    } else if let Some(builder) = builder.downcast_mut::<StructBuilder>() {
+                        append_network_address_value(builder, pg_type, v.as_ref())?;
+                    } else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    }
+                }
+                Type::MACADDR => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder.as_any_mut().downcast_mut::<StringBuilder>() else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v = row
+                        .try_get::<usize, Option<MacAddrFromSql>>(i)
+                        .with_context(|_| FailedToGetRowValueSnafu {
+                            pg_type: Type::MACADDR,
+                        })?;
+
+                    match v {
+                        Some(v) => builder.append_value(v.to_string()),
+                        None => builder.append_null(),
+                    }
+                }
                 Type::INT2_ARRAY => handle_primitive_array_type!(
                     Type::INT2_ARRAY,
                     builder,
@@ -469,6 +1428,24 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                     ListBuilder<StringBuilder>,
                     String
                 ),
+                Type::VARCHAR_ARRAY => handle_converted_array_type!(
+                    Type::VARCHAR_ARRAY,
+                    builder,
+                    row,
+                    i,
+                    ListBuilder<StringBuilder>,
+                    String,
+                    |v| Ok(Some(v))
+                ),
+                Type::BPCHAR_ARRAY => handle_converted_array_type!(
+                    Type::BPCHAR_ARRAY,
+                    builder,
+                    row,
+                    i,
+                    ListBuilder<StringBuilder>,
+                    String,
+                    |v| Ok(Some(v.trim_end().to_string()))
+                ),
                 Type::BOOL_ARRAY => handle_primitive_array_type!(
                     Type::BOOL_ARRAY,
                     builder,
@@ -477,11 +1454,121 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                     ListBuilder<BooleanBuilder>,
                     bool
                 ),
+                Type::DATE_ARRAY => handle_converted_array_type!(
+                    Type::DATE_ARRAY,
+                    builder,
+                    row,
+                    i,
+                    ListBuilder<Date32Builder>,
+                    chrono::NaiveDate,
+                    |v| Ok(Some(Date32Type::from_naive_date(v)))
+                ),
+                Type::TIMESTAMP_ARRAY => handle_converted_array_type!(
+                    Type::TIMESTAMP_ARRAY,
+                    builder,
+                    row,
+                    i,
+                    ListBuilder<TimestampMillisecondBuilder>,
+                    SystemTime,
+                    |v| timestamp_since_epoch(v, TimeUnit::Millisecond).map(Some)
+                ),
+                Type::UUID_ARRAY => handle_converted_array_type!(
+                    Type::UUID_ARRAY,
+                    builder,
+                    row,
+                    i,
+                    ListBuilder<StringBuilder>,
+                    uuid::Uuid,
+                    |v| Ok(Some(v.to_string()))
+                ),
+                Type::NUMERIC_ARRAY => {
+                    let v: Option<Vec<Option<BigDecimalFromSql>>> =
+                        row.try_get(i).context(FailedToGetRowValueSnafu {
+                            pg_type: Type::NUMERIC_ARRAY,
+                        })?;
+
+                    let scale = match arrow_field.as_ref().map(Field::data_type) {
+                        Some(DataType::List(item)) => match item.data_type() {
+                            DataType::Decimal128(_, scale) => *scale,
+                            _ => 0,
+                        },
+                        _ => 0,
+                    };
+
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder
+                        .as_any_mut()
+                        .downcast_mut::<ListBuilder<Decimal128Builder>>()
+                    else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    append_numeric_array_value(builder, v, scale)?;
+                }
+                Type::BYTEA_ARRAY => handle_primitive_array_type!(
+                    Type::BYTEA_ARRAY,
+                    builder,
+                    row,
+                    i,
+                    ListBuilder<BinaryBuilder>,
+                    Vec<u8>
+                ),
                 _ => match *postgres_type.kind() {
+                    Kind::Simple if is_hstore(postgres_type) => {
+                        let Some(builder) = builder else {
+                            return NoBuilderForIndexSnafu { index: i }.fail();
+                        };
+                        let Some(builder) = builder.as_any_mut().downcast_mut::<HstoreBuilder>()
+                        else {
+                            return FailedToDowncastBuilderSnafu {
+                                postgres_type: format!("{postgres_type}"),
+                            }
+                            .fail();
+                        };
+
+                        let v = row.try_get::<usize, Option<HstoreFromSql>>(i).context(
+                            FailedToGetRowValueSnafu {
+                                pg_type: postgres_type.clone(),
+                            },
+                        )?;
+
+                        append_hstore_value(builder, v)?;
+                    }
+                    Kind::Array(ref element_type) if is_hstore(element_type) => {
+                        let Some(builder) = builder else {
+                            return NoBuilderForIndexSnafu { index: i }.fail();
+                        };
+                        let Some(builder) = builder
+                            .as_any_mut()
+                            .downcast_mut::<ListBuilder<HstoreBuilder>>()
+                        else {
+                            return FailedToDowncastBuilderSnafu {
+                                postgres_type: format!("{postgres_type}"),
+                            }
+                            .fail();
+                        };
+
+                        let v = row
+                            .try_get::<usize, Option<Vec<Option<HstoreFromSql>>>>(i)
+                            .context(FailedToGetRowValueSnafu {
+                                pg_type: postgres_type.clone(),
+                            })?;
+
+                        append_hstore_array_value(builder, v)?;
+                    }
                     Kind::Composite(_) => {
                         let Some(builder) = builder else {
                             return NoBuilderForIndexSnafu { index: i }.fail();
                         };
+                        let Some(DataType::Struct(struct_fields)) =
+                            arrow_field.as_ref().map(Field::data_type)
+                        else {
+                            return NoArrowFieldForIndexSnafu { index: i }.fail();
+                        };
                         let Some(builder) = builder.as_any_mut().downcast_mut::<StructBuilder>()
                         else {
                             return FailedToDowncastBuilderSnafu {
@@ -496,44 +1583,54 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                             },
                         )?;
 
-                        let Some(composite_type) = v else {
-                            builder.append_null();
-                            continue;
+                        append_composite_value(builder, struct_fields, postgres_type, v.as_ref())?;
+                    }
+                    Kind::Range(_) => {
+                        let Some(builder) = builder else {
+                            return NoBuilderForIndexSnafu { index: i }.fail();
+                        };
+                        let Some(builder) = builder.as_any_mut().downcast_mut::<StructBuilder>()
+                        else {
+                            return FailedToDowncastBuilderSnafu {
+                                postgres_type: format!("{postgres_type}"),
+                            }
+                            .fail();
+                        };
+
+                        let v = row.try_get::<usize, Option<RangeType>>(i).context(
+                            FailedToGetRowValueSnafu {
+                                pg_type: postgres_type.clone(),
+                            },
+                        )?;
+
+                        append_range_value(builder, postgres_type, v.as_ref())?;
+                    }
+                    Kind::Enum(_) => {
+                        let Some(builder) = builder else {
+                            return NoBuilderForIndexSnafu { index: i }.fail();
+                        };
+                        let Some(builder) = builder
+                            .as_any_mut()
+                            .downcast_mut::<StringDictionaryBuilder<Int32Type>>()
+                        else {
+                            return FailedToDowncastBuilderSnafu {
+                                postgres_type: format!("{postgres_type}"),
+                            }
+                            .fail();
                         };
 
-                        builder.append(true);
-
-                        let fields = composite_type.fields();
-                        for (idx, field) in fields.iter().enumerate() {
-                            let field_name = field.name();
-                            let Some(field_type) = map_column_type_to_data_type(field.type_())
-                            else {
-                                return FailedToDowncastBuilderSnafu {
-                                    postgres_type: format!("{}", field.type_()),
-                                }
-                                .fail();
-                            };
-
-                            handle_composite_types!(
-                                field_type,
-                                field.type_(),
-                                composite_type,
-                                builder,
-                                idx,
-                                field_name,
-                                Boolean => (BooleanBuilder, bool),
-                                Int8 => (Int8Builder, i8),
-                                Int16 => (Int16Builder, i16),
-                                Int32 => (Int32Builder, i32),
-                                Int64 => (Int64Builder, i64),
-                                UInt32 => (UInt32Builder, u32),
-                                Float32 => (Float32Builder, f32),
-                                Float64 => (Float64Builder, f64),
-                                Binary => (BinaryBuilder, Vec<u8>),
-                                LargeBinary => (LargeBinaryBuilder, Vec<u8>),
-                                Utf8 => (StringBuilder, String),
-                                LargeUtf8 => (LargeStringBuilder, String)
-                            );
+                        // An enum value is sent as its label
+                        let v = row
+                            .try_get::<usize, Option<UntypedTextFromSql>>(i)
+                            .context(FailedToGetRowValueSnafu {
+                                pg_type: postgres_type.clone(),
+                            })?;
+
+                        match v {
+                            Some(v) => {
+                                builder.append_value(v.0);
+                            }
+                            None => builder.append_null(),
                         }
                     }
                     _ => {
@@ -544,39 +1641,384 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
         }
     }
 
-    let columns = arrow_columns_builders
-        .into_iter()
-        .filter_map(|builder| builder.map(|mut b| b.finish()))
-        .collect::<Vec<ArrayRef>>();
-    let arrow_fields = arrow_fields.into_iter().flatten().collect::<Vec<Field>>();
+    Ok(())
+}
 
-    let options = &RecordBatchOptions::new().with_row_count(Some(rows.len()));
+/// Assembles the converted columns, in column order, into a `RecordBatch` of `row_count` rows.
+fn assemble_record_batch(
+    row_count: usize,
+    converted_columns: Vec<ConvertedColumn>,
+    options: &ArrowConversionOptions,
+) -> Result<RecordBatch> {
+    let mut arrow_fields = Vec::with_capacity(converted_columns.len());
+    let mut columns = Vec::with_capacity(converted_columns.len());
+    for (_, field, column) in converted_columns {
+        arrow_fields.extend(field);
+        columns.extend(column);
+    }
+    let (arrow_fields, columns) = explode_interval_columns(arrow_fields, columns, options);
+    let (arrow_fields, columns) = add_timestamptz_offset_columns(arrow_fields, columns, options);
+    let arrow_fields =
+        resolve_duplicate_column_names(arrow_fields, options.duplicate_column_names)?;
+
+    let options = &RecordBatchOptions::new().with_row_count(Some(row_count));
     match RecordBatch::try_new_with_options(Arc::new(Schema::new(arrow_fields)), columns, options) {
         Ok(record_batch) => Ok(record_batch),
         Err(e) => Err(e).context(FailedToBuildRecordBatchSnafu),
     }
 }
 
-fn map_column_type_to_data_type(column_type: &Type) -> Option<DataType> {
-    match *column_type {
-        Type::INT2 => Some(DataType::Int16),
-        Type::INT4 => Some(DataType::Int32),
-        Type::INT8 => Some(DataType::Int64),
-        Type::FLOAT4 => Some(DataType::Float32),
-        Type::FLOAT8 => Some(DataType::Float64),
-        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::UUID => Some(DataType::Utf8),
-        Type::BOOL => Some(DataType::Boolean),
-        // Inspect the scale from the first row. Precision will always be 38 for Decimal128.
-        Type::NUMERIC => None,
-        // We get a SystemTime that we can always convert into milliseconds
-        Type::TIMESTAMP | Type::TIMESTAMPTZ => {
-            Some(DataType::Timestamp(TimeUnit::Millisecond, None))
+/// Converts Postgres `Row`s to Arrow `RecordBatch`es of at most `batch_size` rows each, so the builders for a large
+/// result set don't have to hold every row at once.
+///
+/// # Errors
+///
+/// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
+pub fn rows_to_arrow_batched(rows: &[Row], batch_size: usize) -> Result<Vec<RecordBatch>> {
+    rows_to_arrow_batched_with_options(rows, batch_size, &ArrowConversionOptions::default())
+}
+
+/// Like `rows_to_arrow_batched`, applying the given `ArrowConversionOptions`.
+///
+/// # Errors
+///
+/// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
+pub fn rows_to_arrow_batched_with_options(
+    rows: &[Row],
+    batch_size: usize,
+    options: &ArrowConversionOptions,
+) -> Result<Vec<RecordBatch>> {
+    convert_in_batches(rows, batch_size, |rows| {
+        rows_to_arrow_with_options(rows, options)
+    })
+}
+
+/// Converts `rows` in chunks of `batch_size` with `convert`. The schema is inferred from the first row, so later
+/// batches are cast to the schema of the first one, i.e. when the scale of a NUMERIC column differs between chunks.
+fn convert_in_batches<T>(
+    rows: &[T],
+    batch_size: usize,
+    convert: impl Fn(&[T]) -> Result<RecordBatch>,
+) -> Result<Vec<RecordBatch>> {
+    let mut batches: Vec<RecordBatch> = Vec::with_capacity(rows.len().div_ceil(batch_size.max(1)));
+    for chunk in rows.chunks(batch_size.max(1)) {
+        let batch = convert(chunk)?;
+        let batch = match batches.first() {
+            Some(first) if first.schema() != batch.schema() => {
+                // Cast to the types of the first batch, keeping the nullability of this one
+                let fields: Vec<Field> = first
+                    .schema()
+                    .fields()
+                    .iter()
+                    .zip(batch.schema().fields())
+                    .map(|(field, batch_field)| {
+                        field
+                            .as_ref()
+                            .clone()
+                            .with_nullable(batch_field.is_nullable())
+                    })
+                    .collect();
+                let columns = batch
+                    .columns()
+                    .iter()
+                    .zip(&fields)
+                    .map(|(column, field)| cast(column, field.data_type()))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .context(FailedToBuildRecordBatchSnafu)?;
+                let schema = Schema::new_with_metadata(fields, first.schema().metadata().clone());
+                RecordBatch::try_new(Arc::new(schema), columns)
+                    .context(FailedToBuildRecordBatchSnafu)?
+            }
+            _ => batch,
+        };
+        batches.push(batch);
+    }
+
+    // A NOT NULL column that only holds NULLs in some chunks, i.e. on the nullable side of an outer join, is nullable
+    // in all of them
+    let Some(first) = batches.first() else {
+        return Ok(batches);
+    };
+    let first_schema = first.schema();
+    let fields: Vec<Field> = first_schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let nullable = batches
+                .iter()
+                .any(|batch| batch.schema().field(i).is_nullable());
+            field.as_ref().clone().with_nullable(nullable)
+        })
+        .collect();
+    let schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        first_schema.metadata().clone(),
+    ));
+    batches
+        .into_iter()
+        .map(|batch| {
+            batch
+                .with_schema(Arc::clone(&schema))
+                .context(FailedToBuildRecordBatchSnafu)
+        })
+        .collect()
+}
+
+fn resolve_duplicate_column_names(
+    fields: Vec<Field>,
+    duplicate_column_names: DuplicateColumnNames,
+) -> Result<Vec<Field>> {
+    if duplicate_column_names == DuplicateColumnNames::Preserve {
+        return Ok(fields);
+    }
+
+    let mut seen: HashSet<String> = HashSet::with_capacity(fields.len());
+    let mut resolved = Vec::with_capacity(fields.len());
+    for field in fields {
+        if seen.insert(field.name().clone()) {
+            resolved.push(field);
+            continue;
         }
-        Type::DATE => Some(DataType::Date32),
-        Type::INT2_ARRAY => Some(DataType::List(Arc::new(Field::new(
-            "item",
-            DataType::Int16,
-            true,
+
+        if duplicate_column_names == DuplicateColumnNames::Error {
+            return DuplicateColumnNameSnafu {
+                column_name: field.name().clone(),
+            }
+            .fail();
+        }
+
+        // Skip suffixes already taken, i.e. by a column that was literally named `id_1`
+        let mut suffix = 1;
+        let name = loop {
+            let name = format!("{}_{suffix}", field.name());
+            if !seen.contains(&name) {
+                break name;
+            }
+            suffix += 1;
+        };
+        seen.insert(name.clone());
+        resolved.push(field.with_name(name));
+    }
+
+    Ok(resolved)
+}
+
+fn type_metadata(column_type: &Type, type_modifier: i32) -> HashMap<String, String> {
+    HashMap::from([
+        (
+            PG_TYPE_METADATA_KEY.to_string(),
+            column_type.name().to_string(),
+        ),
+        (
+            PG_OID_METADATA_KEY.to_string(),
+            column_type.oid().to_string(),
+        ),
+        (
+            PG_TYPMOD_METADATA_KEY.to_string(),
+            type_modifier.to_string(),
+        ),
+    ])
+}
+
+fn exploded_interval_fields(column_name: &str) -> [Field; 3] {
+    [
+        Field::new(format!("{column_name}_months"), DataType::Int32, true),
+        Field::new(format!("{column_name}_days"), DataType::Int32, true),
+        Field::new(format!("{column_name}_micros"), DataType::Int64, true),
+    ]
+}
+
+/// Splits the interval columns selected in `options` into their month, day and microsecond components.
+fn explode_interval_columns(
+    fields: Vec<Field>,
+    columns: Vec<ArrayRef>,
+    options: &ArrowConversionOptions,
+) -> (Vec<Field>, Vec<ArrayRef>) {
+    let mut exploded_fields = Vec::with_capacity(fields.len());
+    let mut exploded_columns = Vec::with_capacity(columns.len());
+
+    for (field, column) in fields.into_iter().zip(columns) {
+        if !options.explodes_interval(&field) {
+            exploded_fields.push(field);
+            exploded_columns.push(column);
+            continue;
+        }
+
+        let intervals = column.as_primitive::<IntervalMonthDayNanoType>();
+        let months: PrimitiveArray<Int32Type> =
+            intervals.unary(|v| IntervalMonthDayNanoType::to_parts(v).0);
+        let days: PrimitiveArray<Int32Type> =
+            intervals.unary(|v| IntervalMonthDayNanoType::to_parts(v).1);
+        let micros: PrimitiveArray<Int64Type> =
+            intervals.unary(|v| IntervalMonthDayNanoType::to_parts(v).2 / 1_000);
+
+        exploded_fields.extend(exploded_interval_fields(field.name()));
+        exploded_columns.extend([
+            Arc::new(months) as ArrayRef,
+            Arc::new(days),
+            Arc::new(micros),
+        ]);
+    }
+
+    (exploded_fields, exploded_columns)
+}
+
+fn timestamptz_offset_field(column_name: &str) -> Field {
+    Field::new(
+        format!("{column_name}_offset_seconds"),
+        DataType::Int32,
+        true,
+    )
+}
+
+/// Adds the UTC offset column of each `timestamptz` column, if selected in `options`.
+fn add_timestamptz_offset_columns(
+    fields: Vec<Field>,
+    columns: Vec<ArrayRef>,
+    options: &ArrowConversionOptions,
+) -> (Vec<Field>, Vec<ArrayRef>) {
+    let Some(time_zone) = options.timestamptz_offset_time_zone else {
+        return (fields, columns);
+    };
+
+    let mut with_offsets_fields = Vec::with_capacity(fields.len());
+    let mut with_offsets_columns = Vec::with_capacity(columns.len());
+    for (field, column) in fields.into_iter().zip(columns) {
+        if !options.adds_timestamptz_offset(&field) {
+            with_offsets_fields.push(field);
+            with_offsets_columns.push(column);
+            continue;
+        }
+
+        let offsets = match field.data_type() {
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
+                timestamp_offsets::<TimestampMillisecondType>(&column, time_zone)
+            }
+            _ => timestamp_offsets::<TimestampMicrosecondType>(&column, time_zone),
+        };
+        let offset_field = timestamptz_offset_field(field.name());
+        with_offsets_fields.extend([field, offset_field]);
+        with_offsets_columns.push(column);
+        with_offsets_columns.push(Arc::new(offsets) as ArrayRef);
+    }
+
+    (with_offsets_fields, with_offsets_columns)
+}
+
+/// The UTC offset, in seconds, of `time_zone` at each timestamp of `column`.
+fn timestamp_offsets<T: ArrowTimestampType>(
+    column: &ArrayRef,
+    time_zone: Tz,
+) -> PrimitiveArray<Int32Type> {
+    column
+        .as_primitive::<T>()
+        .iter()
+        .map(|v| {
+            let utc = as_datetime::<T>(v?)?;
+            Some(
+                time_zone
+                    .offset_from_utc_datetime(&utc)
+                    .fix()
+                    .local_minus_utc(),
+            )
+        })
+        .collect()
+}
+
+/// Like `map_column_type_to_data_type`, but honors the declared precision of `timestamp(n)` columns, the declared
+/// precision and scale of `numeric(p, s)` columns, and the type mappings selected in `options`.
+fn map_column_type_to_data_type_with_modifier(
+    column_type: &Type,
+    type_modifier: i32,
+    options: &ArrowConversionOptions,
+) -> Option<DataType> {
+    if let Some(element_type) = options.array_element_type(column_type) {
+        return Some(DataType::List(Arc::new(Field::new(
+            "item",
+            element_type.data_type(),
+            true,
+        ))));
+    }
+
+    match *column_type {
+        Type::TIMESTAMP | Type::TIMESTAMPTZ => Some(DataType::Timestamp(
+            timestamp_time_unit(type_modifier),
+            timestamp_time_zone(column_type),
+        )),
+        Type::INET | Type::CIDR if options.structured_network_addresses => {
+            Some(DataType::Struct(network_address_fields()))
+        }
+        Type::BOOL if options.bool_as_int => Some(DataType::Int8),
+        // Every row is read with the declared scale rather than the first row's, so a `numeric(p, 2)` column has the
+        // scale of `money` even when its first value is NULL. Negative scales are still inspected from the rows, see
+        // `append_numeric_value`, as are unconstrained columns unless a default type is configured.
+        Type::NUMERIC => match declared_numeric_data_type(type_modifier) {
+            Some(data_type) => Some(data_type).filter(|data_type| {
+                matches!(data_type, DataType::Decimal128(_, scale) | DataType::Decimal256(_, scale) if *scale >= 0)
+            }),
+            None => options.default_numeric_data_type(),
+        },
+        Type::NUMERIC_ARRAY => Some(numeric_array_data_type(type_modifier, options)),
+        _ => map_column_type_to_data_type(column_type),
+    }
+}
+
+/// `TIMESTAMPTZ` values are UTC instants, while `TIMESTAMP` values are naive wall-clock times.
+fn timestamp_time_zone(column_type: &Type) -> Option<Arc<str>> {
+    match *column_type {
+        Type::TIMESTAMPTZ => Some("UTC".into()),
+        _ => None,
+    }
+}
+
+/// The type modifier of a `timestamp(n)` column is its fractional seconds precision `n`,
+/// or -1 when no precision was declared.
+fn timestamp_time_unit(type_modifier: i32) -> TimeUnit {
+    match type_modifier {
+        4..=6 => TimeUnit::Microsecond,
+        _ => TimeUnit::Millisecond,
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn map_column_type_to_data_type(column_type: &Type) -> Option<DataType> {
+    match *column_type {
+        Type::INT2 => Some(DataType::Int16),
+        Type::INT4 => Some(DataType::Int32),
+        Type::INT8 => Some(DataType::Int64),
+        Type::OID => Some(DataType::UInt32),
+        Type::XID8 => Some(DataType::UInt64),
+        Type::FLOAT4 => Some(DataType::Float32),
+        Type::FLOAT8 => Some(DataType::Float64),
+        Type::TEXT
+        | Type::VARCHAR
+        | Type::BPCHAR
+        | Type::UUID
+        | Type::JSON
+        | Type::JSONB
+        | Type::INET
+        | Type::CIDR
+        | Type::MACADDR => Some(DataType::Utf8),
+        Type::BOOL => Some(DataType::Boolean),
+        Type::BYTEA => Some(DataType::Binary),
+        // Inspect the precision and scale from the first row, see `numeric_data_type`.
+        Type::NUMERIC => None,
+        // The scale comes from the type modifier, see `numeric_array_data_type`.
+        Type::NUMERIC_ARRAY => None,
+        // We get a SystemTime that we can always convert into milliseconds
+        Type::TIMESTAMP | Type::TIMESTAMPTZ => Some(DataType::Timestamp(
+            TimeUnit::Millisecond,
+            timestamp_time_zone(column_type),
+        )),
+        Type::DATE => Some(DataType::Date32),
+        Type::TIME => Some(DataType::Time64(TimeUnit::Microsecond)),
+        Type::INTERVAL => Some(DataType::Interval(IntervalUnit::MonthDayNano)),
+        Type::MONEY => Some(DataType::Decimal128(DECIMAL128_MAX_PRECISION, MONEY_SCALE)),
+        Type::INT2_ARRAY => Some(DataType::List(Arc::new(Field::new(
+            "item",
+            DataType::Int16,
+            true,
         )))),
         Type::INT4_ARRAY => Some(DataType::List(Arc::new(Field::new(
             "item",
@@ -598,17 +2040,34 @@ fn map_column_type_to_data_type(column_type: &Type) -> Option<DataType> {
             DataType::Float64,
             true,
         )))),
-        Type::TEXT_ARRAY => Some(DataType::List(Arc::new(Field::new(
+        Type::TEXT_ARRAY | Type::VARCHAR_ARRAY | Type::BPCHAR_ARRAY | Type::UUID_ARRAY => Some(
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+        ),
+        Type::BOOL_ARRAY => Some(DataType::List(Arc::new(Field::new(
+            "item",
+            DataType::Boolean,
+            true,
+        )))),
+        Type::BYTEA_ARRAY => Some(DataType::List(Arc::new(Field::new(
             "item",
-            DataType::Utf8,
+            DataType::Binary,
             true,
         )))),
-        Type::BOOL_ARRAY => Some(DataType::List(Arc::new(Field::new(
+        Type::DATE_ARRAY => Some(DataType::List(Arc::new(Field::new(
             "item",
-            DataType::Boolean,
+            DataType::Date32,
+            true,
+        )))),
+        Type::TIMESTAMP_ARRAY => Some(DataType::List(Arc::new(Field::new(
+            "item",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
             true,
         )))),
         _ => match *column_type.kind() {
+            Kind::Simple if is_hstore(column_type) => Some(hstore_data_type()),
+            Kind::Array(ref element_type) if is_hstore(element_type) => Some(DataType::List(
+                Arc::new(Field::new("item", hstore_data_type(), true)),
+            )),
             Kind::Composite(ref fields) => {
                 let mut arrow_fields = Vec::new();
                 for field in fields {
@@ -626,146 +2085,2688 @@ fn map_column_type_to_data_type(column_type: &Type) -> Option<DataType> {
                 }
                 Some(DataType::Struct(arrow_fields.into()))
             }
+            // Ranges of unsupported element types are rejected by `check_range_element_type`
+            Kind::Range(ref element_type) => range_bound_data_type(element_type)
+                .map(|bound_type| DataType::Struct(range_fields(bound_type))),
+            // Enums usually have few distinct labels, so they are dictionary encoded
+            Kind::Enum(_) => Some(DataType::Dictionary(
+                Box::new(DataType::Int32),
+                Box::new(DataType::Utf8),
+            )),
             _ => unimplemented!("Unsupported column type {:?}", column_type),
         },
     }
 }
 
-pub(crate) fn map_data_type_to_column_type_postgres(
-    data_type: &DataType,
-    table_name: &str,
-    field_name: &str,
-) -> ColumnType {
-    match data_type {
-        DataType::Struct(_) => ColumnType::Custom(SeaRc::new(Alias::new(
-            get_postgres_composite_type_name(table_name, field_name),
-        ))),
-        _ => map_data_type_to_column_type(data_type),
+fn append_bool_value(builder: &mut dyn ArrayBuilder, value: Option<bool>) -> Result<()> {
+    let builder = builder.as_any_mut();
+    if let Some(builder) = builder.downcast_mut::<BooleanBuilder>() {
+        builder.append_option(value);
+    } else if let Some(builder) = builder.downcast_mut::<Int8Builder>() {
+        builder.append_option(value.map(i8::from));
+    } else {
+        return FailedToDowncastBuilderSnafu {
+            postgres_type: format!("{}", Type::BOOL),
+        }
+        .fail();
     }
+
+    Ok(())
 }
 
-#[must_use]
-pub(crate) fn get_postgres_composite_type_name(table_name: &str, field_name: &str) -> String {
-    format!("struct_{table_name}_{field_name}")
+fn append_timestamp_value<T: ArrowTimestampType>(
+    builder: &mut PrimitiveBuilder<T>,
+    value: Option<SystemTime>,
+) -> Result<()> {
+    match value {
+        Some(v) => builder.append_value(timestamp_since_epoch(v, T::UNIT)?),
+        None => builder.append_null(),
+    }
+    Ok(())
 }
 
-struct BigDecimalFromSql {
-    inner: BigDecimal,
-    scale: u16,
+/// Converts a `SystemTime` to a signed timestamp in `unit` since the Unix epoch.
+///
+/// Timestamps before the epoch are negative, rounded down like Arrow's, so 1969-12-31 23:59:59.9995 is -1ms.
+fn timestamp_since_epoch(value: SystemTime, unit: TimeUnit) -> Result<i64> {
+    let nanoseconds = match value.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => duration_to_nanoseconds(since_epoch),
+        Err(before_epoch) => -duration_to_nanoseconds(before_epoch.duration()),
+    };
+    let nanoseconds_per_unit = match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    };
+
+    nanoseconds
+        .div_euclid(nanoseconds_per_unit)
+        .try_into()
+        .context(FailedToConvertI128toI64Snafu)
 }
 
-impl BigDecimalFromSql {
-    fn to_decimal_128(&self) -> Option<i128> {
-        (&self.inner * 10i128.pow(u32::from(self.scale))).to_i128()
+fn duration_to_nanoseconds(duration: Duration) -> i128 {
+    i128::from(duration.as_secs()) * 1_000_000_000 + i128::from(duration.subsec_nanos())
+}
+
+/// Maps the type modifier of a `numeric(precision, scale)` column to a decimal type. An unconstrained `numeric` is
+/// read with the default type of `options`, or as `Decimal128(38, 10)`.
+fn numeric_type_modifier_to_data_type(
+    type_modifier: i32,
+    options: &ArrowConversionOptions,
+) -> DataType {
+    declared_numeric_data_type(type_modifier)
+        .or_else(|| options.default_numeric_data_type())
+        .unwrap_or(DataType::Decimal128(
+            DECIMAL128_MAX_PRECISION,
+            UNCONSTRAINED_NUMERIC_SCALE,
+        ))
+}
+
+/// Maps the type modifier of a `numeric(precision, scale)` column to a decimal type.
+///
+/// The type modifier is `((precision << 16) | scale) + 4`, or -1 for an unconstrained `numeric`, which has no
+/// declared type.
+#[allow(clippy::cast_possible_truncation)]
+fn declared_numeric_data_type(type_modifier: i32) -> Option<DataType> {
+    // VARHDRSZ, the size of the varlena header Postgres adds to the type modifier
+    const VARHDRSZ: i32 = 4;
+
+    if type_modifier < VARHDRSZ {
+        return None;
     }
 
-    fn scale(&self) -> u16 {
-        self.scale
+    let type_modifier = type_modifier - VARHDRSZ;
+    let precision = u8::try_from((type_modifier >> 16) & 0xFFFF).unwrap_or(u8::MAX);
+    // The scale is an 11-bit signed integer, as Postgres 15 allows negative scales
+    let scale = ((type_modifier & 0x7FF) ^ 0x400) - 0x400;
+    let scale = scale as i8;
+
+    if precision <= DECIMAL128_MAX_PRECISION {
+        Some(DataType::Decimal128(precision, scale))
+    } else {
+        Some(DataType::Decimal256(
+            precision.min(DECIMAL256_MAX_PRECISION),
+            scale,
+        ))
     }
 }
 
-#[allow(clippy::cast_sign_loss)]
-#[allow(clippy::cast_possible_wrap)]
-#[allow(clippy::cast_possible_truncation)]
-impl<'a> FromSql<'a> for BigDecimalFromSql {
-    fn from_sql(
-        _ty: &Type,
-        raw: &'a [u8],
-    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
-        let raw_u16: Vec<u16> = raw
-            .chunks(2)
-            .map(|chunk| {
-                if chunk.len() == 2 {
-                    u16::from_be_bytes([chunk[0], chunk[1]])
-                } else {
-                    u16::from_be_bytes([chunk[0], 0])
-                }
-            })
-            .collect();
+/// Maps a `numeric[]` column to a list of `Decimal128(38, s)`, where `s` is the declared scale of its elements, the
+/// default scale of `options`, or `UNCONSTRAINED_NUMERIC_SCALE`. An array column has the type modifier of its element
+/// type, so every row is read with the same scale, regardless of the scale of the values in the first one. Negative
+/// declared scales are read with a scale of 0.
+fn numeric_array_data_type(type_modifier: i32, options: &ArrowConversionOptions) -> DataType {
+    let scale = match numeric_type_modifier_to_data_type(type_modifier, options) {
+        DataType::Decimal128(_, scale) | DataType::Decimal256(_, scale) => scale.max(0),
+        _ => UNCONSTRAINED_NUMERIC_SCALE,
+    };
+    DataType::List(Arc::new(Field::new(
+        "item",
+        DataType::Decimal128(DECIMAL128_MAX_PRECISION, scale),
+        true,
+    )))
+}
 
-        let base_10_000_digit_count = raw_u16[0];
-        let weight = raw_u16[1] as i16;
-        let sign = raw_u16[2];
-        let scale = raw_u16[3];
+/// Appends a `numeric[]` value to its list builder with `scale`, the scale of the column's `Decimal128` values. Extra
+/// fractional digits are truncated, and values that don't fit are an error.
+fn append_numeric_array_value(
+    builder: &mut ListBuilder<Decimal128Builder>,
+    v: Option<Vec<Option<BigDecimalFromSql>>>,
+    scale: i8,
+) -> Result<()> {
+    let Some(v) = v else {
+        builder.append_null();
+        return Ok(());
+    };
+    let scale = u32::from(scale.unsigned_abs());
+    for element in v {
+        let Some(element) = element else {
+            builder.values().append_null();
+            continue;
+        };
+        let Some(v_i128) = element.to_decimal_128_with_scale(scale) else {
+            return FailedToConvertBigDecimalToI128Snafu {
+                big_decimal: element.inner,
+            }
+            .fail();
+        };
+        builder.values().append_value(v_i128);
+    }
+    builder.append(true);
+    Ok(())
+}
 
-        let mut base_10_000_digits = Vec::new();
-        for i in 4..4 + base_10_000_digit_count {
-            base_10_000_digits.push(raw_u16[i as usize]);
+/// Precision will always be 38 for Decimal128, or 76 when the value needs more digits than Decimal128 can hold.
+fn numeric_data_type(v: Option<&BigDecimalFromSql>) -> DataType {
+    let scale = v.map_or(0, BigDecimalFromSql::scale);
+    let scale = scale.try_into().unwrap_or_default();
+    if v.is_some_and(|v| v.precision() > u64::from(DECIMAL128_MAX_PRECISION)) {
+        DataType::Decimal256(DECIMAL256_MAX_PRECISION, scale)
+    } else {
+        DataType::Decimal128(DECIMAL128_MAX_PRECISION, scale)
+    }
+}
+
+/// Appends a NUMERIC value to its `Decimal128Builder` or `Decimal256Builder`, with the scale of `field`. When the type
+/// was picked from the first row, `widen` is set, so when a later value has a larger scale or needs more digits than
+/// Decimal128 holds, the values appended so far are converted to a type that fits it, instead of dropping the value's
+/// extra fractional digits or failing. Otherwise extra fractional digits are truncated.
+fn append_numeric_value(
+    builder: &mut Box<dyn ArrayBuilder>,
+    field: &mut Field,
+    v: Option<BigDecimalFromSql>,
+    widen: bool,
+) -> Result<()> {
+    if let Some(v) = v.as_ref().filter(|_| widen) {
+        if let Some(data_type) = widened_numeric_data_type(field.data_type(), v) {
+            rescale_numeric_builder(builder, field, data_type)?;
         }
+    }
 
-        let mut u8_digits = Vec::new();
-        for &base_10_000_digit in base_10_000_digits.iter().rev() {
-            let mut base_10_000_digit = base_10_000_digit;
-            let mut temp_result = Vec::new();
-            while base_10_000_digit > 0 {
-                temp_result.push((base_10_000_digit % 10) as u8);
-                base_10_000_digit /= 10;
+    let scale = match field.data_type() {
+        DataType::Decimal128(_, scale) | DataType::Decimal256(_, scale) => {
+            u32::from(scale.unsigned_abs())
+        }
+        _ => 0,
+    };
+
+    let builder = builder.as_any_mut();
+    if let Some(builder) = builder.downcast_mut::<Decimal128Builder>() {
+        let Some(v) = v else {
+            builder.append_null();
+            return Ok(());
+        };
+        let Some(v_i128) = v.to_decimal_128_with_scale(scale) else {
+            return FailedToConvertBigDecimalToI128Snafu {
+                big_decimal: v.inner,
             }
-            while temp_result.len() < 4 {
-                temp_result.push(0);
+            .fail();
+        };
+        builder.append_value(v_i128);
+    } else if let Some(builder) = builder.downcast_mut::<Decimal256Builder>() {
+        let Some(v) = v else {
+            builder.append_null();
+            return Ok(());
+        };
+        let Some(v_i256) = v.to_decimal_256_with_scale(scale) else {
+            return FailedToConvertBigDecimalToI256Snafu {
+                big_decimal: v.inner,
             }
-            u8_digits.extend(temp_result);
+            .fail();
+        };
+        builder.append_value(v_i256);
+    } else {
+        return FailedToDowncastBuilderSnafu {
+            postgres_type: format!("{}", Type::NUMERIC),
         }
-        u8_digits.reverse();
+        .fail();
+    }
 
-        let value_scale = 4 * (i64::from(base_10_000_digit_count) - i64::from(weight) - 1);
-        let size = i64::try_from(u8_digits.len())? + i64::from(scale) - value_scale;
-        u8_digits.resize(size as usize, 0);
+    Ok(())
+}
 
-        let sign = match sign {
-            0x4000 => Sign::Minus,
-            0x0000 => Sign::Plus,
-            _ => {
-                return Err(Box::new(Error::FailedToParseBigDecimalFromPostgres {
-                    bytes: raw.to_vec(),
-                }))
+/// The decimal type that `data_type`, picked from the first row, must be widened to for `v` to fit, if any: the larger
+/// of both scales, and Decimal256 once the integer digits of `v` and that scale need more than Decimal128 holds.
+fn widened_numeric_data_type(data_type: &DataType, v: &BigDecimalFromSql) -> Option<DataType> {
+    let (DataType::Decimal128(_, scale) | DataType::Decimal256(_, scale)) = *data_type else {
+        return None;
+    };
+    let scale = scale.max(i8::try_from(v.scale()).unwrap_or(i8::MAX));
+    let integer_digits = v.precision().saturating_sub(u64::from(v.scale()));
+    let precision = integer_digits + u64::try_from(scale).unwrap_or_default();
+    let widened = match data_type {
+        DataType::Decimal128(..) if precision <= u64::from(DECIMAL128_MAX_PRECISION) => {
+            DataType::Decimal128(DECIMAL128_MAX_PRECISION, scale)
+        }
+        _ => DataType::Decimal256(DECIMAL256_MAX_PRECISION, scale),
+    };
+    (widened != *data_type).then_some(widened)
+}
+
+/// Replaces `builder` by one of the decimal `data_type`, holding the values appended so far converted to it.
+fn rescale_numeric_builder(
+    builder: &mut Box<dyn ArrayBuilder>,
+    field: &mut Field,
+    data_type: DataType,
+) -> Result<()> {
+    match data_type {
+        DataType::Decimal128(precision, scale) => {
+            validate_decimal_precision_and_scale::<Decimal128Type>(precision, scale)
+        }
+        DataType::Decimal256(precision, scale) => {
+            validate_decimal_precision_and_scale::<Decimal256Type>(precision, scale)
+        }
+        _ => {
+            return FailedToDowncastBuilderSnafu {
+                postgres_type: format!("{}", Type::NUMERIC),
             }
-        };
+            .fail()
+        }
+    }
+    .with_context(|_| FailedToRescaleNumericSnafu {
+        data_type: data_type.clone(),
+    })?;
 
-        let Some(digits) = BigInt::from_radix_be(sign, u8_digits.as_slice(), 10) else {
-            return Err(Box::new(Error::FailedToParseBigDecimalFromPostgres {
-                bytes: raw.to_vec(),
-            }));
-        };
-        Ok(BigDecimalFromSql {
-            inner: BigDecimal::new(digits, i64::from(scale)),
-            scale,
-        })
+    // Fail instead of silently nulling values that no longer fit with the larger scale
+    let cast_options = CastOptions {
+        safe: false,
+        ..CastOptions::default()
+    };
+    let values =
+        cast_with_options(&builder.finish(), &data_type, &cast_options).with_context(|_| {
+            FailedToRescaleNumericSnafu {
+                data_type: data_type.clone(),
+            }
+        })?;
+
+    let mut rescaled_builder = map_data_type_to_array_builder(&data_type);
+    let rescaled = rescaled_builder.as_any_mut();
+    if let Some(rescaled) = rescaled.downcast_mut::<Decimal128Builder>() {
+        rescaled.extend(values.as_primitive::<Decimal128Type>().iter());
+    } else if let Some(rescaled) = rescaled.downcast_mut::<Decimal256Builder>() {
+        rescaled.extend(values.as_primitive::<Decimal256Type>().iter());
     }
 
-    fn accepts(ty: &Type) -> bool {
-        matches!(*ty, Type::NUMERIC)
+    *builder = rescaled_builder;
+    *field = field.clone().with_data_type(data_type);
+    Ok(())
+}
+
+fn time_to_microseconds(time: chrono::NaiveTime) -> i64 {
+    i64::from(time.num_seconds_from_midnight()) * 1_000_000 + i64::from(time.nanosecond()) / 1_000
+}
+
+fn network_address_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("addr", DataType::Binary, true),
+        Field::new("prefix_len", DataType::UInt8, true),
+        Field::new("family", DataType::UInt8, true),
+    ])
+}
+
+fn append_network_address_value(
+    builder: &mut StructBuilder,
+    pg_type: &Type,
+    value: Option<&InetFromSql>,
+) -> Result<()> {
+    let downcast_error = || {
+        FailedToDowncastBuilderSnafu {
+            postgres_type: format!("{pg_type}"),
+        }
+        .build()
+    };
+
+    let addr_builder = builder
+        .field_builder::<BinaryBuilder>(0)
+        .ok_or_else(downcast_error)?;
+    match value {
+        Some(v) => addr_builder.append_value(v.octets()),
+        None => addr_builder.append_null(),
+    }
+
+    for (idx, field) in [value.map(|v| v.prefix_len), value.map(InetFromSql::family)]
+        .into_iter()
+        .enumerate()
+    {
+        builder
+            .field_builder::<UInt8Builder>(idx + 1)
+            .ok_or_else(downcast_error)?
+            .append_option(field);
     }
+
+    builder.append(value.is_some());
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::str::FromStr;
+/// `hstore` is an extension type without a fixed OID, so it's recognized by name.
+fn is_hstore(pg_type: &Type) -> bool {
+    matches!(pg_type.kind(), Kind::Simple) && pg_type.name() == "hstore"
+}
 
-    #[allow(clippy::cast_possible_truncation)]
-    #[tokio::test]
-    async fn test_big_decimal_from_sql() {
-        let positive_u16: Vec<u16> = vec![5, 3, 0, 5, 9345, 1293, 2903, 1293, 932];
-        let positive_raw: Vec<u8> = positive_u16
-            .iter()
-            .flat_map(|&x| vec![(x >> 8) as u8, x as u8])
-            .collect();
-        let positive =
-            BigDecimal::from_str("9345129329031293.0932").expect("Failed to parse big decimal");
-        let positive_result = BigDecimalFromSql::from_sql(&Type::NUMERIC, positive_raw.as_slice())
-            .expect("Failed to run FromSql");
-        assert_eq!(positive_result.inner, positive);
+/// `hstore` values are read as maps of their keys to their values, which can be null. This is the data type of a
+/// `HstoreBuilder`.
+fn hstore_data_type() -> DataType {
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", DataType::Utf8, true),
+    ]);
+    DataType::Map(
+        Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+        false,
+    )
+}
 
-        let negative_u16: Vec<u16> = vec![5, 3, 0x4000, 5, 9345, 1293, 2903, 1293, 932];
-        let negative_raw: Vec<u8> = negative_u16
-            .iter()
-            .flat_map(|&x| vec![(x >> 8) as u8, x as u8])
-            .collect();
-        let negative =
-            BigDecimal::from_str("-9345129329031293.0932").expect("Failed to parse big decimal");
-        let negative_result = BigDecimalFromSql::from_sql(&Type::NUMERIC, negative_raw.as_slice())
-            .expect("Failed to run FromSql");
-        assert_eq!(negative_result.inner, negative);
+type HstoreBuilder = MapBuilder<StringBuilder, StringBuilder>;
+
+fn append_hstore_value(builder: &mut HstoreBuilder, value: Option<HstoreFromSql>) -> Result<()> {
+    let is_valid = value.is_some();
+    for (key, value) in value.into_iter().flat_map(|value| value.0) {
+        builder.keys().append_value(key);
+        builder.values().append_option(value);
+    }
+    builder
+        .append(is_valid)
+        .context(FailedToAppendHstoreValueSnafu)
+}
+
+/// The elements of a multi-dimensional `hstore[]` are appended as a flat list, like those of the other arrays.
+fn append_hstore_array_value(
+    builder: &mut ListBuilder<HstoreBuilder>,
+    value: Option<Vec<Option<HstoreFromSql>>>,
+) -> Result<()> {
+    let Some(value) = value else {
+        builder.append_null();
+        return Ok(());
+    };
+    for element in value {
+        append_hstore_value(builder.values(), element)?;
+    }
+    builder.append(true);
+    Ok(())
+}
+
+/// Ranges are represented as a struct of both bounds and their flags. An unbounded side is null.
+fn range_fields(bound_type: DataType) -> Fields {
+    Fields::from(vec![
+        Field::new("lower", bound_type.clone(), true),
+        Field::new("upper", bound_type, true),
+        Field::new("lower_inclusive", DataType::Boolean, true),
+        Field::new("upper_inclusive", DataType::Boolean, true),
+        Field::new("empty", DataType::Boolean, true),
+    ])
+}
+
+/// Appends a composite value to `builder`, whose fields are `struct_fields`, recursing into the `StructBuilder` of
+/// each nested composite field.
+///
+/// Composite fields are matched to the struct fields by name rather than position, so the values land in the right
+/// struct fields even when the composite's fields are ordered differently.
+fn append_composite_value(
+    builder: &mut StructBuilder,
+    struct_fields: &Fields,
+    pg_type: &Type,
+    composite_type: Option<&CompositeType>,
+) -> Result<()> {
+    let Kind::Composite(fields) = pg_type.kind() else {
+        unreachable!()
+    };
+
+    for (idx, struct_field) in struct_fields.iter().enumerate() {
+        let field_name = struct_field.name().as_str();
+        let Some(field) = fields.iter().find(|field| field.name() == field_name) else {
+            return NoCompositeFieldSnafu {
+                pg_type: pg_type.clone(),
+                field_name,
+            }
+            .fail();
+        };
+        let field_type = struct_field.data_type();
+
+        if let (Kind::Composite(_), DataType::Struct(nested_fields)) =
+            (field.type_().kind(), field_type)
+        {
+            let Some(field_builder) = builder.field_builder::<StructBuilder>(idx) else {
+                return FailedToDowncastBuilderSnafu {
+                    postgres_type: format!("{}", field.type_()),
+                }
+                .fail();
+            };
+            let v: Option<CompositeType> = match composite_type {
+                Some(composite_type) => composite_type.try_get(field_name).context(
+                    FailedToGetCompositeRowValueSnafu {
+                        pg_type: field.type_().clone(),
+                    },
+                )?,
+                None => None,
+            };
+            append_composite_value(field_builder, nested_fields, field.type_(), v.as_ref())?;
+            continue;
+        }
+
+        if is_hstore(field.type_()) {
+            let Some(field_builder) = builder.field_builder::<HstoreBuilder>(idx) else {
+                return FailedToDowncastBuilderSnafu {
+                    postgres_type: format!("{}", field.type_()),
+                }
+                .fail();
+            };
+            let v: Option<HstoreFromSql> = match composite_type {
+                Some(composite_type) => composite_type.try_get(field_name).context(
+                    FailedToGetCompositeRowValueSnafu {
+                        pg_type: field.type_().clone(),
+                    },
+                )?,
+                None => None,
+            };
+            append_hstore_value(field_builder, v)?;
+            continue;
+        }
+
+        handle_composite_types!(
+            field_type,
+            field.type_(),
+            composite_type,
+            builder,
+            idx,
+            field_name,
+            Boolean => (BooleanBuilder, bool),
+            Int8 => (Int8Builder, i8),
+            Int16 => (Int16Builder, i16),
+            Int32 => (Int32Builder, i32),
+            Int64 => (Int64Builder, i64),
+            UInt32 => (UInt32Builder, u32),
+            Float32 => (Float32Builder, f32),
+            Float64 => (Float64Builder, f64),
+            Binary => (BinaryBuilder, Vec<u8>),
+            LargeBinary => (LargeBinaryBuilder, Vec<u8>),
+            Utf8 => (StringBuilder, String),
+            LargeUtf8 => (LargeStringBuilder, String)
+        );
+    }
+
+    builder.append(composite_type.is_some());
+
+    Ok(())
+}
+
+/// The data type of the bounds of a range of `element_type`, if ranges of it are supported.
+fn range_bound_data_type(element_type: &Type) -> Option<DataType> {
+    match *element_type {
+        Type::INT4 => Some(DataType::Int32),
+        Type::INT8 => Some(DataType::Int64),
+        Type::NUMERIC => Some(DataType::Decimal128(38, NUMRANGE_SCALE)),
+        Type::DATE => Some(DataType::Date32),
+        Type::TIMESTAMP => Some(DataType::Timestamp(TimeUnit::Millisecond, None)),
+        Type::TIMESTAMPTZ => Some(DataType::Timestamp(
+            TimeUnit::Millisecond,
+            Some("UTC".into()),
+        )),
+        _ => None,
+    }
+}
+
+/// Fails for range types whose bounds can't be read, i.e. a custom range of `float8`.
+fn check_range_element_type(column_type: &Type) -> Result<()> {
+    match column_type.kind() {
+        Kind::Range(element_type) if range_bound_data_type(element_type).is_none() => {
+            UnsupportedRangeElementTypeSnafu {
+                pg_type: column_type.clone(),
+                element_type: element_type.clone(),
+            }
+            .fail()
+        }
+        _ => Ok(()),
+    }
+}
+
+fn append_range_value(
+    builder: &mut StructBuilder,
+    pg_type: &Type,
+    range: Option<&RangeType>,
+) -> Result<()> {
+    let element_type = match pg_type.kind() {
+        Kind::Range(element_type) => element_type,
+        _ => unreachable!(),
+    };
+
+    // Reads both bounds of the range, or two nulls when the value itself is null
+    macro_rules! bounds {
+        ($value_ty:ty) => {
+            match range {
+                Some(r) => (
+                    r.lower::<$value_ty>()
+                        .context(FailedToGetRangeRowValueSnafu {
+                            pg_type: pg_type.clone(),
+                        })?,
+                    r.upper::<$value_ty>()
+                        .context(FailedToGetRangeRowValueSnafu {
+                            pg_type: pg_type.clone(),
+                        })?,
+                ),
+                None => (None, None),
+            }
+        };
+    }
+
+    match *element_type {
+        Type::INT4 => {
+            let (lower, upper) = bounds!(i32);
+            append_range_bounds::<Int32Type>(builder, pg_type, lower, upper)?;
+        }
+        Type::INT8 => {
+            let (lower, upper) = bounds!(i64);
+            append_range_bounds::<Int64Type>(builder, pg_type, lower, upper)?;
+        }
+        Type::NUMERIC => {
+            let (lower, upper) = bounds!(BigDecimalFromSql);
+            let to_i128 = |v: Option<BigDecimalFromSql>| match v {
+                Some(v) => {
+                    match v.to_decimal_128_with_scale(NUMRANGE_SCALE.unsigned_abs().into()) {
+                        Some(v) => Ok(Some(v)),
+                        None => FailedToConvertBigDecimalToI128Snafu {
+                            big_decimal: v.inner,
+                        }
+                        .fail(),
+                    }
+                }
+                None => Ok(None),
+            };
+            append_range_bounds::<Decimal128Type>(
+                builder,
+                pg_type,
+                to_i128(lower)?,
+                to_i128(upper)?,
+            )?;
+        }
+        Type::DATE => {
+            let (lower, upper) = bounds!(chrono::NaiveDate);
+            append_range_bounds::<Date32Type>(
+                builder,
+                pg_type,
+                lower.map(Date32Type::from_naive_date),
+                upper.map(Date32Type::from_naive_date),
+            )?;
+        }
+        Type::TIMESTAMP => {
+            let (lower, upper) = bounds!(chrono::NaiveDateTime);
+            append_range_bounds::<TimestampMillisecondType>(
+                builder,
+                pg_type,
+                lower.map(|v| v.and_utc().timestamp_millis()),
+                upper.map(|v| v.and_utc().timestamp_millis()),
+            )?;
+        }
+        Type::TIMESTAMPTZ => {
+            let (lower, upper) = bounds!(chrono::DateTime<chrono::Utc>);
+            append_range_bounds::<TimestampMillisecondType>(
+                builder,
+                pg_type,
+                lower.map(|v| v.timestamp_millis()),
+                upper.map(|v| v.timestamp_millis()),
+            )?;
+        }
+        _ => {
+            return UnsupportedRangeElementTypeSnafu {
+                pg_type: pg_type.clone(),
+                element_type: element_type.clone(),
+            }
+            .fail();
+        }
+    }
+
+    for (idx, flag) in [
+        range.map(RangeType::lower_inclusive),
+        range.map(RangeType::upper_inclusive),
+        range.map(RangeType::is_empty),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let Some(flag_builder) = builder.field_builder::<BooleanBuilder>(idx + 2) else {
+            return FailedToDowncastBuilderSnafu {
+                postgres_type: format!("{pg_type}"),
+            }
+            .fail();
+        };
+        flag_builder.append_option(flag);
+    }
+
+    builder.append(range.is_some());
+    Ok(())
+}
+
+fn append_range_bounds<T: ArrowPrimitiveType>(
+    builder: &mut StructBuilder,
+    pg_type: &Type,
+    lower: Option<T::Native>,
+    upper: Option<T::Native>,
+) -> Result<()> {
+    for (idx, value) in [lower, upper].into_iter().enumerate() {
+        let Some(bound_builder) = builder.field_builder::<PrimitiveBuilder<T>>(idx) else {
+            return FailedToDowncastBuilderSnafu {
+                postgres_type: format!("{pg_type}"),
+            }
+            .fail();
+        };
+        bound_builder.append_option(value);
+    }
+    Ok(())
+}
+
+pub(crate) fn map_data_type_to_column_type_postgres(
+    data_type: &DataType,
+    table_name: &str,
+    field_name: &str,
+) -> ColumnType {
+    match data_type {
+        DataType::Struct(_) => ColumnType::Custom(SeaRc::new(Alias::new(
+            get_postgres_composite_type_name(table_name, field_name),
+        ))),
+        _ => map_data_type_to_column_type(data_type),
+    }
+}
+
+#[must_use]
+pub(crate) fn get_postgres_composite_type_name(table_name: &str, field_name: &str) -> String {
+    format!("struct_{table_name}_{field_name}")
+}
+
+struct BigDecimalFromSql {
+    inner: BigDecimal,
+    scale: u16,
+}
+
+impl BigDecimalFromSql {
+    fn to_decimal_128_with_scale(&self, scale: u32) -> Option<i128> {
+        (&self.inner * 10i128.pow(scale)).to_i128()
+    }
+
+    fn to_decimal_256_with_scale(&self, scale: u32) -> Option<i256> {
+        let (digits, _) = self
+            .inner
+            .with_scale(i64::from(scale))
+            .into_bigint_and_exponent();
+        let bytes = digits.to_signed_bytes_le();
+        if bytes.len() > 32 {
+            return None;
+        }
+
+        // Sign-extend the little-endian two's complement bytes to 256 bits
+        let fill = if digits.sign() == Sign::Minus {
+            0xFF
+        } else {
+            0
+        };
+        let mut buf = [fill; 32];
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        Some(i256::from_le_bytes(buf))
+    }
+
+    fn scale(&self) -> u16 {
+        self.scale
+    }
+
+    /// The number of significant digits, including the fractional ones.
+    fn precision(&self) -> u64 {
+        self.inner.digits().max(u64::from(self.scale))
+    }
+}
+
+// The special NUMERIC signs, see `numeric.c` in Postgres
+const NUMERIC_NAN: u16 = 0xC000;
+const NUMERIC_PINF: u16 = 0xD000;
+const NUMERIC_NINF: u16 = 0xF000;
+
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_possible_truncation)]
+impl<'a> FromSql<'a> for BigDecimalFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        // The header is 4 u16s: the digit count, weight, sign and scale
+        if raw.len() < 8 {
+            return Err(Box::new(Error::FailedToParseBigDecimalFromPostgres {
+                bytes: raw.to_vec(),
+            }));
+        }
+
+        // NaN and, since Postgres 14, the infinities are encoded as a special sign without any digits
+        let special_value = match u16::from_be_bytes([raw[4], raw[5]]) {
+            NUMERIC_NAN => Some("NaN"),
+            NUMERIC_PINF => Some("Infinity"),
+            NUMERIC_NINF => Some("-Infinity"),
+            _ => None,
+        };
+        if let Some(value) = special_value {
+            return Err(Box::new(Error::UnsupportedNumericSpecialValue { value }));
+        }
+
+        let raw_u16: Vec<u16> = raw
+            .chunks(2)
+            .map(|chunk| {
+                if chunk.len() == 2 {
+                    u16::from_be_bytes([chunk[0], chunk[1]])
+                } else {
+                    u16::from_be_bytes([chunk[0], 0])
+                }
+            })
+            .collect();
+
+        let base_10_000_digit_count = raw_u16[0];
+        let weight = raw_u16[1] as i16;
+        let sign = raw_u16[2];
+        let scale = raw_u16[3];
+
+        let mut base_10_000_digits = Vec::new();
+        for i in 4..4 + base_10_000_digit_count {
+            base_10_000_digits.push(raw_u16[i as usize]);
+        }
+
+        let mut u8_digits = Vec::new();
+        for &base_10_000_digit in base_10_000_digits.iter().rev() {
+            let mut base_10_000_digit = base_10_000_digit;
+            let mut temp_result = Vec::new();
+            while base_10_000_digit > 0 {
+                temp_result.push((base_10_000_digit % 10) as u8);
+                base_10_000_digit /= 10;
+            }
+            while temp_result.len() < 4 {
+                temp_result.push(0);
+            }
+            u8_digits.extend(temp_result);
+        }
+        u8_digits.reverse();
+
+        let value_scale = 4 * (i64::from(base_10_000_digit_count) - i64::from(weight) - 1);
+        let size = i64::try_from(u8_digits.len())? + i64::from(scale) - value_scale;
+        u8_digits.resize(size as usize, 0);
+
+        let sign = match sign {
+            0x4000 => Sign::Minus,
+            0x0000 => Sign::Plus,
+            _ => {
+                return Err(Box::new(Error::FailedToParseBigDecimalFromPostgres {
+                    bytes: raw.to_vec(),
+                }))
+            }
+        };
+
+        let Some(digits) = BigInt::from_radix_be(sign, u8_digits.as_slice(), 10) else {
+            return Err(Box::new(Error::FailedToParseBigDecimalFromPostgres {
+                bytes: raw.to_vec(),
+            }));
+        };
+        Ok(BigDecimalFromSql {
+            inner: BigDecimal::new(digits, i64::from(scale)),
+            scale,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::NUMERIC)
+    }
+}
+
+/// A Postgres `MONEY` value in the smallest currency unit, i.e. cents, see `MONEY_SCALE`.
+struct MoneyFromSql(i64);
+
+impl<'a> FromSql<'a> for MoneyFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let mut buf = raw;
+        let v = buf.read_i64::<BigEndian>()?;
+
+        if !buf.is_empty() {
+            return Err("invalid buffer length: money is not empty".into());
+        }
+
+        Ok(MoneyFromSql(v))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::MONEY)
+    }
+}
+
+/// A Postgres `INTERVAL`, which is stored as separate month, day and microsecond components.
+struct IntervalFromSql {
+    months: i32,
+    days: i32,
+    microseconds: i64,
+}
+
+impl IntervalFromSql {
+    fn to_month_day_nano(
+        &self,
+    ) -> Result<<IntervalMonthDayNanoType as ArrowPrimitiveType>::Native> {
+        let Some(nanoseconds) = self.microseconds.checked_mul(1_000) else {
+            return FailedToConvertIntervalToNanosecondsSnafu {
+                microseconds: self.microseconds,
+            }
+            .fail();
+        };
+        Ok(IntervalMonthDayNanoType::make_value(
+            self.months,
+            self.days,
+            nanoseconds,
+        ))
+    }
+}
+
+impl<'a> FromSql<'a> for IntervalFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        // Binary format of an interval: <microseconds: 8 bytes><days: 4 bytes><months: 4 bytes>
+        let mut buf = raw;
+        let microseconds = buf.read_i64::<BigEndian>()?;
+        let days = buf.read_i32::<BigEndian>()?;
+        let months = buf.read_i32::<BigEndian>()?;
+
+        if !buf.is_empty() {
+            return Err("invalid buffer length: interval is not empty".into());
+        }
+
+        Ok(IntervalFromSql {
+            months,
+            days,
+            microseconds,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::INTERVAL)
+    }
+}
+
+/// A Postgres `INET` or `CIDR` value: an IPv4 or IPv6 address with its network prefix length.
+struct InetFromSql {
+    addr: IpAddr,
+    prefix_len: u8,
+    is_cidr: bool,
+}
+
+impl InetFromSql {
+    fn octets(&self) -> Vec<u8> {
+        match self.addr {
+            IpAddr::V4(addr) => addr.octets().to_vec(),
+            IpAddr::V6(addr) => addr.octets().to_vec(),
+        }
+    }
+
+    fn family(&self) -> u8 {
+        match self.addr {
+            IpAddr::V4(_) => 4,
+            IpAddr::V6(_) => 6,
+        }
+    }
+}
+
+/// Matches the Postgres text output, which omits the prefix length of an `INET` host address.
+impl std::fmt::Display for InetFromSql {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let max_prefix_len = match self.addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if self.is_cidr || self.prefix_len != max_prefix_len {
+            write!(f, "{}/{}", self.addr, self.prefix_len)
+        } else {
+            write!(f, "{}", self.addr)
+        }
+    }
+}
+
+impl<'a> FromSql<'a> for InetFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        // Binary format of an inet/cidr:
+        //     <family: 1 byte, PGSQL_AF_INET (2) or PGSQL_AF_INET6 (3)>
+        //     <prefix length: 1 byte>
+        //     <is cidr: 1 byte>
+        //     <address length: 1 byte>
+        //     <address: 4 or 16 bytes>
+        // https://github.com/postgres/postgres/blob/master/src/backend/utils/adt/network.c
+        let mut buf = raw;
+        let family = buf.read_u8()?;
+        let prefix_len = buf.read_u8()?;
+        let is_cidr = buf.read_u8()? != 0;
+        let len = buf.read_u8()?;
+
+        let addr = match (family, len, buf) {
+            (2, 4, &[a, b, c, d]) => IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+            (3, 16, octets) if octets.len() == 16 => {
+                let mut addr = [0u8; 16];
+                addr.copy_from_slice(octets);
+                IpAddr::V6(Ipv6Addr::from(addr))
+            }
+            _ => return Err(format!("invalid inet value with family {family}").into()),
+        };
+
+        Ok(InetFromSql {
+            addr,
+            prefix_len,
+            is_cidr,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::INET | Type::CIDR)
+    }
+}
+
+/// A Postgres `MACADDR` value.
+struct MacAddrFromSql([u8; 6]);
+
+/// Matches the Postgres text output, e.g. `08:00:2b:01:02:03`.
+impl std::fmt::Display for MacAddrFromSql {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let octets: Vec<String> = self.0.iter().map(|octet| format!("{octet:02x}")).collect();
+        write!(f, "{}", octets.join(":"))
+    }
+}
+
+impl<'a> FromSql<'a> for MacAddrFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let addr = <[u8; 6]>::try_from(raw)
+            .map_err(|_| format!("invalid macaddr value of {} bytes", raw.len()))?;
+        Ok(MacAddrFromSql(addr))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::MACADDR)
+    }
+}
+
+/// A full transaction id, sent as an unsigned 64-bit integer.
+struct Xid8FromSql(u64);
+
+impl<'a> FromSql<'a> for Xid8FromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let mut buf = raw;
+        let v = buf.read_u64::<BigEndian>()?;
+
+        if !buf.is_empty() {
+            return Err("invalid buffer length: xid8 is not empty".into());
+        }
+
+        Ok(Xid8FromSql(v))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::XID8)
+    }
+}
+
+/// A Postgres `hstore` value, as its key and value pairs in the order the server sends them.
+struct HstoreFromSql(Vec<(String, Option<String>)>);
+
+impl<'a> FromSql<'a> for HstoreFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        // Binary format of an hstore:
+        //     <number of pairs: 4 bytes>
+        //     [for each pair]
+        //         <length of key: 4 bytes>
+        //         <key: <length> bytes>
+        //         <length of value: 4 bytes, -1 for a null value>
+        //         <value: <length> bytes>
+        //     [end for]
+        // https://github.com/postgres/postgres/blob/master/contrib/hstore/hstore_io.c
+        let mut buf = raw;
+        let count = usize::try_from(buf.read_i32::<BigEndian>()?)?;
+
+        let mut pairs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let key = read_hstore_string(&mut buf)?.ok_or("invalid hstore: null key")?;
+            let value = read_hstore_string(&mut buf)?;
+            pairs.push((key, value));
+        }
+
+        if !buf.is_empty() {
+            return Err("invalid buffer length: hstore is not empty".into());
+        }
+
+        Ok(HstoreFromSql(pairs))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        is_hstore(ty)
+    }
+}
+
+fn read_hstore_string(
+    buf: &mut &[u8],
+) -> std::prelude::v1::Result<Option<String>, Box<dyn std::error::Error + Sync + Send>> {
+    // A negative length is a null value
+    let Ok(len) = usize::try_from(buf.read_i32::<BigEndian>()?) else {
+        return Ok(None);
+    };
+    if buf.len() < len {
+        return Err("invalid hstore: string is longer than the buffer".into());
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(Some(std::str::from_utf8(value)?.to_string()))
+}
+
+/// The value of a type without native support, read as UTF-8 text.
+struct UntypedTextFromSql(String);
+
+impl<'a> FromSql<'a> for UntypedTextFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(UntypedTextFromSql(std::str::from_utf8(raw)?.to_string()))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+/// The value of a type without native support, read as the raw bytes of its binary representation.
+struct UntypedBytesFromSql(Vec<u8>);
+
+impl<'a> FromSql<'a> for UntypedBytesFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(UntypedBytesFromSql(raw.to_vec()))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        Array, ArrayAccessor, Decimal128Array, IntervalMonthDayNanoArray, MapArray, StringArray,
+        StructArray,
+    };
+    use arrow::datatypes::{Decimal256Type, Int8Type, UInt32Type, UInt8Type};
+    use std::str::FromStr;
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[tokio::test]
+    async fn test_big_decimal_from_sql() {
+        let positive_u16: Vec<u16> = vec![5, 3, 0, 5, 9345, 1293, 2903, 1293, 932];
+        let positive_raw: Vec<u8> = positive_u16
+            .iter()
+            .flat_map(|&x| vec![(x >> 8) as u8, x as u8])
+            .collect();
+        let positive =
+            BigDecimal::from_str("9345129329031293.0932").expect("Failed to parse big decimal");
+        let positive_result = BigDecimalFromSql::from_sql(&Type::NUMERIC, positive_raw.as_slice())
+            .expect("Failed to run FromSql");
+        assert_eq!(positive_result.inner, positive);
+
+        let negative_u16: Vec<u16> = vec![5, 3, 0x4000, 5, 9345, 1293, 2903, 1293, 932];
+        let negative_raw: Vec<u8> = negative_u16
+            .iter()
+            .flat_map(|&x| vec![(x >> 8) as u8, x as u8])
+            .collect();
+        let negative =
+            BigDecimal::from_str("-9345129329031293.0932").expect("Failed to parse big decimal");
+        let negative_result = BigDecimalFromSql::from_sql(&Type::NUMERIC, negative_raw.as_slice())
+            .expect("Failed to run FromSql");
+        assert_eq!(negative_result.inner, negative);
+    }
+
+    #[test]
+    fn test_numeric_scale_across_rows() {
+        let numeric = |v: &str, scale: u16| BigDecimalFromSql {
+            inner: BigDecimal::from_str(v).expect("valid big decimal"),
+            scale,
+        };
+        let first = numeric("1.25", 2);
+        let mut field = Field::new("n", numeric_data_type(Some(&first)), true);
+        let mut builder = map_data_type_to_array_builder(field.data_type());
+
+        for v in [
+            Some(first),
+            Some(numeric("3.14159", 5)),
+            None,
+            Some(numeric("2.5", 1)),
+        ] {
+            append_numeric_value(&mut builder, &mut field, v, true).expect("value is appended");
+        }
+
+        // The values of scale 2 are rescaled to the larger scale of the second row
+        assert_eq!(*field.data_type(), DataType::Decimal128(38, 5));
+        let array = builder.finish();
+        let array = array.as_primitive::<Decimal128Type>();
+        assert_eq!(*array.data_type(), DataType::Decimal128(38, 5));
+        assert_eq!(array.value_as_string(0), "1.25000");
+        assert_eq!(array.value_as_string(1), "3.14159");
+        assert!(array.is_null(2));
+        assert_eq!(array.value_as_string(3), "2.50000");
+
+        // A scale that doesn't fit Decimal128 widens the values to Decimal256
+        let mut field = Field::new("n", DataType::Decimal128(38, 2), true);
+        let mut builder = map_data_type_to_array_builder(field.data_type());
+        let precise = numeric("0.1234567890123456789012345678901234567890", 40);
+        append_numeric_value(&mut builder, &mut field, Some(precise), true)
+            .expect("value is appended");
+        assert_eq!(*field.data_type(), DataType::Decimal256(76, 40));
+
+        // A scale that doesn't fit Decimal256 either is an error rather than a truncated value
+        let too_precise = numeric(&format!("0.{}", "1".repeat(80)), 80);
+        assert!(append_numeric_value(&mut builder, &mut field, Some(too_precise), true).is_err());
+
+        // Without widening, extra fractional digits are truncated to the scale of the field
+        let mut field = Field::new("n", DataType::Decimal128(12, 2), true);
+        let mut builder = map_data_type_to_array_builder(field.data_type());
+        append_numeric_value(&mut builder, &mut field, Some(numeric("3.14159", 5)), false)
+            .expect("value is appended");
+        assert_eq!(*field.data_type(), DataType::Decimal128(12, 2));
+        let array = builder.finish();
+        assert_eq!(
+            array.as_primitive::<Decimal128Type>().value_as_string(0),
+            "3.14"
+        );
+    }
+
+    #[test]
+    fn test_numeric_precision_across_rows() {
+        let numeric = |v: &str, scale: u16| BigDecimalFromSql {
+            inner: BigDecimal::from_str(v).expect("valid big decimal"),
+            scale,
+        };
+        let digits = "12345678901234567890123456789012345678901234567890";
+        let first = numeric("1.5", 1);
+        let mut field = Field::new("n", numeric_data_type(Some(&first)), true);
+        let mut builder = map_data_type_to_array_builder(field.data_type());
+
+        for v in [
+            Some(first),
+            Some(numeric(digits, 0)),
+            Some(numeric("-2", 0)),
+        ] {
+            append_numeric_value(&mut builder, &mut field, v, true).expect("value is appended");
+        }
+
+        // The first row fits in Decimal128, the values are widened to Decimal256 for the second one
+        assert_eq!(*field.data_type(), DataType::Decimal256(76, 1));
+        let array = builder.finish();
+        let array = array.as_primitive::<Decimal256Type>();
+        assert_eq!(array.value_as_string(0), "1.5");
+        assert_eq!(array.value_as_string(1), format!("{digits}.0"));
+        assert_eq!(array.value_as_string(2), "-2.0");
+
+        // Without widening, the declared Decimal128 type is kept and the value is an error
+        let mut field = Field::new("n", DataType::Decimal128(38, 0), true);
+        let mut builder = map_data_type_to_array_builder(field.data_type());
+        assert!(matches!(
+            append_numeric_value(&mut builder, &mut field, Some(numeric(digits, 0)), false),
+            Err(Error::FailedToConvertBigDecimalToI128 { .. })
+        ));
+    }
+
+    #[test]
+    fn test_big_decimal_special_values() {
+        // 'NaN'::numeric has no digits, a weight and scale of 0, and the NaN sign
+        let nan_raw = [0, 0, 0, 0, 0xC0, 0, 0, 0];
+        let Err(err) = BigDecimalFromSql::from_sql(&Type::NUMERIC, &nan_raw) else {
+            panic!("NaN can't be read as a BigDecimal");
+        };
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnsupportedNumericSpecialValue { value: "NaN" })
+        ));
+
+        let infinity_raw = [0, 0, 0, 0, 0xD0, 0, 0, 0];
+        let Err(err) = BigDecimalFromSql::from_sql(&Type::NUMERIC, &infinity_raw) else {
+            panic!("Infinity can't be read as a BigDecimal");
+        };
+        assert_eq!(
+            err.to_string(),
+            "Postgres NUMERIC value Infinity cannot be represented as a Decimal. Filter it out or cast the column to float8 in the query."
+        );
+
+        // A truncated header is an error instead of a panic
+        assert!(BigDecimalFromSql::from_sql(&Type::NUMERIC, &[0, 1, 0]).is_err());
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[test]
+    fn test_big_decimal_256() {
+        let digits = "12345678901234567890123456789012345678901234567890";
+
+        // 50 digits are sent as 13 base 10000 digits, the most significant one holding the leftover "12"
+        let mut raw_u16: Vec<u16> = vec![13, 12, 0, 0, 12];
+        raw_u16.extend(digits.as_bytes()[2..].chunks(4).map(|chunk| {
+            std::str::from_utf8(chunk)
+                .expect("ascii digits")
+                .parse::<u16>()
+                .expect("base 10000 digit")
+        }));
+        let raw: Vec<u8> = raw_u16
+            .iter()
+            .flat_map(|&x| vec![(x >> 8) as u8, x as u8])
+            .collect();
+
+        let value = BigDecimalFromSql::from_sql(&Type::NUMERIC, raw.as_slice())
+            .expect("Failed to run FromSql");
+        assert_eq!(
+            value.inner,
+            BigDecimal::from_str(digits).expect("valid big decimal")
+        );
+        assert!(value.to_decimal_128_with_scale(0).is_none());
+        assert_eq!(numeric_data_type(Some(&value)), DataType::Decimal256(76, 0));
+
+        let mut builder = map_data_type_to_array_builder(&numeric_data_type(Some(&value)));
+        let Some(dec_builder) = builder.as_any_mut().downcast_mut::<Decimal256Builder>() else {
+            panic!("expected a Decimal256Builder");
+        };
+        dec_builder.append_value(
+            value
+                .to_decimal_256_with_scale(0)
+                .expect("value fits in i256"),
+        );
+
+        let negative = BigDecimalFromSql {
+            inner: BigDecimal::from_str(&format!("-{digits}")).expect("valid big decimal"),
+            scale: 0,
+        };
+        dec_builder.append_value(
+            negative
+                .to_decimal_256_with_scale(0)
+                .expect("value fits in i256"),
+        );
+
+        let array = builder.finish();
+        let array = array.as_primitive::<Decimal256Type>();
+        assert_eq!(array.value_as_string(0), digits);
+        assert_eq!(array.value_as_string(1), format!("-{digits}"));
+
+        // Values that fit keep using Decimal128
+        let small = BigDecimalFromSql {
+            inner: BigDecimal::from_str("1.50").expect("valid big decimal"),
+            scale: 2,
+        };
+        assert_eq!(numeric_data_type(Some(&small)), DataType::Decimal128(38, 2));
+        assert_eq!(numeric_data_type(None), DataType::Decimal128(38, 0));
+    }
+
+    #[test]
+    fn test_numeric_type_modifier() {
+        let options = ArrowConversionOptions::default();
+        // numeric(10, 2)
+        assert_eq!(
+            numeric_type_modifier_to_data_type(((10 << 16) | 2) + 4, &options),
+            DataType::Decimal128(10, 2)
+        );
+        // numeric(50, 5)
+        assert_eq!(
+            numeric_type_modifier_to_data_type(((50 << 16) | 5) + 4, &options),
+            DataType::Decimal256(50, 5)
+        );
+        // numeric(5, -2)
+        assert_eq!(
+            numeric_type_modifier_to_data_type(((5 << 16) | (-2i32 & 0x7FF)) + 4, &options),
+            DataType::Decimal128(5, -2)
+        );
+        // Unconstrained numeric
+        assert_eq!(
+            numeric_type_modifier_to_data_type(-1, &options),
+            DataType::Decimal128(38, 10)
+        );
+
+        // The configured defaults only apply to unconstrained numerics
+        let options = ArrowConversionOptions::new()
+            .with_default_numeric_precision(20)
+            .with_default_numeric_scale(4);
+        assert_eq!(
+            numeric_type_modifier_to_data_type(-1, &options),
+            DataType::Decimal128(20, 4)
+        );
+        assert_eq!(
+            numeric_type_modifier_to_data_type(((10 << 16) | 2) + 4, &options),
+            DataType::Decimal128(10, 2)
+        );
+        assert_eq!(
+            numeric_type_modifier_to_data_type(
+                -1,
+                &ArrowConversionOptions::new().with_default_numeric_precision(50)
+            ),
+            DataType::Decimal256(50, 10)
+        );
+        assert_eq!(
+            numeric_type_modifier_to_data_type(
+                -1,
+                &ArrowConversionOptions::new().with_default_numeric_scale(6)
+            ),
+            DataType::Decimal128(38, 6)
+        );
+        // The scale can't exceed the precision
+        assert_eq!(
+            numeric_type_modifier_to_data_type(
+                -1,
+                &ArrowConversionOptions::new()
+                    .with_default_numeric_precision(5)
+                    .with_default_numeric_scale(8)
+            ),
+            DataType::Decimal128(5, 5)
+        );
+    }
+
+    #[test]
+    fn test_numeric_array_scale_across_rows() {
+        let numeric = |v: &str, scale: u16| {
+            Some(BigDecimalFromSql {
+                inner: BigDecimal::from_str(v).expect("valid big decimal"),
+                scale,
+            })
+        };
+        let options = ArrowConversionOptions::default();
+
+        // numeric(10, 2)[] is read with the declared scale, whatever the scale of the first row
+        let data_type = map_column_type_to_data_type_with_modifier(
+            &Type::NUMERIC_ARRAY,
+            ((10 << 16) | 2) + 4,
+            &options,
+        )
+        .expect("numeric[] data type");
+        let DataType::List(item) = &data_type else {
+            panic!("expected a list, got {data_type}");
+        };
+        assert_eq!(*item.data_type(), DataType::Decimal128(38, 2));
+
+        // An unconstrained numeric[] is read with the unconstrained numeric scale
+        let data_type = numeric_array_data_type(-1, &options);
+        let DataType::List(item) = &data_type else {
+            panic!("expected a list, got {data_type}");
+        };
+        assert_eq!(
+            *item.data_type(),
+            DataType::Decimal128(38, UNCONSTRAINED_NUMERIC_SCALE)
+        );
+
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(list_builder) = builder
+            .as_any_mut()
+            .downcast_mut::<ListBuilder<Decimal128Builder>>()
+        else {
+            panic!("expected a ListBuilder<Decimal128Builder>");
+        };
+        for v in [
+            Some(vec![numeric("1", 0), None]),
+            Some(vec![numeric("3.14159", 5), numeric("2.5", 1)]),
+            None,
+        ] {
+            append_numeric_array_value(list_builder, v, UNCONSTRAINED_NUMERIC_SCALE)
+                .expect("value is appended");
+        }
+
+        // The fractional digits of later rows aren't truncated to the scale of the first
+        let array = builder.finish();
+        let array = array.as_list::<i32>();
+        let first = array.value(0);
+        let first = first.as_primitive::<Decimal128Type>();
+        assert_eq!(first.value_as_string(0), "1.0000000000");
+        assert!(first.is_null(1));
+        let second = array.value(1);
+        let second = second.as_primitive::<Decimal128Type>();
+        assert_eq!(second.value_as_string(0), "3.1415900000");
+        assert_eq!(second.value_as_string(1), "2.5000000000");
+        assert!(array.is_null(2));
+    }
+
+    #[test]
+    fn test_numeric_and_money_scales_match() {
+        let options = ArrowConversionOptions::default();
+        let money = map_column_type_to_data_type_with_modifier(&Type::MONEY, -1, &options);
+        let numeric = map_column_type_to_data_type_with_modifier(
+            &Type::NUMERIC,
+            ((10 << 16) | 2) + 4,
+            &options,
+        );
+        assert_eq!(money, Some(DataType::Decimal128(38, 2)));
+        assert_eq!(numeric, Some(DataType::Decimal128(10, 2)));
+
+        // Unconstrained and negative scales are inspected from the rows
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::NUMERIC, -1, &options),
+            None
+        );
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(
+                &Type::NUMERIC,
+                ((5 << 16) | (-2i32 & 0x7FF)) + 4,
+                &options
+            ),
+            None
+        );
+
+        // With a configured default, unconstrained numerics are read with it instead
+        let options = ArrowConversionOptions::new().with_default_numeric_scale(4);
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::NUMERIC, -1, &options),
+            Some(DataType::Decimal128(38, 4))
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        for pg_type in [Type::JSON, Type::JSONB] {
+            assert_eq!(map_column_type_to_data_type(&pg_type), Some(DataType::Utf8));
+        }
+
+        // JSONB is sent as a version byte followed by the JSON text
+        let value =
+            serde_json::Value::from_sql(&Type::JSONB, b"\x01{\"a\": 1}").expect("valid jsonb");
+        let round_tripped: serde_json::Value =
+            serde_json::from_str(&value.to_string()).expect("valid json");
+        assert_eq!(round_tripped, serde_json::json!({"a": 1}));
+
+        let value = serde_json::Value::from_sql(&Type::JSON, b"{\"a\": 1}").expect("valid json");
+        assert_eq!(value.to_string(), r#"{"a":1}"#);
+    }
+
+    /// Encodes a one-dimensional array in the Postgres binary format.
+    fn array_value(element_type: &Type, elements: &[Option<&[u8]>]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        raw.extend_from_slice(&1_i32.to_be_bytes()); // dimensions
+        raw.extend_from_slice(&i32::from(elements.contains(&None)).to_be_bytes());
+        raw.extend_from_slice(&element_type.oid().to_be_bytes());
+        raw.extend_from_slice(
+            &i32::try_from(elements.len())
+                .expect("length fits in i32")
+                .to_be_bytes(),
+        );
+        raw.extend_from_slice(&1_i32.to_be_bytes()); // lower bound
+        for element in elements {
+            match element {
+                Some(element) => {
+                    raw.extend_from_slice(
+                        &i32::try_from(element.len())
+                            .expect("length fits in i32")
+                            .to_be_bytes(),
+                    );
+                    raw.extend_from_slice(element);
+                }
+                None => raw.extend_from_slice(&(-1_i32).to_be_bytes()),
+            }
+        }
+        raw
+    }
+
+    #[test]
+    fn test_array_element_type_override() {
+        let semver = Type::new(
+            "semver".to_string(),
+            90_001,
+            Kind::Simple,
+            "public".to_string(),
+        );
+        let semver_array = Type::new(
+            "_semver".to_string(),
+            90_002,
+            Kind::Array(semver.clone()),
+            "public".to_string(),
+        );
+
+        let options =
+            ArrowConversionOptions::new().with_array_element_type("semver", ArrayElementType::Utf8);
+        let data_type = map_column_type_to_data_type_with_modifier(&semver_array, -1, &options)
+            .expect("overridden array type");
+        assert_eq!(
+            data_type,
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)))
+        );
+
+        // Other array types keep their built-in mapping
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::INT4_ARRAY, -1, &options),
+            map_column_type_to_data_type(&Type::INT4_ARRAY)
+        );
+
+        let raw = array_value(&semver, &[Some(b"1.2.3"), None]);
+        let value = Vec::<Option<UntypedTextFromSql>>::from_sql(&semver_array, &raw)
+            .expect("valid semver array");
+
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(list_builder) = builder
+            .as_any_mut()
+            .downcast_mut::<ListBuilder<StringBuilder>>()
+        else {
+            panic!("expected a ListBuilder<StringBuilder>");
+        };
+        list_builder.append_value(value.into_iter().map(|v| v.map(|v| v.0)));
+
+        let array = builder.finish();
+        let values = array.as_list::<i32>().value(0);
+        let values = values.as_string::<i32>();
+        assert_eq!(values.value(0), "1.2.3");
+        assert!(values.is_null(1));
+    }
+
+    /// Encodes a composite value in the Postgres binary format.
+    fn composite_value(fields: &[(&Type, Option<&[u8]>)]) -> Vec<u8> {
+        let mut raw = i32::try_from(fields.len())
+            .expect("field count fits in i32")
+            .to_be_bytes()
+            .to_vec();
+        for (field_type, value) in fields {
+            raw.extend_from_slice(&field_type.oid().to_be_bytes());
+            match value {
+                Some(value) => {
+                    raw.extend_from_slice(
+                        &i32::try_from(value.len())
+                            .expect("length fits in i32")
+                            .to_be_bytes(),
+                    );
+                    raw.extend_from_slice(value);
+                }
+                None => raw.extend_from_slice(&(-1_i32).to_be_bytes()),
+            }
+        }
+        raw
+    }
+
+    #[test]
+    fn test_nested_composite() {
+        // CREATE TYPE address AS (street text, zip int4)
+        // CREATE TYPE person AS (name text, home address)
+        let address = Type::new(
+            "address".to_string(),
+            90_004,
+            Kind::Composite(vec![
+                tokio_postgres::types::Field::new("street".to_string(), Type::TEXT),
+                tokio_postgres::types::Field::new("zip".to_string(), Type::INT4),
+            ]),
+            "public".to_string(),
+        );
+        let person = Type::new(
+            "person".to_string(),
+            90_005,
+            Kind::Composite(vec![
+                tokio_postgres::types::Field::new("name".to_string(), Type::TEXT),
+                tokio_postgres::types::Field::new("home".to_string(), address.clone()),
+            ]),
+            "public".to_string(),
+        );
+
+        let data_type = map_column_type_to_data_type(&person).expect("composite data type");
+        let DataType::Struct(struct_fields) = &data_type else {
+            panic!("expected a struct");
+        };
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(struct_builder) = builder.as_any_mut().downcast_mut::<StructBuilder>() else {
+            panic!("expected a StructBuilder");
+        };
+
+        // ('alice', ('1 Main St', 98101)), ('bob', NULL), NULL
+        let home = composite_value(&[
+            (&Type::TEXT, Some(b"1 Main St")),
+            (&Type::INT4, Some(&98_101_i32.to_be_bytes())),
+        ]);
+        let alice = composite_value(&[(&Type::TEXT, Some(b"alice")), (&address, Some(&home))]);
+        let bob = composite_value(&[(&Type::TEXT, Some(b"bob")), (&address, None)]);
+        for raw in [Some(alice.as_slice()), Some(bob.as_slice()), None] {
+            let value = raw.map(|raw| CompositeType::from_sql(&person, raw).expect("valid person"));
+            append_composite_value(struct_builder, struct_fields, &person, value.as_ref())
+                .expect("composite appended");
+        }
+
+        let array = builder.finish();
+        let people = array.as_struct();
+        assert!(people.is_valid(0));
+        assert!(people.is_valid(1));
+        assert!(people.is_null(2));
+
+        let names = people
+            .column_by_name("name")
+            .expect("name")
+            .as_string::<i32>();
+        assert_eq!(names.value(0), "alice");
+        assert_eq!(names.value(1), "bob");
+
+        let homes = people.column_by_name("home").expect("home").as_struct();
+        assert!(homes.is_valid(0));
+        assert!(homes.is_null(1));
+        assert_eq!(
+            homes
+                .column_by_name("street")
+                .expect("street")
+                .as_string::<i32>()
+                .value(0),
+            "1 Main St"
+        );
+        assert_eq!(
+            homes
+                .column_by_name("zip")
+                .expect("zip")
+                .as_primitive::<Int32Type>()
+                .value(0),
+            98_101
+        );
+    }
+
+    #[test]
+    fn test_composite_fields_are_matched_by_name() {
+        // CREATE TYPE pair AS (first int4, second text), with the fields returned in the opposite order
+        let pair = Type::new(
+            "pair".to_string(),
+            90_006,
+            Kind::Composite(vec![
+                tokio_postgres::types::Field::new("second".to_string(), Type::TEXT),
+                tokio_postgres::types::Field::new("first".to_string(), Type::INT4),
+            ]),
+            "public".to_string(),
+        );
+        let struct_fields = Fields::from(vec![
+            Field::new("first", DataType::Int32, true),
+            Field::new("second", DataType::Utf8, true),
+        ]);
+        let mut builder = map_data_type_to_array_builder(&DataType::Struct(struct_fields.clone()));
+        let Some(struct_builder) = builder.as_any_mut().downcast_mut::<StructBuilder>() else {
+            panic!("expected a StructBuilder");
+        };
+
+        let raw = composite_value(&[
+            (&Type::TEXT, Some(b"two")),
+            (&Type::INT4, Some(&1_i32.to_be_bytes())),
+        ]);
+        let value = CompositeType::from_sql(&pair, &raw).expect("valid pair");
+        append_composite_value(struct_builder, &struct_fields, &pair, Some(&value))
+            .expect("composite appended");
+
+        let array = builder.finish();
+        let pairs = array.as_struct();
+        assert_eq!(pairs.column(0).as_primitive::<Int32Type>().value(0), 1);
+        assert_eq!(pairs.column(1).as_string::<i32>().value(0), "two");
+
+        // A struct field without a composite field of the same name is an error
+        let struct_fields = Fields::from(vec![Field::new("third", DataType::Int32, true)]);
+        let mut builder = map_data_type_to_array_builder(&DataType::Struct(struct_fields.clone()));
+        let Some(struct_builder) = builder.as_any_mut().downcast_mut::<StructBuilder>() else {
+            panic!("expected a StructBuilder");
+        };
+        assert!(matches!(
+            append_composite_value(struct_builder, &struct_fields, &pair, Some(&value)),
+            Err(Error::NoCompositeField { field_name, .. }) if field_name == "third"
+        ));
+    }
+
+    fn hstore_type() -> Type {
+        Type::new(
+            "hstore".to_string(),
+            90_007,
+            Kind::Simple,
+            "public".to_string(),
+        )
+    }
+
+    /// Encodes an hstore value in the Postgres binary format.
+    fn hstore_value(pairs: &[(&str, Option<&str>)]) -> Vec<u8> {
+        let len = |value: &str| {
+            i32::try_from(value.len())
+                .expect("length fits in i32")
+                .to_be_bytes()
+        };
+        let mut raw = i32::try_from(pairs.len())
+            .expect("pair count fits in i32")
+            .to_be_bytes()
+            .to_vec();
+        for (key, value) in pairs {
+            raw.extend_from_slice(&len(key));
+            raw.extend_from_slice(key.as_bytes());
+            match value {
+                Some(value) => {
+                    raw.extend_from_slice(&len(value));
+                    raw.extend_from_slice(value.as_bytes());
+                }
+                None => raw.extend_from_slice(&(-1_i32).to_be_bytes()),
+            }
+        }
+        raw
+    }
+
+    type HstorePairs = Vec<(String, Option<String>)>;
+
+    /// The pairs of each map in `maps`, or `None` for a null map.
+    fn hstore_pairs(maps: &MapArray) -> Vec<Option<HstorePairs>> {
+        (0..maps.len())
+            .map(|i| {
+                maps.is_valid(i).then(|| {
+                    let entries = maps.value(i);
+                    let keys = entries.column(0).as_string::<i32>();
+                    let values = entries.column(1).as_string::<i32>();
+                    keys.iter()
+                        .zip(values.iter())
+                        .map(|(key, value)| {
+                            (
+                                key.expect("keys are not null").to_string(),
+                                value.map(str::to_string),
+                            )
+                        })
+                        .collect()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hstore_array() {
+        let hstore = hstore_type();
+        let hstore_array = Type::new(
+            "_hstore".to_string(),
+            90_008,
+            Kind::Array(hstore.clone()),
+            "public".to_string(),
+        );
+
+        let data_type = map_column_type_to_data_type(&hstore_array).expect("hstore[] data type");
+        assert_eq!(
+            data_type,
+            DataType::List(Arc::new(Field::new("item", hstore_data_type(), true)))
+        );
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(list_builder) = builder
+            .as_any_mut()
+            .downcast_mut::<ListBuilder<HstoreBuilder>>()
+        else {
+            panic!("expected a ListBuilder of HstoreBuilder");
+        };
+
+        // ARRAY['a=>1, b=>NULL', NULL, ''], NULL
+        let first = hstore_value(&[("a", Some("1")), ("b", None)]);
+        let empty = hstore_value(&[]);
+        let elements = [Some(first.as_slice()), None, Some(empty.as_slice())]
+            .into_iter()
+            .map(|raw| raw.map(|raw| HstoreFromSql::from_sql(&hstore, raw).expect("valid hstore")))
+            .collect();
+        append_hstore_array_value(list_builder, Some(elements)).expect("hstore[] appended");
+        append_hstore_array_value(list_builder, None).expect("null hstore[] appended");
+
+        let array = builder.finish();
+        assert_eq!(*array.data_type(), data_type);
+        let lists = array.as_list::<i32>();
+        assert!(lists.is_null(1));
+        assert_eq!(
+            hstore_pairs(lists.value(0).as_map()),
+            vec![
+                Some(vec![
+                    ("a".to_string(), Some("1".to_string())),
+                    ("b".to_string(), None)
+                ]),
+                None,
+                Some(vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_composite_with_hstore() {
+        // CREATE TYPE tagged AS (name text, tags hstore)
+        let hstore = hstore_type();
+        let tagged = Type::new(
+            "tagged".to_string(),
+            90_009,
+            Kind::Composite(vec![
+                tokio_postgres::types::Field::new("name".to_string(), Type::TEXT),
+                tokio_postgres::types::Field::new("tags".to_string(), hstore.clone()),
+            ]),
+            "public".to_string(),
+        );
+
+        let data_type = map_column_type_to_data_type(&tagged).expect("composite data type");
+        let DataType::Struct(struct_fields) = &data_type else {
+            panic!("expected a struct");
+        };
+        assert_eq!(struct_fields[1].data_type(), &hstore_data_type());
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(struct_builder) = builder.as_any_mut().downcast_mut::<StructBuilder>() else {
+            panic!("expected a StructBuilder");
+        };
+
+        // ('x', 'color=>red'), ('y', NULL), NULL
+        let tags = hstore_value(&[("color", Some("red"))]);
+        let x = composite_value(&[(&Type::TEXT, Some(b"x")), (&hstore, Some(&tags))]);
+        let y = composite_value(&[(&Type::TEXT, Some(b"y")), (&hstore, None)]);
+        for raw in [Some(x.as_slice()), Some(y.as_slice()), None] {
+            let value = raw.map(|raw| CompositeType::from_sql(&tagged, raw).expect("valid tagged"));
+            append_composite_value(struct_builder, struct_fields, &tagged, value.as_ref())
+                .expect("composite appended");
+        }
+
+        let array = builder.finish();
+        let tagged = array.as_struct();
+        assert!(tagged.is_null(2));
+        assert_eq!(
+            hstore_pairs(tagged.column_by_name("tags").expect("tags").as_map()),
+            vec![
+                Some(vec![("color".to_string(), Some("red".to_string()))]),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_hstore() {
+        let hstore = hstore_type();
+        let mut truncated = hstore_value(&[("key", Some("value"))]);
+        truncated.pop();
+        assert!(HstoreFromSql::from_sql(&hstore, &truncated).is_err());
+
+        let mut null_key = 1_i32.to_be_bytes().to_vec();
+        null_key.extend_from_slice(&(-1_i32).to_be_bytes());
+        null_key.extend_from_slice(&(-1_i32).to_be_bytes());
+        assert!(HstoreFromSql::from_sql(&hstore, &null_key).is_err());
+    }
+
+    #[test]
+    fn test_convert_in_batches() {
+        let rows = (0..2500).collect::<Vec<i64>>();
+        let convert = |rows: &[i64]| {
+            // The first row decides the scale, like a NUMERIC column
+            let scale = if rows[0] == 0 { 2 } else { 4 };
+            let values = rows
+                .iter()
+                .map(|v| i128::from(*v) * 10_i128.pow(scale))
+                .collect::<Decimal128Array>()
+                .with_precision_and_scale(38, i8::try_from(scale).expect("scale fits in i8"))
+                .expect("valid decimal");
+            let schema = Schema::new(vec![Field::new("v", values.data_type().clone(), true)]);
+            RecordBatch::try_new(Arc::new(schema), vec![Arc::new(values)])
+                .context(FailedToBuildRecordBatchSnafu)
+        };
+
+        let batches = convert_in_batches(&rows, 1000, convert).expect("rows converted");
+        assert_eq!(
+            batches
+                .iter()
+                .map(RecordBatch::num_rows)
+                .collect::<Vec<_>>(),
+            vec![1000, 1000, 500]
+        );
+        for batch in &batches {
+            assert_eq!(batch.schema(), batches[0].schema());
+        }
+        let last = batches[2].column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(last.value(499), 249_900);
+
+        assert!(convert_in_batches(&[] as &[i64], 1000, convert)
+            .expect("no rows converted")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_convert_in_batches_nullability() {
+        // A NOT NULL column that holds NULLs in the second chunk only, like on the nullable side of an outer join
+        let rows = [Some(1), Some(2), None, Some(4)];
+        let convert = |rows: &[Option<i32>]| {
+            let values = arrow::array::Int32Array::from(rows.to_vec());
+            let nullable = values.null_count() > 0;
+            let schema = Schema::new(vec![Field::new("v", DataType::Int32, nullable)]);
+            RecordBatch::try_new(Arc::new(schema), vec![Arc::new(values)])
+                .context(FailedToBuildRecordBatchSnafu)
+        };
+
+        let batches = convert_in_batches(&rows, 2, convert).expect("rows converted");
+        assert_eq!(batches.len(), 2);
+        for batch in &batches {
+            assert!(batch.schema().field(0).is_nullable());
+        }
+
+        // Without any NULL, the column stays not nullable
+        let batches =
+            convert_in_batches(&[Some(1), Some(2), Some(3)], 2, convert).expect("rows converted");
+        for batch in &batches {
+            assert!(!batch.schema().field(0).is_nullable());
+        }
+    }
+
+    #[test]
+    fn test_enum() {
+        // CREATE TYPE mood AS ENUM ('sad', 'happy')
+        let mood = Type::new(
+            "mood".to_string(),
+            90_003,
+            Kind::Enum(vec!["sad".to_string(), "happy".to_string()]),
+            "public".to_string(),
+        );
+        let data_type = map_column_type_to_data_type(&mood).expect("enum data type");
+        assert_eq!(
+            data_type,
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+        );
+
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(dictionary_builder) = builder
+            .as_any_mut()
+            .downcast_mut::<StringDictionaryBuilder<Int32Type>>()
+        else {
+            panic!("expected a StringDictionaryBuilder<Int32Type>");
+        };
+        for raw in [
+            Some(b"happy".as_slice()),
+            Some(b"sad"),
+            None,
+            Some(b"happy"),
+        ] {
+            match raw {
+                Some(raw) => {
+                    let label = UntypedTextFromSql::from_sql(&mood, raw).expect("valid label");
+                    dictionary_builder.append_value(label.0);
+                }
+                None => dictionary_builder.append_null(),
+            }
+        }
+
+        let array = builder.finish();
+        let array = array.as_dictionary::<Int32Type>();
+        assert_eq!(array.values().len(), 2);
+        let labels = array
+            .downcast_dict::<StringArray>()
+            .expect("string dictionary");
+        assert_eq!(labels.value(0), "happy");
+        assert_eq!(labels.value(1), "sad");
+        assert!(labels.is_null(2));
+        assert_eq!(array.keys().value(0), array.keys().value(3));
+    }
+
+    #[test]
+    fn test_character_arrays() {
+        let list_of_utf8 = DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)));
+        assert_eq!(
+            map_column_type_to_data_type(&Type::VARCHAR_ARRAY),
+            Some(list_of_utf8.clone())
+        );
+        assert_eq!(
+            map_column_type_to_data_type(&Type::BPCHAR_ARRAY),
+            Some(list_of_utf8.clone())
+        );
+
+        // ARRAY['a','b']::varchar[]
+        let raw = array_value(&Type::VARCHAR, &[Some(b"a"), Some(b"b")]);
+        let value = Vec::<Option<String>>::from_sql(&Type::VARCHAR_ARRAY, &raw)
+            .expect("valid varchar array");
+
+        let mut builder = map_data_type_to_array_builder(&list_of_utf8);
+        let Some(list_builder) = builder
+            .as_any_mut()
+            .downcast_mut::<ListBuilder<StringBuilder>>()
+        else {
+            panic!("expected a ListBuilder<StringBuilder>");
+        };
+        list_builder.append_value(value);
+
+        let array = builder.finish();
+        let values = array.as_list::<i32>().value(0);
+        let values = values.as_string::<i32>();
+        assert_eq!(values.value(0), "a");
+        assert_eq!(values.value(1), "b");
+    }
+
+    #[test]
+    fn test_bytea() {
+        assert_eq!(
+            map_column_type_to_data_type(&Type::BYTEA),
+            Some(DataType::Binary)
+        );
+
+        // '\xDEADBEEF'::bytea is sent as the raw bytes
+        let raw = [0xDE, 0xAD, 0xBE, 0xEF];
+        let value = Vec::<u8>::from_sql(&Type::BYTEA, &raw).expect("valid bytea");
+
+        let mut builder = map_data_type_to_array_builder(&DataType::Binary);
+        let Some(binary_builder) = builder.as_any_mut().downcast_mut::<BinaryBuilder>() else {
+            panic!("expected a BinaryBuilder");
+        };
+        binary_builder.append_value(value);
+        binary_builder.append_null();
+
+        let array = builder.finish();
+        let array = array.as_binary::<i32>();
+        assert_eq!(array.value(0), raw);
+        assert!(array.is_null(1));
+
+        let Some(DataType::List(item)) = map_column_type_to_data_type(&Type::BYTEA_ARRAY) else {
+            panic!("expected a list type");
+        };
+        let mut builder = map_data_type_to_array_builder(&DataType::List(item));
+        let Some(list_builder) = builder
+            .as_any_mut()
+            .downcast_mut::<ListBuilder<BinaryBuilder>>()
+        else {
+            panic!("expected a ListBuilder<BinaryBuilder>");
+        };
+        list_builder.append_value([Some(raw.to_vec())]);
+
+        let array = builder.finish();
+        let values = array.as_list::<i32>().value(0);
+        assert_eq!(values.as_binary::<i32>().value(0), raw);
+    }
+
+    #[test]
+    fn test_timestamp_precision() {
+        let options = ArrowConversionOptions::default();
+        for (pg_type, time_zone) in [
+            (Type::TIMESTAMP, None),
+            (Type::TIMESTAMPTZ, Some("UTC".into())),
+        ] {
+            assert_eq!(
+                map_column_type_to_data_type_with_modifier(&pg_type, 6, &options),
+                Some(DataType::Timestamp(
+                    TimeUnit::Microsecond,
+                    time_zone.clone()
+                ))
+            );
+            assert_eq!(
+                map_column_type_to_data_type_with_modifier(&pg_type, 3, &options),
+                Some(DataType::Timestamp(
+                    TimeUnit::Millisecond,
+                    time_zone.clone()
+                ))
+            );
+            // No declared precision
+            assert_eq!(
+                map_column_type_to_data_type_with_modifier(&pg_type, -1, &options),
+                Some(DataType::Timestamp(TimeUnit::Millisecond, time_zone))
+            );
+        }
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::INT4, -1, &options),
+            Some(DataType::Int32)
+        );
+    }
+
+    #[test]
+    fn test_money() {
+        let data_type = map_column_type_to_data_type(&Type::MONEY).expect("money data type");
+        assert_eq!(data_type, DataType::Decimal128(38, 2));
+
+        // '$12.34'::money
+        let money =
+            MoneyFromSql::from_sql(&Type::MONEY, &1234_i64.to_be_bytes()).expect("valid money");
+        assert_eq!(money.0, 1234);
+        assert!(MoneyFromSql::from_sql(&Type::MONEY, &[0, 1]).is_err());
+
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(decimal_builder) = builder.as_any_mut().downcast_mut::<Decimal128Builder>() else {
+            panic!("expected a Decimal128Builder");
+        };
+        decimal_builder.append_option(Some(i128::from(money.0)));
+        decimal_builder.append_option(None);
+
+        let array = builder.finish();
+        let array = array.as_primitive::<Decimal128Type>();
+        assert_eq!(array.value_as_string(0), "12.34");
+        assert!(array.is_null(1));
+    }
+
+    #[test]
+    fn test_bool_as_int() {
+        let options = ArrowConversionOptions::new().with_bool_as_int(true);
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::BOOL, -1, &options),
+            Some(DataType::Int8)
+        );
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(
+                &Type::BOOL,
+                -1,
+                &ArrowConversionOptions::default()
+            ),
+            Some(DataType::Boolean)
+        );
+
+        let mut builder = map_data_type_to_array_builder(&DataType::Int8);
+        for raw in [Some([1]), Some([0]), None] {
+            let value = raw.map(|raw| bool::from_sql(&Type::BOOL, &raw).expect("valid bool"));
+            append_bool_value(builder.as_mut(), value).expect("value is appended");
+        }
+
+        let array = builder.finish();
+        let array = array.as_primitive::<Int8Type>();
+        assert_eq!(array.value(0), 1);
+        assert_eq!(array.value(1), 0);
+        assert!(array.is_null(2));
+    }
+
+    #[test]
+    fn test_type_metadata() {
+        let field = Field::new("name", DataType::Utf8, true);
+        let options = ArrowConversionOptions::new().with_type_metadata(true);
+        let field = options.attach_type_metadata(field, &Type::VARCHAR, 68);
+        let metadata = field.metadata();
+        assert_eq!(metadata[PG_TYPE_METADATA_KEY], "varchar");
+        assert_eq!(metadata[PG_OID_METADATA_KEY], "1043");
+        assert_eq!(metadata[PG_TYPMOD_METADATA_KEY], "68");
+
+        let field = options.attach_type_metadata(
+            Field::new("tags", DataType::Utf8, true),
+            &Type::TEXT_ARRAY,
+            -1,
+        );
+        assert_eq!(field.metadata()[PG_TYPE_METADATA_KEY], "_text");
+        assert_eq!(field.metadata()[PG_TYPMOD_METADATA_KEY], "-1");
+
+        let field = ArrowConversionOptions::default().attach_type_metadata(
+            Field::new("name", DataType::Utf8, true),
+            &Type::VARCHAR,
+            68,
+        );
+        assert!(field.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_timestamptz_offset_columns() {
+        let options = ArrowConversionOptions::new()
+            .with_timestamptz_offset_columns(chrono_tz::America::New_York);
+        let fields = vec![
+            Field::new(
+                "created_at",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                true,
+            ),
+            Field::new(
+                "local_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+        ];
+        // 2024-01-15 12:00:00 UTC is in EST, 2024-07-15 12:00:00 UTC is in EDT
+        let instants = vec![
+            Some(1_705_320_000_000_000),
+            Some(1_721_044_800_000_000),
+            None,
+        ];
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(
+                PrimitiveArray::<TimestampMicrosecondType>::from(instants.clone())
+                    .with_timezone("UTC"),
+            ),
+            Arc::new(PrimitiveArray::<TimestampMicrosecondType>::from(instants)),
+        ];
+
+        let (fields, columns) = add_timestamptz_offset_columns(fields, columns, &options);
+
+        // Timestamps without a time zone have no offset
+        let names: Vec<&str> = fields.iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["created_at", "created_at_offset_seconds", "local_at"]
+        );
+        assert_eq!(*fields[1].data_type(), DataType::Int32);
+
+        let offsets = columns[1].as_primitive::<Int32Type>();
+        assert_eq!(offsets.value(0), -5 * 3600);
+        assert_eq!(offsets.value(1), -4 * 3600);
+        assert!(offsets.is_null(2));
+
+        // Without the option, no column is added
+        let (fields, _) = add_timestamptz_offset_columns(
+            fields[..1].to_vec(),
+            columns[..1].to_vec(),
+            &ArrowConversionOptions::default(),
+        );
+        assert_eq!(fields.len(), 1);
+    }
+
+    #[test]
+    fn test_oid() {
+        let data_type = map_column_type_to_data_type_with_modifier(
+            &Type::OID,
+            -1,
+            &ArrowConversionOptions::default(),
+        )
+        .expect("oid is supported");
+        assert_eq!(data_type, DataType::UInt32);
+
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let Some(oid_builder) = builder.as_any_mut().downcast_mut::<UInt32Builder>() else {
+            panic!("expected a UInt32Builder");
+        };
+        // OIDs are unsigned, i.e. values above i32::MAX are valid
+        let v = u32::from_sql(&Type::OID, &3_000_000_000u32.to_be_bytes()).expect("valid oid");
+        oid_builder.append_value(v);
+        let array = builder.finish();
+        assert_eq!(array.as_primitive::<UInt32Type>().value(0), 3_000_000_000);
+    }
+
+    #[test]
+    fn test_server_version_gated_types() {
+        // The server version is unknown by default, so nothing is rejected
+        let options = ArrowConversionOptions::default();
+        assert!(options.check_server_version(&Type::XID8).is_ok());
+
+        let options = ArrowConversionOptions::new().with_server_version_num(130_004);
+        assert!(options.check_server_version(&Type::XID8).is_ok());
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::XID8, -1, &options),
+            Some(DataType::UInt64)
+        );
+        let v = Xid8FromSql::from_sql(&Type::XID8, &u64::MAX.to_be_bytes()).expect("valid xid8");
+        assert_eq!(v.0, u64::MAX);
+
+        let options = ArrowConversionOptions::new().with_server_version_num(120_015);
+        assert!(options.check_server_version(&Type::INT8).is_ok());
+        let err = options
+            .check_server_version(&Type::XID8)
+            .expect_err("xid8 doesn't exist before Postgres 13");
+        assert!(matches!(
+            err,
+            Error::UnsupportedTypeForServerVersion {
+                min_version: 130_000,
+                server_version: 120_015,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Postgres type xid8 requires server version 130000 or later, but the server is version 120015"
+        );
+    }
+
+    #[test]
+    fn test_network_addresses() {
+        let default_options = ArrowConversionOptions::default();
+        let structured_options =
+            ArrowConversionOptions::new().with_structured_network_addresses(true);
+        assert_eq!(
+            map_column_type_to_data_type_with_modifier(&Type::MACADDR, -1, &default_options),
+            Some(DataType::Utf8)
+        );
+        for pg_type in [Type::INET, Type::CIDR] {
+            assert_eq!(
+                map_column_type_to_data_type_with_modifier(&pg_type, -1, &default_options),
+                Some(DataType::Utf8)
+            );
+            assert_eq!(
+                map_column_type_to_data_type_with_modifier(&pg_type, -1, &structured_options),
+                Some(DataType::Struct(network_address_fields()))
+            );
+        }
+
+        // '192.168.1.0/24'::cidr
+        let ipv4 =
+            InetFromSql::from_sql(&Type::CIDR, &[2, 24, 1, 4, 192, 168, 1, 0]).expect("valid cidr");
+        assert_eq!(ipv4.to_string(), "192.168.1.0/24");
+
+        // '2001:db8::1'::inet
+        let mut raw = vec![3, 128, 0, 16];
+        raw.extend(
+            "2001:db8::1"
+                .parse::<Ipv6Addr>()
+                .expect("valid ipv6")
+                .octets(),
+        );
+        let ipv6 = InetFromSql::from_sql(&Type::INET, &raw).expect("valid inet");
+        assert_eq!(ipv6.to_string(), "2001:db8::1");
+
+        let mut builder =
+            map_data_type_to_array_builder(&DataType::Struct(network_address_fields()));
+        let Some(struct_builder) = builder.as_any_mut().downcast_mut::<StructBuilder>() else {
+            panic!("expected a StructBuilder");
+        };
+        for value in [Some(&ipv4), Some(&ipv6), None] {
+            append_network_address_value(struct_builder, &Type::INET, value)
+                .expect("value is appended");
+        }
+
+        let array = builder.finish();
+        let array = array.as_struct();
+        let addr = array.column(0).as_binary::<i32>();
+        let prefix_len = array.column(1).as_primitive::<UInt8Type>();
+        let family = array.column(2).as_primitive::<UInt8Type>();
+
+        assert_eq!(addr.value(0), [192, 168, 1, 0]);
+        assert_eq!(prefix_len.value(0), 24);
+        assert_eq!(family.value(0), 4);
+
+        assert_eq!(addr.value(1), &raw[4..]);
+        assert_eq!(prefix_len.value(1), 128);
+        assert_eq!(family.value(1), 6);
+
+        assert!(array.is_null(2));
+
+        // '08:00:2b:01:02:03'::macaddr
+        let mac = MacAddrFromSql::from_sql(&Type::MACADDR, &[0x08, 0x00, 0x2b, 0x01, 0x02, 0x03])
+            .expect("valid macaddr");
+        assert_eq!(mac.to_string(), "08:00:2b:01:02:03");
+        assert!(MacAddrFromSql::from_sql(&Type::MACADDR, &[0x08, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_before_epoch() {
+        // '1969-12-31 23:59:59Z', sent as microseconds since 2000-01-01
+        let microseconds: i64 = -(946_684_800 + 1) * 1_000_000;
+        let value = SystemTime::from_sql(&Type::TIMESTAMPTZ, &microseconds.to_be_bytes())
+            .expect("valid timestamptz");
+        assert_eq!(
+            timestamp_since_epoch(value, TimeUnit::Millisecond).expect("timestamp fits"),
+            -1_000
+        );
+
+        let mut builder = TimestampMillisecondBuilder::new();
+        append_timestamp_value(&mut builder, Some(value)).expect("value is appended");
+        append_timestamp_value(&mut builder, None).expect("null is appended");
+        let array = builder.finish();
+        assert_eq!(array.len(), 2);
+        assert_eq!(array.value(0), -1_000);
+        assert!(array.is_null(1));
+
+        // Partial units before the epoch round down
+        let value = UNIX_EPOCH - Duration::from_micros(1);
+        assert_eq!(
+            timestamp_since_epoch(value, TimeUnit::Microsecond).expect("timestamp fits"),
+            -1
+        );
+        assert_eq!(
+            timestamp_since_epoch(value, TimeUnit::Millisecond).expect("timestamp fits"),
+            -1
+        );
+        assert_eq!(
+            timestamp_since_epoch(
+                UNIX_EPOCH + Duration::from_micros(1_500),
+                TimeUnit::Millisecond
+            )
+            .expect("timestamp fits"),
+            1
+        );
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eq!(
+            map_column_type_to_data_type(&Type::TIME),
+            Some(DataType::Time64(TimeUnit::Microsecond))
+        );
+
+        // TIME is sent as microseconds since midnight
+        let raw = 49_530_500_000i64.to_be_bytes();
+        let time = chrono::NaiveTime::from_sql(&Type::TIME, &raw).expect("valid time");
+        assert_eq!(
+            time,
+            chrono::NaiveTime::from_hms_milli_opt(13, 45, 30, 500).expect("valid time")
+        );
+        assert_eq!(time_to_microseconds(time), 49_530_500_000);
+    }
+
+    #[test]
+    fn test_interval() {
+        assert_eq!(
+            map_column_type_to_data_type(&Type::INTERVAL),
+            Some(DataType::Interval(IntervalUnit::MonthDayNano))
+        );
+
+        // '1 year 2 mons 3 days 04:05:06'
+        let microseconds: i64 = (4 * 3600 + 5 * 60 + 6) * 1_000_000;
+        let mut raw = microseconds.to_be_bytes().to_vec();
+        raw.extend(3i32.to_be_bytes());
+        raw.extend(14i32.to_be_bytes());
+
+        let interval = IntervalFromSql::from_sql(&Type::INTERVAL, &raw).expect("valid interval");
+        assert_eq!(
+            interval.to_month_day_nano().expect("interval fits"),
+            IntervalMonthDayNanoType::make_value(14, 3, 14_706_000_000_000)
+        );
+
+        let overflowing = IntervalFromSql {
+            months: 0,
+            days: 0,
+            microseconds: i64::MAX,
+        };
+        assert!(overflowing.to_month_day_nano().is_err());
+    }
+
+    #[test]
+    fn test_duplicate_column_names() {
+        let fields = vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("id_1", DataType::Int32, true),
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("id", DataType::Utf8, true),
+        ];
+        let names =
+            |fields: &[Field]| -> Vec<String> { fields.iter().map(|f| f.name().clone()).collect() };
+
+        let preserved =
+            resolve_duplicate_column_names(fields.clone(), DuplicateColumnNames::Preserve)
+                .expect("duplicates are preserved");
+        assert_eq!(names(&preserved), names(&fields));
+
+        let disambiguated =
+            resolve_duplicate_column_names(fields.clone(), DuplicateColumnNames::Disambiguate)
+                .expect("duplicates are disambiguated");
+        assert_eq!(
+            names(&disambiguated),
+            vec!["id", "id_1", "id_2", "name", "id_3"]
+        );
+        assert_eq!(disambiguated[2].data_type(), &DataType::Int64);
+
+        let Err(err) = resolve_duplicate_column_names(fields, DuplicateColumnNames::Error) else {
+            panic!("duplicates should be rejected");
+        };
+        assert!(matches!(err, Error::DuplicateColumnName { column_name } if column_name == "id"));
+    }
+
+    #[test]
+    fn test_exploded_interval() {
+        // '1 year 2 days 3 hours'
+        let interval = IntervalFromSql {
+            months: 12,
+            days: 2,
+            microseconds: 3 * 3600 * 1_000_000,
+        };
+        let fields = vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new(
+                "duration",
+                DataType::Interval(IntervalUnit::MonthDayNano),
+                true,
+            ),
+        ];
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(arrow::array::Int32Array::from(vec![1, 2])),
+            Arc::new(IntervalMonthDayNanoArray::from(vec![
+                Some(interval.to_month_day_nano().expect("interval fits")),
+                None,
+            ])),
+        ];
+
+        let options = ArrowConversionOptions::new().with_exploded_interval_column("duration");
+        let (fields, columns) = explode_interval_columns(fields, columns, &options);
+
+        let names: Vec<&str> = fields.iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["id", "duration_months", "duration_days", "duration_micros"]
+        );
+        assert_eq!(fields[1].data_type(), &DataType::Int32);
+        assert_eq!(fields[2].data_type(), &DataType::Int32);
+        assert_eq!(fields[3].data_type(), &DataType::Int64);
+
+        assert_eq!(columns[1].as_primitive::<Int32Type>().value(0), 12);
+        assert_eq!(columns[2].as_primitive::<Int32Type>().value(0), 2);
+        assert_eq!(
+            columns[3].as_primitive::<Int64Type>().value(0),
+            10_800_000_000
+        );
+        assert!(columns[1..].iter().all(|c| c.is_null(1)));
+
+        // Columns that aren't selected keep the interval type
+        let (fields, _) = explode_interval_columns(
+            vec![Field::new(
+                "duration",
+                DataType::Interval(IntervalUnit::MonthDayNano),
+                true,
+            )],
+            vec![Arc::new(IntervalMonthDayNanoArray::from(vec![None]))],
+            &ArrowConversionOptions::new(),
+        );
+        assert_eq!(
+            fields[0].data_type(),
+            &DataType::Interval(IntervalUnit::MonthDayNano)
+        );
+    }
+
+    fn range_bound(raw: &[u8]) -> Vec<u8> {
+        let mut bytes = i32::try_from(raw.len())
+            .expect("bound should fit in i32")
+            .to_be_bytes()
+            .to_vec();
+        bytes.extend_from_slice(raw);
+        bytes
+    }
+
+    fn range_to_struct_array(pg_type: &Type, raw: Option<&[u8]>) -> StructArray {
+        let data_type = map_column_type_to_data_type(pg_type).expect("range data type");
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let struct_builder = builder
+            .as_any_mut()
+            .downcast_mut::<StructBuilder>()
+            .expect("struct builder");
+        let range = raw.map(|raw| RangeType::from_sql(pg_type, raw).expect("valid range"));
+        append_range_value(struct_builder, pg_type, range.as_ref()).expect("range appended");
+        builder
+            .finish()
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .expect("struct array")
+            .clone()
+    }
+
+    #[test]
+    fn test_unsupported_range_element_type() {
+        let float_range = Type::new(
+            "floatrange".to_string(),
+            0,
+            Kind::Range(Type::FLOAT8),
+            "public".to_string(),
+        );
+
+        assert!(check_range_element_type(&Type::INT4_RANGE).is_ok());
+        assert!(matches!(
+            check_range_element_type(&float_range),
+            Err(Error::UnsupportedRangeElementType { element_type, .. }) if element_type == Type::FLOAT8
+        ));
+        assert_eq!(map_column_type_to_data_type(&float_range), None);
+
+        // Appending a value is an error rather than a panic
+        let data_type = map_column_type_to_data_type(&Type::INT4_RANGE).expect("range data type");
+        let mut builder = map_data_type_to_array_builder(&data_type);
+        let struct_builder = builder
+            .as_any_mut()
+            .downcast_mut::<StructBuilder>()
+            .expect("struct builder");
+        assert!(matches!(
+            append_range_value(struct_builder, &float_range, None),
+            Err(Error::UnsupportedRangeElementType { .. })
+        ));
+    }
+
+    fn range_flags(array: &StructArray) -> (bool, bool, bool) {
+        let flag = |name: &str| {
+            array
+                .column_by_name(name)
+                .expect(name)
+                .as_boolean()
+                .value(0)
+        };
+        (
+            flag("lower_inclusive"),
+            flag("upper_inclusive"),
+            flag("empty"),
+        )
+    }
+
+    #[test]
+    fn test_numrange() {
+        assert_eq!(
+            map_column_type_to_data_type(&Type::NUM_RANGE),
+            Some(DataType::Struct(range_fields(DataType::Decimal128(
+                38,
+                NUMRANGE_SCALE
+            ))))
+        );
+
+        // [1.5,10)
+        let mut raw = vec![0x02];
+        raw.extend(range_bound(&[0, 2, 0, 0, 0, 0, 0, 1, 0, 1, 0x13, 0x88]));
+        raw.extend(range_bound(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 10]));
+        let array = range_to_struct_array(&Type::NUM_RANGE, Some(&raw));
+
+        let lower = array.column(0).as_primitive::<Decimal128Type>();
+        let upper = array.column(1).as_primitive::<Decimal128Type>();
+        assert_eq!(lower.value(0), 15_000_000_000);
+        assert_eq!(upper.value(0), 100_000_000_000);
+        assert_eq!(range_flags(&array), (true, false, false));
+
+        // (,10]
+        let mut raw = vec![0x04 | 0x08];
+        raw.extend(range_bound(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 10]));
+        let array = range_to_struct_array(&Type::NUM_RANGE, Some(&raw));
+
+        assert!(array.column(0).is_null(0));
+        assert!(array.column(1).is_valid(0));
+        assert_eq!(range_flags(&array), (false, true, false));
+    }
+
+    #[test]
+    fn test_daterange() {
+        assert_eq!(
+            map_column_type_to_data_type(&Type::DATE_RANGE),
+            Some(DataType::Struct(range_fields(DataType::Date32)))
+        );
+
+        // [2000-01-11,) - dates are days since 2000-01-01
+        let mut raw = vec![0x02 | 0x10];
+        raw.extend(range_bound(&10i32.to_be_bytes()));
+        let array = range_to_struct_array(&Type::DATE_RANGE, Some(&raw));
+
+        let lower = array.column(0).as_primitive::<Date32Type>();
+        assert_eq!(
+            lower.value_as_date(0),
+            chrono::NaiveDate::from_ymd_opt(2000, 1, 11)
+        );
+        assert!(array.column(1).is_null(0));
+        assert_eq!(range_flags(&array), (true, false, false));
+
+        // empty
+        let array = range_to_struct_array(&Type::DATE_RANGE, Some(&[0x01]));
+        assert!(array.column(0).is_null(0));
+        assert!(array.column(1).is_null(0));
+        assert_eq!(range_flags(&array), (false, false, true));
+
+        let array = range_to_struct_array(&Type::DATE_RANGE, None);
+        assert!(array.is_null(0));
+    }
+
+    #[test]
+    fn test_tstzrange() {
+        assert_eq!(
+            map_column_type_to_data_type(&Type::TSTZ_RANGE),
+            Some(DataType::Struct(range_fields(DataType::Timestamp(
+                TimeUnit::Millisecond,
+                Some("UTC".into())
+            ))))
+        );
+
+        // (2000-01-01 00:00:01+00,2000-01-01 00:00:02+00] - timestamps are microseconds since 2000-01-01
+        let mut raw = vec![0x04];
+        raw.extend(range_bound(&1_000_000i64.to_be_bytes()));
+        raw.extend(range_bound(&2_000_000i64.to_be_bytes()));
+        let array = range_to_struct_array(&Type::TSTZ_RANGE, Some(&raw));
+
+        let lower = array.column(0).as_primitive::<TimestampMillisecondType>();
+        let upper = array.column(1).as_primitive::<TimestampMillisecondType>();
+        assert_eq!(lower.value(0), 946_684_801_000);
+        assert_eq!(upper.value(0), 946_684_802_000);
+        assert_eq!(range_flags(&array), (false, true, false));
+
+        // (,)
+        let array = range_to_struct_array(&Type::TSTZ_RANGE, Some(&[0x08 | 0x10]));
+        assert!(array.column(0).is_null(0));
+        assert!(array.column(1).is_null(0));
+        assert_eq!(range_flags(&array), (false, false, false));
     }
 }
@@ -0,0 +1,162 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+#![allow(clippy::module_name_repetitions)]
+
+use byteorder::{BigEndian, ReadBytesExt};
+use snafu::prelude::*;
+use std::ops::Range;
+use tokio_postgres::types::{FromSql, Kind, Type, WrongType};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    UnableToConvertType { source: WrongType },
+
+    #[snafu(display("Unable to convert raw bytes into expected type: {source}"))]
+    UnableToConvertBytesToType {
+        source: Box<dyn std::error::Error + Sync + Send>,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A `PostgreSQL` array with any number of dimensions, i.e. a 2-D `int[][]` value. Unlike `Vec<T>`, which only
+/// accepts one-dimensional arrays, the dimensions are kept. The elements are decoded lazily using
+/// `ArrayType::elements`.
+pub struct ArrayType<'a> {
+    element_type: Type,
+    body: &'a [u8],
+    dimensions: Vec<usize>,
+    elements: Vec<Option<Range<usize>>>,
+}
+
+impl<'a> FromSql<'a> for ArrayType<'a> {
+    fn from_sql(
+        type_: &Type,
+        body: &'a [u8],
+    ) -> Result<ArrayType<'a>, Box<dyn std::error::Error + Sync + Send>> {
+        let Kind::Array(ref element_type) = *type_.kind() else {
+            return Err(format!("expected array type, got {type_}").into());
+        };
+
+        // Binary format of an array:
+        //     <number of dimensions: 4 bytes>
+        //     <has nulls flag: 4 bytes>
+        //     <element type oid: 4 bytes>
+        //     [for each dimension]
+        //         <length: 4 bytes>
+        //         <lower bound: 4 bytes>
+        //     [end for]
+        //     [for each element, in row-major order]
+        //         <length of element, -1 for NULL: 4 bytes>
+        //         <element: <length> bytes>
+        //     [end for]
+        // https://github.com/postgres/postgres/blob/master/src/backend/utils/adt/arrayfuncs.c
+        let mut buf = body;
+        let ndim = buf.read_i32::<BigEndian>()?;
+        let _has_nulls = buf.read_i32::<BigEndian>()?;
+        let _element_oid = buf.read_u32::<BigEndian>()?;
+
+        let mut dimensions = Vec::new();
+        for _ in 0..ndim {
+            dimensions.push(usize::try_from(buf.read_i32::<BigEndian>()?)?);
+            let _lower_bound = buf.read_i32::<BigEndian>()?;
+        }
+
+        // An array without dimensions is empty, rather than a single element
+        let element_count = if dimensions.is_empty() {
+            0
+        } else {
+            dimensions
+                .iter()
+                .try_fold(1_usize, |count, len| count.checked_mul(*len))
+                .ok_or("invalid array dimensions: element count overflows")?
+        };
+
+        let mut elements = Vec::new();
+        for _ in 0..element_count {
+            let len = buf.read_i32::<BigEndian>()?;
+            let Ok(len) = usize::try_from(len) else {
+                elements.push(None);
+                continue;
+            };
+            if buf.len() < len {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "unexpected EOF",
+                )));
+            }
+            let base = body.len() - buf.len();
+            buf = &buf[len..];
+            elements.push(Some(base..base + len));
+        }
+
+        if !buf.is_empty() {
+            return Err("invalid buffer length: array has trailing bytes".into());
+        }
+
+        Ok(ArrayType {
+            element_type: element_type.clone(),
+            body,
+            dimensions,
+            elements,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty.kind(), Kind::Array(_))
+    }
+}
+
+impl<'a> ArrayType<'a> {
+    /// Returns the type of the array elements.
+    #[must_use]
+    pub fn element_type(&self) -> &Type {
+        &self.element_type
+    }
+
+    /// Returns the length of each dimension, outermost first, i.e. `[2, 3]` for `'{{1,2,3},{4,5,6}}'`. An empty
+    /// array has no dimensions.
+    #[must_use]
+    pub fn dimensions(&self) -> &[usize] {
+        &self.dimensions
+    }
+
+    /// Deserializes the elements in row-major order, i.e. `[1, 2, 3, 4, 5, 6]` for `'{{1,2,3},{4,5,6}}'`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the elements cannot be converted to the specified type.
+    pub fn elements<'b, T>(&'b self) -> Result<Vec<Option<T>>>
+    where
+        T: FromSql<'b>,
+    {
+        if !T::accepts(&self.element_type) {
+            return Err(WrongType::new::<T>(self.element_type.clone()))
+                .context(UnableToConvertTypeSnafu);
+        }
+
+        self.elements
+            .iter()
+            .map(|element| match element {
+                Some(r) => T::from_sql(&self.element_type, &self.body[r.clone()])
+                    .map(Some)
+                    .context(UnableToConvertBytesToTypeSnafu),
+                None => Ok(None),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,180 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+#![allow(clippy::module_name_repetitions)]
+
+use byteorder::{BigEndian, ReadBytesExt};
+use snafu::prelude::*;
+use std::ops::Range;
+use tokio_postgres::types::{FromSql, Kind, Type, WrongType};
+
+// Range flags, from https://github.com/postgres/postgres/blob/master/src/include/utils/rangetypes.h
+const RANGE_EMPTY: u8 = 0x01;
+const RANGE_LB_INC: u8 = 0x02;
+const RANGE_UB_INC: u8 = 0x04;
+const RANGE_LB_INF: u8 = 0x08;
+const RANGE_UB_INF: u8 = 0x10;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    UnableToConvertType { source: WrongType },
+
+    #[snafu(display("Unable to conver raw bytes into expected type: {source}"))]
+    UnableToConvertBytesToType {
+        source: Box<dyn std::error::Error + Sync + Send>,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A `PostgreSQL` range type, i.e. `int4range`, `numrange`, `daterange` or `tstzrange`.
+/// The bounds are decoded lazily using `RangeType::lower` and `RangeType::upper`.
+pub struct RangeType<'a> {
+    element_type: Type,
+    body: &'a [u8],
+    flags: u8,
+    lower: Option<Range<usize>>,
+    upper: Option<Range<usize>>,
+}
+
+#[allow(clippy::cast_sign_loss)]
+impl<'a> FromSql<'a> for RangeType<'a> {
+    fn from_sql(
+        type_: &Type,
+        body: &'a [u8],
+    ) -> Result<RangeType<'a>, Box<dyn std::error::Error + Sync + Send>> {
+        let Kind::Range(ref element_type) = *type_.kind() else {
+            return Err(format!("expected range type, got {type_}").into());
+        };
+
+        // Binary format of a range type:
+        //     <flags: 1 byte>
+        //     [if lower bound is present]
+        //         <length of lower bound: 4 bytes>
+        //         <lower bound: <length> bytes>
+        //     [end if]
+        //     [if upper bound is present]
+        //         <length of upper bound: 4 bytes>
+        //         <upper bound: <length> bytes>
+        //     [end if]
+        // https://github.com/postgres/postgres/blob/master/src/backend/utils/adt/rangetypes.c
+        let mut buf = body;
+        let flags = buf.read_u8()?;
+
+        let mut read_bound = |present: bool| -> std::io::Result<Option<Range<usize>>> {
+            if !present {
+                return Ok(None);
+            }
+            let len = buf.read_i32::<BigEndian>()?;
+            if len < 0 || buf.len() < len as usize {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "unexpected EOF",
+                ));
+            }
+            let base = body.len() - buf.len();
+            buf = &buf[len as usize..];
+            Ok(Some(base..base + len as usize))
+        };
+
+        let has_bounds = flags & RANGE_EMPTY == 0;
+        let lower = read_bound(has_bounds && flags & RANGE_LB_INF == 0)?;
+        let upper = read_bound(has_bounds && flags & RANGE_UB_INF == 0)?;
+
+        if !buf.is_empty() {
+            return Err("invalid buffer length: range type is not empty".into());
+        }
+
+        Ok(RangeType {
+            element_type: element_type.clone(),
+            body,
+            flags,
+            lower,
+            upper,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty.kind(), Kind::Range(_))
+    }
+}
+
+impl<'a> RangeType<'a> {
+    /// Returns the type of the range bounds.
+    #[must_use]
+    pub fn element_type(&self) -> &Type {
+        &self.element_type
+    }
+
+    /// Determines if the range is the special `empty` range.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.flags & RANGE_EMPTY != 0
+    }
+
+    /// Determines if the lower bound is included in the range.
+    #[must_use]
+    pub fn lower_inclusive(&self) -> bool {
+        self.flags & RANGE_LB_INC != 0
+    }
+
+    /// Determines if the upper bound is included in the range.
+    #[must_use]
+    pub fn upper_inclusive(&self) -> bool {
+        self.flags & RANGE_UB_INC != 0
+    }
+
+    /// Deserializes the lower bound, returning `None` if the range is unbounded below or empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bound cannot be converted to the specified type.
+    pub fn lower<'b, T>(&'b self) -> Result<Option<T>>
+    where
+        T: FromSql<'b>,
+    {
+        self.get_bound(self.lower.clone())
+    }
+
+    /// Deserializes the upper bound, returning `None` if the range is unbounded above or empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bound cannot be converted to the specified type.
+    pub fn upper<'b, T>(&'b self) -> Result<Option<T>>
+    where
+        T: FromSql<'b>,
+    {
+        self.get_bound(self.upper.clone())
+    }
+
+    fn get_bound<'b, T>(&'b self, range: Option<Range<usize>>) -> Result<Option<T>>
+    where
+        T: FromSql<'b>,
+    {
+        if !T::accepts(&self.element_type) {
+            return Err(WrongType::new::<T>(self.element_type.clone()))
+                .context(UnableToConvertTypeSnafu);
+        }
+
+        match range {
+            Some(r) => T::from_sql(&self.element_type, &self.body[r])
+                .map(Some)
+                .context(UnableToConvertBytesToTypeSnafu),
+            None => Ok(None),
+        }
+    }
+}
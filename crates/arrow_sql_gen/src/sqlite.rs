@@ -16,7 +16,9 @@ limitations under the License.
 
 use std::sync::Arc;
 
+use crate::arrow::coerce_empty_strings;
 use crate::arrow::map_data_type_to_array_builder;
+use crate::arrow::EmptyStringHandling;
 use arrow::array::ArrayBuilder;
 use arrow::array::ArrayRef;
 use arrow::array::BinaryBuilder;
@@ -60,7 +62,20 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 /// # Errors
 ///
 /// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
-pub fn rows_to_arrow(mut rows: Rows, num_cols: usize) -> Result<RecordBatch> {
+pub fn rows_to_arrow(rows: Rows, num_cols: usize) -> Result<RecordBatch> {
+    rows_to_arrow_with_empty_strings(rows, num_cols, EmptyStringHandling::Preserve)
+}
+
+/// Like `rows_to_arrow`, reading the empty strings and NULLs of text columns with `empty_strings`.
+///
+/// # Errors
+///
+/// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
+pub fn rows_to_arrow_with_empty_strings(
+    mut rows: Rows,
+    num_cols: usize,
+    empty_strings: EmptyStringHandling,
+) -> Result<RecordBatch> {
     let mut arrow_fields: Vec<Field> = Vec::new();
     let mut arrow_columns_builders: Vec<Box<dyn ArrayBuilder>> = Vec::new();
     let mut sqlite_types: Vec<Type> = Vec::new();
@@ -95,7 +110,7 @@ pub fn rows_to_arrow(mut rows: Rows, num_cols: usize) -> Result<RecordBatch> {
 
     let columns = arrow_columns_builders
         .into_iter()
-        .map(|mut b| b.finish())
+        .map(|mut b| coerce_empty_strings(b.finish(), empty_strings))
         .collect::<Vec<ArrayRef>>();
 
     let options = &RecordBatchOptions::new().with_row_count(Some(row_count));
@@ -159,3 +174,48 @@ fn map_column_type_to_data_type(column_type: Type) -> DataType {
         Type::Blob => DataType::Binary,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+    use rusqlite::Connection;
+
+    use super::*;
+
+    fn read_strings(empty_strings: EmptyStringHandling) -> Vec<Option<String>> {
+        let conn = Connection::open_in_memory().expect("database is opened");
+        let mut stmt = conn
+            .prepare("SELECT 'a' AS s UNION ALL SELECT '' UNION ALL SELECT NULL")
+            .expect("query is prepared");
+        let column_count = stmt.column_count();
+        let rows = stmt.query([]).expect("query is executed");
+        let batch = rows_to_arrow_with_empty_strings(rows, column_count, empty_strings)
+            .expect("rows are converted");
+        batch
+            .column(0)
+            .as_string::<i32>()
+            .iter()
+            .map(|value| value.map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_strings() {
+        assert_eq!(
+            read_strings(EmptyStringHandling::Preserve),
+            vec![Some("a".to_string()), Some(String::new()), None]
+        );
+        assert_eq!(
+            read_strings(EmptyStringHandling::EmptyAsNull),
+            vec![Some("a".to_string()), None, None]
+        );
+        assert_eq!(
+            read_strings(EmptyStringHandling::NullAsEmpty),
+            vec![
+                Some("a".to_string()),
+                Some(String::new()),
+                Some(String::new())
+            ]
+        );
+    }
+}
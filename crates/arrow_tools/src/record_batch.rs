@@ -16,7 +16,7 @@ limitations under the License.
 
 use arrow::{
     array::{new_null_array, Array, RecordBatch},
-    compute::cast,
+    compute::{cast_with_options, CastOptions},
     datatypes::SchemaRef,
 };
 use snafu::prelude::*;
@@ -38,8 +38,22 @@ pub enum Error {
 /// # Errors
 ///
 /// This function will return an error if the record batch cannot be cast.
-#[allow(clippy::needless_pass_by_value)]
 pub fn try_cast_to(record_batch: RecordBatch, schema: SchemaRef) -> Result<RecordBatch> {
+    try_cast_to_with_options(record_batch, schema, &CastOptions::default())
+}
+
+/// Like `try_cast_to`, casting the columns with `cast_options`, i.e. `safe: false` to fail on values that don't fit
+/// the new type rather than replacing them with NULL.
+///
+/// # Errors
+///
+/// This function will return an error if the record batch cannot be cast.
+#[allow(clippy::needless_pass_by_value)]
+pub fn try_cast_to_with_options(
+    record_batch: RecordBatch,
+    schema: SchemaRef,
+    cast_options: &CastOptions,
+) -> Result<RecordBatch> {
     let existing_schema = record_batch.schema();
 
     // When schema is superset of the existing schema, including a new column, and nullable column,
@@ -62,8 +76,12 @@ pub fn try_cast_to(record_batch: RecordBatch, schema: SchemaRef) -> Result<Recor
                     Ok(Arc::clone(column))
                 } else {
                     {
-                        return cast(&*Arc::clone(column), field.data_type())
-                            .context(UnableToConvertRecordBatchSnafu);
+                        return cast_with_options(
+                            &*Arc::clone(column),
+                            field.data_type(),
+                            cast_options,
+                        )
+                        .context(UnableToConvertRecordBatchSnafu);
                     }
                 }
             } else if field.is_nullable() {
@@ -344,7 +344,8 @@ impl DuckDB {
         );
 
         if let Some(on_conflict) = on_conflict {
-            let on_conflict_sql = on_conflict.build_on_conflict_statement(&self.schema);
+            let on_conflict_sql = on_conflict
+                .build_on_conflict_statement(&self.schema, &table_to_insert_into.constraints);
             insert_sql.push_str(&format!(" {on_conflict_sql}"));
         }
         tracing::debug!("{insert_sql}");
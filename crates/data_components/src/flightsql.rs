@@ -16,6 +16,7 @@ limitations under the License.
 
 use arrow::{
     array::{array, Array, RecordBatch},
+    compute::concat_batches,
     datatypes::Schema,
 };
 use async_stream::stream;
@@ -24,7 +25,7 @@ use flight_client::tls::new_tls_flight_channel;
 use futures::{Stream, StreamExt, TryStreamExt};
 use snafu::prelude::*;
 use sql_provider_datafusion::expr;
-use std::{any::Any, fmt, sync::Arc, vec};
+use std::{any::Any, fmt, num::NonZeroUsize, sync::Arc, vec};
 
 use arrow_flight::{
     error::FlightError,
@@ -81,12 +82,25 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 pub struct FlightSQLFactory {
     client: FlightSqlServiceClient<Channel>,
     endpoint: String,
+    batch_size: Option<NonZeroUsize>,
 }
 
 impl FlightSQLFactory {
     #[must_use]
     pub fn new(client: FlightSqlServiceClient<Channel>, endpoint: String) -> Self {
-        Self { client, endpoint }
+        Self {
+            client,
+            endpoint,
+            batch_size: None,
+        }
+    }
+
+    /// Splits and concatenates the batches sent by the server, so the tables of this factory return batches of
+    /// `rows` rows however the server batches its results.
+    #[must_use]
+    pub fn with_batch_size(mut self, rows: NonZeroUsize) -> Self {
+        self.batch_size = Some(rows);
+        self
     }
 }
 
@@ -96,15 +110,17 @@ impl Read for FlightSQLFactory {
         &self,
         table_reference: TableReference,
     ) -> Result<Arc<dyn TableProvider + 'static>, Box<dyn std::error::Error + Send + Sync>> {
-        let table_provider = Arc::new(
-            FlightSQLTable::create(
-                "flightsql",
-                &self.endpoint,
-                self.client.clone(),
-                table_reference,
-            )
-            .await?,
-        );
+        let mut table = FlightSQLTable::create(
+            "flightsql",
+            &self.endpoint,
+            self.client.clone(),
+            table_reference,
+        )
+        .await?;
+        if let Some(rows) = self.batch_size {
+            table = table.with_batch_size(rows);
+        }
+        let table_provider = Arc::new(table);
 
         let table_provider = Arc::new(
             table_provider
@@ -122,6 +138,7 @@ pub struct FlightSQLTable {
     client: FlightSqlServiceClient<Channel>,
     table_reference: TableReference,
     schema: SchemaRef,
+    batch_size: Option<NonZeroUsize>,
 }
 
 #[allow(clippy::needless_pass_by_value)]
@@ -140,9 +157,17 @@ impl FlightSQLTable {
             table_reference,
             schema,
             join_push_down_context: format!("endpoint={endpoint}"),
+            batch_size: None,
         })
     }
 
+    /// Returns query results in batches of `rows` rows, the last batch excepted, however the server batches them.
+    #[must_use]
+    pub fn with_batch_size(mut self, rows: NonZeroUsize) -> Self {
+        self.batch_size = Some(rows);
+        self
+    }
+
     pub async fn from_static(
         s: &'static str,
         table_reference: impl Into<TableReference>,
@@ -291,6 +316,7 @@ impl FlightSQLTable {
             self.client.clone(),
             filters,
             limit,
+            self.batch_size,
         )?))
     }
 }
@@ -342,6 +368,7 @@ struct FlightSqlExec {
     client: FlightSqlServiceClient<Channel>,
     filters: Vec<Expr>,
     limit: Option<usize>,
+    batch_size: Option<NonZeroUsize>,
     properties: PlanProperties,
 }
 
@@ -353,6 +380,7 @@ impl FlightSqlExec {
         client: FlightSqlServiceClient<Channel>,
         filters: &[Expr],
         limit: Option<usize>,
+        batch_size: Option<NonZeroUsize>,
     ) -> DataFusionResult<Self> {
         let projected_schema = project_schema(schema, projections)?;
         Ok(Self {
@@ -361,6 +389,7 @@ impl FlightSqlExec {
             client,
             filters: filters.to_vec(),
             limit,
+            batch_size,
             properties: PlanProperties::new(
                 EquivalenceProperties::new(projected_schema),
                 Partitioning::UnknownPartitioning(1),
@@ -455,15 +484,38 @@ impl ExecutionPlan for FlightSqlExec {
 
         let stream_adapter = RecordBatchStreamAdapter::new(
             self.schema(),
-            query_to_stream(self.client.clone(), sql.as_str()),
+            query_to_stream(self.client.clone(), sql.as_str(), self.batch_size),
         );
 
         Ok(Box::pin(stream_adapter))
     }
 }
 
-#[allow(clippy::needless_pass_by_value)]
+/// Runs `sql`, streaming its results in batches of `batch_size` rows if one is set, or as the server sent them.
 fn query_to_stream(
+    client: FlightSqlServiceClient<Channel>,
+    sql: &str,
+    batch_size: Option<NonZeroUsize>,
+) -> impl Stream<Item = DataFusionResult<RecordBatch>> {
+    let batches = query_to_server_batches(client, sql);
+    stream! {
+        match batch_size {
+            Some(batch_size) => {
+                for await batch in normalize_batch_size(batches, batch_size) {
+                    yield batch;
+                }
+            }
+            None => {
+                for await batch in batches {
+                    yield batch;
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn query_to_server_batches(
     mut client: FlightSqlServiceClient<Channel>,
     sql: &str,
 ) -> impl Stream<Item = DataFusionResult<RecordBatch>> {
@@ -495,6 +547,50 @@ fn query_to_stream(
     }
 }
 
+/// Slices and concatenates `batches` into batches of exactly `batch_size` rows. The last batch holds the remaining
+/// rows, and empty batches are dropped.
+fn normalize_batch_size<S>(
+    batches: S,
+    batch_size: NonZeroUsize,
+) -> impl Stream<Item = DataFusionResult<RecordBatch>>
+where
+    S: Stream<Item = DataFusionResult<RecordBatch>>,
+{
+    let batch_size = batch_size.get();
+    stream! {
+        let mut pending: Vec<RecordBatch> = Vec::new();
+        let mut pending_rows = 0;
+
+        for await batch in batches {
+            let mut batch = match batch {
+                Ok(batch) => batch,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+
+            while pending_rows + batch.num_rows() >= batch_size {
+                let rows = batch_size - pending_rows;
+                pending.push(batch.slice(0, rows));
+                batch = batch.slice(rows, batch.num_rows() - rows);
+                yield concat_batches(&pending[0].schema(), &pending).map_err(DataFusionError::from);
+                pending.clear();
+                pending_rows = 0;
+            }
+
+            if batch.num_rows() > 0 {
+                pending_rows += batch.num_rows();
+                pending.push(batch);
+            }
+        }
+
+        if let Some(first) = pending.first() {
+            yield concat_batches(&first.schema(), &pending).map_err(DataFusionError::from);
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn to_execution_error(e: impl Into<Box<dyn std::error::Error>>) -> DataFusionError {
     DataFusionError::Execution(format!("{}", e.into()).to_string())
@@ -511,3 +607,78 @@ pub async fn get_client_for_flight_endpoint(
         Ok(FlightSqlServiceClient::new(channel))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{AsArray, Int64Array},
+        datatypes::{DataType, Field, Int64Type},
+    };
+    use futures::stream;
+
+    use super::*;
+
+    fn ids_batch(ids: std::ops::Range<i64>) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from_iter_values(ids))],
+        )
+        .expect("batch is created")
+    }
+
+    #[tokio::test]
+    async fn test_normalize_batch_size() {
+        // However the server batches the rows, they are returned in batches of 4
+        let server_batches = [0..3, 3..3, 3..4, 4..11, 11..13, 13..17];
+        let batches: Vec<RecordBatch> = normalize_batch_size(
+            stream::iter(server_batches.map(|ids| Ok(ids_batch(ids)))),
+            NonZeroUsize::new(4).expect("batch size is non-zero"),
+        )
+        .try_collect()
+        .await
+        .expect("batches are normalized");
+
+        let sizes: Vec<usize> = batches.iter().map(RecordBatch::num_rows).collect();
+        assert_eq!(sizes, vec![4, 4, 4, 4, 1]);
+        let ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(ids, (0..17).collect::<Vec<_>>());
+
+        let batches: Vec<RecordBatch> = normalize_batch_size(
+            stream::iter([Ok(ids_batch(0..2))]),
+            NonZeroUsize::new(4).expect("batch size is non-zero"),
+        )
+        .try_collect()
+        .await
+        .expect("batches are normalized");
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_normalize_batch_size_stops_at_error() {
+        let server_batches = vec![
+            Ok(ids_batch(0..3)),
+            Err(DataFusionError::Execution("connection reset".to_string())),
+            Ok(ids_batch(3..6)),
+        ];
+        let results: Vec<DataFusionResult<RecordBatch>> = normalize_batch_size(
+            stream::iter(server_batches),
+            NonZeroUsize::new(2).expect("batch size is non-zero"),
+        )
+        .collect()
+        .await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().map(RecordBatch::num_rows).ok(), Some(2));
+        assert!(results[1].is_err());
+    }
+}
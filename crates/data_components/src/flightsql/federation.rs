@@ -66,7 +66,7 @@ impl SQLExecutor for FlightSQLTable {
     ) -> DataFusionResult<SendableRecordBatchStream> {
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             schema,
-            query_to_stream(self.client.clone(), query),
+            query_to_stream(self.client.clone(), query, self.batch_size),
         )))
     }
 
@@ -23,10 +23,7 @@ use arrow_sql_gen::statement::{
     CreateTableBuilder, Error as SqlGenError, IndexBuilder, InsertBuilder,
 };
 use async_trait::async_trait;
-use bb8_postgres::{
-    tokio_postgres::{types::ToSql, Transaction},
-    PostgresConnectionManager,
-};
+use bb8_postgres::tokio_postgres::{types::ToSql, Transaction};
 use datafusion::{
     common::Constraints,
     datasource::{provider::TableProviderFactory, TableProvider},
@@ -37,7 +34,7 @@ use datafusion::{
 };
 use db_connection_pool::{
     dbconnection::{postgresconn::PostgresConnection, DbConnection},
-    postgrespool::{self, PostgresConnectionPool},
+    postgrespool::{self, PostgresConnectionManager, PostgresConnectionPool},
     DbConnectionPool,
 };
 use postgres_native_tls::MakeTlsConnector;
@@ -417,7 +414,7 @@ impl Postgres {
         let insert_table_builder = InsertBuilder::new(&self.table_name, vec![batch]);
 
         let sea_query_on_conflict =
-            on_conflict.map(|oc| oc.build_sea_query_on_conflict(&self.schema));
+            on_conflict.map(|oc| oc.build_sea_query_on_conflict(&self.schema, &self.constraints));
 
         let sql = insert_table_builder
             .build_postgres(sea_query_on_conflict)
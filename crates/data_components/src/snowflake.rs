@@ -5,12 +5,12 @@ use db_connection_pool::DbConnectionPool;
 use snafu::prelude::*;
 use snowflake_api::SnowflakeApi;
 use sql_provider_datafusion::SqlTable;
-use std::sync::Arc;
+use std::{any::Any, sync::Arc};
 
 use crate::Read;
 
 pub type SnowflakeConnectionPool =
-    dyn DbConnectionPool<Arc<SnowflakeApi>, &'static (dyn Sync)> + Send + Sync;
+    dyn DbConnectionPool<Arc<SnowflakeApi>, &'static (dyn Any + Sync)> + Send + Sync;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -16,6 +16,7 @@ limitations under the License.
 
 use arrow::{array::RecordBatch, datatypes::SchemaRef};
 use arrow_sql_gen::statement::{CreateTableBuilder, IndexBuilder, InsertBuilder};
+use arrow_sql_gen::EmptyStringHandling;
 use async_trait::async_trait;
 use datafusion::{
     common::Constraints,
@@ -23,17 +24,26 @@ use datafusion::{
     error::{DataFusionError, Result as DataFusionResult},
     execution::context::SessionState,
     logical_expr::CreateExternalTable,
+    scalar::ScalarValue,
     sql::TableReference,
 };
 use db_connection_pool::{
     dbconnection::{sqliteconn::SqliteConnection, DbConnection},
-    sqlitepool::SqliteConnectionPool,
+    sqlitepool::{SqliteConnectionPool, SqliteConnectionPoolOptions, DEFAULT_BUSY_TIMEOUT},
     DbConnectionPool, Mode,
 };
 use rusqlite::{ToSql, Transaction};
+use secrecy::SecretString;
 use snafu::prelude::*;
 use sql_provider_datafusion::{expr::Engine, SqlTable};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio_rusqlite::Connection;
 
 use crate::{
@@ -49,6 +59,7 @@ use crate::{
 
 use self::write::SqliteTableWriter;
 
+pub mod fts;
 pub mod write;
 
 #[derive(Debug, Snafu)]
@@ -72,12 +83,18 @@ pub enum Error {
     #[snafu(display("Unable to create table in Sqlite: {source}"))]
     UnableToCreateTable { source: tokio_rusqlite::Error },
 
+    #[snafu(display("Unable to create advised indexes in Sqlite: {source}"))]
+    UnableToCreateAdvisedIndexes { source: tokio_rusqlite::Error },
+
     #[snafu(display("Unable to insert data into the Sqlite table: {source}"))]
     UnableToInsertIntoTable { source: rusqlite::Error },
 
     #[snafu(display("Unable to insert data into the Sqlite table: {source}"))]
     UnableToInsertIntoTableAsync { source: tokio_rusqlite::Error },
 
+    #[snafu(display("Unable to vacuum the Sqlite database: {source}"))]
+    UnableToVacuum { source: tokio_rusqlite::Error },
+
     #[snafu(display("Unable to deleta all table data in Sqlite: {source}"))]
     UnableToDeleteAllTableData { source: rusqlite::Error },
 
@@ -92,6 +109,28 @@ pub enum Error {
 
     #[snafu(display("Error parsing on_conflict: {source}"))]
     UnableToParseOnConflict { source: on_conflict::Error },
+
+    #[snafu(display("Error parsing {option}: {source}"))]
+    UnableToParseOption {
+        option: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display("Unable to bind {value:?} as a Sqlite parameter"))]
+    UnsupportedParameter { value: ScalarValue },
+
+    #[snafu(display("Error parsing sqlite_read_only: {source}"))]
+    UnableToParseReadOnly { source: std::str::ParseBoolError },
+
+    #[snafu(display(
+        "Invalid sqlite_empty_string_handling {value}: must be one of preserve, empty_as_null, null_as_empty"
+    ))]
+    InvalidEmptyStringHandling { value: String },
+
+    #[snafu(display(
+        "Table {table_name} doesn't exist in the read-only Sqlite database {db_path}"
+    ))]
+    ReadOnlyTableNotFound { table_name: String, db_path: String },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -162,26 +201,119 @@ impl TableProviderFactory for SqliteTableFactory {
             );
         }
 
+        let page_size = options
+            .remove("sqlite_page_size")
+            .map(|s| s.parse::<u32>())
+            .transpose()
+            .context(UnableToParseOptionSnafu {
+                option: "sqlite_page_size".to_string(),
+            })
+            .map_err(to_datafusion_error)?;
+
+        let cache_size = options
+            .remove("sqlite_cache_size")
+            .map(|s| s.parse::<i64>())
+            .transpose()
+            .context(UnableToParseOptionSnafu {
+                option: "sqlite_cache_size".to_string(),
+            })
+            .map_err(to_datafusion_error)?;
+
+        let max_insert_variables = options
+            .remove("sqlite_max_insert_variables")
+            .map(|s| s.parse::<usize>())
+            .transpose()
+            .context(UnableToParseOptionSnafu {
+                option: "sqlite_max_insert_variables".to_string(),
+            })
+            .map_err(to_datafusion_error)?
+            .unwrap_or(DEFAULT_MAX_INSERT_VARIABLES);
+
+        let vacuum_interval = options
+            .remove("sqlite_vacuum_interval")
+            .map(|s| s.parse::<usize>())
+            .transpose()
+            .context(UnableToParseOptionSnafu {
+                option: "sqlite_vacuum_interval".to_string(),
+            })
+            .map_err(to_datafusion_error)?
+            .unwrap_or(0);
+
+        let journal_mode = options.remove("sqlite_journal_mode");
+        let synchronous = options.remove("sqlite_synchronous");
+
+        let busy_timeout = options
+            .remove("sqlite_busy_timeout")
+            .map(|s| s.parse::<u64>().map(Duration::from_millis))
+            .transpose()
+            .context(UnableToParseOptionSnafu {
+                option: "sqlite_busy_timeout".to_string(),
+            })
+            .map_err(to_datafusion_error)?
+            .unwrap_or(DEFAULT_BUSY_TIMEOUT);
+
+        let read_only = options
+            .remove("sqlite_read_only")
+            .map(|s| s.parse::<bool>())
+            .transpose()
+            .context(UnableToParseReadOnlySnafu)
+            .map_err(to_datafusion_error)?
+            .unwrap_or(false);
+
+        // Never logged, see `SqliteConnectionPool::new`
+        let encryption_key = options
+            .remove("sqlite_encryption_key")
+            .map(SecretString::new);
+
+        let empty_strings = options
+            .remove("sqlite_empty_string_handling")
+            .map(|value| {
+                EmptyStringHandling::parse(&value)
+                    .context(InvalidEmptyStringHandlingSnafu { value })
+            })
+            .transpose()
+            .map_err(to_datafusion_error)?
+            .unwrap_or_default();
+
+        let pool_options = SqliteConnectionPoolOptions::new()
+            .with_page_size(page_size)
+            .with_cache_size(cache_size)
+            .with_journal_mode(journal_mode)
+            .with_synchronous(synchronous)
+            .with_busy_timeout(busy_timeout)
+            .with_encryption_key(encryption_key)
+            .with_empty_strings(empty_strings);
+
         let db_path = cmd
             .options
             .get(self.db_path_param.as_str())
             .cloned()
             .unwrap_or(format!("{name}_sqlite.db"));
 
+        let schema: SchemaRef = Arc::new(cmd.schema.as_ref().into());
+        if read_only {
+            return read_only_table(name, schema, &db_path, pool_options)
+                .await
+                .map_err(to_datafusion_error);
+        }
+
         let pool: Arc<SqliteConnectionPool> = Arc::new(
-            SqliteConnectionPool::new(&db_path, mode)
+            SqliteConnectionPool::new(&db_path, mode, pool_options)
                 .await
                 .context(DbConnectionPoolSnafu)
                 .map_err(to_datafusion_error)?,
         );
 
-        let schema: SchemaRef = Arc::new(cmd.schema.as_ref().into());
-        let sqlite = Arc::new(Sqlite::new(
-            name.clone(),
-            Arc::clone(&schema),
-            Arc::clone(&pool),
-            cmd.constraints.clone(),
-        ));
+        let sqlite = Arc::new(
+            Sqlite::new(
+                name.clone(),
+                Arc::clone(&schema),
+                Arc::clone(&pool),
+                cmd.constraints.clone(),
+            )
+            .with_max_insert_variables(max_insert_variables)
+            .with_vacuum_interval(vacuum_interval),
+        );
 
         let mut db_conn = sqlite.connect().await.map_err(to_datafusion_error)?;
         let sqlite_conn = Sqlite::sqlite_conn(&mut db_conn).map_err(to_datafusion_error)?;
@@ -200,7 +332,7 @@ impl TableProviderFactory for SqliteTableFactory {
                         sqlite_in_conn.create_index(
                             &transaction,
                             index.0.iter().collect(),
-                            index.1 == IndexType::Unique,
+                            index.1,
                         )?;
                     }
                     transaction.commit()?;
@@ -232,16 +364,87 @@ impl TableProviderFactory for SqliteTableFactory {
     }
 }
 
+/// Registers the existing table `name` of the database file at `db_path`, i.e. one produced by another application, as
+/// a read-only source. The file is opened read-only, so nothing is created or written.
+async fn read_only_table(
+    name: String,
+    schema: SchemaRef,
+    db_path: &str,
+    pool_options: SqliteConnectionPoolOptions,
+) -> Result<Arc<dyn TableProvider>> {
+    let pool = Arc::new(
+        SqliteConnectionPool::new_read_only(db_path, pool_options)
+            .await
+            .context(DbConnectionPoolSnafu)?,
+    );
+
+    let sqlite = Sqlite::new(
+        name.clone(),
+        Arc::clone(&schema),
+        Arc::clone(&pool),
+        Constraints::empty(),
+    );
+    let mut db_conn = sqlite.connect().await?;
+    let sqlite_conn = Sqlite::sqlite_conn(&mut db_conn)?;
+    ensure!(
+        sqlite.table_exists(sqlite_conn).await,
+        ReadOnlyTableNotFoundSnafu {
+            table_name: name,
+            db_path,
+        }
+    );
+
+    let dyn_pool: Arc<DynSqliteConnectionPool> = pool;
+    Ok(Arc::new(SqlTable::new_with_schema(
+        "sqlite",
+        &dyn_pool,
+        schema,
+        TableReference::bare(name),
+        Some(Engine::SQLite),
+    )))
+}
+
+/// Boxes the value of a filter parameter to bind it to a statement, see `util::filters_to_parameterized_sql`.
+#[allow(clippy::cast_precision_loss)]
+fn to_sqlite_param(value: ScalarValue) -> Result<Box<dyn ToSql + Send>> {
+    match value {
+        ScalarValue::Boolean(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::Int8(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::Int16(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::Int32(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::Int64(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::UInt8(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::UInt16(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::UInt32(Some(v)) => Ok(Box::new(v)),
+        // Like SQLite reads an integer literal too large for an INTEGER
+        ScalarValue::UInt64(Some(v)) => Ok(match i64::try_from(v) {
+            Ok(v) => Box::new(v),
+            Err(_) => Box::new(v as f64),
+        }),
+        ScalarValue::Float64(Some(v)) => Ok(Box::new(v)),
+        ScalarValue::Utf8(Some(v)) | ScalarValue::LargeUtf8(Some(v)) => Ok(Box::new(v)),
+        value => UnsupportedParameterSnafu { value }.fail(),
+    }
+}
+
 fn to_datafusion_error(error: Error) -> DataFusionError {
     DataFusionError::External(Box::new(error))
 }
 
+/// The default of `sqlite_max_insert_variables`, the most values written by a single `INSERT` statement. It matches
+/// `SQLite`'s default `SQLITE_MAX_VARIABLE_NUMBER` since 3.32, although the values are inlined as literals rather than
+/// bound as variables, so it caps the size of each statement rather than avoiding that limit.
+pub const DEFAULT_MAX_INSERT_VARIABLES: usize = 32_766;
+
 #[derive(Clone)]
 pub struct Sqlite {
     table_name: String,
     schema: SchemaRef,
     pool: Arc<SqliteConnectionPool>,
     constraints: Constraints,
+    max_insert_variables: usize,
+    vacuum_interval: usize,
+    overwrites: Arc<AtomicUsize>,
 }
 
 impl Sqlite {
@@ -257,9 +460,29 @@ impl Sqlite {
             schema,
             pool,
             constraints,
+            max_insert_variables: DEFAULT_MAX_INSERT_VARIABLES,
+            vacuum_interval: 0,
+            overwrites: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Sets the most values, rows times columns, written by a single `INSERT` statement. The values are inlined into
+    /// the statement as literals, so this is a cap on the size of each statement. Larger batches are inserted in chunks
+    /// of as many rows as fit, within the same transaction.
+    #[must_use]
+    pub fn with_max_insert_variables(mut self, max_insert_variables: usize) -> Self {
+        self.max_insert_variables = max_insert_variables.max(1);
+        self
+    }
+
+    /// Vacuums the database after every `vacuum_interval` writes that overwrite the table, i.e. full refreshes, which
+    /// otherwise leave the pages of the deleted rows free in the file. `0`, the default, never vacuums.
+    #[must_use]
+    pub fn with_vacuum_interval(mut self, vacuum_interval: usize) -> Self {
+        self.vacuum_interval = vacuum_interval;
+        self
+    }
+
     #[must_use]
     pub fn constraints(&self) -> &Constraints {
         &self.constraints
@@ -280,23 +503,25 @@ impl Sqlite {
             .ok_or_else(|| UnableToDowncastDbConnectionSnafu {}.build())
     }
 
+    /// Looks the table up case-insensitively, like SQLite resolves identifiers. The name is matched without the
+    /// quotes or schema it may arrive with, i.e. `main."MyTable"` matches a table created as `mytable`.
     async fn table_exists(&self, sqlite_conn: &mut SqliteConnection) -> bool {
-        let sql = format!(
-            r#"SELECT EXISTS (
+        let name = TableReference::from(self.table_name.as_str())
+            .table()
+            .to_string();
+        let sql = r"SELECT EXISTS (
           SELECT 1
           FROM sqlite_master
           WHERE type='table'
-          AND name = '{name}'
-        )"#,
-            name = self.table_name
-        );
+          AND name = ?1 COLLATE NOCASE
+        )";
         tracing::trace!("{sql}");
 
         sqlite_conn
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare(&sql)?;
-                let exists = stmt.query_row([], |row| row.get(0))?;
+                let mut stmt = conn.prepare(sql)?;
+                let exists = stmt.query_row([name], |row| row.get(0))?;
                 Ok(exists)
             })
             .await
@@ -309,16 +534,109 @@ impl Sqlite {
         batch: RecordBatch,
         on_conflict: Option<&OnConflict>,
     ) -> rusqlite::Result<()> {
-        let insert_table_builder = InsertBuilder::new(&self.table_name, vec![batch]);
+        for sql in self.insert_statements(&batch, on_conflict) {
+            transaction.execute(&sql?, [])?;
+        }
+
+        Ok(())
+    }
 
-        let sea_query_on_conflict =
-            on_conflict.map(|oc| oc.build_sea_query_on_conflict(&self.schema));
+    /// The `INSERT` statements that write `batch`, each of as many rows as fit in `max_insert_variables` values, and
+    /// each with the `on_conflict` clause.
+    fn insert_statements<'a>(
+        &'a self,
+        batch: &'a RecordBatch,
+        on_conflict: Option<&'a OnConflict>,
+    ) -> impl Iterator<Item = rusqlite::Result<String>> + 'a {
+        let rows_per_statement = (self.max_insert_variables / batch.num_columns().max(1)).max(1);
 
-        let sql = insert_table_builder
-            .build_sqlite(sea_query_on_conflict)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        (0..batch.num_rows())
+            .step_by(rows_per_statement)
+            .map(move |offset| {
+                let chunk = batch.slice(offset, rows_per_statement.min(batch.num_rows() - offset));
+                let insert_table_builder = InsertBuilder::new(&self.table_name, vec![chunk]);
 
-        transaction.execute(&sql, [])?;
+                let sea_query_on_conflict = on_conflict
+                    .map(|oc| oc.build_sea_query_on_conflict(&self.schema, &self.constraints));
+
+                insert_table_builder
+                    .build_sqlite(sea_query_on_conflict)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))
+            })
+    }
+
+    /// Inserts `batches` into the table in a single transaction, after deleting all of its rows if `delete_first` is
+    /// set, i.e. for a full refresh. If any batch fails to insert, the whole write is rolled back and the table keeps
+    /// its previous rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rows can't be deleted or any of the batches can't be inserted.
+    pub async fn insert_batches(
+        &self,
+        batches: Vec<RecordBatch>,
+        delete_first: bool,
+        on_conflict: Option<OnConflict>,
+    ) -> Result<()> {
+        let mut db_conn = self.connect().await?;
+        let sqlite_conn = Self::sqlite_conn(&mut db_conn)?;
+
+        let sqlite = self.clone();
+        sqlite_conn
+            .conn
+            .call(move |conn| {
+                // Dropping the transaction without committing it rolls back everything written so far
+                let transaction = conn.transaction()?;
+
+                if delete_first {
+                    sqlite.delete_all_table_data(&transaction)?;
+                }
+
+                for batch in batches {
+                    if batch.num_rows() > 0 {
+                        sqlite.insert_batch(&transaction, batch, on_conflict.as_ref())?;
+                    }
+                }
+
+                transaction.commit()?;
+
+                Ok(())
+            })
+            .await
+            .context(UnableToInsertIntoTableAsyncSnafu)
+    }
+
+    /// Rebuilds the database file with `VACUUM`, returning the pages freed by deleted rows to the file system.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be vacuumed, e.g. while another statement is writing to it.
+    pub async fn vacuum(&self) -> Result<()> {
+        let mut db_conn = self.connect().await?;
+        let sqlite_conn = Self::sqlite_conn(&mut db_conn)?;
+
+        tracing::debug!("Vacuuming the Sqlite database of table {}", self.table_name);
+        sqlite_conn
+            .conn
+            .call(|conn| {
+                conn.execute_batch("VACUUM")?;
+                Ok(())
+            })
+            .await
+            .context(UnableToVacuumSnafu)
+    }
+
+    /// Counts a write that overwrote the table, and vacuums the database when it's the `vacuum_interval`th one since
+    /// the last vacuum. See `with_vacuum_interval`.
+    async fn vacuum_if_due(&self) -> Result<()> {
+        if self.vacuum_interval == 0 {
+            return Ok(());
+        }
+
+        let overwrites = self.overwrites.fetch_add(1, Ordering::Relaxed) + 1;
+        if overwrites % self.vacuum_interval == 0 {
+            self.vacuum().await?;
+        }
 
         Ok(())
     }
@@ -329,10 +647,12 @@ impl Sqlite {
         Ok(())
     }
 
+    /// Deletes the rows matching `where_clause`, whose `?` placeholders are bound to `params`.
     fn delete_from(
         &self,
         transaction: &Transaction<'_>,
         where_clause: &str,
+        params: &[&dyn ToSql],
     ) -> rusqlite::Result<u64> {
         transaction.execute(
             format!(
@@ -340,7 +660,7 @@ impl Sqlite {
                 self.table_name, where_clause
             )
             .as_str(),
-            [],
+            params,
         )?;
         let count: u64 = transaction.query_row("SELECT changes()", [], |row| row.get(0))?;
 
@@ -366,10 +686,14 @@ impl Sqlite {
         &self,
         transaction: &Transaction<'_>,
         columns: Vec<&str>,
-        unique: bool,
+        index_type: IndexType,
     ) -> rusqlite::Result<()> {
+        if index_type == IndexType::FullText {
+            return self.create_full_text_index(transaction, &columns);
+        }
+
         let mut index_builder = IndexBuilder::new(&self.table_name, columns);
-        if unique {
+        if index_type == IndexType::Unique {
             index_builder = index_builder.unique();
         }
         let sql = index_builder.build_sqlite();
@@ -378,4 +702,637 @@ impl Sqlite {
 
         Ok(())
     }
+
+    /// The name of the FTS5 table that indexes the full-text columns of the table. Read through a `SqlTable`, its
+    /// columns can be filtered with [`fts::FtsMatch`].
+    #[must_use]
+    pub fn full_text_table_name(&self) -> String {
+        format!("{}_fts", self.table_name)
+    }
+
+    /// Creates an FTS5 table, named by `full_text_table_name`, over `columns` of the table. The FTS5 table reads
+    /// its content from the table, by `rowid`, and triggers on the table keep its index in sync as rows are
+    /// inserted, updated and deleted.
+    fn create_full_text_index(
+        &self,
+        transaction: &Transaction<'_>,
+        columns: &[&str],
+    ) -> rusqlite::Result<()> {
+        let table_name = &self.table_name;
+        let fts_table_name = self.full_text_table_name();
+        let column_list = |prefix: &str| {
+            columns
+                .iter()
+                .map(|column| format!(r#"{prefix}"{column}""#))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let (fts_columns, new_values, old_values) =
+            (column_list(""), column_list("new."), column_list("old."));
+
+        let insert = format!(
+            r#"INSERT INTO "{fts_table_name}"(rowid, {fts_columns}) VALUES (new.rowid, {new_values});"#
+        );
+        let delete = format!(
+            r#"INSERT INTO "{fts_table_name}"("{fts_table_name}", rowid, {fts_columns}) VALUES ('delete', old.rowid, {old_values});"#
+        );
+        let sql = format!(
+            r#"CREATE VIRTUAL TABLE "{fts_table_name}" USING fts5({fts_columns}, content='{table_name}', content_rowid='rowid');
+CREATE TRIGGER "{fts_table_name}_insert" AFTER INSERT ON "{table_name}" BEGIN {insert} END;
+CREATE TRIGGER "{fts_table_name}_delete" AFTER DELETE ON "{table_name}" BEGIN {delete} END;
+CREATE TRIGGER "{fts_table_name}_update" AFTER UPDATE ON "{table_name}" BEGIN {delete} {insert} END;"#
+        );
+        tracing::trace!("{sql}");
+
+        transaction.execute_batch(&sql)
+    }
+
+    /// Lists the columns of each index of the table, in index order. Expressions in an index are listed as an empty
+    /// column name.
+    fn list_indexes(&self, transaction: &Transaction<'_>) -> rusqlite::Result<Vec<Vec<String>>> {
+        let mut index_list = transaction.prepare("SELECT name FROM pragma_index_list(?1)")?;
+        let index_names = index_list
+            .query_map([&self.table_name], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut index_info =
+            transaction.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+        index_names
+            .iter()
+            .map(|index_name| {
+                index_info
+                    .query_map([index_name], |row| {
+                        Ok(row.get::<_, Option<String>>(0)?.unwrap_or_default())
+                    })?
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Creates an index for each of `advised_columns`, i.e. the columns the runtime sees queries frequently filter or
+    /// join on, unless an index of the table already covers them. An index covers columns that are its leading
+    /// columns, in any order. Returns the columns that were indexed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the existing indexes can't be listed or an index can't be created, in which case no index
+    /// is created.
+    pub async fn create_advised_indexes(
+        &self,
+        advised_columns: Vec<ColumnReference>,
+    ) -> Result<Vec<ColumnReference>> {
+        let mut db_conn = self.connect().await?;
+        let sqlite_conn = Self::sqlite_conn(&mut db_conn)?;
+
+        let sqlite = self.clone();
+        sqlite_conn
+            .conn
+            .call(move |conn| {
+                let transaction = conn.transaction()?;
+                let mut indexes = sqlite.list_indexes(&transaction)?;
+                let mut created = Vec::new();
+                for columns in advised_columns {
+                    if columns.is_empty()
+                        || indexes.iter().any(|index| index_covers(index, &columns))
+                    {
+                        continue;
+                    }
+                    tracing::debug!(
+                        "Creating advised index on {columns} for Sqlite table {}",
+                        sqlite.table_name
+                    );
+                    sqlite.create_index(
+                        &transaction,
+                        columns.iter().collect(),
+                        IndexType::Enabled,
+                    )?;
+                    indexes.push(columns.iter().map(ToString::to_string).collect());
+                    created.push(columns);
+                }
+                transaction.commit()?;
+                Ok(created)
+            })
+            .await
+            .context(UnableToCreateAdvisedIndexesSnafu)
+    }
+}
+
+/// Whether the index on `index_columns` covers `columns`, i.e. they are its leading columns in any order.
+fn index_covers(index_columns: &[String], columns: &ColumnReference) -> bool {
+    let column_count = columns.iter().count();
+    index_columns.len() >= column_count
+        && index_columns[..column_count]
+            .iter()
+            .all(|index_column| columns.contains(index_column))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{AsArray, Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{assert_batches_eq, common::ToDFSchema, prelude::SessionContext};
+
+    use super::*;
+
+    async fn sqlite_table(indexes: &[(&str, IndexType)]) -> Sqlite {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("tenant", DataType::Utf8, false),
+            Field::new("created_at", DataType::Int64, false),
+        ]));
+        create_sqlite_table("advised", schema, indexes).await
+    }
+
+    async fn create_sqlite_table(
+        table_name: &str,
+        schema: SchemaRef,
+        indexes: &[(&str, IndexType)],
+    ) -> Sqlite {
+        let pool = Arc::new(
+            SqliteConnectionPool::new("", Mode::Memory, SqliteConnectionPoolOptions::new())
+                .await
+                .expect("pool is created"),
+        );
+        let sqlite = Sqlite::new(table_name.to_string(), schema, pool, Constraints::empty());
+
+        let indexes = indexes
+            .iter()
+            .map(|(columns, index_type)| {
+                (
+                    ColumnReference::try_from(*columns).expect("valid columns"),
+                    *index_type,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut db_conn = sqlite.connect().await.expect("connection is opened");
+        let sqlite_in_conn = sqlite.clone();
+        Sqlite::sqlite_conn(&mut db_conn)
+            .expect("sqlite connection")
+            .conn
+            .call(move |conn| {
+                let transaction = conn.transaction()?;
+                sqlite_in_conn.create_table(&transaction, vec![])?;
+                for (columns, index_type) in indexes {
+                    sqlite_in_conn.create_index(
+                        &transaction,
+                        columns.iter().collect(),
+                        index_type,
+                    )?;
+                }
+                transaction.commit()?;
+                Ok(())
+            })
+            .await
+            .expect("table is created");
+        sqlite
+    }
+
+    fn column_references(columns: &[&str]) -> Vec<ColumnReference> {
+        columns
+            .iter()
+            .map(|columns| ColumnReference::try_from(*columns).expect("valid columns"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_advised_indexes_are_created_once() {
+        let sqlite = sqlite_table(&[]).await;
+
+        let advised = column_references(&["tenant", "(tenant, created_at)"]);
+        let created = sqlite
+            .create_advised_indexes(advised.clone())
+            .await
+            .expect("advised indexes are created");
+        assert_eq!(created, advised);
+
+        // The indexes now exist, so advising the same columns again doesn't recreate them
+        let created = sqlite
+            .create_advised_indexes(advised)
+            .await
+            .expect("advised indexes are checked");
+        assert!(created.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_existing_indexes_cover_advised_columns() {
+        // Index columns are sorted, so the existing index is on `(created_at, tenant)`
+        let sqlite = sqlite_table(&[("(tenant, created_at)", IndexType::Enabled)]).await;
+
+        // `created_at` leads the existing index, `tenant` and `id` don't
+        let created = sqlite
+            .create_advised_indexes(column_references(&[
+                "created_at",
+                "(tenant, created_at)",
+                "tenant",
+                "id",
+            ]))
+            .await
+            .expect("advised indexes are created");
+        assert_eq!(created, column_references(&["tenant", "id"]));
+    }
+
+    #[tokio::test]
+    async fn test_insert_statements_are_chunked() {
+        // Three columns, so two rows fit in seven values
+        let sqlite = sqlite_table(&[]).await.with_max_insert_variables(7);
+        let batch = RecordBatch::try_new(
+            Arc::clone(&sqlite.schema),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..5)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..5).map(|i| format!("t{i}")),
+                )),
+                Arc::new(Int64Array::from_iter_values(0..5)),
+            ],
+        )
+        .expect("batch is created");
+        let on_conflict = OnConflict::try_from("upsert:id").expect("valid on_conflict");
+
+        let statements = sqlite
+            .insert_statements(&batch, Some(&on_conflict))
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .expect("statements are built");
+        assert_eq!(statements.len(), 3);
+        for statement in &statements {
+            assert!(statement.contains("ON CONFLICT"), "{statement}");
+        }
+        assert!(statements[2].contains("'t4'") && !statements[2].contains("'t3'"));
+    }
+
+    #[tokio::test]
+    async fn test_full_text_index_matches_through_datafusion() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("title", DataType::Utf8, false),
+            Field::new("body", DataType::Utf8, false),
+        ]));
+        let sqlite = create_sqlite_table(
+            "docs",
+            Arc::clone(&schema),
+            &[("(title, body)", IndexType::FullText)],
+        )
+        .await;
+
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![
+                    "Rust in action",
+                    "Learning SQL",
+                    "Gardening",
+                ])),
+                Arc::new(StringArray::from(vec![
+                    "Systems programming",
+                    "Queries, from SQL to Rust",
+                    "Growing tomatoes",
+                ])),
+            ],
+        )
+        .expect("batch is created");
+        let mut db_conn = sqlite.connect().await.expect("connection is opened");
+        let sqlite_in_conn = sqlite.clone();
+        Sqlite::sqlite_conn(&mut db_conn)
+            .expect("sqlite connection")
+            .conn
+            .call(move |conn| {
+                let transaction = conn.transaction()?;
+                sqlite_in_conn.insert_batch(&transaction, batch, None)?;
+                // The update trigger replaces the indexed text of the row
+                transaction.execute(
+                    r#"UPDATE "docs" SET "body" = 'Rust for gardeners' WHERE "id" = 3"#,
+                    [],
+                )?;
+                transaction.commit()?;
+                Ok(())
+            })
+            .await
+            .expect("rows are inserted");
+
+        let fts_schema = Arc::new(Schema::new(vec![
+            Field::new("title", DataType::Utf8, false),
+            Field::new("body", DataType::Utf8, false),
+        ]));
+        let dyn_pool: Arc<DynSqliteConnectionPool> = Arc::clone(&sqlite.pool);
+        let fts_table = SqlTable::new_with_schema(
+            "sqlite",
+            &dyn_pool,
+            fts_schema,
+            TableReference::bare(sqlite.full_text_table_name()),
+            Some(Engine::SQLite),
+        );
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(fts::FtsMatch::new().into());
+        ctx.register_table("docs_fts", Arc::new(fts_table))
+            .expect("table is registered");
+
+        let batches = ctx
+            .sql("SELECT title FROM docs_fts WHERE fts_match(body, 'rust') ORDER BY title")
+            .await
+            .expect("query is planned")
+            .collect()
+            .await
+            .expect("query is executed");
+        assert_batches_eq!(
+            [
+                "+--------------+",
+                "| title        |",
+                "+--------------+",
+                "| Gardening    |",
+                "| Learning SQL |",
+                "+--------------+",
+            ],
+            &batches
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_only_database_file() {
+        let path = std::env::temp_dir().join(format!("read_only_source_{}.db", std::process::id()));
+        let db_path = path.to_str().expect("temp path is UTF-8").to_string();
+        rusqlite::Connection::open(&db_path)
+            .expect("database is created")
+            .execute_batch(
+                "CREATE TABLE events (id INTEGER NOT NULL, name TEXT NOT NULL);
+                INSERT INTO events VALUES (1, 'signup'), (2, 'login');",
+            )
+            .expect("table is populated");
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let external_table = |name: &str| CreateExternalTable {
+            schema: ToDFSchema::to_dfschema_ref(Arc::clone(&schema)).expect("df schema"),
+            name: TableReference::bare(name),
+            location: String::new(),
+            file_type: String::new(),
+            table_partition_cols: vec![],
+            if_not_exists: true,
+            definition: None,
+            order_exprs: vec![],
+            unbounded: false,
+            options: HashMap::from([
+                ("sqlite_read_only".to_string(), "true".to_string()),
+                ("sqlite_file".to_string(), db_path.clone()),
+            ]),
+            constraints: Constraints::empty(),
+            column_defaults: HashMap::default(),
+        };
+
+        let ctx = SessionContext::new();
+        let table = SqliteTableFactory::default()
+            .create(&ctx.state(), &external_table("events"))
+            .await
+            .expect("table is registered");
+        // Only the read provider is returned, without a writer
+        assert!(table
+            .as_any()
+            .downcast_ref::<SqlTable<Connection, &'static (dyn ToSql + Sync)>>()
+            .is_some());
+
+        ctx.register_table("events", table)
+            .expect("table is registered");
+        let batches = ctx
+            .sql("SELECT name FROM events WHERE id = 2")
+            .await
+            .expect("query is planned")
+            .collect()
+            .await
+            .expect("query is executed");
+        assert_batches_eq!(
+            [
+                "+-------+",
+                "| name  |",
+                "+-------+",
+                "| login |",
+                "+-------+"
+            ],
+            &batches
+        );
+
+        // A table missing from the file isn't created
+        assert!(SqliteTableFactory::default()
+            .create(&ctx.state(), &external_table("missing"))
+            .await
+            .is_err());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_empty_string_handling() {
+        let path = std::env::temp_dir().join(format!("empty_strings_{}.db", std::process::id()));
+        let db_path = path.to_str().expect("temp path is UTF-8").to_string();
+        rusqlite::Connection::open(&db_path)
+            .expect("database is created")
+            .execute_batch(
+                "CREATE TABLE notes (id INTEGER NOT NULL, note TEXT);
+                INSERT INTO notes VALUES (1, 'a'), (2, ''), (3, NULL);",
+            )
+            .expect("table is populated");
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let external_table = |empty_string_handling: &str| CreateExternalTable {
+            schema: ToDFSchema::to_dfschema_ref(Arc::clone(&schema)).expect("df schema"),
+            name: TableReference::bare("notes"),
+            location: String::new(),
+            file_type: String::new(),
+            table_partition_cols: vec![],
+            if_not_exists: true,
+            definition: None,
+            order_exprs: vec![],
+            unbounded: false,
+            options: HashMap::from([
+                ("sqlite_read_only".to_string(), "true".to_string()),
+                ("sqlite_file".to_string(), db_path.clone()),
+                (
+                    "sqlite_empty_string_handling".to_string(),
+                    empty_string_handling.to_string(),
+                ),
+            ]),
+            constraints: Constraints::empty(),
+            column_defaults: HashMap::default(),
+        };
+
+        let ctx = SessionContext::new();
+        let table = SqliteTableFactory::default()
+            .create(&ctx.state(), &external_table("empty_as_null"))
+            .await
+            .expect("table is registered");
+        ctx.register_table("notes", table)
+            .expect("table is registered");
+        // The empty string is read as NULL, which `assert_batches_eq` would print like an empty string
+        let batches = ctx
+            .sql("SELECT note FROM notes ORDER BY id")
+            .await
+            .expect("query is planned")
+            .collect()
+            .await
+            .expect("query is executed");
+        let notes = batches
+            .iter()
+            .flat_map(|batch| batch.column(0).as_string::<i32>().iter())
+            .map(|note| note.map(str::to_string))
+            .collect::<Vec<_>>();
+        assert_eq!(notes, vec![Some("a".to_string()), None, None]);
+
+        let Err(err) = SqliteTableFactory::default()
+            .create(&ctx.state(), &external_table("blank"))
+            .await
+        else {
+            panic!("an unknown empty_string_handling should be rejected");
+        };
+        assert!(
+            err.to_string().contains("sqlite_empty_string_handling"),
+            "{err}"
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_shrinks_file_after_full_refresh() {
+        let path = std::env::temp_dir().join(format!("vacuum_{}.db", std::process::id()));
+        let db_path = path.to_str().expect("temp path is UTF-8").to_string();
+        let pool = Arc::new(
+            SqliteConnectionPool::new(&db_path, Mode::File, SqliteConnectionPoolOptions::new())
+                .await
+                .expect("pool is created"),
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("payload", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..10_000)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..10_000).map(|id| format!("{id:0>100}")),
+                )),
+            ],
+        )
+        .expect("batch is created");
+        let sqlite = Sqlite::new("refreshed".to_string(), schema, pool, Constraints::empty())
+            .with_vacuum_interval(2);
+
+        let mut db_conn = sqlite.connect().await.expect("connection is opened");
+        let sqlite_in_conn = sqlite.clone();
+        Sqlite::sqlite_conn(&mut db_conn)
+            .expect("sqlite connection")
+            .conn
+            .call(move |conn| {
+                let transaction = conn.transaction()?;
+                sqlite_in_conn.create_table(&transaction, vec![])?;
+                sqlite_in_conn.insert_batch(&transaction, batch, None)?;
+                transaction.commit()?;
+                let transaction = conn.transaction()?;
+                sqlite_in_conn.delete_all_table_data(&transaction)?;
+                transaction.commit()?;
+                Ok(())
+            })
+            .await
+            .expect("rows are inserted and deleted");
+        let file_size = || std::fs::metadata(&path).expect("file exists").len();
+        let refreshed_size = file_size();
+
+        // Only every second overwrite vacuums
+        sqlite.vacuum_if_due().await.expect("first overwrite");
+        assert_eq!(file_size(), refreshed_size);
+        sqlite.vacuum_if_due().await.expect("database is vacuumed");
+        assert!(file_size() < refreshed_size);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_rolls_back_refresh() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let sqlite = create_sqlite_table("refreshed", schema, &[]).await;
+        let batch = |ids: Vec<Option<i64>>| {
+            let names = StringArray::from(vec!["name"; ids.len()]);
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new("id", DataType::Int64, true),
+                    Field::new("name", DataType::Utf8, false),
+                ])),
+                vec![Arc::new(Int64Array::from(ids)), Arc::new(names)],
+            )
+            .expect("batch is created")
+        };
+        let ids = || async {
+            let mut db_conn = sqlite.connect().await.expect("connection is opened");
+            Sqlite::sqlite_conn(&mut db_conn)
+                .expect("sqlite connection")
+                .conn
+                .call(|conn| {
+                    let mut stmt = conn.prepare(r#"SELECT id FROM "refreshed" ORDER BY id"#)?;
+                    let ids = stmt
+                        .query_map([], |row| row.get(0))?
+                        .collect::<rusqlite::Result<Vec<i64>>>()?;
+                    Ok(ids)
+                })
+                .await
+                .expect("ids are read")
+        };
+
+        sqlite
+            .insert_batches(vec![batch(vec![Some(1), Some(2)])], true, None)
+            .await
+            .expect("table is refreshed");
+        assert_eq!(ids().await, vec![1, 2]);
+
+        // The NULL id of the second batch violates the NOT NULL constraint, after the rows were deleted and the first
+        // batch was inserted
+        let result = sqlite
+            .insert_batches(
+                vec![batch(vec![Some(3), Some(4)]), batch(vec![Some(5), None])],
+                true,
+                None,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::UnableToInsertIntoTableAsync { .. })
+        ));
+        assert_eq!(ids().await, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_table_exists_ignores_case_quotes_and_schema() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let sqlite = create_sqlite_table("mytable", schema, &[]).await;
+        let mut db_conn = sqlite.connect().await.expect("connection is opened");
+        let sqlite_conn = Sqlite::sqlite_conn(&mut db_conn).expect("sqlite connection");
+
+        for (table_name, exists) in [
+            ("mytable", true),
+            ("MyTable", true),
+            (r#""MyTable""#, true),
+            (r#"main."MyTable""#, true),
+            ("main.mytable", true),
+            ("other", false),
+            (r#""mytable'""#, false),
+        ] {
+            let sqlite = Sqlite {
+                table_name: table_name.to_string(),
+                ..sqlite.clone()
+            };
+            assert_eq!(
+                sqlite.table_exists(sqlite_conn).await,
+                exists,
+                "{table_name}"
+            );
+        }
+    }
 }
@@ -0,0 +1,75 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow::datatypes::DataType;
+use datafusion::{
+    common::{exec_err, Result as DataFusionResult},
+    logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, TypeSignature, Volatility},
+};
+use sql_provider_datafusion::expr::FTS_MATCH;
+
+/// `fts_match(column, query)`, true for the rows of a `SQLite` full-text table whose `column` matches the FTS5
+/// `query`.
+///
+/// The function has no implementation of its own: it's pushed down to `SQLite` as `column MATCH query`, so it can
+/// only filter a full-text table read through a `SqlTable`.
+#[derive(Debug)]
+pub struct FtsMatch {
+    signature: Signature,
+}
+
+impl Default for FtsMatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FtsMatch {
+    #[must_use]
+    pub fn new() -> Self {
+        FtsMatch {
+            signature: Signature::one_of(
+                vec![
+                    TypeSignature::Exact(vec![DataType::Utf8, DataType::Utf8]),
+                    TypeSignature::Exact(vec![DataType::LargeUtf8, DataType::Utf8]),
+                ],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl ScalarUDFImpl for FtsMatch {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        FTS_MATCH
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> DataFusionResult<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke(&self, _args: &[ColumnarValue]) -> DataFusionResult<ColumnarValue> {
+        exec_err!("{FTS_MATCH} can only filter the columns of a Sqlite full-text table")
+    }
+}
@@ -31,6 +31,7 @@ use datafusion::{
     },
 };
 use futures::StreamExt;
+use rusqlite::ToSql;
 use snafu::prelude::*;
 use sql_provider_datafusion::expr::Engine;
 
@@ -39,7 +40,7 @@ use crate::{
     util::{constraints, on_conflict::OnConflict},
 };
 
-use super::{to_datafusion_error, Sqlite};
+use super::{to_datafusion_error, to_sqlite_param, Sqlite};
 
 pub struct SqliteTableWriter {
     read_provider: Arc<dyn TableProvider>,
@@ -134,9 +135,6 @@ impl DataSink for SqliteDataSink {
     ) -> datafusion::common::Result<u64> {
         let mut num_rows: u64 = 0;
 
-        let mut db_conn = self.sqlite.connect().await.map_err(to_datafusion_error)?;
-        let sqlite_conn = Sqlite::sqlite_conn(&mut db_conn).map_err(to_datafusion_error)?;
-
         let data_batches_result = data
             .collect::<Vec<datafusion::common::Result<RecordBatch>>>()
             .await;
@@ -156,32 +154,18 @@ impl DataSink for SqliteDataSink {
             })?;
         }
 
-        let overwrite = self.overwrite;
-        let sqlite = Arc::clone(&self.sqlite);
-        let on_conflict = self.on_conflict.clone();
-        sqlite_conn
-            .conn
-            .call(move |conn| {
-                let transaction = conn.transaction()?;
-
-                if overwrite {
-                    sqlite.delete_all_table_data(&transaction)?;
-                }
-
-                for batch in data_batches {
-                    if batch.num_rows() > 0 {
-                        sqlite.insert_batch(&transaction, batch, on_conflict.as_ref())?;
-                    }
-                }
-
-                transaction.commit()?;
-
-                Ok(())
-            })
+        self.sqlite
+            .insert_batches(data_batches, self.overwrite, self.on_conflict.clone())
             .await
-            .context(super::UnableToInsertIntoTableAsyncSnafu)
             .map_err(to_datafusion_error)?;
 
+        // The rows are written either way, so a failed vacuum is left for the next one
+        if self.overwrite {
+            if let Err(e) = self.sqlite.vacuum_if_due().await {
+                tracing::warn!("{e}");
+            }
+        }
+
         Ok(num_rows)
     }
 }
@@ -242,14 +226,23 @@ impl DeletionSink for SqliteDeletionSink {
         let mut db_conn = self.sqlite.connect().await?;
         let sqlite_conn = Sqlite::sqlite_conn(&mut db_conn)?;
         let sqlite = Arc::clone(&self.sqlite);
-        let sql = crate::util::filters_to_sql(&self.filters, Some(Engine::SQLite))?;
+        let (sql, params) =
+            crate::util::filters_to_parameterized_sql(&self.filters, Some(Engine::SQLite))?;
+        let params = params
+            .into_iter()
+            .map(to_sqlite_param)
+            .collect::<Result<Vec<_>, _>>()?;
 
         let count: u64 = sqlite_conn
             .conn
             .call(move |conn| {
                 let tx = conn.transaction()?;
 
-                let count = sqlite.delete_from(&tx, &sql)?;
+                let params: Vec<&dyn ToSql> = params
+                    .iter()
+                    .map(|param| param.as_ref() as &dyn ToSql)
+                    .collect();
+                let count = sqlite.delete_from(&tx, &sql, &params)?;
 
                 tx.commit()?;
 
@@ -267,30 +260,33 @@ mod tests {
 
     use arrow::{
         array::{Int64Array, RecordBatch, StringArray, UInt64Array},
-        datatypes::{DataType, Schema},
+        datatypes::{DataType, Schema, SchemaRef},
     };
     use datafusion::{
         common::{Constraints, TableReference, ToDFSchema},
-        datasource::provider::TableProviderFactory,
+        datasource::{provider::TableProviderFactory, TableProvider},
         execution::context::SessionContext,
         logical_expr::{cast, col, lit, CreateExternalTable},
         physical_plan::{collect, test::exec::MockExec},
         scalar::ScalarValue,
     };
 
-    use crate::{delete::get_deletion_provider, sqlite::SqliteTableFactory};
+    use crate::{
+        delete::get_deletion_provider, sqlite::SqliteTableFactory,
+        util::constraints::tests::get_pk_constraints,
+    };
 
-    #[tokio::test]
-    #[allow(clippy::unreadable_literal)]
-    async fn test_round_trip_sqlite() {
-        let schema = Arc::new(Schema::new(vec![
-            arrow::datatypes::Field::new("time_in_string", DataType::Utf8, false),
-            arrow::datatypes::Field::new("time_int", DataType::Int64, false),
-        ]));
-        let df_schema = ToDFSchema::to_dfschema_ref(Arc::clone(&schema)).expect("df schema");
+    /// Creates the Sqlite table `name` with `schema`, created with `options` and `constraints`.
+    async fn create_test_table(
+        ctx: &SessionContext,
+        name: &str,
+        schema: &SchemaRef,
+        options: HashMap<String, String>,
+        constraints: Constraints,
+    ) -> Arc<dyn TableProvider> {
         let external_table = CreateExternalTable {
-            schema: df_schema,
-            name: TableReference::bare("test_table"),
+            schema: ToDFSchema::to_dfschema_ref(Arc::clone(schema)).expect("df schema"),
+            name: TableReference::bare(name),
             location: String::new(),
             file_type: String::new(),
             table_partition_cols: vec![],
@@ -298,15 +294,63 @@ mod tests {
             definition: None,
             order_exprs: vec![],
             unbounded: false,
-            options: HashMap::new(),
-            constraints: Constraints::empty(),
+            options,
+            constraints,
             column_defaults: HashMap::default(),
         };
-        let ctx = SessionContext::new();
-        let table = SqliteTableFactory::default()
+        SqliteTableFactory::default()
             .create(&ctx.state(), &external_table)
             .await
-            .expect("table should be created");
+            .expect("table should be created")
+    }
+
+    async fn insert_batch(
+        ctx: &SessionContext,
+        table: &Arc<dyn TableProvider>,
+        batch: RecordBatch,
+    ) {
+        let schema = batch.schema();
+        let exec = MockExec::new(vec![Ok(batch)], schema);
+        let insertion = table
+            .insert_into(&ctx.state(), Arc::new(exec), false)
+            .await
+            .expect("insertion should be successful");
+        collect(insertion, ctx.task_ctx())
+            .await
+            .expect("insert successful");
+    }
+
+    /// Runs `sql`, returning the first batch of its result.
+    async fn query_batch(ctx: &SessionContext, sql: &str) -> RecordBatch {
+        let result = ctx
+            .sql(sql)
+            .await
+            .expect("query should be planned")
+            .collect()
+            .await
+            .expect("query should be executed");
+        result
+            .into_iter()
+            .next()
+            .expect("result should have a batch")
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unreadable_literal)]
+    async fn test_round_trip_sqlite() {
+        let schema = Arc::new(Schema::new(vec![
+            arrow::datatypes::Field::new("time_in_string", DataType::Utf8, false),
+            arrow::datatypes::Field::new("time_int", DataType::Int64, false),
+        ]));
+        let ctx = SessionContext::new();
+        let table = create_test_table(
+            &ctx,
+            "test_table",
+            &schema,
+            HashMap::new(),
+            Constraints::empty(),
+        )
+        .await;
 
         let arr1 = StringArray::from(vec![
             "1970-01-01",
@@ -316,17 +360,7 @@ mod tests {
         let arr3 = Int64Array::from(vec![0, 1354360271, 1354360272]);
         let data = RecordBatch::try_new(Arc::clone(&schema), vec![Arc::new(arr1), Arc::new(arr3)])
             .expect("data should be created");
-
-        let exec = MockExec::new(vec![Ok(data)], schema);
-
-        let insertion = table
-            .insert_into(&ctx.state(), Arc::new(exec), false)
-            .await
-            .expect("insertion should be successful");
-
-        collect(insertion, ctx.task_ctx())
-            .await
-            .expect("insert successful");
+        insert_batch(&ctx, &table, data).await;
 
         let table =
             get_deletion_provider(table).expect("table should be returned as deletion provider");
@@ -376,4 +410,243 @@ mod tests {
         let expected = UInt64Array::from(vec![1]);
         assert_eq!(actual, &expected);
     }
+
+    #[tokio::test]
+    async fn test_upsert_on_unique_column() {
+        let schema = Arc::new(Schema::new(vec![
+            arrow::datatypes::Field::new("id", DataType::Int64, false),
+            arrow::datatypes::Field::new("email", DataType::Utf8, false),
+            arrow::datatypes::Field::new("name", DataType::Utf8, false),
+        ]));
+        let ctx = SessionContext::new();
+        let table = create_test_table(
+            &ctx,
+            "test_upsert_table",
+            &schema,
+            HashMap::from([
+                ("indexes".to_string(), "email:unique".to_string()),
+                ("on_conflict".to_string(), "upsert:email".to_string()),
+            ]),
+            get_pk_constraints(&["id"], Arc::clone(&schema)),
+        )
+        .await;
+
+        for (ids, emails, names) in [
+            (vec![1, 2], vec!["a@spice.ai", "b@spice.ai"], vec!["a", "b"]),
+            // Conflicts on the unique email of the first row, but not on the primary key
+            (vec![3], vec!["a@spice.ai"], vec!["updated"]),
+        ] {
+            let data = RecordBatch::try_new(
+                Arc::clone(&schema),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(emails)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .expect("data should be created");
+            insert_batch(&ctx, &table, data).await;
+        }
+
+        ctx.register_table("test_upsert_table", table)
+            .expect("table should be registered");
+        let batch = query_batch(&ctx, "SELECT id, name FROM test_upsert_table ORDER BY id").await;
+
+        // The existing row took the update path and kept its primary key
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .expect("ids should be Int64Array"),
+            &Int64Array::from(vec![1, 2])
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .expect("names should be StringArray"),
+            &StringArray::from(vec!["updated", "b"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upsert_on_composite_primary_key() {
+        let schema = Arc::new(Schema::new(vec![
+            arrow::datatypes::Field::new("tenant", DataType::Utf8, false),
+            arrow::datatypes::Field::new("id", DataType::Int64, false),
+            arrow::datatypes::Field::new("name", DataType::Utf8, false),
+        ]));
+        let ctx = SessionContext::new();
+        let table = create_test_table(
+            &ctx,
+            "test_composite_upsert_table",
+            &schema,
+            HashMap::from([("on_conflict".to_string(), "upsert:(tenant, id)".to_string())]),
+            get_pk_constraints(&["tenant", "id"], Arc::clone(&schema)),
+        )
+        .await;
+
+        for (tenants, ids, names) in [
+            (vec!["a", "b"], vec![1, 1], vec!["a-1", "b-1"]),
+            // Only the first row matches an existing (tenant, id), the id alone matches both
+            (vec!["a", "a"], vec![1, 2], vec!["updated", "a-2"]),
+        ] {
+            let data = RecordBatch::try_new(
+                Arc::clone(&schema),
+                vec![
+                    Arc::new(StringArray::from(tenants)),
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .expect("data should be created");
+            insert_batch(&ctx, &table, data).await;
+        }
+
+        ctx.register_table("test_composite_upsert_table", table)
+            .expect("table should be registered");
+        let batch = query_batch(
+            &ctx,
+            "SELECT name FROM test_composite_upsert_table ORDER BY tenant, id",
+        )
+        .await;
+        assert_eq!(
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .expect("names should be StringArray"),
+            &StringArray::from(vec!["updated", "a-2", "b-1"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_string_with_single_quote() {
+        let schema = Arc::new(Schema::new(vec![
+            arrow::datatypes::Field::new("id", DataType::Int64, false),
+            arrow::datatypes::Field::new("name", DataType::Utf8, false),
+        ]));
+        let ctx = SessionContext::new();
+        let table = create_test_table(
+            &ctx,
+            "test_delete_quote_table",
+            &schema,
+            HashMap::new(),
+            Constraints::empty(),
+        )
+        .await;
+
+        let data = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["O'Brien", "Smith"])),
+            ],
+        )
+        .expect("data should be created");
+        insert_batch(&ctx, &table, data).await;
+
+        let deletion_provider = get_deletion_provider(Arc::clone(&table))
+            .expect("table should be returned as deletion provider");
+        // The second value would match every row if it was interpolated into the SQL
+        for (name, expected_count) in [("O'Brien", 1), ("x' OR '1'='1", 0)] {
+            let plan = deletion_provider
+                .delete_from(&ctx.state(), &vec![col("name").eq(lit(name))])
+                .await
+                .expect("deletion should be successful");
+            let result = collect(plan, ctx.task_ctx())
+                .await
+                .expect("deletion successful");
+            let actual = result
+                .first()
+                .expect("result should have at least one batch")
+                .column(0)
+                .as_any()
+                .downcast_ref::<UInt64Array>()
+                .expect("result should be UInt64Array");
+            assert_eq!(actual, &UInt64Array::from(vec![expected_count]));
+        }
+
+        ctx.register_table("test_delete_quote_table", table)
+            .expect("table should be registered");
+        let batch = query_batch(&ctx, "SELECT name FROM test_delete_quote_table").await;
+        assert_eq!(
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .expect("names should be StringArray"),
+            &StringArray::from(vec!["Smith"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_large_batch_in_chunks() {
+        const ROWS: i64 = 50_000;
+        const UPSERTED_FROM: i64 = 20_001;
+
+        let schema = Arc::new(Schema::new(vec![
+            arrow::datatypes::Field::new("id", DataType::Int64, false),
+            arrow::datatypes::Field::new("name", DataType::Utf8, false),
+        ]));
+        let ctx = SessionContext::new();
+        let table = create_test_table(
+            &ctx,
+            "test_large_insert_table",
+            &schema,
+            HashMap::from([
+                ("on_conflict".to_string(), "upsert:id".to_string()),
+                // Two columns, so each statement writes 500 rows
+                (
+                    "sqlite_max_insert_variables".to_string(),
+                    "1000".to_string(),
+                ),
+            ]),
+            get_pk_constraints(&["id"], Arc::clone(&schema)),
+        )
+        .await;
+
+        // The second batch starts mid-chunk, so its chunks mix updates of existing ids and new rows
+        for (name, ids) in [
+            ("inserted", 0..ROWS),
+            ("upserted", UPSERTED_FROM..UPSERTED_FROM + ROWS),
+        ] {
+            let data = RecordBatch::try_new(
+                Arc::clone(&schema),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(ids.clone())),
+                    Arc::new(StringArray::from_iter_values(
+                        ids.map(|id| format!("{name}-{id}")),
+                    )),
+                ],
+            )
+            .expect("data should be created");
+            insert_batch(&ctx, &table, data).await;
+        }
+
+        ctx.register_table("test_large_insert_table", table)
+            .expect("table should be registered");
+        let batch = query_batch(
+            &ctx,
+            "SELECT COUNT(*), COUNT(CASE WHEN name LIKE 'upserted-%' THEN 1 END), \
+             MIN(CASE WHEN name LIKE 'upserted-%' THEN id END) \
+             FROM test_large_insert_table",
+        )
+        .await;
+        let values = batch
+            .columns()
+            .iter()
+            .map(|column| {
+                column
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .expect("counts should be Int64Array")
+                    .value(0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![UPSERTED_FROM + ROWS, ROWS, UPSERTED_FROM]);
+    }
 }
@@ -1,10 +1,11 @@
-use datafusion::logical_expr::Expr;
+use datafusion::{logical_expr::Expr, scalar::ScalarValue};
 use snafu::prelude::*;
 use sql_provider_datafusion::expr::{self, Engine};
 use std::{collections::HashMap, hash::Hash};
 
 pub mod column_reference;
 pub mod constraints;
+pub mod csv_import;
 pub mod indexes;
 pub mod on_conflict;
 pub mod secrets;
@@ -24,6 +25,23 @@ pub fn filters_to_sql(filters: &[Expr], engine: Option<Engine>) -> Result<String
         .join(" AND "))
 }
 
+/// Like `filters_to_sql`, but with the literal values of the filters bound as parameters, see
+/// `expr::to_parameterized_sql_with_engine`.
+pub fn filters_to_parameterized_sql(
+    filters: &[Expr],
+    engine: Option<Engine>,
+) -> Result<(String, Vec<ScalarValue>), Error> {
+    let mut params = Vec::new();
+    let mut sql = Vec::with_capacity(filters.len());
+    for expr in filters {
+        let (expr_sql, expr_params) = expr::to_parameterized_sql_with_engine(expr, engine)
+            .context(UnableToGenerateSQLSnafu)?;
+        sql.push(expr_sql);
+        params.extend(expr_params);
+    }
+    Ok((sql.join(" AND "), params))
+}
+
 #[must_use]
 pub fn hashmap_from_option_string<K, V>(hashmap_option_str: &str) -> HashMap<K, V>
 where
@@ -0,0 +1,572 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, BooleanArray, RecordBatch},
+    compute::{cast, filter},
+    csv::{reader::Format, Reader, ReaderBuilder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    error::ArrowError,
+};
+use datafusion::{
+    datasource::TableProvider,
+    error::DataFusionError,
+    execution::context::SessionState,
+    physical_plan::{collect, memory::MemoryExec},
+};
+use regex::Regex;
+use snafu::prelude::*;
+
+const DEFAULT_BATCH_SIZE: usize = 8192;
+const MAX_SCHEMA_INFERENCE_RECORDS: usize = 1000;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to infer the schema of the CSV data: {source}"))]
+    UnableToInferSchema { source: ArrowError },
+
+    #[snafu(display("Unable to rewind the CSV data after inferring its schema: {source}"))]
+    UnableToRewind { source: std::io::Error },
+
+    #[snafu(display("Unable to read CSV data: {source}"))]
+    UnableToReadCsv { source: ArrowError },
+
+    #[snafu(display("The task reading the CSV data failed: {source}"))]
+    ReadTaskFailed { source: tokio::task::JoinError },
+
+    #[snafu(display(
+        "Unable to coerce the value {value:?} in column {column} of row {row} to {data_type}"
+    ))]
+    UnableToCoerceValue {
+        row: usize,
+        column: String,
+        value: String,
+        data_type: DataType,
+    },
+
+    #[snafu(display(
+        "Unable to import a null in column {column} of row {row}, which isn't nullable"
+    ))]
+    NullInNonNullableColumn { row: usize, column: String },
+
+    #[snafu(display("Unable to build a record batch from CSV data: {source}"))]
+    UnableToBuildRecordBatch { source: ArrowError },
+
+    #[snafu(display("Unable to insert CSV data: {source}"))]
+    UnableToInsert { source: DataFusionError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What to do with a row holding a value that can't be coerced to the type of its column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvErrorPolicy {
+    /// Fail the import with `Error::UnableToCoerceValue`. Batches before the failing one are already inserted.
+    #[default]
+    Fail,
+    /// Skip the row.
+    SkipRow,
+    /// Import the row with a null in place of the value. The import fails with `Error::UnableToCoerceValue` if the
+    /// column isn't nullable.
+    Null,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CsvImportSummary {
+    pub rows_imported: usize,
+    pub rows_skipped: usize,
+}
+
+/// Imports CSV data into a `TableProvider`, i.e. a Sqlite or Postgres acceleration, through its `insert_into`.
+///
+/// The data is read and inserted one batch at a time, and read on a blocking thread. Values are read as text first and
+/// then coerced to the column types, so a value that doesn't fit its column is handled per row according to the
+/// `CsvErrorPolicy` rather than failing the whole batch. A null in a column that isn't nullable is handled the same
+/// way, except that `CsvErrorPolicy::Null` can't import it.
+#[derive(Debug, Clone)]
+pub struct CsvImporter {
+    schema: Option<SchemaRef>,
+    has_header: bool,
+    delimiter: u8,
+    quote: u8,
+    null_regex: Option<Regex>,
+    batch_size: usize,
+    error_policy: CsvErrorPolicy,
+}
+
+impl Default for CsvImporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvImporter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            schema: None,
+            has_header: true,
+            delimiter: b',',
+            quote: b'"',
+            null_regex: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            error_policy: CsvErrorPolicy::default(),
+        }
+    }
+
+    /// Coerces the CSV columns to `schema` instead of inferring the schema from the data.
+    #[must_use]
+    pub fn with_schema(mut self, schema: SchemaRef) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    #[must_use]
+    pub fn with_header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    #[must_use]
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    #[must_use]
+    pub fn with_quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Reads values matching `null_regex` as nulls. By default, only empty values are nulls.
+    #[must_use]
+    pub fn with_null_regex(mut self, null_regex: Regex) -> Self {
+        self.null_regex = Some(null_regex);
+        self
+    }
+
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    #[must_use]
+    pub fn with_error_policy(mut self, error_policy: CsvErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Reads the CSV data from `reader` and inserts it into `table`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CSV data can't be read, if a value can't be imported with the `CsvErrorPolicy`, or
+    /// if `table` rejects the insert.
+    pub async fn import<R: Read + Seek + Send + 'static>(
+        &self,
+        reader: R,
+        table: &dyn TableProvider,
+        state: &SessionState,
+    ) -> Result<CsvImportSummary> {
+        let (reader, schema) = match &self.schema {
+            Some(schema) => (reader, Arc::clone(schema)),
+            None => {
+                let format = self.format();
+                tokio::task::spawn_blocking(move || infer_schema(reader, &format))
+                    .await
+                    .context(ReadTaskFailedSnafu)??
+            }
+        };
+
+        // Every column is read as text, so a malformed value never fails the batch it is in
+        let text_schema = Arc::new(Schema::new(
+            schema
+                .fields()
+                .iter()
+                .map(|field| Field::new(field.name(), DataType::Utf8, true))
+                .collect::<Vec<_>>(),
+        ));
+        let mut builder = ReaderBuilder::new(text_schema)
+            .with_header(self.has_header)
+            .with_delimiter(self.delimiter)
+            .with_quote(self.quote)
+            .with_batch_size(self.batch_size);
+        if let Some(null_regex) = &self.null_regex {
+            builder = builder.with_null_regex(null_regex.clone());
+        }
+        let mut csv_reader = builder.build(reader).context(UnableToReadCsvSnafu)?;
+
+        let mut summary = CsvImportSummary::default();
+        loop {
+            let (reader, text_batch) = next_batch(csv_reader).await?;
+            csv_reader = reader;
+            let Some(text_batch) = text_batch else {
+                break;
+            };
+            let text_batch = text_batch.context(UnableToReadCsvSnafu)?;
+            let first_row = summary.rows_imported + summary.rows_skipped;
+            let (batch, rows_skipped) = self.coerce(&text_batch, &schema, first_row)?;
+            summary.rows_skipped += rows_skipped;
+
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            summary.rows_imported += batch.num_rows();
+            insert_batch(table, state, batch).await?;
+        }
+
+        Ok(summary)
+    }
+
+    fn format(&self) -> Format {
+        let format = Format::default()
+            .with_header(self.has_header)
+            .with_delimiter(self.delimiter)
+            .with_quote(self.quote);
+        match &self.null_regex {
+            Some(null_regex) => format.with_null_regex(null_regex.clone()),
+            None => format,
+        }
+    }
+
+    /// Coerces the text columns of `text_batch` to `schema`, returning the coerced batch and the number of skipped
+    /// rows. `first_row` is the number of rows read before this batch, to report the row of a value that fails.
+    fn coerce(
+        &self,
+        text_batch: &RecordBatch,
+        schema: &SchemaRef,
+        first_row: usize,
+    ) -> Result<(RecordBatch, usize)> {
+        let mut invalid_rows = vec![false; text_batch.num_rows()];
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+        for (text, field) in text_batch.columns().iter().zip(schema.fields()) {
+            let coerced = cast(text, field.data_type()).context(UnableToBuildRecordBatchSnafu)?;
+            for (row, invalid) in invalid_rows.iter_mut().enumerate() {
+                if coerced.is_valid(row) || (text.is_null(row) && field.is_nullable()) {
+                    continue;
+                }
+                // A non-null value that casts to null couldn't be coerced
+                let uncoerced = text.is_valid(row);
+                match self.error_policy {
+                    CsvErrorPolicy::SkipRow => *invalid = true,
+                    CsvErrorPolicy::Null if uncoerced && field.is_nullable() => {}
+                    _ if uncoerced => {
+                        return UnableToCoerceValueSnafu {
+                            row: first_row + row + 1,
+                            column: field.name(),
+                            value: text.as_string::<i32>().value(row),
+                            data_type: field.data_type().clone(),
+                        }
+                        .fail();
+                    }
+                    _ => {
+                        return NullInNonNullableColumnSnafu {
+                            row: first_row + row + 1,
+                            column: field.name(),
+                        }
+                        .fail();
+                    }
+                }
+            }
+            columns.push(coerced);
+        }
+
+        let mut rows_skipped = 0;
+        if self.error_policy == CsvErrorPolicy::SkipRow && invalid_rows.contains(&true) {
+            let keep = invalid_rows
+                .iter()
+                .map(|invalid| Some(!invalid))
+                .collect::<BooleanArray>();
+            columns = columns
+                .iter()
+                .map(|column| filter(column, &keep))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .context(UnableToBuildRecordBatchSnafu)?;
+            rows_skipped = invalid_rows.iter().filter(|invalid| **invalid).count();
+        }
+
+        let batch = RecordBatch::try_new(Arc::clone(schema), columns)
+            .context(UnableToBuildRecordBatchSnafu)?;
+        Ok((batch, rows_skipped))
+    }
+}
+
+/// Infers the schema of the CSV data of `reader` with `format`, then rewinds `reader` to where the data starts.
+fn infer_schema<R: Read + Seek>(mut reader: R, format: &Format) -> Result<(R, SchemaRef)> {
+    let start = reader.stream_position().context(UnableToRewindSnafu)?;
+    let (schema, _) = format
+        .infer_schema(&mut reader, Some(MAX_SCHEMA_INFERENCE_RECORDS))
+        .context(UnableToInferSchemaSnafu)?;
+    reader
+        .seek(SeekFrom::Start(start))
+        .context(UnableToRewindSnafu)?;
+    Ok((reader, Arc::new(schema)))
+}
+
+/// Reads the next batch of `csv_reader` on a blocking thread, as reading it blocks, and hands the reader back with it.
+async fn next_batch<R: Read + Send + 'static>(
+    mut csv_reader: Reader<R>,
+) -> Result<(Reader<R>, Option<Result<RecordBatch, ArrowError>>)> {
+    tokio::task::spawn_blocking(move || {
+        let text_batch = csv_reader.next();
+        (csv_reader, text_batch)
+    })
+    .await
+    .context(ReadTaskFailedSnafu)
+}
+
+async fn insert_batch(
+    table: &dyn TableProvider,
+    state: &SessionState,
+    batch: RecordBatch,
+) -> Result<()> {
+    let schema = batch.schema();
+    let input = MemoryExec::try_new(&[vec![batch]], schema, None).context(UnableToInsertSnafu)?;
+    let plan = table
+        .insert_into(state, Arc::new(input), false)
+        .await
+        .context(UnableToInsertSnafu)?;
+    collect(plan, state.task_ctx())
+        .await
+        .context(UnableToInsertSnafu)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow::{
+        array::Int64Array,
+        compute::concat_batches,
+        datatypes::{Float64Type, Int64Type},
+    };
+    use datafusion::execution::context::SessionContext;
+
+    use super::*;
+    use crate::arrow::write::MemTable;
+
+    async fn read_table(ctx: &SessionContext, table: Arc<MemTable>) -> RecordBatch {
+        let schema = table.schema();
+        let batches = ctx
+            .read_table(table)
+            .expect("table should be read")
+            .collect()
+            .await
+            .expect("batches should be collected");
+        concat_batches(&schema, &batches).expect("batches should be concatenated")
+    }
+
+    #[tokio::test]
+    async fn test_import_csv() {
+        let csv = "id,name,score\n1,\"Smith, Jane\",9.5\n2,,7\n3,\"say \"\"hi\"\"\",\n";
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let table = Arc::new(
+            MemTable::try_new(Arc::clone(&schema), vec![]).expect("mem table should be created"),
+        );
+        let ctx = SessionContext::new();
+
+        // The schema is inferred from the data
+        let summary = CsvImporter::new()
+            .with_batch_size(2)
+            .import(Cursor::new(csv), table.as_ref(), &ctx.state())
+            .await
+            .expect("csv should be imported");
+        assert_eq!(summary.rows_imported, 3);
+        assert_eq!(summary.rows_skipped, 0);
+
+        let batch = read_table(&ctx, table).await;
+        assert_eq!(batch.schema(), schema);
+
+        assert_eq!(
+            batch.column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![1, 2, 3])
+        );
+
+        let names = batch.column(1).as_string::<i32>();
+        assert_eq!(names.value(0), "Smith, Jane");
+        assert!(names.is_null(1));
+        assert_eq!(names.value(2), "say \"hi\"");
+
+        let scores = batch.column(2).as_primitive::<Float64Type>();
+        assert!((scores.value(0) - 9.5).abs() < f64::EPSILON);
+        assert!((scores.value(1) - 7.0).abs() < f64::EPSILON);
+        assert!(scores.is_null(2));
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_error_policy() {
+        let csv = "id,score\n1,1.5\n2,abc\n3,2.5\n";
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let ctx = SessionContext::new();
+        let importer = CsvImporter::new().with_schema(Arc::clone(&schema));
+
+        let table = MemTable::try_new(Arc::clone(&schema), vec![]).expect("mem table");
+        let err = importer
+            .import(Cursor::new(csv), &table, &ctx.state())
+            .await
+            .expect_err("the import should fail");
+        assert_eq!(
+            err.to_string(),
+            "Unable to coerce the value \"abc\" in column score of row 2 to Float64"
+        );
+
+        let table = Arc::new(MemTable::try_new(Arc::clone(&schema), vec![]).expect("mem table"));
+        let summary = importer
+            .clone()
+            .with_error_policy(CsvErrorPolicy::SkipRow)
+            .import(Cursor::new(csv), table.as_ref(), &ctx.state())
+            .await
+            .expect("csv should be imported");
+        assert_eq!(summary.rows_imported, 2);
+        assert_eq!(summary.rows_skipped, 1);
+        let batch = read_table(&ctx, table).await;
+        assert_eq!(
+            batch.column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![1, 3])
+        );
+
+        let table = Arc::new(MemTable::try_new(Arc::clone(&schema), vec![]).expect("mem table"));
+        let summary = importer
+            .with_error_policy(CsvErrorPolicy::Null)
+            .import(Cursor::new(csv), table.as_ref(), &ctx.state())
+            .await
+            .expect("csv should be imported");
+        assert_eq!(summary.rows_imported, 3);
+        assert_eq!(summary.rows_skipped, 0);
+        let batch = read_table(&ctx, table).await;
+        assert!(batch.column(1).is_null(1));
+
+        // A column that isn't nullable can't take the null of a value that can't be coerced, nor an empty value
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("score", DataType::Float64, false),
+        ]));
+        let table = MemTable::try_new(Arc::clone(&schema), vec![]).expect("mem table");
+        let importer = CsvImporter::new()
+            .with_schema(schema)
+            .with_error_policy(CsvErrorPolicy::Null);
+        let err = importer
+            .import(Cursor::new(csv), &table, &ctx.state())
+            .await
+            .expect_err("the import should fail");
+        assert_eq!(
+            err.to_string(),
+            "Unable to coerce the value \"abc\" in column score of row 2 to Float64"
+        );
+        let err = importer
+            .import(Cursor::new("id,score\n1,1.5\n2,\n"), &table, &ctx.state())
+            .await
+            .expect_err("the import should fail");
+        assert_eq!(
+            err.to_string(),
+            "Unable to import a null in column score of row 2, which isn't nullable"
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_import_csv_into_sqlite() {
+        use std::collections::HashMap;
+
+        use datafusion::{
+            assert_batches_eq,
+            common::{Constraints, ToDFSchema},
+            datasource::provider::TableProviderFactory,
+            logical_expr::CreateExternalTable,
+            sql::TableReference,
+        };
+
+        use crate::sqlite::SqliteTableFactory;
+
+        let csv = "id,name,score\n2,,7\n1,\"Smith, Jane\",9.5\n";
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let external_table = CreateExternalTable {
+            schema: ToDFSchema::to_dfschema_ref(Arc::clone(&schema)).expect("df schema"),
+            name: TableReference::bare("scores"),
+            location: String::new(),
+            file_type: String::new(),
+            table_partition_cols: vec![],
+            if_not_exists: true,
+            definition: None,
+            order_exprs: vec![],
+            unbounded: false,
+            options: HashMap::new(),
+            constraints: Constraints::empty(),
+            column_defaults: HashMap::default(),
+        };
+        let ctx = SessionContext::new();
+        let table = SqliteTableFactory::default()
+            .create(&ctx.state(), &external_table)
+            .await
+            .expect("table should be created");
+
+        let summary = CsvImporter::new()
+            .with_schema(Arc::clone(&schema))
+            .import(Cursor::new(csv), table.as_ref(), &ctx.state())
+            .await
+            .expect("csv should be imported");
+        assert_eq!(summary.rows_imported, 2);
+
+        ctx.register_table("scores", table)
+            .expect("table should be registered");
+        let batches = ctx
+            .sql("SELECT id, name, score FROM scores ORDER BY id")
+            .await
+            .expect("query should be planned")
+            .collect()
+            .await
+            .expect("query should be executed");
+        let data_types = batches[0]
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.data_type().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            data_types,
+            vec![DataType::Int64, DataType::Utf8, DataType::Float64]
+        );
+        assert_batches_eq!(
+            [
+                "+----+-------------+-------+",
+                "| id | name        | score |",
+                "+----+-------------+-------+",
+                "| 1  | Smith, Jane | 9.5   |",
+                "| 2  |             | 7.0   |",
+                "+----+-------------+-------+",
+            ],
+            &batches
+        );
+    }
+}
@@ -21,12 +21,15 @@ pub enum IndexType {
     #[default]
     Enabled,
     Unique,
+    /// A full-text index. Engines without full-text indexes create a regular index instead.
+    FullText,
 }
 
 impl From<&str> for IndexType {
     fn from(index_type: &str) -> Self {
         match index_type.to_lowercase().as_str() {
             "unique" => IndexType::Unique,
+            "fulltext" => IndexType::FullText,
             _ => IndexType::Enabled,
         }
     }
@@ -37,6 +40,7 @@ impl Display for IndexType {
         match self {
             IndexType::Unique => write!(f, "unique"),
             IndexType::Enabled => write!(f, "enabled"),
+            IndexType::FullText => write!(f, "fulltext"),
         }
     }
 }
@@ -53,15 +57,17 @@ mod tests {
         assert_eq!(IndexType::from("enabled"), IndexType::Enabled);
         assert_eq!(IndexType::from("Enabled"), IndexType::Enabled);
         assert_eq!(IndexType::from("ENABLED"), IndexType::Enabled);
+        assert_eq!(IndexType::from("FullText"), IndexType::FullText);
     }
 
     #[test]
     fn test_indexes_from_option_string() {
-        let indexes_option_str = "index1:unique;index2";
+        let indexes_option_str = "index1:unique;index2;index3:fulltext";
         let indexes: HashMap<String, IndexType> =
             crate::util::hashmap_from_option_string(indexes_option_str);
-        assert_eq!(indexes.len(), 2);
+        assert_eq!(indexes.len(), 3);
         assert_eq!(indexes.get("index1"), Some(&IndexType::Unique));
         assert_eq!(indexes.get("index2"), Some(&IndexType::Enabled));
+        assert_eq!(indexes.get("index3"), Some(&IndexType::FullText));
     }
 }
@@ -16,11 +16,15 @@ limitations under the License.
 #![allow(clippy::module_name_repetitions)]
 use arrow::datatypes::SchemaRef;
 use arrow_sql_gen::sea_query::{self, Alias};
+use datafusion::common::Constraints;
 use itertools::Itertools;
 use snafu::prelude::*;
 use std::fmt::Display;
 
-use super::column_reference::{self, ColumnReference};
+use super::{
+    column_reference::{self, ColumnReference},
+    constraints::get_primary_keys_from_constraints,
+};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -42,8 +46,32 @@ pub enum OnConflict {
 }
 
 impl OnConflict {
+    /// The columns an upsert on `target` sets from the conflicting row.
+    ///
+    /// The target can be any unique constraint, not only the primary key, and can have several columns, i.e.
+    /// `upsert:(tenant, id)`. The primary key of the existing row is preserved when upserting on another unique
+    /// constraint, so the update path never rewrites a row's identity. When the target and primary key are all the
+    /// columns, there is nothing to set and the conflicting row is kept as it is.
+    fn upsert_columns(
+        target: &ColumnReference,
+        schema: &SchemaRef,
+        constraints: &Constraints,
+    ) -> Vec<String> {
+        let primary_keys = get_primary_keys_from_constraints(constraints, schema);
+        schema
+            .fields()
+            .iter()
+            .map(|f| f.name().to_string())
+            .filter(|name| !target.contains(name) && !primary_keys.contains(name))
+            .collect()
+    }
+
     #[must_use]
-    pub fn build_on_conflict_statement(&self, schema: &SchemaRef) -> String {
+    pub fn build_on_conflict_statement(
+        &self,
+        schema: &SchemaRef,
+        constraints: &Constraints,
+    ) -> String {
         match self {
             OnConflict::DoNothingAll => "ON CONFLICT DO NOTHING".to_string(),
             OnConflict::DoNothing(column) => {
@@ -53,12 +81,11 @@ impl OnConflict {
                 )
             }
             OnConflict::Upsert(column) => {
-                let non_constraint_columns = schema
-                    .fields()
-                    .iter()
-                    .filter(|f| !column.contains(f.name()))
-                    .map(|f| f.name().to_string())
-                    .collect::<Vec<String>>();
+                let non_constraint_columns = Self::upsert_columns(column, schema, constraints);
+                if non_constraint_columns.is_empty() {
+                    return OnConflict::DoNothing(column.clone())
+                        .build_on_conflict_statement(schema, constraints);
+                }
                 let mut update_cols = String::new();
                 for (i, col) in non_constraint_columns.iter().enumerate() {
                     update_cols.push_str(&format!(r#""{col}" = EXCLUDED."{col}""#));
@@ -75,7 +102,11 @@ impl OnConflict {
     }
 
     #[must_use]
-    pub fn build_sea_query_on_conflict(&self, schema: &SchemaRef) -> sea_query::OnConflict {
+    pub fn build_sea_query_on_conflict(
+        &self,
+        schema: &SchemaRef,
+        constraints: &Constraints,
+    ) -> sea_query::OnConflict {
         match self {
             OnConflict::DoNothingAll => {
                 let mut on_conflict = sea_query::OnConflict::new();
@@ -94,14 +125,16 @@ impl OnConflict {
                     column.iter().map(Alias::new).collect(),
                 );
 
-                let non_constraint_columns = schema
-                    .fields()
-                    .iter()
-                    .filter(|f| !column.contains(f.name()))
-                    .map(|f| Alias::new(f.name()))
+                let non_constraint_columns = Self::upsert_columns(column, schema, constraints)
+                    .into_iter()
+                    .map(Alias::new)
                     .collect::<Vec<Alias>>();
 
-                on_conflict.update_columns(non_constraint_columns);
+                if non_constraint_columns.is_empty() {
+                    on_conflict.do_nothing();
+                } else {
+                    on_conflict.update_columns(non_constraint_columns);
+                }
 
                 on_conflict
             }
@@ -155,8 +188,12 @@ mod tests {
     use std::sync::Arc;
 
     use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::common::Constraints;
 
-    use crate::util::{column_reference::ColumnReference, on_conflict::OnConflict};
+    use crate::util::{
+        column_reference::ColumnReference, constraints::tests::get_pk_constraints,
+        on_conflict::OnConflict,
+    };
 
     #[test]
     fn test_on_conflict_from_str() {
@@ -213,20 +250,82 @@ mod tests {
         ]));
         let on_conflict = OnConflict::DoNothingAll;
         assert_eq!(
-            on_conflict.build_on_conflict_statement(&schema),
+            on_conflict.build_on_conflict_statement(&schema, &Constraints::empty()),
             "ON CONFLICT DO NOTHING".to_string()
         );
 
         let on_conflict = OnConflict::DoNothing(ColumnReference::new(vec!["col1".to_string()]));
         assert_eq!(
-            on_conflict.build_on_conflict_statement(&schema),
+            on_conflict.build_on_conflict_statement(&schema, &Constraints::empty()),
             r#"ON CONFLICT ("col1") DO NOTHING"#.to_string()
         );
 
         let on_conflict = OnConflict::Upsert(ColumnReference::new(vec!["col2".to_string()]));
         assert_eq!(
-            on_conflict.build_on_conflict_statement(&schema),
+            on_conflict.build_on_conflict_statement(&schema, &Constraints::empty()),
             r#"ON CONFLICT ("col2") DO UPDATE SET "col1" = EXCLUDED."col1""#.to_string()
         );
     }
+
+    #[test]
+    fn test_upsert_on_unique_column_preserves_primary_key() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("email", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let constraints = get_pk_constraints(&["id"], Arc::clone(&schema));
+
+        let on_conflict = OnConflict::Upsert(ColumnReference::new(vec!["email".to_string()]));
+        assert_eq!(
+            on_conflict.build_on_conflict_statement(&schema, &constraints),
+            r#"ON CONFLICT ("email") DO UPDATE SET "name" = EXCLUDED."name""#.to_string()
+        );
+
+        let on_conflict = OnConflict::Upsert(ColumnReference::new(vec!["id".to_string()]));
+        assert_eq!(
+            on_conflict.build_on_conflict_statement(&schema, &constraints),
+            r#"ON CONFLICT ("id") DO UPDATE SET "email" = EXCLUDED."email", "name" = EXCLUDED."name""#
+                .to_string()
+        );
+
+        // Without other columns to set, the conflicting row is kept
+        let schema = Arc::new(schema.project(&[0, 1]).expect("key columns"));
+        let on_conflict = OnConflict::Upsert(ColumnReference::new(vec!["email".to_string()]));
+        assert_eq!(
+            on_conflict.build_on_conflict_statement(&schema, &constraints),
+            r#"ON CONFLICT ("email") DO NOTHING"#.to_string()
+        );
+    }
+
+    #[test]
+    fn test_upsert_on_composite_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("tenant", DataType::Utf8, false),
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let constraints = get_pk_constraints(&["tenant", "id"], Arc::clone(&schema));
+
+        let on_conflict = OnConflict::try_from("upsert:(tenant, id)").expect("valid on conflict");
+        assert_eq!(
+            on_conflict,
+            OnConflict::Upsert(ColumnReference::new(vec![
+                "id".to_string(),
+                "tenant".to_string()
+            ]))
+        );
+        assert_eq!(on_conflict.to_string(), "upsert:(id, tenant)");
+        assert_eq!(
+            on_conflict.build_on_conflict_statement(&schema, &constraints),
+            r#"ON CONFLICT ("id", "tenant") DO UPDATE SET "name" = EXCLUDED."name""#.to_string()
+        );
+
+        // Without other columns to set, the conflicting row is kept
+        let schema = Arc::new(schema.project(&[0, 1]).expect("key columns"));
+        assert_eq!(
+            on_conflict.build_on_conflict_statement(&schema, &constraints),
+            r#"ON CONFLICT ("id", "tenant") DO NOTHING"#.to_string()
+        );
+    }
 }
@@ -0,0 +1,536 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use datafusion::{execution::SendableRecordBatchStream, sql::TableReference};
+use snafu::prelude::*;
+use tokio::time::Instant;
+
+use super::{DbConnectionPool, JoinPushDown, Result};
+use crate::dbconnection::{self, AsyncDbConnection, DbConnection, SyncDbConnection};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The circuit breaker is open after {failures} consecutive failures, retry in {}s",
+        retry_in.as_secs()
+    ))]
+    CircuitOpen { failures: u32, retry_in: Duration },
+
+    #[snafu(display("The connection is in use by another call"))]
+    ConnectionInUse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through to the backend.
+    Closed,
+    /// Calls fail fast with `Error::CircuitOpen` until the cooldown has elapsed.
+    Open,
+    /// The cooldown has elapsed and a single call probes the backend.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probing_since: Option<Instant>,
+}
+
+/// Tracks consecutive failures of a backend and trips once `failure_threshold` is reached.
+///
+/// While open, `check` fails with `Error::CircuitOpen`. Once `cooldown` has elapsed the breaker half-opens and lets a
+/// single call probe the backend again: a success closes it, a failure re-opens it for another cooldown. Other calls
+/// keep failing while the probe is in flight, unless it hasn't reported back within another cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+impl CircuitBreaker {
+    #[must_use]
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                opened_at: None,
+                probing_since: None,
+            }),
+        }
+    }
+
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        let Ok(state) = self.state.lock() else {
+            return CircuitState::Closed;
+        };
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() >= self.cooldown => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    /// Checks whether a call to the backend is allowed. While half-open, the allowed call is the probe, and its
+    /// outcome must be reported with `record_success` or `record_failure`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::CircuitOpen` if the breaker is open and the cooldown has not elapsed yet, or if it is half-open
+    /// and another call is already probing the backend.
+    pub fn check(&self) -> Result<(), Error> {
+        let Ok(mut state) = self.state.lock() else {
+            return Ok(());
+        };
+        let Some(opened_at) = state.opened_at else {
+            return Ok(());
+        };
+        if opened_at.elapsed() < self.cooldown {
+            return CircuitOpenSnafu {
+                failures: state.consecutive_failures,
+                retry_in: self.cooldown.saturating_sub(opened_at.elapsed()),
+            }
+            .fail();
+        }
+        match state.probing_since {
+            Some(probing_since) if probing_since.elapsed() < self.cooldown => CircuitOpenSnafu {
+                failures: state.consecutive_failures,
+                retry_in: self.cooldown.saturating_sub(probing_since.elapsed()),
+            }
+            .fail(),
+            _ => {
+                state.probing_since = Some(Instant::now());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.consecutive_failures = 0;
+            state.opened_at = None;
+            state.probing_since = None;
+        }
+    }
+
+    pub fn record_failure(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.probing_since = None;
+            state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+            if state.consecutive_failures >= self.failure_threshold {
+                if state.opened_at.is_none() {
+                    tracing::warn!(
+                        "Circuit breaker opened after {} consecutive failures",
+                        state.consecutive_failures
+                    );
+                }
+                // A failed probe while half-open starts a new cooldown
+                state.opened_at = Some(Instant::now());
+            }
+        }
+    }
+}
+
+/// A `DbConnectionPool` that guards another pool with a `CircuitBreaker`.
+///
+/// Failed `connect()` calls count towards the breaker, and so do failed calls on the connections it hands out, which
+/// are wrapped in a `CircuitBreakerConnection`.
+pub struct CircuitBreakerPool<T, P: 'static> {
+    pool: Arc<dyn DbConnectionPool<T, P> + Send + Sync>,
+    circuit_breaker: Arc<CircuitBreaker>,
+}
+
+impl<T, P: 'static> CircuitBreakerPool<T, P> {
+    #[must_use]
+    pub fn new(pool: Arc<dyn DbConnectionPool<T, P> + Send + Sync>) -> Self {
+        Self {
+            pool,
+            circuit_breaker: Arc::new(CircuitBreaker::default()),
+        }
+    }
+
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Arc::new(circuit_breaker);
+        self
+    }
+
+    #[must_use]
+    pub fn circuit_breaker(&self) -> Arc<CircuitBreaker> {
+        Arc::clone(&self.circuit_breaker)
+    }
+}
+
+#[async_trait]
+impl<T: 'static, P: Sync + 'static> DbConnectionPool<T, P> for CircuitBreakerPool<T, P> {
+    async fn connect(&self) -> Result<Box<dyn DbConnection<T, P>>> {
+        self.circuit_breaker.check()?;
+
+        match self.pool.connect().await {
+            Ok(conn) => {
+                self.circuit_breaker.record_success();
+                Ok(Box::new(CircuitBreakerConnection::new(
+                    conn,
+                    Arc::clone(&self.circuit_breaker),
+                )))
+            }
+            Err(e) => {
+                self.circuit_breaker.record_failure();
+                Err(e)
+            }
+        }
+    }
+
+    fn join_push_down(&self) -> JoinPushDown {
+        self.pool.join_push_down()
+    }
+}
+
+/// A connection handed out by `CircuitBreakerPool`, which records the outcome of each call on the connection it wraps
+/// in the pool's `CircuitBreaker`. Errors of record batch streams are not recorded, only those of the calls returning
+/// them.
+///
+/// `as_any` returns the wrapper rather than the wrapped connection, which `into_inner` returns.
+pub struct CircuitBreakerConnection<T: 'static, P: 'static> {
+    // Locked for the duration of each call, as the boxed connection isn't `Sync`
+    conn: tokio::sync::Mutex<Box<dyn DbConnection<T, P>>>,
+    is_sync: bool,
+    is_async: bool,
+    circuit_breaker: Arc<CircuitBreaker>,
+}
+
+impl<T: 'static, P: 'static> CircuitBreakerConnection<T, P> {
+    #[must_use]
+    pub fn new(conn: Box<dyn DbConnection<T, P>>, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        Self {
+            is_sync: conn.as_sync().is_some(),
+            is_async: conn.as_async().is_some(),
+            conn: tokio::sync::Mutex::new(conn),
+            circuit_breaker,
+        }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> Box<dyn DbConnection<T, P>> {
+        self.conn.into_inner()
+    }
+
+    fn record<R, E>(&self, result: Result<R, E>) -> Result<R, E> {
+        match &result {
+            Ok(_) => self.circuit_breaker.record_success(),
+            Err(_) => self.circuit_breaker.record_failure(),
+        }
+        result
+    }
+
+    fn try_lock(&self) -> Result<tokio::sync::MutexGuard<'_, Box<dyn DbConnection<T, P>>>, Error> {
+        self.conn
+            .try_lock()
+            .map_err(|_| ConnectionInUseSnafu.build())
+    }
+}
+
+impl<T: 'static, P: Sync + 'static> DbConnection<T, P> for CircuitBreakerConnection<T, P> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_sync(&self) -> Option<&dyn SyncDbConnection<T, P>> {
+        self.is_sync.then_some(self as &dyn SyncDbConnection<T, P>)
+    }
+
+    fn as_async(&self) -> Option<&dyn AsyncDbConnection<T, P>> {
+        self.is_async
+            .then_some(self as &dyn AsyncDbConnection<T, P>)
+    }
+}
+
+impl<T: 'static, P: Sync + 'static> SyncDbConnection<T, P> for CircuitBreakerConnection<T, P> {
+    fn get_schema(
+        &self,
+        table_reference: &TableReference,
+    ) -> Result<SchemaRef, dbconnection::Error> {
+        let conn = self
+            .try_lock()
+            .map_err(|e| dbconnection::Error::UnableToGetSchema { source: e.into() })?;
+        let sync_conn = conn
+            .as_sync()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        self.record(sync_conn.get_schema(table_reference))
+    }
+
+    fn query_arrow(&self, sql: &str, params: &[P]) -> Result<SendableRecordBatchStream> {
+        let conn = self.try_lock()?;
+        let sync_conn = conn
+            .as_sync()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        self.record(sync_conn.query_arrow(sql, params))
+    }
+
+    fn execute(&self, sql: &str, params: &[P]) -> Result<u64> {
+        let conn = self.try_lock()?;
+        let sync_conn = conn
+            .as_sync()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        self.record(sync_conn.execute(sql, params))
+    }
+}
+
+#[async_trait]
+impl<T: 'static, P: Sync + 'static> AsyncDbConnection<T, P> for CircuitBreakerConnection<T, P> {
+    async fn get_schema(
+        &self,
+        table_reference: &TableReference,
+    ) -> Result<SchemaRef, dbconnection::Error> {
+        let conn = self.conn.lock().await;
+        let async_conn = conn
+            .as_async()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        self.record(async_conn.get_schema(table_reference).await)
+    }
+
+    async fn query_arrow(&self, sql: &str, params: &[P]) -> Result<SendableRecordBatchStream> {
+        let conn = self.conn.lock().await;
+        let async_conn = conn
+            .as_async()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        self.record(async_conn.query_arrow(sql, params).await)
+    }
+
+    async fn execute(&self, sql: &str, params: &[P]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let async_conn = conn
+            .as_async()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        self.record(async_conn.execute(sql, params).await)
+    }
+
+    // Transaction control isn't recorded, as connections that don't support transactions fail it
+    async fn begin_read_only(&self) -> Result<()> {
+        let conn = self.conn.lock().await;
+        let async_conn = conn
+            .as_async()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        async_conn.begin_read_only().await
+    }
+
+    async fn commit(&self) -> Result<()> {
+        let conn = self.conn.lock().await;
+        let async_conn = conn
+            .as_async()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        async_conn.commit().await
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        let conn = self.conn.lock().await;
+        let async_conn = conn
+            .as_async()
+            .ok_or(dbconnection::Error::UnableToDowncastConnection {})?;
+        async_conn.rollback().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct TestConnection;
+
+    impl DbConnection<(), ()> for TestConnection {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+
+        fn as_async(&self) -> Option<&dyn AsyncDbConnection<(), ()>> {
+            Some(self)
+        }
+    }
+
+    // Queries fail, statements succeed
+    #[async_trait]
+    impl AsyncDbConnection<(), ()> for TestConnection {
+        async fn get_schema(
+            &self,
+            _table_reference: &TableReference,
+        ) -> Result<SchemaRef, dbconnection::Error> {
+            Err(dbconnection::Error::UnableToGetSchema {
+                source: "connection reset".into(),
+            })
+        }
+
+        async fn query_arrow(
+            &self,
+            _sql: &str,
+            _params: &[()],
+        ) -> Result<SendableRecordBatchStream> {
+            Err("connection reset".into())
+        }
+
+        async fn execute(&self, _sql: &str, _params: &[()]) -> Result<u64> {
+            Ok(1)
+        }
+    }
+
+    #[derive(Default)]
+    struct FlakyPool {
+        healthy: AtomicBool,
+        connects: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DbConnectionPool<(), ()> for FlakyPool {
+        async fn connect(&self) -> Result<Box<dyn DbConnection<(), ()>>> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            if self.healthy.load(Ordering::SeqCst) {
+                Ok(Box::new(TestConnection))
+            } else {
+                Err("connection refused".into())
+            }
+        }
+
+        fn join_push_down(&self) -> JoinPushDown {
+            JoinPushDown::Disallow
+        }
+    }
+
+    // The clock is paused so the cooldowns elapse instantly
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_opens_and_half_opens() {
+        let backend = Arc::new(FlakyPool::default());
+        let pool = CircuitBreakerPool::new(Arc::clone(&backend) as _)
+            .with_circuit_breaker(CircuitBreaker::new(3, Duration::from_secs(30)));
+        let circuit_breaker = pool.circuit_breaker();
+
+        for _ in 0..3 {
+            assert!(pool.connect().await.is_err());
+        }
+        assert_eq!(circuit_breaker.state(), CircuitState::Open);
+
+        // Fails fast without reaching the backend
+        let Err(err) = pool.connect().await else {
+            panic!("connect should fail while the circuit is open");
+        };
+        assert!(err.downcast_ref::<Error>().is_some());
+        assert_eq!(backend.connects.load(Ordering::SeqCst), 3);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(circuit_breaker.state(), CircuitState::HalfOpen);
+
+        // A failed probe re-opens the circuit
+        assert!(pool.connect().await.is_err());
+        assert_eq!(backend.connects.load(Ordering::SeqCst), 4);
+        assert_eq!(circuit_breaker.state(), CircuitState::Open);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        backend.healthy.store(true, Ordering::SeqCst);
+
+        // A successful probe closes it again
+        assert!(pool.connect().await.is_ok());
+        assert_eq!(circuit_breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_query_failures_trip_the_breaker() {
+        let backend = Arc::new(FlakyPool::default());
+        backend.healthy.store(true, Ordering::SeqCst);
+        let pool = CircuitBreakerPool::new(Arc::clone(&backend) as _)
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(30)));
+        let circuit_breaker = pool.circuit_breaker();
+
+        let conn = pool.connect().await.expect("connection is handed out");
+        let async_conn = conn.as_async().expect("connection is async");
+        assert!(async_conn.query_arrow("SELECT 1", &[]).await.is_err());
+        // A successful call resets the consecutive failures
+        assert_eq!(async_conn.execute("DELETE FROM t", &[]).await.ok(), Some(1));
+        assert!(async_conn.query_arrow("SELECT 1", &[]).await.is_err());
+        assert_eq!(circuit_breaker.state(), CircuitState::Closed);
+        assert!(async_conn
+            .get_schema(&TableReference::bare("t"))
+            .await
+            .is_err());
+        assert_eq!(circuit_breaker.state(), CircuitState::Open);
+
+        // Fails fast without reaching the backend
+        let Err(err) = pool.connect().await else {
+            panic!("connect should fail while the circuit is open");
+        };
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::CircuitOpen { failures: 2, .. })
+        ));
+        assert_eq!(backend.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_half_open_circuit_allows_a_single_probe() {
+        let circuit_breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        circuit_breaker.record_failure();
+        assert!(circuit_breaker.check().is_err());
+
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert_eq!(circuit_breaker.state(), CircuitState::Open);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(circuit_breaker.state(), CircuitState::HalfOpen);
+
+        // The first call probes the backend, the others fail until it reports back
+        assert!(circuit_breaker.check().is_ok());
+        assert!(matches!(
+            circuit_breaker.check(),
+            Err(Error::CircuitOpen { .. })
+        ));
+
+        // A probe that doesn't report back within a cooldown is replaced by another one
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(circuit_breaker.check().is_ok());
+        assert!(circuit_breaker.check().is_err());
+
+        circuit_breaker.record_success();
+        assert_eq!(circuit_breaker.state(), CircuitState::Closed);
+        assert!(circuit_breaker.check().is_ok());
+        assert!(circuit_breaker.check().is_ok());
+    }
+}
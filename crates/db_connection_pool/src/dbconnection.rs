@@ -25,6 +25,8 @@ use snafu::prelude::*;
 pub mod clickhouseconn;
 #[cfg(feature = "duckdb")]
 pub mod duckdbconn;
+#[cfg(feature = "mock")]
+pub mod mockconn;
 #[cfg(feature = "mysql")]
 pub mod mysqlconn;
 #[cfg(feature = "odbc")]
@@ -55,13 +57,12 @@ pub enum Error {
         table_name: String,
         source: GenericError,
     },
+
+    #[snafu(display("Read-only transactions are not supported by this connection"))]
+    ReadOnlyTransactionNotSupported {},
 }
 
 pub trait SyncDbConnection<T, P>: DbConnection<T, P> {
-    fn new(conn: T) -> Self
-    where
-        Self: Sized;
-
     /// Get the schema for a table reference.
     ///
     /// # Arguments
@@ -100,12 +101,133 @@ pub trait SyncDbConnection<T, P>: DbConnection<T, P> {
 
 #[async_trait::async_trait]
 pub trait AsyncDbConnection<T, P>: DbConnection<T, P> + Sync {
-    fn new(conn: T) -> Self
-    where
-        Self: Sized;
     async fn get_schema(&self, table_reference: &TableReference) -> Result<SchemaRef, Error>;
     async fn query_arrow(&self, sql: &str, params: &[P]) -> Result<SendableRecordBatchStream>;
     async fn execute(&self, sql: &str, params: &[P]) -> Result<u64>;
+
+    /// Starts a read-only transaction, so that all queries on this connection read from the same snapshot until
+    /// `commit` or `rollback` is called. Use `ReadOnlyTransaction` rather than calling this directly, so the
+    /// transaction is rolled back when it isn't ended before the connection is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be started, or if the connection doesn't support read-only
+    /// transactions.
+    async fn begin_read_only(&self) -> Result<()> {
+        ReadOnlyTransactionNotSupportedSnafu.fail()?
+    }
+
+    /// Ends the transaction started by `begin_read_only`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be committed.
+    async fn commit(&self) -> Result<()> {
+        ReadOnlyTransactionNotSupportedSnafu.fail()?
+    }
+
+    /// Ends the transaction started by `begin_read_only`, discarding it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be rolled back.
+    async fn rollback(&self) -> Result<()> {
+        ReadOnlyTransactionNotSupportedSnafu.fail()?
+    }
+}
+
+/// A read-only transaction on a connection, see `AsyncDbConnection::begin_read_only`. Dropping it without calling
+/// `commit` or `rollback` rolls the transaction back in the background before the connection is returned to its pool.
+pub struct ReadOnlyTransaction<T: 'static, P: 'static> {
+    conn: Option<Box<dyn DbConnection<T, P>>>,
+}
+
+impl<T: 'static, P: 'static> ReadOnlyTransaction<T, P> {
+    /// Starts a read-only transaction on `conn`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be started, or if the connection doesn't support read-only
+    /// transactions.
+    pub async fn begin(conn: Box<dyn DbConnection<T, P>>) -> Result<Self> {
+        let async_conn = conn
+            .as_async()
+            .context(ReadOnlyTransactionNotSupportedSnafu)?;
+        async_conn.begin_read_only().await?;
+        Ok(Self { conn: Some(conn) })
+    }
+
+    /// Queries the snapshot of the transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn query_arrow(
+        &mut self,
+        sql: &str,
+        params: &[P],
+    ) -> Result<SendableRecordBatchStream> {
+        // Takes `&mut self` so the future is `Send`: the boxed connection isn't `Sync`, only its async view held
+        // across the await is
+        let async_conn = self.async_conn()?;
+        async_conn.query_arrow(sql, params).await
+    }
+
+    /// Commits the transaction, returning the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be committed.
+    pub async fn commit(mut self) -> Result<Box<dyn DbConnection<T, P>>> {
+        let async_conn = self.async_conn()?;
+        async_conn.commit().await?;
+        Ok(self.conn.take().context(UnableToDowncastConnectionSnafu)?)
+    }
+
+    /// Rolls the transaction back, returning the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be rolled back.
+    pub async fn rollback(mut self) -> Result<Box<dyn DbConnection<T, P>>> {
+        let async_conn = self.async_conn()?;
+        async_conn.rollback().await?;
+        Ok(self.conn.take().context(UnableToDowncastConnectionSnafu)?)
+    }
+
+    fn async_conn(&self) -> Result<&dyn AsyncDbConnection<T, P>> {
+        Ok(self
+            .conn
+            .as_deref()
+            .and_then(DbConnection::as_async)
+            .context(UnableToDowncastConnectionSnafu)?)
+    }
+}
+
+impl<T: 'static, P: 'static> Drop for ReadOnlyTransaction<T, P> {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        // The connection is moved into the task, so it only goes back to its pool once the rollback is done
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    let Some(async_conn) = conn.as_async() else {
+                        return;
+                    };
+                    if let Err(e) = async_conn.rollback().await {
+                        tracing::warn!("Failed to roll back a dropped read-only transaction: {e}");
+                    }
+                });
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "A read-only transaction was dropped outside of a runtime and can't be rolled back"
+                );
+            }
+        }
+    }
 }
 
 pub trait DbConnection<T, P>: Send {
@@ -169,3 +291,41 @@ pub async fn query_arrow<T, P>(
         return Err(Error::UnableToDowncastConnection {});
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::mockconn::MockDbConnection;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dropped_read_only_transaction_is_rolled_back() {
+        let conn: MockDbConnection = MockDbConnection::default();
+
+        let transaction = ReadOnlyTransaction::begin(Box::new(conn.clone()))
+            .await
+            .expect("transaction should begin");
+        transaction
+            .commit()
+            .await
+            .expect("transaction should commit");
+        assert_eq!(conn.statements(), vec!["BEGIN READ ONLY", "COMMIT"]);
+
+        let transaction = ReadOnlyTransaction::begin(Box::new(conn.clone()))
+            .await
+            .expect("transaction should begin");
+        drop(transaction);
+        // The rollback runs on a spawned task
+        for _ in 0..100 {
+            if conn.statements().len() == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            conn.statements(),
+            vec!["BEGIN READ ONLY", "COMMIT", "BEGIN READ ONLY", "ROLLBACK"]
+        );
+    }
+}
@@ -85,11 +85,6 @@ impl<'a> DbConnection<ClientHandle, &'a (dyn Sync)> for ClickhouseConnection {
 // But keep it in mind.
 #[async_trait::async_trait]
 impl<'a> AsyncDbConnection<ClientHandle, &'a (dyn Sync)> for ClickhouseConnection {
-    // Required by trait, but not used.
-    fn new(_: ClientHandle) -> Self {
-        unreachable!()
-    }
-
     async fn get_schema(
         &self,
         table_reference: &TableReference,
@@ -43,6 +43,11 @@ pub struct DuckDbConnection {
 }
 
 impl DuckDbConnection {
+    #[must_use]
+    pub fn new(conn: r2d2::PooledConnection<DuckdbConnectionManager>) -> Self {
+        DuckDbConnection { conn }
+    }
+
     pub fn get_underlying_conn_mut(
         &mut self,
     ) -> &mut r2d2::PooledConnection<DuckdbConnectionManager> {
@@ -72,10 +77,6 @@ impl<'a> DbConnection<r2d2::PooledConnection<DuckdbConnectionManager>, &'a dyn T
 impl SyncDbConnection<r2d2::PooledConnection<DuckdbConnectionManager>, &dyn ToSql>
     for DuckDbConnection
 {
-    fn new(conn: r2d2::PooledConnection<DuckdbConnectionManager>) -> Self {
-        DuckDbConnection { conn }
-    }
-
     fn get_schema(&self, table_reference: &TableReference) -> Result<SchemaRef, super::Error> {
         let table_str = if is_table_function(table_reference) {
             table_reference.to_string()
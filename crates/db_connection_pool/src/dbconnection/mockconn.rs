@@ -0,0 +1,266 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::physical_plan::memory::MemoryStream;
+use datafusion::sql::TableReference;
+use snafu::prelude::*;
+
+use super::AsyncDbConnection;
+use super::DbConnection;
+use super::Result;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{message}"))]
+    MockError { message: String },
+
+    #[snafu(display("No mock response left for {call}"))]
+    NoMockResponse { call: String },
+
+    #[snafu(display("The mock connection state is poisoned"))]
+    PoisonedState {},
+}
+
+#[derive(Default)]
+struct MockState {
+    schemas: VecDeque<Result<SchemaRef, String>>,
+    query_results: VecDeque<Result<(SchemaRef, Vec<RecordBatch>), String>>,
+    execute_results: VecDeque<Result<u64, String>>,
+    statements: Vec<String>,
+}
+
+/// A fake backend that returns programmed responses, for testing code that is generic over `DbConnection`.
+///
+/// Responses are queued per call type and consumed in order. Clones share the same queues, so a clone can be
+/// handed to a `MockPool` while the original is kept to inspect the executed statements.
+pub struct MockDbConnection<P = ()> {
+    state: Arc<Mutex<MockState>>,
+    _params: PhantomData<fn(P)>,
+}
+
+impl<P> Clone for MockDbConnection<P> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+            _params: PhantomData,
+        }
+    }
+}
+
+impl<P> Default for MockDbConnection<P> {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockState::default())),
+            _params: PhantomData,
+        }
+    }
+}
+
+impl<P> MockDbConnection<P> {
+    #[must_use]
+    pub fn with_schema(self, schema: SchemaRef) -> Self {
+        self.update(|state| state.schemas.push_back(Ok(schema)));
+        self
+    }
+
+    #[must_use]
+    pub fn with_schema_error(self, message: &str) -> Self {
+        self.update(|state| state.schemas.push_back(Err(message.to_string())));
+        self
+    }
+
+    #[must_use]
+    pub fn with_query_result(self, schema: SchemaRef, batches: Vec<RecordBatch>) -> Self {
+        self.update(|state| state.query_results.push_back(Ok((schema, batches))));
+        self
+    }
+
+    #[must_use]
+    pub fn with_query_error(self, message: &str) -> Self {
+        self.update(|state| state.query_results.push_back(Err(message.to_string())));
+        self
+    }
+
+    #[must_use]
+    pub fn with_execute_result(self, row_count: u64) -> Self {
+        self.update(|state| state.execute_results.push_back(Ok(row_count)));
+        self
+    }
+
+    #[must_use]
+    pub fn with_execute_error(self, message: &str) -> Self {
+        self.update(|state| state.execute_results.push_back(Err(message.to_string())));
+        self
+    }
+
+    /// Returns the SQL passed to `query_arrow` and `execute`, and `BEGIN READ ONLY`, `COMMIT` or `ROLLBACK` for the
+    /// read-only transaction calls, in call order.
+    #[must_use]
+    pub fn statements(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .map(|state| state.statements.clone())
+            .unwrap_or_default()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut MockState)) {
+        if let Ok(mut state) = self.state.lock() {
+            f(&mut state);
+        }
+    }
+
+    fn next<T>(
+        &self,
+        call: &str,
+        sql: Option<&str>,
+        queue: impl FnOnce(&mut MockState) -> Option<Result<T, String>>,
+    ) -> Result<T, Error> {
+        let mut state = self.state.lock().map_err(|_| PoisonedStateSnafu.build())?;
+        if let Some(sql) = sql {
+            state.statements.push(sql.to_string());
+        }
+        match queue(&mut state) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(message)) => MockSnafu { message }.fail(),
+            None => NoMockResponseSnafu { call }.fail(),
+        }
+    }
+}
+
+impl<P: Send + Sync + 'static> DbConnection<(), P> for MockDbConnection<P> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_async(&self) -> Option<&dyn AsyncDbConnection<(), P>> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl<P: Send + Sync + 'static> AsyncDbConnection<(), P> for MockDbConnection<P> {
+    async fn get_schema(
+        &self,
+        _table_reference: &TableReference,
+    ) -> Result<SchemaRef, super::Error> {
+        self.next("get_schema", None, |state| state.schemas.pop_front())
+            .boxed()
+            .context(super::UnableToGetSchemaSnafu)
+    }
+
+    async fn query_arrow(&self, sql: &str, _params: &[P]) -> Result<SendableRecordBatchStream> {
+        let (schema, batches) = self.next("query_arrow", Some(sql), |state| {
+            state.query_results.pop_front()
+        })?;
+
+        Ok(Box::pin(MemoryStream::try_new(batches, schema, None)?))
+    }
+
+    async fn execute(&self, sql: &str, _params: &[P]) -> Result<u64> {
+        Ok(self.next("execute", Some(sql), |state| {
+            state.execute_results.pop_front()
+        })?)
+    }
+
+    async fn begin_read_only(&self) -> Result<()> {
+        self.update(|state| state.statements.push("BEGIN READ ONLY".to_string()));
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        self.update(|state| state.statements.push("COMMIT".to_string()));
+        Ok(())
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        self.update(|state| state.statements.push("ROLLBACK".to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_programmed_responses() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .expect("valid record batch");
+
+        let conn: MockDbConnection = MockDbConnection::default()
+            .with_schema(Arc::clone(&schema))
+            .with_query_error("connection reset")
+            .with_query_result(Arc::clone(&schema), vec![batch.clone()])
+            .with_execute_result(3);
+
+        let table = TableReference::bare("test");
+        assert_eq!(
+            conn.get_schema(&table).await.expect("schema is returned"),
+            schema
+        );
+        assert!(conn.get_schema(&table).await.is_err());
+
+        let Err(err) = conn.query_arrow("SELECT * FROM test", &[]).await else {
+            panic!("first query should fail");
+        };
+        assert_eq!(err.to_string(), "connection reset");
+
+        let batches: Vec<RecordBatch> = conn
+            .query_arrow("SELECT * FROM test", &[])
+            .await
+            .expect("second query succeeds")
+            .try_collect()
+            .await
+            .expect("stream is collected");
+        assert_eq!(batches, vec![batch]);
+
+        assert_eq!(
+            conn.execute("DELETE FROM test", &[])
+                .await
+                .expect("execute succeeds"),
+            3
+        );
+        assert_eq!(
+            conn.statements(),
+            vec![
+                "SELECT * FROM test",
+                "SELECT * FROM test",
+                "DELETE FROM test"
+            ]
+        );
+    }
+}
@@ -42,6 +42,15 @@ pub struct MySQLConnection {
     pub conn: Arc<Mutex<Conn>>,
 }
 
+impl MySQLConnection {
+    #[must_use]
+    pub fn new(conn: Conn) -> Self {
+        MySQLConnection {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+}
+
 impl<'a> DbConnection<Conn, &'a (dyn ToValue + Sync)> for MySQLConnection {
     fn as_any(&self) -> &dyn Any {
         self
@@ -58,12 +67,6 @@ impl<'a> DbConnection<Conn, &'a (dyn ToValue + Sync)> for MySQLConnection {
 
 #[async_trait::async_trait]
 impl<'a> AsyncDbConnection<Conn, &'a (dyn ToValue + Sync)> for MySQLConnection {
-    fn new(conn: Conn) -> Self {
-        MySQLConnection {
-            conn: Arc::new(Mutex::new(conn)),
-        }
-    }
-
     async fn get_schema(
         &self,
         table_reference: &TableReference,
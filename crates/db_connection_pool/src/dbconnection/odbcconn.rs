@@ -34,6 +34,7 @@ use odbc_api::handles::StatementImpl;
 use odbc_api::parameter::InputParameter;
 use odbc_api::Cursor;
 use odbc_api::CursorImpl;
+use odbc_api::Prepared;
 use secrecy::{ExposeSecret, Secret, SecretString};
 use snafu::prelude::*;
 use snafu::Snafu;
@@ -72,6 +73,10 @@ pub enum Error {
     TryFromError { source: std::num::TryFromIntError },
     #[snafu(display("Unable to bind integer parameter: {source}"))]
     UnableToBindIntParameter { source: std::num::TryFromIntError },
+    #[snafu(display(
+        "Expected {placeholders} parameters for the placeholders of the query, but {params} were provided"
+    ))]
+    ParameterCountMismatch { placeholders: u16, params: usize },
 }
 
 pub struct ODBCConnection<'a> {
@@ -101,13 +106,6 @@ impl<'a> AsyncDbConnection<Connection<'a>, ODBCParameter> for ODBCConnection<'a>
 where
     'a: 'static,
 {
-    fn new(conn: Connection<'a>) -> Self {
-        ODBCConnection {
-            conn: Arc::new(conn.into()),
-            params: Arc::new(HashMap::new()),
-        }
-    }
-
     #[must_use]
     async fn get_schema(
         &self,
@@ -139,6 +137,7 @@ where
     ) -> Result<SendableRecordBatchStream> {
         let cxn = self.conn.lock().await;
         let mut prepared = cxn.prepare(sql)?;
+        check_parameter_count(&mut prepared, params)?;
         let schema = Arc::new(arrow_schema_from(&mut prepared)?);
         let mut statement = prepared.into_statement();
 
@@ -161,7 +160,8 @@ where
 
     async fn execute(&self, query: &str, params: &[ODBCParameter]) -> Result<u64> {
         let cxn = self.conn.lock().await;
-        let prepared = cxn.prepare(query)?;
+        let mut prepared = cxn.prepare(query)?;
+        check_parameter_count(&mut prepared, params)?;
         let mut statement = prepared.into_statement();
 
         bind_parameters(&mut statement, params)?;
@@ -175,6 +175,70 @@ where
     }
 }
 
+impl<'a> ODBCConnection<'a>
+where
+    'a: 'static,
+{
+    #[must_use]
+    pub fn new(conn: Connection<'a>) -> Self {
+        ODBCConnection {
+            conn: Arc::new(conn.into()),
+            params: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Executes a query that may produce several result sets, i.e. a batch of statements or a stored procedure,
+    /// returning one stream per result set in the order they were produced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails or a result set cannot be converted to Arrow.
+    pub async fn query_arrow_multiple(
+        &self,
+        sql: &str,
+        params: &[ODBCParameter],
+    ) -> Result<Vec<SendableRecordBatchStream>> {
+        let cxn = self.conn.lock().await;
+        let mut prepared = cxn.prepare(sql)?;
+        check_parameter_count(&mut prepared, params)?;
+        let mut statement = prepared.into_statement();
+
+        bind_parameters(&mut statement, params)?;
+
+        // StatementImpl<'_>::execute is unsafe, CursorImpl<_>::new is unsafe
+        let mut cursor = unsafe {
+            statement
+                .execute()
+                .into_result(&statement)
+                .context(ODBCAPISnafu)?;
+            CursorImpl::new(statement.as_stmt_ref())
+        };
+
+        let mut streams: Vec<SendableRecordBatchStream> = vec![];
+        loop {
+            let schema = Arc::new(arrow_schema_from(&mut cursor).context(ArrowODBCSnafu)?);
+            let mut reader = build_odbc_reader(cursor, &schema, &self.params)?;
+            let mut results: Vec<RecordBatch> = vec![];
+            for batch in &mut reader {
+                results.push(batch.context(ArrowSnafu)?);
+            }
+            streams.push(Box::pin(MemoryStream::try_new(results, schema, None)?));
+
+            match reader
+                .into_cursor()
+                .context(ArrowODBCSnafu)?
+                .more_results()
+                .context(ODBCAPISnafu)?
+            {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+
+        Ok(streams)
+    }
+}
+
 fn build_odbc_reader<C: Cursor>(
     cursor: C,
     schema: &Arc<Schema>,
@@ -209,6 +273,24 @@ fn build_odbc_reader<C: Cursor>(
     Ok(builder.build(cursor).context(ArrowODBCSnafu)?)
 }
 
+/// Fails unless a parameter is provided for each placeholder of the `prepared` statement, as the driver would
+/// otherwise execute it with unbound, or ignore the extra, parameters.
+fn check_parameter_count(
+    prepared: &mut Prepared<StatementImpl>,
+    params: &[ODBCParameter],
+) -> Result<()> {
+    let placeholders = prepared.num_params().context(ODBCAPISnafu)?;
+    ensure!(
+        usize::from(placeholders) == params.len(),
+        ParameterCountMismatchSnafu {
+            placeholders,
+            params: params.len(),
+        }
+    );
+
+    Ok(())
+}
+
 /// Binds parameter to an ODBC statement.
 ///
 /// `StatementImpl<'_>::bind_input_parameter` is unsafe.
@@ -229,6 +311,8 @@ fn bind_parameters(statement: &mut StatementImpl, params: &[ODBCParameter]) -> R
 
 #[cfg(test)]
 mod tests {
+    use arrow::util::display::array_value_to_string;
+    use futures::TryStreamExt;
     use odbc_api::handles::OutputStringBuffer;
     use odbc_api::IntoParameter;
 
@@ -239,6 +323,14 @@ mod tests {
 
     use super::*;
 
+    fn sqlite_pool() -> ODBCPool {
+        ODBCPool::new(Arc::new(HashMap::from([(
+            "odbc_connection_string".to_string(),
+            SecretString::new("Driver={SQLite}".to_string()),
+        )])))
+        .expect("Must create ODBC pool")
+    }
+
     // This test crudely validates that parameters are being received by the ODBC driver
     #[cfg(feature = "odbc")]
     #[tokio::test]
@@ -246,7 +338,7 @@ mod tests {
         use odbc_api::Cursor;
 
         // It is possible to connect to the SQLite driver without an underlying file
-        let pool = ODBCPool::new(Arc::new(HashMap::new())).expect("Must create ODBC pool");
+        let pool = sqlite_pool();
         let env = pool.odbc_environment();
         let driver_cxn = env
             .driver_connect(
@@ -292,4 +384,90 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "odbc")]
+    #[tokio::test]
+    async fn test_query_arrow_multiple() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let pool = sqlite_pool();
+        let env = pool.odbc_environment();
+        let driver_cxn = env
+            .driver_connect(
+                "Driver={SQLite}",
+                &mut OutputStringBuffer::empty(),
+                odbc_api::DriverCompleteOption::NoPrompt,
+            )
+            .expect("Must make driver connection");
+        let conn = ODBCConnection::new(driver_cxn);
+
+        let streams = conn
+            .query_arrow_multiple("select 1 as id; select 'hopper' as name, 100 as age", &[])
+            .await
+            .expect("Must query multiple result sets");
+
+        assert_eq!(streams.len(), 2);
+
+        let names = |stream: &SendableRecordBatchStream| {
+            stream
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&streams[0]), vec!["id"]);
+        assert_eq!(names(&streams[1]), vec!["name", "age"]);
+
+        let mut streams = streams.into_iter();
+        let ids: Vec<RecordBatch> = streams
+            .next()
+            .expect("first result set")
+            .try_collect()
+            .await?;
+        assert_eq!(ids.iter().map(RecordBatch::num_rows).sum::<usize>(), 1);
+        assert_eq!(array_value_to_string(ids[0].column(0), 0)?, "1");
+
+        let cats: Vec<RecordBatch> = streams
+            .next()
+            .expect("second result set")
+            .try_collect()
+            .await?;
+        assert_eq!(cats.iter().map(RecordBatch::num_rows).sum::<usize>(), 1);
+        assert_eq!(array_value_to_string(cats[0].column(0), 0)?, "hopper");
+        assert_eq!(array_value_to_string(cats[0].column(1), 0)?, "100");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "odbc")]
+    #[tokio::test]
+    async fn test_query_arrow_binds_parameters() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let pool = sqlite_pool();
+        let env = pool.odbc_environment();
+        let driver_cxn = env
+            .driver_connect(
+                "Driver={SQLite}",
+                &mut OutputStringBuffer::empty(),
+                odbc_api::DriverCompleteOption::NoPrompt,
+            )
+            .expect("Must make driver connection");
+        let conn = ODBCConnection::new(driver_cxn);
+
+        let sql = "select * from (select 1 as id, 'hopper' as name union all select 2, 'cat') as cats where id = ?";
+        let params: Vec<ODBCParameter> = vec![Box::new((2_i32).into_parameter())];
+        let batches: Vec<RecordBatch> = conn.query_arrow(sql, &params).await?.try_collect().await?;
+
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 1);
+        assert_eq!(array_value_to_string(batches[0].column(0), 0)?, "2");
+        assert_eq!(array_value_to_string(batches[0].column(1), 0)?, "cat");
+
+        let Err(err) = conn.query_arrow(sql, &[]).await else {
+            panic!("A query with an unbound placeholder must fail");
+        };
+        assert_eq!(
+            err.to_string(),
+            "Expected 1 parameters for the placeholders of the query, but 0 were provided"
+        );
+
+        Ok(())
+    }
 }
@@ -15,13 +15,15 @@ limitations under the License.
 */
 
 use std::any::Any;
+use std::collections::HashSet;
 use std::error::Error;
 
 use arrow::datatypes::SchemaRef;
-use arrow_sql_gen::postgres::columns_to_schema;
-use arrow_sql_gen::postgres::rows_to_arrow;
+use arrow_sql_gen::postgres::columns_to_schema_with_not_null_columns;
+use arrow_sql_gen::postgres::rows_to_arrow_with_options;
+use arrow_sql_gen::postgres::ArrowConversionOptions;
+use arrow_sql_gen::EmptyStringHandling;
 use bb8_postgres::tokio_postgres::types::ToSql;
-use bb8_postgres::PostgresConnectionManager;
 use datafusion::execution::SendableRecordBatchStream;
 use datafusion::physical_plan::memory::MemoryStream;
 use datafusion::sql::TableReference;
@@ -31,6 +33,7 @@ use snafu::prelude::*;
 use super::AsyncDbConnection;
 use super::DbConnection;
 use super::Result;
+use crate::postgrespool::PostgresConnectionManager;
 
 #[derive(Debug, Snafu)]
 pub enum PostgresError {
@@ -52,6 +55,8 @@ pub enum PostgresError {
 
 pub struct PostgresConnection {
     pub conn: bb8::PooledConnection<'static, PostgresConnectionManager<MakeTlsConnector>>,
+    server_version_num: Option<u32>,
+    empty_strings: EmptyStringHandling,
 }
 
 impl<'a>
@@ -80,6 +85,81 @@ impl<'a>
     }
 }
 
+impl PostgresConnection {
+    #[must_use]
+    pub fn new(
+        conn: bb8::PooledConnection<'static, PostgresConnectionManager<MakeTlsConnector>>,
+    ) -> Self {
+        PostgresConnection {
+            conn,
+            server_version_num: None,
+            empty_strings: EmptyStringHandling::default(),
+        }
+    }
+
+    /// Sets the `server_version_num` of the server, so that types it doesn't have are rejected when converting.
+    #[must_use]
+    pub fn with_server_version_num(mut self, server_version_num: Option<u32>) -> Self {
+        self.server_version_num = server_version_num;
+        self
+    }
+
+    /// The `server_version_num` of the server, i.e. `130004` for 13.4, if it is known.
+    #[must_use]
+    pub fn server_version_num(&self) -> Option<u32> {
+        self.server_version_num
+    }
+
+    /// Reads the empty strings and NULLs of text columns in query results with `empty_strings`.
+    #[must_use]
+    pub fn with_empty_strings(mut self, empty_strings: EmptyStringHandling) -> Self {
+        self.empty_strings = empty_strings;
+        self
+    }
+
+    fn conversion_options(&self) -> ArrowConversionOptions {
+        let options = ArrowConversionOptions::new().with_empty_strings(self.empty_strings);
+        match self.server_version_num {
+            Some(server_version_num) => options.with_server_version_num(server_version_num),
+            None => options,
+        }
+    }
+
+    /// Returns the `(table_oid, column_id)` of the `columns` declared `NOT NULL`. If the catalog can't be read, no
+    /// column is reported and all of them are treated as nullable.
+    async fn not_null_columns(&self, columns: &[tokio_postgres::Column]) -> HashSet<(u32, i16)> {
+        let table_oids: Vec<u32> = columns
+            .iter()
+            .filter_map(tokio_postgres::Column::table_oid)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if table_oids.is_empty() {
+            return HashSet::new();
+        }
+
+        match self
+            .conn
+            .query(
+                "SELECT attrelid, attnum FROM pg_catalog.pg_attribute WHERE attrelid = ANY($1) AND attnum > 0 AND attnotnull",
+                &[&table_oids],
+            )
+            .await
+        {
+            Ok(rows) => rows
+                .iter()
+                .map(|row| (row.get::<_, u32>(0), row.get::<_, i16>(1)))
+                .collect(),
+            Err(e) => {
+                tracing::debug!(
+                    "Unable to read column nullability, columns are treated as nullable: {e}"
+                );
+                HashSet::new()
+            }
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<'a>
     AsyncDbConnection<
@@ -87,12 +167,6 @@ impl<'a>
         &'a (dyn ToSql + Sync),
     > for PostgresConnection
 {
-    fn new(
-        conn: bb8::PooledConnection<'static, PostgresConnectionManager<MakeTlsConnector>>,
-    ) -> Self {
-        PostgresConnection { conn }
-    }
-
     async fn get_schema(
         &self,
         table_reference: &TableReference,
@@ -106,9 +180,14 @@ impl<'a>
             .await
         {
             Ok(statement) => {
-                return columns_to_schema(statement.columns())
-                    .boxed()
-                    .context(super::UnableToGetSchemaSnafu)
+                let not_null_columns = self.not_null_columns(statement.columns()).await;
+                return columns_to_schema_with_not_null_columns(
+                    statement.columns(),
+                    &self.conversion_options(),
+                    &not_null_columns,
+                )
+                .boxed()
+                .context(super::UnableToGetSchemaSnafu);
             }
             Err(err) => {
                 if let Some(error_source) = err.source() {
@@ -137,7 +216,15 @@ impl<'a>
         params: &[&'a (dyn ToSql + Sync)],
     ) -> Result<SendableRecordBatchStream> {
         let rows = self.conn.query(sql, params).await.context(QuerySnafu)?;
-        let rec = rows_to_arrow(rows.as_slice()).context(ConversionSnafu)?;
+        // The same nullability as `get_schema`, so the batches match the schema of the table
+        let not_null_columns = match rows.first() {
+            Some(row) => self.not_null_columns(row.columns()).await,
+            None => HashSet::new(),
+        };
+        let options = self
+            .conversion_options()
+            .with_not_null_columns(not_null_columns);
+        let rec = rows_to_arrow_with_options(rows.as_slice(), &options).context(ConversionSnafu)?;
         let schema = rec.schema();
         let recs = vec![rec];
         Ok(Box::pin(MemoryStream::try_new(recs, schema, None)?))
@@ -146,4 +233,22 @@ impl<'a>
     async fn execute(&self, sql: &str, params: &[&'a (dyn ToSql + Sync)]) -> Result<u64> {
         Ok(self.conn.execute(sql, params).await?)
     }
+
+    async fn begin_read_only(&self) -> Result<()> {
+        // The default READ COMMITTED takes a new snapshot for each query, REPEATABLE READ keeps the first one
+        self.conn
+            .batch_execute("BEGIN TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .await?;
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        self.conn.batch_execute("COMMIT").await?;
+        Ok(())
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        self.conn.batch_execute("ROLLBACK").await?;
+        Ok(())
+    }
 }
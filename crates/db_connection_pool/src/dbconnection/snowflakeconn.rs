@@ -15,22 +15,40 @@ limitations under the License.
 */
 
 use std::any::Any;
+use std::fmt::Write;
+use std::future::Future;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use arrow::array::{
-    Array, ArrayRef, Int32Array, Int64Array, RecordBatch, StructArray, TimestampMillisecondBuilder,
+    Array, ArrayRef, AsArray, Int32Array, Int64Array, RecordBatch, StringArray, StructArray,
+    TimestampMillisecondBuilder,
 };
-use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow::datatypes::{
+    DataType, Decimal128Type, Field, Int64Type, Schema, SchemaRef, TimeUnit, UInt64Type,
+    DECIMAL128_MAX_PRECISION,
+};
+use arrow::error::ArrowError;
 use async_trait::async_trait;
 use datafusion::error::DataFusionError;
 use datafusion::execution::SendableRecordBatchStream;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::sql::TableReference;
-use futures::stream;
+use futures::stream::{self, BoxStream};
+use futures::Stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
 use snafu::prelude::*;
-use snowflake_api::SnowflakeApi;
+use snowflake_api::connection::ConnectionError;
+use snowflake_api::{SnowflakeApi, SnowflakeApiError};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use util::fibonacci_backoff::FibonacciBackoffBuilder;
+use util::{retry, RetryError};
 
 use super::AsyncDbConnection;
 use super::DbConnection;
@@ -38,9 +56,6 @@ use super::Result;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Not implemented"))]
-    NotImplemented,
-
     #[snafu(display("Unable to retrieve schema: {reason}"))]
     UnableToRetrieveSchema { reason: String },
 
@@ -55,90 +70,173 @@ pub enum Error {
     #[snafu(display("Error executing query: {source}"))]
     SnowflakeArrowError { source: arrow::error::ArrowError },
 
-    #[snafu(display("Failed to cast snowflake timestamp to arrow timestamp: {reason}"))]
-    UnableToCastSnowflakeTimestamp { reason: String },
+    #[snafu(display(
+        "Failed to cast snowflake timestamp column {column} to arrow timestamp: {reason}"
+    ))]
+    UnableToCastSnowflakeTimestamp { column: String, reason: String },
+
+    #[snafu(display("Failed to cast snowflake number to arrow decimal: {reason}"))]
+    UnableToCastSnowflakeNumber { reason: String },
+
+    #[snafu(display("Failed to cast snowflake geospatial value to arrow string: {reason}"))]
+    UnableToCastSnowflakeGeospatial { reason: String },
 
     #[snafu(display("Failed to create record batch: {source}"))]
     FailedToCreateRecordBatch { source: arrow::error::ArrowError },
+
+    #[snafu(display("Query result has no column {column} of the target schema"))]
+    MissingTargetColumn { column: String },
+
+    #[snafu(display("Failed to cast column {column} from {from} to {to}: {source}"))]
+    UnableToCoerceColumn {
+        column: String,
+        from: DataType,
+        to: DataType,
+        source: arrow::error::ArrowError,
+    },
+
+    #[snafu(display(
+        "Unexpected statement response, expected the number of affected rows: {reason}"
+    ))]
+    UnexpectedStatementResponse { reason: String },
+
+    #[snafu(display(
+        "Expected {placeholders} parameters for the placeholders of the query, but {params} were provided"
+    ))]
+    ParameterCountMismatch { placeholders: usize, params: usize },
+
+    #[snafu(display(
+        "Unsupported type for query parameter {index}, expected an integer, float, bool or string"
+    ))]
+    UnsupportedParameterType { index: usize },
+
+    #[snafu(display("Statements can't be executed in a read-only transaction"))]
+    StatementInReadOnlyTransaction {},
 }
 
 pub struct SnowflakeConnection {
     pub api: Arc<SnowflakeApi>,
+    keep_alive: Option<KeepAlive>,
+    cast_options: CastStreamOptions,
+    /// Whether a read-only transaction is open, see `begin_read_only`.
+    read_only: AtomicBool,
 }
 
-impl<'a> DbConnection<Arc<SnowflakeApi>, &'a (dyn Sync)> for SnowflakeConnection {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+/// How the batches returned by Snowflake are cast to standard Arrow types, see `cast_stream`.
+#[derive(Debug, Clone, Copy, Default)]
+struct CastStreamOptions {
+    batch_size: Option<usize>,
+    concurrency: Option<usize>,
+    ordered: bool,
+}
 
-    fn as_any_mut(&mut self) -> &mut dyn Any {
-        self
+impl SnowflakeConnection {
+    #[must_use]
+    pub fn new(api: Arc<SnowflakeApi>) -> Self {
+        SnowflakeConnection {
+            api,
+            keep_alive: None,
+            cast_options: CastStreamOptions::default(),
+            read_only: AtomicBool::new(false),
+        }
     }
 
-    fn as_async(&self) -> Option<&dyn super::AsyncDbConnection<Arc<SnowflakeApi>, &'a (dyn Sync)>> {
-        Some(self)
-    }
-}
+    /// Creates a connection that runs `SELECT 1` every `interval` while it is held, so an idle session doesn't expire.
+    #[must_use]
+    pub fn with_keep_alive(api: Arc<SnowflakeApi>, interval: Duration) -> Self {
+        let keep_alive_api = Arc::clone(&api);
+        let keep_alive = KeepAlive::spawn(interval, move || {
+            let api = Arc::clone(&keep_alive_api);
+            async move {
+                if let Err(e) = api.exec("SELECT 1").await {
+                    tracing::warn!("Snowflake session keep-alive failed: {e}");
+                }
+            }
+        });
 
-#[async_trait]
-impl<'a> AsyncDbConnection<Arc<SnowflakeApi>, &'a (dyn Sync)> for SnowflakeConnection {
-    fn new(api: Arc<SnowflakeApi>) -> Self {
-        SnowflakeConnection { api }
+        SnowflakeConnection {
+            api,
+            keep_alive: Some(keep_alive),
+            cast_options: CastStreamOptions::default(),
+            read_only: AtomicBool::new(false),
+        }
     }
 
-    async fn get_schema(
-        &self,
-        table_reference: &TableReference,
-    ) -> Result<SchemaRef, super::Error> {
-        let table = table_reference.to_quoted_string();
+    /// Buffers query results until at least `rows` rows are available before casting them to standard Arrow types,
+    /// so the cast isn't repeated for each of many small batches returned by Snowflake.
+    #[must_use]
+    pub fn with_cast_batch_size(mut self, rows: usize) -> Self {
+        self.cast_options.batch_size = Some(rows);
+        self
+    }
 
-        let res = self
-            .api
-            .exec(format!("SELECT * FROM {table} limit 1").as_str())
-            .await
-            .boxed()
-            .context(super::UnableToGetSchemaSnafu)?;
+    /// Casts up to `tasks` batches at once on blocking threads. Batches may then be emitted out of order, unless the
+    /// query has an `ORDER BY` or `with_preserve_order` is set.
+    #[must_use]
+    pub fn with_cast_concurrency(mut self, tasks: usize) -> Self {
+        self.cast_options.concurrency = Some(tasks);
+        self
+    }
 
-        match res {
-            snowflake_api::QueryResult::Arrow(record_batches) => {
-                let record_batch = snowflake_schema_cast(&record_batches[0])
-                    .boxed()
-                    .context(super::UnableToGetSchemaSnafu)?;
-                let schema = record_batch.schema();
-                return Ok(Arc::clone(&schema));
-            }
-            snowflake_api::QueryResult::Empty => Err(super::Error::UnableToGetSchema {
-                source: "Empty response".to_string().into(),
-            }),
-            snowflake_api::QueryResult::Json(_json) => Err(super::Error::UnableToGetSchema {
-                source: "Unexpected response".to_string().into(),
-            }),
-        }
+    /// Always emits batches in the order Snowflake returned them, even when they are cast concurrently for a query
+    /// without an `ORDER BY`, i.e. when the order is established by a view or a subquery.
+    #[must_use]
+    pub fn with_preserve_order(mut self, preserve_order: bool) -> Self {
+        self.cast_options.ordered = preserve_order;
+        self
     }
 
-    async fn query_arrow(
+    /// Like `query_arrow`, additionally coercing each batch to `target_schema` when it is set, see
+    /// `coerce_to_schema`. The stream of an empty result has the target schema.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails, or if the first batch can't be coerced to `target_schema`. Later batches
+    /// that can't be coerced fail the stream.
+    pub async fn query_arrow_with_schema(
         &self,
         sql: &str,
-        _: &[&'a (dyn Sync)],
+        target_schema: Option<SchemaRef>,
     ) -> Result<SendableRecordBatchStream> {
         let sql = sql.to_string();
 
-        let stream = self
-            .api
-            .exec_streamed(&sql)
+        let stream = retry_transient(|| self.api.exec_streamed(&sql))
             .await
             .context(SnowflakeQuerySnafu)?;
 
-        let mut transformed_stream = stream.map(|batch| {
-            batch.and_then(|batch| {
-                snowflake_schema_cast(&batch)
-                    .map_err(|e| arrow::error::ArrowError::ExternalError(Box::new(e)))
-            })
+        let cast_options = CastStreamOptions {
+            ordered: self.cast_options.ordered || has_order_by(&sql),
+            ..self.cast_options
+        };
+        let api = Arc::clone(&self.api);
+        let stream = reexecute_on_expired_result(
+            stream.boxed(),
+            move || {
+                let api = Arc::clone(&api);
+                let sql = sql.clone();
+                async move {
+                    api.exec_streamed(&sql)
+                        .await
+                        .map(StreamExt::boxed)
+                        .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+                }
+            },
+            cast_options.ordered,
+        );
+        let empty_schema = target_schema
+            .clone()
+            .unwrap_or_else(|| Arc::new(Schema::empty()));
+        let mut transformed_stream = cast_stream(stream, cast_options, move |batch| {
+            let batch = snowflake_schema_cast(batch)?;
+            match &target_schema {
+                Some(target_schema) => coerce_to_schema(&batch, target_schema),
+                None => Ok(batch),
+            }
         });
 
         let Some(first_batch) = transformed_stream.next().await else {
             return Ok(Box::pin(RecordBatchStreamAdapter::new(
-                Arc::new(Schema::empty()),
+                empty_schema,
                 stream::empty(),
             )));
         };
@@ -158,14 +256,746 @@ impl<'a> AsyncDbConnection<Arc<SnowflakeApi>, &'a (dyn Sync)> for SnowflakeConne
             ),
         );
 
-        return Ok(Box::pin(stream_adapter));
+        Ok(Box::pin(stream_adapter))
+    }
+}
+
+/// A background task that runs `keep_alive` every `interval`. The task is stopped when this is dropped.
+struct KeepAlive {
+    task: JoinHandle<()>,
+}
+
+impl KeepAlive {
+    fn spawn<F, Fut>(interval: Duration, keep_alive: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately, and the session was just used
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                keep_alive().await;
+            }
+        });
+
+        Self { task }
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl<'a> DbConnection<Arc<SnowflakeApi>, &'a (dyn Any + Sync)> for SnowflakeConnection {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_async(
+        &self,
+    ) -> Option<&dyn super::AsyncDbConnection<Arc<SnowflakeApi>, &'a (dyn Any + Sync)>> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl<'a> AsyncDbConnection<Arc<SnowflakeApi>, &'a (dyn Any + Sync)> for SnowflakeConnection {
+    async fn get_schema(
+        &self,
+        table_reference: &TableReference,
+    ) -> Result<SchemaRef, super::Error> {
+        let table = table_reference.to_quoted_string();
+
+        // Unlike selecting a row, describing the table also works when it's empty
+        let sql = format!("DESCRIBE TABLE {table}");
+        let res = retry_transient(|| self.api.exec(&sql))
+            .await
+            .boxed()
+            .context(super::UnableToGetSchemaSnafu)?;
+
+        let columns = described_columns(res)
+            .boxed()
+            .context(super::UnableToGetSchemaSnafu)?;
+
+        // The Arrow type of a column whose Snowflake type isn't known, i.e. VECTOR, is taken from a row of the table
+        let queried_schema = if columns
+            .iter()
+            .any(|(_, sf_type, _)| sf_type_to_data_type(sf_type).is_none())
+        {
+            let sql = format!("SELECT * FROM {table} LIMIT 1");
+            Some(self.query_arrow_with_schema(&sql, None).await?.schema())
+        } else {
+            None
+        };
+
+        let schema = described_schema(columns, queried_schema.as_deref())
+            .boxed()
+            .context(super::UnableToGetSchemaSnafu)?;
+        Ok(Arc::new(schema))
+    }
+
+    async fn query_arrow(
+        &self,
+        sql: &str,
+        params: &[&'a (dyn Any + Sync)],
+    ) -> Result<SendableRecordBatchStream> {
+        let sql = inline_params(sql, params)?;
+        self.query_arrow_with_schema(&sql, None).await
+    }
+
+    async fn execute(&self, query: &str, params: &[&'a (dyn Any + Sync)]) -> Result<u64> {
+        ensure!(
+            !self.read_only.load(Ordering::Acquire),
+            StatementInReadOnlyTransactionSnafu
+        );
+        let query = inline_params(query, params)?;
+        // Not retried, as a statement that timed out may still have been committed, and would then run twice
+        let result = self.api.exec(&query).await.context(SnowflakeQuerySnafu)?;
+        Ok(affected_rows(result)?)
+    }
+
+    /// Snowflake transactions only support `READ COMMITTED`, so each query reads its own snapshot, taken when the
+    /// query starts. The transaction is therefore statement-level: it only rejects `execute` until it ends.
+    async fn begin_read_only(&self) -> Result<()> {
+        self.read_only.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        self.read_only.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        self.read_only.store(false, Ordering::Release);
+        Ok(())
     }
+}
+
+/// Replaces the `?` placeholders of `sql` with `params` in order, rendered as escaped Snowflake literals.
+/// Placeholders in string literals, quoted identifiers and comments are left as they are.
+///
+/// These aren't bind variables: the Snowflake API client only submits query text, so the values are inlined into it.
+fn inline_params(sql: &str, params: &[&(dyn Any + Sync)]) -> Result<String, Error> {
+    if params.is_empty() {
+        return Ok(sql.to_string());
+    }
+
+    let mut inlined = String::with_capacity(sql.len());
+    let mut values = params.iter().enumerate();
+    let mut placeholders = 0;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '?' => {
+                placeholders += 1;
+                if let Some((index, param)) = values.next() {
+                    inlined.push_str(&sf_literal(index, *param)?);
+                }
+            }
+            // A backslash escapes the next character of a string literal, but not of a quoted identifier
+            '\'' | '"' => {
+                inlined.push(c);
+                while let Some(next) = chars.next() {
+                    inlined.push(next);
+                    if next == '\\' && c == '\'' {
+                        inlined.extend(chars.next());
+                    } else if next == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                push_until(&mut chars, &mut inlined, c, "\n");
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                push_until(&mut chars, &mut inlined, c, "*/");
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                push_until(&mut chars, &mut inlined, c, "$$");
+            }
+            _ => inlined.push(c),
+        }
+    }
+
+    ensure!(
+        placeholders == params.len(),
+        ParameterCountMismatchSnafu {
+            placeholders,
+            params: params.len(),
+        }
+    );
+    Ok(inlined)
+}
+
+/// Pushes the two character opening `first` of a comment or dollar-quoted string, and the rest of `chars` up to and
+/// including `closing`, to `inlined`.
+fn push_until(chars: &mut Peekable<Chars>, inlined: &mut String, first: char, closing: &str) {
+    inlined.push(first);
+    inlined.extend(chars.next());
+    let start = inlined.len();
+    for next in chars.by_ref() {
+        inlined.push(next);
+        if inlined.len() >= start + closing.len() && inlined.ends_with(closing) {
+            break;
+        }
+    }
+}
+
+/// Renders the query parameter at `index` as a Snowflake literal.
+fn sf_literal(index: usize, param: &(dyn Any + Sync)) -> Result<String, Error> {
+    let param: &dyn Any = param;
+    macro_rules! integer_literal {
+        ($($integer:ty),*) => {
+            $(
+                if let Some(value) = param.downcast_ref::<$integer>() {
+                    return Ok(value.to_string());
+                }
+            )*
+        };
+    }
+    integer_literal!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+    let float = param
+        .downcast_ref::<f64>()
+        .copied()
+        .or_else(|| param.downcast_ref::<f32>().copied().map(f64::from));
+    if let Some(value) = float {
+        return Ok(match value {
+            value if value.is_nan() => "'NaN'::FLOAT".to_string(),
+            f64::INFINITY => "'inf'::FLOAT".to_string(),
+            f64::NEG_INFINITY => "'-inf'::FLOAT".to_string(),
+            value => value.to_string(),
+        });
+    }
+    if let Some(value) = param.downcast_ref::<bool>() {
+        return Ok(if *value { "TRUE" } else { "FALSE" }.to_string());
+    }
+
+    let string = param
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| param.downcast_ref::<&str>().copied());
+    match string {
+        Some(value) => Ok(format!(
+            "'{}'",
+            value.replace('\\', "\\\\").replace('\'', "''")
+        )),
+        None => UnsupportedParameterTypeSnafu { index }.fail(),
+    }
+}
+
+/// Whether `column_name` is one of the row counts Snowflake returns for a DML statement, i.e.
+/// `number of rows inserted`, or `number of rows updated` and `number of rows deleted` for a `MERGE`.
+fn is_row_count_column(column_name: &str) -> bool {
+    column_name.to_lowercase().starts_with("number of rows ")
+}
+
+/// Interprets the response to a DML or DDL statement as the number of rows it affected. DDL statements, which
+/// return a status message or nothing at all, affect 0 rows. Any other result set is rejected, as it means the
+/// statement was a query.
+fn affected_rows(result: snowflake_api::QueryResult) -> Result<u64, Error> {
+    match result {
+        snowflake_api::QueryResult::Empty => Ok(0),
+        snowflake_api::QueryResult::Arrow(record_batches) => {
+            let schema = match record_batches.first() {
+                Some(record_batch) => record_batch.schema(),
+                None => return Ok(0),
+            };
+            if is_status_result(schema.fields().iter().map(|field| field.name().as_str())) {
+                return Ok(0);
+            }
+            let row_count_columns = schema
+                .fields()
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| is_row_count_column(field.name()))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            ensure!(
+                !row_count_columns.is_empty(),
+                UnexpectedStatementResponseSnafu {
+                    reason: format!("got a result set with the columns {schema}"),
+                }
+            );
+
+            let mut affected_rows = 0;
+            for record_batch in &record_batches {
+                for i in &row_count_columns {
+                    let counts = arrow::compute::cast(record_batch.column(*i), &DataType::UInt64)
+                        .context(SnowflakeArrowSnafu)?;
+                    affected_rows += counts
+                        .as_primitive::<UInt64Type>()
+                        .iter()
+                        .flatten()
+                        .sum::<u64>();
+                }
+            }
+            Ok(affected_rows)
+        }
+        snowflake_api::QueryResult::Json(json) => {
+            if is_status_result(json.schema.iter().map(|field| field.name.as_str())) {
+                return Ok(0);
+            }
+            let row_count_columns = json
+                .schema
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| is_row_count_column(&field.name))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            ensure!(
+                !row_count_columns.is_empty(),
+                UnexpectedStatementResponseSnafu {
+                    reason: format!("got a result set {json}"),
+                }
+            );
+
+            // Snowflake returns the values of a JSON result set as strings
+            let rows = json.value.as_array().map_or(&[][..], Vec::as_slice);
+            let mut affected_rows = 0;
+            for row in rows {
+                for i in &row_count_columns {
+                    let count = row.get(*i).and_then(|count| match count.as_str() {
+                        Some(count) => count.parse::<u64>().ok(),
+                        None => count.as_u64(),
+                    });
+                    affected_rows += count.context(UnexpectedStatementResponseSnafu {
+                        reason: format!("the row count of {row} is not a number"),
+                    })?;
+                }
+            }
+            Ok(affected_rows)
+        }
+    }
+}
+
+/// Whether the result set is the single `status` column DDL statements return, i.e. `Table T successfully created.`
+fn is_status_result<'a>(mut column_names: impl Iterator<Item = &'a str>) -> bool {
+    matches!(
+        (column_names.next(), column_names.next()),
+        (Some(column_name), None) if column_name.eq_ignore_ascii_case("status")
+    )
+}
+
+/// Builds the schema of a table from the `columns` of its `DESCRIBE TABLE` response, with the Arrow types its columns
+/// are read as. A column of a Snowflake type `sf_type_to_data_type` doesn't know has its type in `queried_schema`, the
+/// schema a query of the table returned.
+fn described_schema(
+    columns: Vec<(String, String, bool)>,
+    queried_schema: Option<&Schema>,
+) -> Result<Schema, Error> {
+    let fields = columns
+        .into_iter()
+        .map(|(name, sf_type, nullable)| {
+            let data_type = sf_type_to_data_type(&sf_type)
+                .or_else(|| {
+                    queried_schema
+                        .and_then(|schema| schema.field_with_name(&name).ok())
+                        .map(|field| field.data_type().clone())
+                })
+                .with_context(|| UnableToRetrieveSchemaSnafu {
+                    reason: format!("unsupported Snowflake type {sf_type} of column {name}"),
+                })?;
+            Ok(Field::new(name, data_type, nullable))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(Schema::new(fields))
+}
+
+/// The `(name, type, nullable)` of each column of a `DESCRIBE TABLE` response.
+fn described_columns(
+    result: snowflake_api::QueryResult,
+) -> Result<Vec<(String, String, bool)>, Error> {
+    let described_column =
+        |name: Option<&str>, sf_type: Option<&str>, null: Option<&str>| match (name, sf_type) {
+            (Some(name), Some(sf_type)) => Ok((
+                name.to_string(),
+                sf_type.to_string(),
+                !null.is_some_and(|null| null.eq_ignore_ascii_case("N")),
+            )),
+            _ => UnableToRetrieveSchemaSnafu {
+                reason: "a column has no name or type",
+            }
+            .fail(),
+        };
+
+    match result {
+        snowflake_api::QueryResult::Empty => UnableToRetrieveSchemaSnafu {
+            reason: "the table has no columns",
+        }
+        .fail(),
+        snowflake_api::QueryResult::Arrow(record_batches) => {
+            let mut columns = Vec::new();
+            for record_batch in &record_batches {
+                let column = |column_name: &str| {
+                    record_batch
+                        .schema()
+                        .fields()
+                        .iter()
+                        .position(|field| field.name().eq_ignore_ascii_case(column_name))
+                        .map(|i| {
+                            arrow::compute::cast(record_batch.column(i), &DataType::Utf8)
+                                .context(SnowflakeArrowSnafu)
+                        })
+                        .transpose()
+                };
+                let (Some(names), Some(sf_types)) = (column("name")?, column("type")?) else {
+                    return UnableToRetrieveSchemaSnafu {
+                        reason: "the description has no name or type column",
+                    }
+                    .fail();
+                };
+                let nulls = column("null?")?;
+                for i in 0..record_batch.num_rows() {
+                    columns.push(described_column(
+                        string_value(&names, i),
+                        string_value(&sf_types, i),
+                        nulls.as_ref().and_then(|nulls| string_value(nulls, i)),
+                    )?);
+                }
+            }
+            Ok(columns)
+        }
+        snowflake_api::QueryResult::Json(json) => {
+            let [names, sf_types, nulls] = ["name", "type", "null?"].map(|column| {
+                json.schema
+                    .iter()
+                    .position(|field| field.name.eq_ignore_ascii_case(column))
+            });
+            let rows = json.value.as_array().map_or(&[][..], Vec::as_slice);
+            rows.iter()
+                .map(|row| {
+                    let value = |i: Option<usize>| {
+                        i.and_then(|i| row.get(i)).and_then(|value| value.as_str())
+                    };
+                    described_column(value(names), value(sf_types), value(nulls))
+                })
+                .collect()
+        }
+    }
+}
+
+/// The `i`th value of the `Utf8` array `column`, unless it's NULL.
+fn string_value(column: &ArrayRef, i: usize) -> Option<&str> {
+    let column = column.as_string::<i32>();
+    column.is_valid(i).then(|| column.value(i))
+}
+
+/// The Arrow type a column of the Snowflake type `sf_type`, as `DESCRIBE TABLE` reports it, i.e. `NUMBER(38,2)`, is
+/// read as. Matches the casts of `snowflake_schema_cast`. `None` if the type isn't known.
+fn sf_type_to_data_type(sf_type: &str) -> Option<DataType> {
+    let (type_name, parameters) = match sf_type.split_once('(') {
+        Some((type_name, parameters)) => (type_name, parameters.trim_end_matches(')')),
+        None => (sf_type, ""),
+    };
+    let parameters = parameters
+        .split(',')
+        .filter_map(|parameter| parameter.trim().parse::<i64>().ok())
+        .collect::<Vec<_>>();
+
+    let data_type = match type_name.trim().to_uppercase().as_str() {
+        "NUMBER" | "DECIMAL" | "NUMERIC" => {
+            let precision = parameters
+                .first()
+                .map_or(DECIMAL128_MAX_PRECISION, |precision| {
+                    u8::try_from(*precision).unwrap_or(DECIMAL128_MAX_PRECISION)
+                })
+                .min(DECIMAL128_MAX_PRECISION);
+            let scale = parameters
+                .get(1)
+                .map_or(0, |scale| i8::try_from(*scale).unwrap_or(i8::MAX));
+            if precision <= MAX_INT64_NUMBER_PRECISION && scale == 0 {
+                DataType::Int64
+            } else {
+                DataType::Decimal128(precision, scale)
+            }
+        }
+        "FLOAT" | "FLOAT4" | "FLOAT8" | "DOUBLE" | "DOUBLE PRECISION" | "REAL" => DataType::Float64,
+        // Semi-structured and geospatial values are sent as their JSON text
+        "VARCHAR" | "CHAR" | "CHARACTER" | "STRING" | "TEXT" | "VARIANT" | "OBJECT" | "ARRAY"
+        | "GEOGRAPHY" | "GEOMETRY" => DataType::Utf8,
+        "BINARY" | "VARBINARY" => DataType::Binary,
+        "BOOLEAN" => DataType::Boolean,
+        "DATE" => DataType::Date32,
+        // A TIME is read as its count of fractional seconds at the scale of the column
+        "TIME" => DataType::Int64,
+        "TIMESTAMP_NTZ" | "DATETIME" => DataType::Timestamp(TimeUnit::Millisecond, None),
+        "TIMESTAMP_LTZ" | "TIMESTAMP_TZ" => {
+            DataType::Timestamp(TimeUnit::Millisecond, Some(UTC.into()))
+        }
+        _ => return None,
+    };
+    Some(data_type)
+}
 
-    async fn execute(&self, _query: &str, _: &[&'a (dyn Sync)]) -> Result<u64> {
-        return NotImplementedSnafu.fail()?;
+/// Applies `cast` to each batch of `stream`. When `options.batch_size` is set, incoming batches are first
+/// concatenated until they hold at least that many rows, so `cast` runs once per combined batch.
+///
+/// When `options.concurrency` is set, that many batches are cast at once on blocking threads. The cast batches are
+/// emitted as they complete, unless `options.ordered` is set.
+fn cast_stream<S, F>(
+    stream: S,
+    options: CastStreamOptions,
+    cast: F,
+) -> BoxStream<'static, Result<RecordBatch, ArrowError>>
+where
+    S: Stream<Item = Result<RecordBatch, ArrowError>> + Send + 'static,
+    F: Fn(&RecordBatch) -> Result<RecordBatch, Error> + Send + Sync + 'static,
+{
+    let stream = match options.batch_size {
+        Some(rows) => coalesce_batches(stream, rows).boxed(),
+        None => stream.boxed(),
+    };
+
+    let Some(concurrency) = options.concurrency else {
+        return stream
+            .map(move |batch| {
+                batch.and_then(|batch| {
+                    cast(&batch).map_err(|e| ArrowError::ExternalError(Box::new(e)))
+                })
+            })
+            .boxed();
+    };
+
+    let cast = Arc::new(cast);
+    let casts = stream.map(move |batch| {
+        let cast = Arc::clone(&cast);
+        async move {
+            let batch = batch?;
+            tokio::task::spawn_blocking(move || cast(&batch))
+                .await
+                .map_err(|e| ArrowError::ExternalError(Box::new(e)))?
+                .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+        }
+    });
+    if options.ordered {
+        casts.buffered(concurrency.max(1)).boxed()
+    } else {
+        casts.buffer_unordered(concurrency.max(1)).boxed()
     }
 }
 
+/// How many times a query is executed again when its result expires while it's read, see
+/// `reexecute_on_expired_result`.
+const MAX_RESULT_REEXECUTIONS: usize = 1;
+
+/// How many times a query that failed with a transient error is retried, see `retry_transient`.
+const MAX_TRANSIENT_RETRIES: usize = 3;
+
+/// The Snowflake error code of a statement whose result is no longer available, see `is_result_expired`.
+const RESULT_EXPIRED_CODE: &str = "000709";
+
+type BatchStream = BoxStream<'static, Result<RecordBatch, ArrowError>>;
+
+/// A query result being read by `reexecute_on_expired_result`.
+struct ExpiringResult<F> {
+    batches: BatchStream,
+    execute: F,
+    ordered: bool,
+    streamed_rows: usize,
+    rows_to_skip: usize,
+    reexecutions: usize,
+}
+
+/// Streams `batches`, the result of a query, executing the query again with `execute` when Snowflake reports that the
+/// result expired while it was read, i.e. when its chunks are fetched after the result retention window. The query is
+/// executed again at most `MAX_RESULT_REEXECUTIONS` times, after which the expiration fails the stream.
+///
+/// The rows already streamed are skipped from the new result, so a result is only resumed when it's `ordered`, or when
+/// none of its rows were streamed yet. Otherwise the expiration fails the stream, instead of streaming rows twice.
+fn reexecute_on_expired_result<F, Fut>(
+    batches: BatchStream,
+    execute: F,
+    ordered: bool,
+) -> BatchStream
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<BatchStream, ArrowError>> + Send,
+{
+    let result = ExpiringResult {
+        batches,
+        execute,
+        ordered,
+        streamed_rows: 0,
+        rows_to_skip: 0,
+        reexecutions: 0,
+    };
+
+    stream::unfold(Some(result), |result| async move {
+        let mut result = result?;
+        loop {
+            match result.batches.next().await {
+                Some(Ok(batch)) => {
+                    let skipped = result.rows_to_skip.min(batch.num_rows());
+                    result.rows_to_skip -= skipped;
+                    if skipped > 0 && skipped == batch.num_rows() {
+                        continue;
+                    }
+                    let batch = batch.slice(skipped, batch.num_rows() - skipped);
+                    result.streamed_rows += batch.num_rows();
+                    return Some((Ok(batch), Some(result)));
+                }
+                Some(Err(e))
+                    if is_result_expired(&e)
+                        && result.reexecutions < MAX_RESULT_REEXECUTIONS
+                        && (result.ordered || result.streamed_rows == 0) =>
+                {
+                    tracing::warn!(
+                        "Snowflake query result expired after {} rows, executing the query again: {e}",
+                        result.streamed_rows
+                    );
+                    result.reexecutions += 1;
+                    match (result.execute)().await {
+                        Ok(batches) => {
+                            result.batches = batches;
+                            result.rows_to_skip = result.streamed_rows;
+                        }
+                        Err(e) => return Some((Err(e), None)),
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => return None,
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Runs `exec`, retrying it with a Fibonacci backoff while it fails with a transient error, see
+/// `is_transient_error`, at most `MAX_TRANSIENT_RETRIES` times. Other errors, i.e. a syntax or authentication
+/// error, are returned right away.
+///
+/// Only queries and metadata calls are retried. A DML statement may have been committed before its response was
+/// lost, so retrying it could apply it twice.
+async fn retry_transient<T, F, Fut>(exec: F) -> Result<T, SnowflakeApiError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SnowflakeApiError>>,
+{
+    retry_if(exec, is_transient_error).await
+}
+
+/// Runs `exec`, retrying it with a Fibonacci backoff while `is_transient` holds for its error, at most
+/// `MAX_TRANSIENT_RETRIES` times.
+async fn retry_if<T, E, F, Fut>(mut exec: F, is_transient: fn(&E) -> bool) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let backoff = FibonacciBackoffBuilder::new()
+        .max_retries(Some(MAX_TRANSIENT_RETRIES))
+        .build();
+    retry(backoff, || {
+        let attempt = exec();
+        async move {
+            attempt.await.map_err(|e| {
+                if is_transient(&e) {
+                    tracing::debug!("Retrying Snowflake statement after a transient error: {e}");
+                    RetryError::transient(e)
+                } else {
+                    RetryError::permanent(e)
+                }
+            })
+        }
+    })
+    .await
+}
+
+/// Whether `error` may go away when the query is retried: the request timing out or failing to connect, or Snowflake
+/// throttling it or being unavailable, i.e. an HTTP 429 or 5xx response.
+fn is_transient_error(error: &SnowflakeApiError) -> bool {
+    match error {
+        SnowflakeApiError::RequestError(ConnectionError::RequestError(e)) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+/// Whether `error` is Snowflake reporting that a query result is no longer available: the result itself expired, or
+/// the presigned URL of one of its chunks did, which the storage service rejects with an HTTP 403.
+fn is_result_expired(error: &ArrowError) -> bool {
+    let ArrowError::ExternalError(source) = error else {
+        return false;
+    };
+    match source.downcast_ref::<SnowflakeApiError>() {
+        Some(SnowflakeApiError::ApiError(code, _)) => code == RESULT_EXPIRED_CODE,
+        Some(SnowflakeApiError::RequestError(ConnectionError::RequestError(e))) => {
+            e.status().is_some_and(|status| status.as_u16() == 403)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `sql` may order its results. This is conservative: an `ORDER BY` anywhere in the query, i.e. in a
+/// subquery or a window function, counts.
+fn has_order_by(sql: &str) -> bool {
+    let words: Vec<String> = sql
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_uppercase)
+        .collect();
+    words
+        .windows(2)
+        .any(|pair| pair[0] == "ORDER" && pair[1] == "BY")
+}
+
+/// Concatenates consecutive batches of `stream` until each holds at least `min_rows` rows. The last batch may be
+/// smaller. Ends after yielding the first error.
+fn coalesce_batches<S>(
+    stream: S,
+    min_rows: usize,
+) -> impl Stream<Item = Result<RecordBatch, ArrowError>>
+where
+    S: Stream<Item = Result<RecordBatch, ArrowError>>,
+{
+    stream::unfold(Some(Box::pin(stream)), move |stream| async move {
+        let mut stream = stream?;
+        let mut batches = Vec::new();
+        let mut rows = 0;
+        let mut exhausted = false;
+
+        while rows < min_rows {
+            match stream.next().await {
+                Some(Ok(batch)) => {
+                    rows += batch.num_rows();
+                    batches.push(batch);
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    exhausted = true;
+                    break;
+                }
+            }
+        }
+
+        let next_state = if exhausted { None } else { Some(stream) };
+        match batches.as_slice() {
+            [] => None,
+            [batch] => Some((Ok(batch.clone()), next_state)),
+            [first, ..] => Some((
+                arrow::compute::concat_batches(&first.schema(), &batches),
+                next_state,
+            )),
+        }
+    })
+}
+
 fn to_execution_error(e: impl Into<Box<dyn std::error::Error>>) -> DataFusionError {
     DataFusionError::Execution(format!("{}", e.into()).to_string())
 }
@@ -182,14 +1012,88 @@ pub fn snowflake_schema_cast(record_batch: &RecordBatch) -> Result<RecordBatch,
     for (idx, field) in record_batch.schema().fields().iter().enumerate() {
         let column = record_batch.column(idx);
         if let Some(sf_logical_type) = field.metadata().get("logicalType") {
-            if sf_logical_type.to_lowercase().as_str() == "timestamp_ntz" {
-                fields.push(Arc::new(Field::new(
-                    field.name(),
-                    DataType::Timestamp(TimeUnit::Millisecond, None),
-                    field.is_nullable(),
-                )));
-                columns.push(cast_sf_timestamp_ntz_to_arrow_timestamp(column)?);
-                continue;
+            match sf_logical_type.to_lowercase().as_str() {
+                "timestamp_ntz" => {
+                    fields.push(Arc::new(Field::new(
+                        field.name(),
+                        DataType::Timestamp(TimeUnit::Millisecond, None),
+                        field.is_nullable(),
+                    )));
+                    columns.push(cast_sf_timestamp_to_arrow_timestamp(
+                        field.name(),
+                        column,
+                        None,
+                    )?);
+                    continue;
+                }
+                // The epoch of both is a UTC instant. TIMESTAMP_TZ also sends the offset it was written with, which
+                // is dropped, like Postgres does for a `timestamptz`.
+                "timestamp_tz" | "timestamp_ltz" => {
+                    fields.push(Arc::new(Field::new(
+                        field.name(),
+                        DataType::Timestamp(TimeUnit::Millisecond, Some(UTC.into())),
+                        field.is_nullable(),
+                    )));
+                    columns.push(cast_sf_timestamp_to_arrow_timestamp(
+                        field.name(),
+                        column,
+                        Some(UTC),
+                    )?);
+                    continue;
+                }
+                "geography" | "geometry" => {
+                    fields.push(Arc::new(Field::new(
+                        field.name(),
+                        DataType::Utf8,
+                        field.is_nullable(),
+                    )));
+                    columns.push(cast_sf_geospatial_to_arrow_utf8(column)?);
+                    continue;
+                }
+                // Snowflake sends a NUMBER as the narrowest integer that fits the unscaled values of each chunk, so
+                // a wide column can't be assumed to fit an Int64, and the scale is only in the metadata. Read those
+                // as a decimal of their declared precision and scale instead. Integers that fit an Int64 are read as
+                // an Int64, whatever the width of the chunk.
+                "fixed" => match sf_number_precision_and_scale(field) {
+                    Some((precision, scale))
+                        if precision > MAX_INT64_NUMBER_PRECISION || scale != 0 =>
+                    {
+                        fields.push(Arc::new(Field::new(
+                            field.name(),
+                            DataType::Decimal128(precision, scale),
+                            field.is_nullable(),
+                        )));
+                        columns.push(cast_sf_fixed_to_arrow_decimal(column, precision, scale)?);
+                        continue;
+                    }
+                    Some(_) => {
+                        fields.push(Arc::new(Field::new(
+                            field.name(),
+                            DataType::Int64,
+                            field.is_nullable(),
+                        )));
+                        columns.push(
+                            arrow::compute::cast(column, &DataType::Int64)
+                                .context(SnowflakeArrowSnafu)?,
+                        );
+                        continue;
+                    }
+                    None => {}
+                },
+                // A TIME is sent as an Int32 or an Int64 depending on the scale of the column
+                "time" => {
+                    fields.push(Arc::new(Field::new(
+                        field.name(),
+                        DataType::Int64,
+                        field.is_nullable(),
+                    )));
+                    columns.push(
+                        arrow::compute::cast(column, &DataType::Int64)
+                            .context(SnowflakeArrowSnafu)?,
+                    );
+                    continue;
+                }
+                _ => {}
             }
         }
         fields.push(Arc::clone(field));
@@ -200,34 +1104,154 @@ pub fn snowflake_schema_cast(record_batch: &RecordBatch) -> Result<RecordBatch,
     RecordBatch::try_new(schema, columns).context(FailedToCreateRecordBatchSnafu)
 }
 
-fn cast_sf_timestamp_ntz_to_arrow_timestamp(column: &ArrayRef) -> Result<ArrayRef, Error> {
+/// Casts the columns of `record_batch` to the fields of `target_schema` with the same name, in the order of
+/// `target_schema`, i.e. an `Int64` column to the `Int32` a caller expects. Casts are checked, so a value that
+/// doesn't fit the target type fails rather than becoming NULL, as does a NULL in a non-nullable target field.
+///
+/// # Errors
+///
+/// Returns an error if a target field has no column, or a column can't be cast to its target type.
+pub fn coerce_to_schema(
+    record_batch: &RecordBatch,
+    target_schema: &SchemaRef,
+) -> Result<RecordBatch, Error> {
+    let cast_options = CastOptions {
+        safe: false,
+        ..CastOptions::default()
+    };
+    let columns = target_schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let column =
+                record_batch
+                    .column_by_name(field.name())
+                    .context(MissingTargetColumnSnafu {
+                        column: field.name(),
+                    })?;
+            if column.data_type() == field.data_type() {
+                return Ok(Arc::clone(column));
+            }
+            cast_with_options(column, field.data_type(), &cast_options).context(
+                UnableToCoerceColumnSnafu {
+                    column: field.name(),
+                    from: column.data_type().clone(),
+                    to: field.data_type().clone(),
+                },
+            )
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    RecordBatch::try_new(Arc::clone(target_schema), columns).context(FailedToCreateRecordBatchSnafu)
+}
+
+/// The time zone of the timestamps `TIMESTAMP_TZ` and `TIMESTAMP_LTZ` columns are read as.
+const UTC: &str = "UTC";
+
+/// The widest `NUMBER` whose values always fit an `Int64`.
+const MAX_INT64_NUMBER_PRECISION: u8 = 18;
+
+/// Reads the declared precision and scale of a Snowflake `NUMBER` field from its metadata.
+fn sf_number_precision_and_scale(field: &Field) -> Option<(u8, i8)> {
+    let metadata = field.metadata();
+    let precision = metadata.get("precision")?.parse::<u8>().ok()?;
+    let scale = metadata.get("scale")?.parse::<i8>().ok()?;
+    Some((precision.min(DECIMAL128_MAX_PRECISION), scale))
+}
+
+/// Casts a Snowflake `NUMBER` column to a `Decimal128` of `precision` and `scale`. Integer columns hold the unscaled
+/// values, e.g. `1.50` of a `NUMBER(38,2)` is sent as `150`.
+fn cast_sf_fixed_to_arrow_decimal(
+    column: &ArrayRef,
+    precision: u8,
+    scale: i8,
+) -> Result<ArrayRef, Error> {
+    let data_type = DataType::Decimal128(precision, scale);
+    match column.data_type() {
+        DataType::Decimal128(_, _) => {
+            arrow::compute::cast(column, &data_type).context(SnowflakeArrowSnafu)
+        }
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            let values =
+                arrow::compute::cast(column, &DataType::Int64).context(SnowflakeArrowSnafu)?;
+            let decimals = values
+                .as_primitive::<Int64Type>()
+                .unary::<_, Decimal128Type>(i128::from)
+                .with_precision_and_scale(precision, scale)
+                .context(SnowflakeArrowSnafu)?;
+            Ok(Arc::new(decimals) as ArrayRef)
+        }
+        other => UnableToCastSnowflakeNumberSnafu {
+            reason: format!("unexpected array type {other}"),
+        }
+        .fail(),
+    }
+}
+
+/// Casts a Snowflake `GEOGRAPHY` or `GEOMETRY` column to `Utf8`. Snowflake sends the `GeoJSON`, WKT or EWKT text of
+/// each value, or its WKB bytes when the output format is `WKB` or `EWKB`, which are read as their hex text, like
+/// Snowflake displays them.
+fn cast_sf_geospatial_to_arrow_utf8(column: &ArrayRef) -> Result<ArrayRef, Error> {
+    match column.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            arrow::compute::cast(column, &DataType::Utf8).context(SnowflakeArrowSnafu)
+        }
+        DataType::Binary => {
+            let hex: StringArray = column
+                .as_binary::<i32>()
+                .iter()
+                .map(|value| {
+                    value.map(|bytes| {
+                        bytes.iter().fold(
+                            String::with_capacity(bytes.len() * 2),
+                            |mut hex, byte| {
+                                let _ = write!(hex, "{byte:02X}");
+                                hex
+                            },
+                        )
+                    })
+                })
+                .collect();
+            Ok(Arc::new(hex) as ArrayRef)
+        }
+        other => UnableToCastSnowflakeGeospatialSnafu {
+            reason: format!("unexpected array type {other}"),
+        }
+        .fail(),
+    }
+}
+
+/// Casts a Snowflake timestamp, a struct of the `epoch` seconds and the `fraction` nanoseconds of the second, to
+/// milliseconds in `time_zone`. A `TIMESTAMP_TZ` struct also has a `timezone` field, which is ignored. `column_name`
+/// names the column in the errors.
+fn cast_sf_timestamp_to_arrow_timestamp(
+    column_name: &str,
+    column: &ArrayRef,
+    time_zone: Option<&str>,
+) -> Result<ArrayRef, Error> {
     let struct_array = column.as_any().downcast_ref::<StructArray>().context(
         UnableToCastSnowflakeTimestampSnafu {
+            column: column_name,
             reason: "value is not a struct",
         },
     )?;
-    if struct_array.columns().len() < 2 {
-        return UnableToCastSnowflakeTimestampSnafu {
-            reason: "value is not a struct with 2 columns",
-        }
-        .fail();
-    }
+    // TIMESTAMP_TZ puts the `timezone` field second, so the fields are looked up by name
     let epoch_array = struct_array
-        .column(0)
-        .as_any()
-        .downcast_ref::<Int64Array>()
+        .column_by_name("epoch")
+        .and_then(|epoch| epoch.as_any().downcast_ref::<Int64Array>())
         .context(UnableToCastSnowflakeTimestampSnafu {
+            column: column_name,
             reason: "epoch is missing",
         })?;
     let fraction_array = struct_array
-        .column(1)
-        .as_any()
-        .downcast_ref::<Int32Array>()
+        .column_by_name("fraction")
+        .and_then(|fraction| fraction.as_any().downcast_ref::<Int32Array>())
         .context(UnableToCastSnowflakeTimestampSnafu {
+            column: column_name,
             reason: "fraction is missing",
         })?;
 
-    let mut builder = TimestampMillisecondBuilder::new();
+    let mut builder = TimestampMillisecondBuilder::new().with_timezone_opt(time_zone);
 
     for idx in 0..struct_array.len() {
         if struct_array.is_null(idx) {
@@ -246,8 +1270,8 @@ fn cast_sf_timestamp_ntz_to_arrow_timestamp(column: &ArrayRef) -> Result<ArrayRe
 mod tests {
     use super::*;
     use arrow::array::{
-        ArrayBuilder, ArrayRef, Int32Builder, Int64Builder, StructBuilder,
-        TimestampMillisecondArray,
+        ArrayBuilder, ArrayRef, Decimal128Array, Int16Array, Int32Builder, Int64Builder,
+        StructBuilder, TimestampMillisecondArray,
     };
     use arrow::datatypes::{DataType, Field};
     use std::sync::Arc;
@@ -258,7 +1282,7 @@ mod tests {
             vec![Some(1_696_164_330), None, Some(1_714_647_301)],
             vec![Some(0), None, Some(739_000_000)],
         );
-        let result = cast_sf_timestamp_ntz_to_arrow_timestamp(&timestamp_ntz_array)
+        let result = cast_sf_timestamp_to_arrow_timestamp("created_at", &timestamp_ntz_array, None)
             .expect("Should cast Snowflake timestamp to Arrow timestamp");
         let result = result
             .as_any()
@@ -285,11 +1309,290 @@ mod tests {
             epoch_array,
         )]);
 
-        let result = cast_sf_timestamp_ntz_to_arrow_timestamp(
-            &(Arc::new(timestamp_ntz_no_fraction) as ArrayRef),
-        );
+        let timestamp_ntz_no_fraction = Arc::new(timestamp_ntz_no_fraction) as ArrayRef;
+        let result =
+            cast_sf_timestamp_to_arrow_timestamp("created_at", &timestamp_ntz_no_fraction, None);
 
         assert!(result.is_err());
+
+        // The failing column is named when casting a whole batch
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            sf_field(
+                "updated_at",
+                timestamp_ntz_no_fraction.data_type().clone(),
+                "TIMESTAMP_NTZ",
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                timestamp_ntz_no_fraction,
+            ],
+        )
+        .expect("batch is created");
+        let Err(err) = snowflake_schema_cast(&batch) else {
+            panic!("a timestamp without a fraction should be rejected");
+        };
+        assert!(err.to_string().contains("updated_at"), "{err}");
+    }
+
+    fn sf_field(name: &str, data_type: DataType, logical_type: &str) -> Field {
+        Field::new(name, data_type, true)
+            .with_metadata([("logicalType".to_string(), logical_type.to_string())].into())
+    }
+
+    #[test]
+    fn test_cast_sf_timestamp_ltz_to_arrow_timestamp() {
+        let timestamp_ltz_array = create_timestamp_ntz_array(
+            vec![Some(1_696_164_330), None, Some(1_714_647_301)],
+            vec![Some(0), None, Some(739_000_000)],
+        );
+        let schema = Arc::new(Schema::new(vec![sf_field(
+            "created_at",
+            timestamp_ltz_array.data_type().clone(),
+            "TIMESTAMP_LTZ",
+        )]));
+        let batch =
+            RecordBatch::try_new(schema, vec![timestamp_ltz_array]).expect("batch is created");
+
+        let result = snowflake_schema_cast(&batch).expect("Should cast Snowflake timestamp");
+        assert_eq!(
+            *result.schema().field(0).data_type(),
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+        );
+        let result = result
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .expect("Should downcast to TimestampMillisecondArray");
+
+        assert_eq!(result.value(0), 1_696_164_330_000);
+        assert!(result.is_null(1));
+        assert_eq!(result.value(2), 1_714_647_301_739);
+    }
+
+    #[test]
+    fn test_cast_sf_timestamp_tz_to_arrow_timestamp() {
+        // The offset is sent in minutes, shifted by 1440 so it's positive. The epoch is UTC regardless.
+        let timestamp_tz_array = Arc::new(StructArray::from(vec![
+            (
+                Arc::new(Field::new("epoch", DataType::Int64, true)),
+                Arc::new(Int64Array::from(vec![1_696_164_330, 1_714_647_301])) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("fraction", DataType::Int32, true)),
+                Arc::new(Int32Array::from(vec![0, 739_000_000])) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("timezone", DataType::Int32, true)),
+                Arc::new(Int32Array::from(vec![1440 + 120, 1440 - 300])) as ArrayRef,
+            ),
+        ])) as ArrayRef;
+        let schema = Arc::new(Schema::new(vec![sf_field(
+            "created_at",
+            timestamp_tz_array.data_type().clone(),
+            "TIMESTAMP_TZ",
+        )]));
+        let batch =
+            RecordBatch::try_new(schema, vec![timestamp_tz_array]).expect("batch is created");
+
+        let result = snowflake_schema_cast(&batch).expect("Should cast Snowflake timestamp");
+        assert_eq!(
+            *result.schema().field(0).data_type(),
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+        );
+        let result = result
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .expect("Should downcast to TimestampMillisecondArray");
+
+        assert_eq!(result.value(0), 1_696_164_330_000);
+        assert_eq!(result.value(1), 1_714_647_301_739);
+    }
+
+    #[test]
+    fn test_geospatial_values_are_cast_to_utf8() {
+        let geo_json = r#"{"coordinates": [-122.35, 37.55], "type": "Point"}"#;
+        // POINT(1 2) as little endian WKB
+        let wkb: &[u8] = &[
+            0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+        ];
+        let location =
+            Arc::new(arrow::array::StringArray::from(vec![Some(geo_json), None])) as ArrayRef;
+        let shape = Arc::new(arrow::array::BinaryArray::from(vec![Some(wkb), None])) as ArrayRef;
+        let schema = Arc::new(Schema::new(vec![
+            sf_field("location", location.data_type().clone(), "GEOGRAPHY"),
+            sf_field("shape", shape.data_type().clone(), "GEOMETRY"),
+        ]));
+        let batch = RecordBatch::try_new(schema, vec![location, shape]).expect("batch is created");
+
+        let result =
+            snowflake_schema_cast(&batch).expect("Should cast Snowflake geospatial values");
+        assert_eq!(*result.schema().field(0).data_type(), DataType::Utf8);
+        assert_eq!(*result.schema().field(1).data_type(), DataType::Utf8);
+
+        let location = result.column(0).as_string::<i32>();
+        assert_eq!(location.value(0), geo_json);
+        assert!(location.is_null(1));
+        let shape = result.column(1).as_string::<i32>();
+        assert_eq!(shape.value(0), "0101000000000000000000F03F0000000000000040");
+        assert!(shape.is_null(1));
+    }
+
+    fn sf_number_field(name: &str, data_type: DataType, precision: u8, scale: i8) -> Field {
+        Field::new(name, data_type, true).with_metadata(
+            [
+                ("logicalType".to_string(), "FIXED".to_string()),
+                ("precision".to_string(), precision.to_string()),
+                ("scale".to_string(), scale.to_string()),
+            ]
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_wide_numbers_are_cast_to_decimal() {
+        let beyond_i64 = i128::from(i64::MAX) + 1;
+        let schema = Arc::new(Schema::new(vec![
+            sf_number_field("big", DataType::Decimal128(38, 0), 38, 0),
+            sf_number_field("small", DataType::Int64, 38, 0),
+            sf_number_field("price", DataType::Int16, 38, 2),
+            sf_number_field("narrow", DataType::Int32, 9, 0),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(
+                    Decimal128Array::from(vec![Some(beyond_i64), None])
+                        .with_precision_and_scale(38, 0)
+                        .expect("valid precision and scale"),
+                ),
+                Arc::new(Int64Array::from(vec![Some(i64::MIN), Some(1)])),
+                Arc::new(Int16Array::from(vec![Some(150), None])),
+                Arc::new(Int32Array::from(vec![1, 2])),
+            ],
+        )
+        .expect("batch is created");
+
+        let result = snowflake_schema_cast(&batch).expect("Should cast Snowflake numbers");
+        let data_types: Vec<_> = result
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.data_type().clone())
+            .collect();
+        assert_eq!(
+            data_types,
+            vec![
+                DataType::Decimal128(38, 0),
+                DataType::Decimal128(38, 0),
+                DataType::Decimal128(38, 2),
+                DataType::Int64,
+            ]
+        );
+
+        let big = result.column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(big.value(0), beyond_i64);
+        assert_eq!(big.value_as_string(0), "9223372036854775808");
+        assert!(big.is_null(1));
+
+        let small = result.column(1).as_primitive::<Decimal128Type>();
+        assert_eq!(small.value(0), i128::from(i64::MIN));
+        assert_eq!(small.value(1), 1);
+
+        let price = result.column(2).as_primitive::<Decimal128Type>();
+        assert_eq!(price.value_as_string(0), "1.50");
+        assert!(price.is_null(1));
+    }
+
+    #[test]
+    fn test_scaled_numbers_are_cast_to_decimal() {
+        let schema = Arc::new(Schema::new(vec![
+            sf_number_field("amount", DataType::Int32, 38, 4),
+            sf_number_field("rate", DataType::Int64, 10, 4),
+            sf_number_field("quantity", DataType::Int64, 18, 0),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![Some(12_345), None, Some(-5)])),
+                Arc::new(Int64Array::from(vec![1, 10_000, 99_999])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .expect("batch is created");
+
+        let result = snowflake_schema_cast(&batch).expect("Should cast Snowflake numbers");
+        assert_eq!(
+            *result.schema().field(0).data_type(),
+            DataType::Decimal128(38, 4)
+        );
+        assert_eq!(
+            *result.schema().field(1).data_type(),
+            DataType::Decimal128(10, 4)
+        );
+        assert_eq!(*result.schema().field(2).data_type(), DataType::Int64);
+
+        let amount = result.column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(amount.value_as_string(0), "1.2345");
+        assert!(amount.is_null(1));
+        assert_eq!(amount.value_as_string(2), "-0.0005");
+
+        let rate = result.column(1).as_primitive::<Decimal128Type>();
+        assert_eq!(rate.value_as_string(1), "1.0000");
+        assert_eq!(rate.value_as_string(2), "9.9999");
+    }
+
+    #[test]
+    fn test_coerce_to_schema() {
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "name",
+                Arc::new(arrow::array::StringArray::from(vec!["a", "b"])) as ArrayRef,
+            ),
+            ("id", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+        ])
+        .expect("batch is created");
+        let target_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+
+        let coerced = coerce_to_schema(&batch, &target_schema).expect("batch is coerced");
+        assert_eq!(coerced.schema(), target_schema);
+        assert_eq!(
+            coerced
+                .column(0)
+                .as_primitive::<arrow::datatypes::Int32Type>()
+                .values(),
+            &[1, 2]
+        );
+
+        // A value that doesn't fit the target type isn't silently replaced by NULL
+        let batch = RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(Int64Array::from(vec![i64::MAX])) as ArrayRef,
+        )])
+        .expect("batch is created");
+        let target_schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, true)]));
+        assert!(matches!(
+            coerce_to_schema(&batch, &target_schema),
+            Err(Error::UnableToCoerceColumn { ref column, .. }) if column == "id"
+        ));
+
+        let target_schema = Arc::new(Schema::new(vec![Field::new(
+            "missing",
+            DataType::Int32,
+            true,
+        )]));
+        assert!(matches!(
+            coerce_to_schema(&batch, &target_schema),
+            Err(Error::MissingTargetColumn { ref column }) if column == "missing"
+        ));
     }
 
     fn create_timestamp_ntz_array(
@@ -335,4 +1638,508 @@ mod tests {
 
         Arc::new(builder.finish()) as ArrayRef
     }
+
+    #[tokio::test]
+    async fn test_cast_stream_coalesces_before_cast() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batches = (0..10)
+            .map(|i| {
+                RecordBatch::try_new(
+                    Arc::clone(&schema),
+                    vec![Arc::new(Int64Array::from(vec![2 * i, 2 * i + 1]))],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .expect("batches are created");
+
+        let casts = Arc::new(AtomicUsize::new(0));
+        let counting_cast = |casts: Arc<AtomicUsize>| {
+            move |batch: &RecordBatch| {
+                casts.fetch_add(1, Ordering::SeqCst);
+                snowflake_schema_cast(batch)
+            }
+        };
+
+        let uncoalesced: Vec<RecordBatch> = cast_stream(
+            stream::iter(batches.clone().into_iter().map(Ok)),
+            CastStreamOptions::default(),
+            counting_cast(Arc::clone(&casts)),
+        )
+        .try_collect()
+        .await
+        .expect("batches are cast");
+        assert_eq!(uncoalesced.len(), 10);
+        assert_eq!(casts.swap(0, Ordering::SeqCst), 10);
+
+        let coalesced: Vec<RecordBatch> = cast_stream(
+            stream::iter(batches.into_iter().map(Ok)),
+            CastStreamOptions {
+                batch_size: Some(5),
+                ..CastStreamOptions::default()
+            },
+            counting_cast(Arc::clone(&casts)),
+        )
+        .try_collect()
+        .await
+        .expect("batches are cast");
+        assert_eq!(casts.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            coalesced
+                .iter()
+                .map(RecordBatch::num_rows)
+                .collect::<Vec<_>>(),
+            vec![6, 6, 6, 2]
+        );
+
+        let ids = arrow::compute::concat_batches(&schema, &coalesced).expect("batches concat");
+        let ids = ids
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .expect("ids are Int64");
+        assert_eq!(ids.values().to_vec(), (0..20).collect::<Vec<i64>>());
+    }
+
+    #[tokio::test]
+    async fn test_cast_stream_preserves_order_when_ordered() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batches = (0..8)
+            .map(|i| {
+                RecordBatch::try_new(
+                    Arc::clone(&schema),
+                    vec![Arc::new(Int64Array::from(vec![i]))],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .expect("batches are created");
+
+        // Earlier batches take longer to cast, so they complete last when cast concurrently
+        let slow_cast = |batch: &RecordBatch| {
+            let id = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .expect("ids are Int64")
+                .value(0);
+            std::thread::sleep(Duration::from_millis(10 * (8 - id.unsigned_abs())));
+            Ok(batch.clone())
+        };
+        let ids = |batches: Vec<RecordBatch>| -> Vec<i64> {
+            batches
+                .iter()
+                .flat_map(|batch| {
+                    batch
+                        .column(0)
+                        .as_any()
+                        .downcast_ref::<Int64Array>()
+                        .expect("ids are Int64")
+                        .values()
+                        .to_vec()
+                })
+                .collect()
+        };
+
+        for ordered in [true, false] {
+            let cast: Vec<RecordBatch> = cast_stream(
+                stream::iter(batches.clone().into_iter().map(Ok)),
+                CastStreamOptions {
+                    concurrency: Some(4),
+                    ordered,
+                    ..CastStreamOptions::default()
+                },
+                slow_cast,
+            )
+            .try_collect()
+            .await
+            .expect("batches are cast");
+
+            let mut ids = ids(cast);
+            if ordered {
+                assert_eq!(ids, (0..8).collect::<Vec<i64>>());
+            } else {
+                // Every batch is still emitted once
+                ids.sort_unstable();
+                assert_eq!(ids, (0..8).collect::<Vec<i64>>());
+            }
+        }
+    }
+
+    #[test]
+    fn test_has_order_by() {
+        assert!(has_order_by("SELECT * FROM t ORDER BY id"));
+        assert!(has_order_by("select *\nfrom t\norder\n  by id desc"));
+        assert!(has_order_by(
+            "SELECT id, ROW_NUMBER() OVER (ORDER BY ts) FROM t"
+        ));
+        assert!(!has_order_by("SELECT * FROM t"));
+        assert!(!has_order_by("SELECT border, by_order FROM t"));
+    }
+
+    // The clock is paused so the keep-alive interval elapses instantly
+    #[tokio::test(start_paused = true)]
+    async fn test_keep_alive_runs_until_dropped() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let keep_alive_runs = Arc::clone(&runs);
+        let keep_alive = KeepAlive::spawn(Duration::from_secs(60), move || {
+            let runs = Arc::clone(&keep_alive_runs);
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        // Nothing runs before the first interval has elapsed
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        // Idle past several intervals, the session is kept alive on each of them
+        tokio::time::sleep(Duration::from_secs(160)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        drop(keep_alive);
+        tokio::time::sleep(Duration::from_secs(600)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_reexecute_on_expired_result() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = |ids: Vec<i64>| {
+            RecordBatch::try_new(Arc::clone(&schema), vec![Arc::new(Int64Array::from(ids))])
+                .expect("batch is created")
+        };
+        let expired = || {
+            ArrowError::ExternalError(Box::new(SnowflakeApiError::ApiError(
+                RESULT_EXPIRED_CODE.to_string(),
+                "Result for query 01b2c3d4 has expired".to_string(),
+            )))
+        };
+        // The first chunk is read, then the result expires before the second one is fetched
+        let expiring_result =
+            || -> BatchStream { stream::iter(vec![Ok(batch(vec![1, 2])), Err(expired())]).boxed() };
+        let reexecute = |executions: Arc<AtomicUsize>, expires_again: bool| {
+            let full_result = vec![batch(vec![1, 2, 3]), batch(vec![4, 5])];
+            move || {
+                executions.fetch_add(1, Ordering::SeqCst);
+                let result: BatchStream = if expires_again {
+                    stream::iter(vec![Err(expired())]).boxed()
+                } else {
+                    stream::iter(full_result.clone().into_iter().map(Ok)).boxed()
+                };
+                async move { Ok(result) }
+            }
+        };
+        let ids = |batches: Vec<RecordBatch>| -> Vec<i64> {
+            batches
+                .iter()
+                .flat_map(|batch| {
+                    batch
+                        .column(0)
+                        .as_primitive::<Int64Type>()
+                        .values()
+                        .to_vec()
+                })
+                .collect()
+        };
+
+        // The query is executed once more, and the rows already streamed are skipped
+        let executions = Arc::new(AtomicUsize::new(0));
+        let batches: Vec<RecordBatch> = reexecute_on_expired_result(
+            expiring_result(),
+            reexecute(Arc::clone(&executions), false),
+            true,
+        )
+        .try_collect()
+        .await
+        .expect("result is resumed");
+        assert_eq!(executions.load(Ordering::SeqCst), 1);
+        assert_eq!(ids(batches), vec![1, 2, 3, 4, 5]);
+
+        // Re-executions are bounded, so a result that keeps expiring fails
+        let executions = Arc::new(AtomicUsize::new(0));
+        let result: Result<Vec<RecordBatch>, ArrowError> = reexecute_on_expired_result(
+            expiring_result(),
+            reexecute(Arc::clone(&executions), true),
+            true,
+        )
+        .try_collect()
+        .await;
+        assert!(result.is_err());
+        assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+        // Without an order, rows already streamed can't be skipped, so the result isn't resumed
+        let executions = Arc::new(AtomicUsize::new(0));
+        let result: Result<Vec<RecordBatch>, ArrowError> = reexecute_on_expired_result(
+            expiring_result(),
+            reexecute(Arc::clone(&executions), false),
+            false,
+        )
+        .try_collect()
+        .await;
+        assert!(result.is_err());
+        assert_eq!(executions.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_affected_rows() {
+        let row_counts = |columns: Vec<(&str, ArrayRef)>| {
+            let batch = RecordBatch::try_from_iter(columns).expect("batch is created");
+            affected_rows(snowflake_api::QueryResult::Arrow(vec![batch]))
+        };
+
+        assert_eq!(
+            affected_rows(snowflake_api::QueryResult::Empty).expect("empty response"),
+            0
+        );
+        assert_eq!(
+            row_counts(vec![(
+                "number of rows inserted",
+                Arc::new(Int64Array::from(vec![3])) as ArrayRef
+            )])
+            .expect("rows are inserted"),
+            3
+        );
+        // A MERGE reports the inserted, updated and deleted rows separately
+        assert_eq!(
+            row_counts(vec![
+                (
+                    "number of rows inserted",
+                    Arc::new(Int64Array::from(vec![2])) as ArrayRef
+                ),
+                (
+                    "number of rows updated",
+                    Arc::new(Int64Array::from(vec![5])) as ArrayRef
+                ),
+            ])
+            .expect("rows are merged"),
+            7
+        );
+        assert_eq!(
+            row_counts(vec![(
+                "status",
+                Arc::new(arrow::array::StringArray::from(vec![
+                    "Table T successfully created."
+                ])) as ArrayRef
+            )])
+            .expect("table is created"),
+            0
+        );
+        assert!(matches!(
+            row_counts(vec![(
+                "ID",
+                Arc::new(Int64Array::from(vec![1])) as ArrayRef
+            )]),
+            Err(Error::UnexpectedStatementResponse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_described_schema_of_empty_table() {
+        // `DESCRIBE TABLE` describes the columns whether or not the table has rows
+        let column = |values: Vec<Option<&str>>| {
+            Arc::new(arrow::array::StringArray::from(values)) as ArrayRef
+        };
+        let description = RecordBatch::try_from_iter(vec![
+            (
+                "name",
+                column(vec![
+                    Some("ID"),
+                    Some("PRICE"),
+                    Some("NAME"),
+                    Some("CREATED_AT"),
+                    Some("UPDATED_AT"),
+                ]),
+            ),
+            (
+                "type",
+                column(vec![
+                    Some("NUMBER(18,0)"),
+                    Some("NUMBER(38,4)"),
+                    Some("VARCHAR(16777216)"),
+                    Some("TIMESTAMP_NTZ(9)"),
+                    Some("TIMESTAMP_TZ(9)"),
+                ]),
+            ),
+            ("kind", column(vec![Some("COLUMN"); 5])),
+            (
+                "null?",
+                column(vec![Some("N"), Some("Y"), Some("Y"), Some("N"), Some("Y")]),
+            ),
+        ])
+        .expect("description is created");
+
+        let columns = described_columns(snowflake_api::QueryResult::Arrow(vec![description]))
+            .expect("columns are described");
+        let schema = described_schema(columns, None).expect("schema is described");
+        assert_eq!(
+            schema,
+            Schema::new(vec![
+                Field::new("ID", DataType::Int64, false),
+                Field::new("PRICE", DataType::Decimal128(38, 4), true),
+                Field::new("NAME", DataType::Utf8, true),
+                Field::new(
+                    "CREATED_AT",
+                    DataType::Timestamp(TimeUnit::Millisecond, None),
+                    false
+                ),
+                Field::new(
+                    "UPDATED_AT",
+                    DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                    true
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_described_schema_matches_cast_batches() {
+        let described = |sf_type: &str| sf_type_to_data_type(sf_type).expect("type is known");
+        let time_field = Field::new("AT", DataType::Int32, true).with_metadata(
+            [
+                ("logicalType".to_string(), "TIME".to_string()),
+                ("scale".to_string(), "0".to_string()),
+            ]
+            .into(),
+        );
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                sf_number_field("ID", DataType::Int16, 9, 0),
+                sf_number_field("PRICE", DataType::Int32, 38, 2),
+                time_field,
+            ])),
+            vec![
+                Arc::new(Int16Array::from(vec![1])),
+                Arc::new(Int32Array::from(vec![150])),
+                Arc::new(Int32Array::from(vec![3_600])),
+            ],
+        )
+        .expect("batch is created");
+
+        let result = snowflake_schema_cast(&batch).expect("Should cast Snowflake types");
+        assert_eq!(
+            *result.schema().field(0).data_type(),
+            described("NUMBER(9,0)")
+        );
+        assert_eq!(
+            *result.schema().field(1).data_type(),
+            described("NUMBER(38,2)")
+        );
+        assert_eq!(*result.schema().field(2).data_type(), described("TIME(0)"));
+        assert_eq!(result.column(2).as_primitive::<Int64Type>().value(0), 3_600);
+    }
+
+    #[test]
+    fn test_described_schema_of_unknown_types() {
+        let columns = || {
+            vec![
+                ("ID".to_string(), "NUMBER(18,0)".to_string(), false),
+                ("EMBEDDING".to_string(), "VECTOR(INT, 3)".to_string(), true),
+            ]
+        };
+        assert!(sf_type_to_data_type("VECTOR(INT, 3)").is_none());
+
+        let Err(err) = described_schema(columns(), None) else {
+            panic!("an unknown type without a queried schema should be rejected");
+        };
+        assert!(err.to_string().contains("VECTOR(INT, 3)"), "{err}");
+
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Int32, true)), 3);
+        let queried_schema = Schema::new(vec![
+            Field::new("ID", DataType::Int64, true),
+            Field::new("EMBEDDING", vector_type.clone(), true),
+        ]);
+        let schema =
+            described_schema(columns(), Some(&queried_schema)).expect("schema is described");
+        assert_eq!(
+            schema,
+            Schema::new(vec![
+                Field::new("ID", DataType::Int64, false),
+                Field::new("EMBEDDING", vector_type, true),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_inline_params() {
+        assert_eq!(
+            inline_params("SELECT * FROM t WHERE id = ?", &[&42_i64]).expect("id is inlined"),
+            "SELECT * FROM t WHERE id = 42"
+        );
+
+        // Placeholders in literals, quoted identifiers and comments aren't replaced
+        let sql = "SELECT '?', 'it\\'s?', \"a?\", $$?$$ /* ? */ FROM t -- ?\nWHERE name = ? AND score > ? AND active = ?";
+        assert_eq!(
+            inline_params(sql, &[&"O'Brien\\", &f64::NAN, &true]).expect("params are inlined"),
+            "SELECT '?', 'it\\'s?', \"a?\", $$?$$ /* ? */ FROM t -- ?\nWHERE name = 'O''Brien\\\\' AND score > 'NaN'::FLOAT AND active = TRUE"
+        );
+
+        assert!(matches!(
+            inline_params("SELECT * FROM t WHERE id = ? AND name = ?", &[&1_i32]),
+            Err(Error::ParameterCountMismatch {
+                placeholders: 2,
+                params: 1
+            })
+        ));
+        assert!(matches!(
+            inline_params("SELECT * FROM t WHERE id = ?", &[&vec![1]]),
+            Err(Error::UnsupportedParameterType { index: 0 })
+        ));
+    }
+
+    // The clock is paused so the backoff intervals elapse instantly
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_transient_errors() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Errors are marked transient by the classifier, so the retries don't depend on the network
+        let is_transient: fn(&&str) -> bool = |e| *e == "transient";
+
+        let attempts = AtomicUsize::new(0);
+        let result = retry_if(
+            || async {
+                if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err("transient")
+                } else {
+                    Ok(42)
+                }
+            },
+            is_transient,
+        )
+        .await;
+        assert_eq!(result.expect("query succeeds after retrying"), 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Retrying stops after MAX_TRANSIENT_RETRIES
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), _> = retry_if(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err("transient")
+            },
+            is_transient,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_TRANSIENT_RETRIES + 1);
+
+        // Errors that won't go away aren't retried
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), _> = retry_transient(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(SnowflakeApiError::ApiError(
+                "001003".to_string(),
+                "SQL compilation error: syntax error line 1 at position 0 unexpected 'SELEC'."
+                    .to_string(),
+            ))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
 }
@@ -15,20 +15,25 @@ limitations under the License.
 */
 
 use std::any::Any;
+use std::sync::{Arc, Mutex, PoisonError};
 
+use arrow::array::RecordBatch;
 use arrow::datatypes::SchemaRef;
-use arrow_sql_gen::sqlite::rows_to_arrow;
+use arrow_sql_gen::sqlite::{rows_to_arrow, rows_to_arrow_with_empty_strings};
+use arrow_sql_gen::EmptyStringHandling;
 use async_trait::async_trait;
 use datafusion::execution::SendableRecordBatchStream;
 use datafusion::physical_plan::memory::MemoryStream;
 use datafusion::sql::TableReference;
-use rusqlite::ToSql;
+use rusqlite::{InterruptHandle, OpenFlags, ToSql};
+use secrecy::{ExposeSecret, SecretString};
 use snafu::prelude::*;
 use tokio_rusqlite::Connection;
 
 use super::AsyncDbConnection;
 use super::DbConnection;
 use super::Result;
+use crate::sqlitepool::DEFAULT_BUSY_TIMEOUT;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -46,6 +51,10 @@ pub enum Error {
 
 pub struct SqliteConnection {
     pub conn: Connection,
+    encryption_key: Option<SecretString>,
+    empty_strings: EmptyStringHandling,
+    /// The separate connection of the open read-only transaction, see `begin_read_only`.
+    snapshot: Mutex<Option<Connection>>,
 }
 
 impl DbConnection<Connection, &'static (dyn ToSql + Sync)> for SqliteConnection {
@@ -64,10 +73,6 @@ impl DbConnection<Connection, &'static (dyn ToSql + Sync)> for SqliteConnection
 
 #[async_trait]
 impl AsyncDbConnection<Connection, &'static (dyn ToSql + Sync)> for SqliteConnection {
-    fn new(conn: Connection) -> Self {
-        SqliteConnection { conn }
-    }
-
     async fn get_schema(
         &self,
         table_reference: &TableReference,
@@ -100,20 +105,12 @@ impl AsyncDbConnection<Connection, &'static (dyn ToSql + Sync)> for SqliteConnec
         let sql = sql.to_string();
         let params = params.to_vec();
 
+        let cancel_on_drop = CancelOnDrop::default();
+        let state = Arc::clone(&cancel_on_drop.0);
+        let empty_strings = self.empty_strings;
         let rec = self
-            .conn
-            .call(move |conn| {
-                let mut stmt = conn.prepare(sql.as_str())?;
-                for (i, param) in params.iter().enumerate() {
-                    stmt.raw_bind_parameter(i + 1, param)?;
-                }
-                let column_count = stmt.column_count();
-                let rows = stmt.raw_query();
-                let rec = rows_to_arrow(rows, column_count)
-                    .context(ConversionSnafu)
-                    .map_err(to_tokio_rusqlite_error)?;
-                Ok(rec)
-            })
+            .active_conn()
+            .call(move |conn| query_rows_to_arrow(conn, &state, &sql, &params, empty_strings))
             .await
             .context(ConnectionSnafu)?;
 
@@ -127,7 +124,7 @@ impl AsyncDbConnection<Connection, &'static (dyn ToSql + Sync)> for SqliteConnec
         let params = params.to_vec();
 
         let rows_modified = self
-            .conn
+            .active_conn()
             .call(move |conn| {
                 let mut stmt = conn.prepare(sql.as_str())?;
                 for (i, param) in params.iter().enumerate() {
@@ -140,8 +137,351 @@ impl AsyncDbConnection<Connection, &'static (dyn ToSql + Sync)> for SqliteConnec
             .context(ConnectionSnafu)?;
         Ok(rows_modified as u64)
     }
+
+    /// The `SQLite` connection pool shares a single connection between all of its users, so the transaction runs on
+    /// a separate read-only connection to the same file instead, which doesn't block the writers of the shared one.
+    /// Queries and statements of this connection use it until the transaction ends. The snapshot is taken by the
+    /// first query, and is only isolated from writes in WAL mode. An in-memory database can't be opened twice, so it
+    /// doesn't support read-only transactions.
+    async fn begin_read_only(&self) -> Result<()> {
+        let path = self
+            .conn
+            .call(|conn| {
+                Ok(conn.query_row(
+                    "SELECT file FROM pragma_database_list WHERE name = 'main'",
+                    [],
+                    |row| row.get::<_, String>(0),
+                )?)
+            })
+            .await
+            .context(ConnectionSnafu)?;
+        ensure!(
+            !path.is_empty(),
+            super::ReadOnlyTransactionNotSupportedSnafu
+        );
+
+        let snapshot = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .await
+        .context(ConnectionSnafu)?;
+        let encryption_key = self.encryption_key.clone();
+        snapshot
+            .call(move |conn| {
+                if let Some(encryption_key) = encryption_key {
+                    conn.pragma_update(None, "key", encryption_key.expose_secret())?;
+                }
+                conn.busy_timeout(DEFAULT_BUSY_TIMEOUT)?;
+                conn.execute_batch("BEGIN DEFERRED")?;
+                Ok(())
+            })
+            .await
+            .context(ConnectionSnafu)?;
+
+        *self.snapshot.lock().unwrap_or_else(PoisonError::into_inner) = Some(snapshot);
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        self.end_read_only("COMMIT").await
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        self.end_read_only("ROLLBACK").await
+    }
+}
+
+impl SqliteConnection {
+    #[must_use]
+    pub fn new(conn: Connection) -> Self {
+        SqliteConnection {
+            conn,
+            encryption_key: None,
+            empty_strings: EmptyStringHandling::default(),
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Sets the key of the database, so a read-only transaction can open an encrypted file, see `begin_read_only`.
+    #[must_use]
+    pub fn with_encryption_key(mut self, encryption_key: Option<SecretString>) -> Self {
+        self.encryption_key = encryption_key;
+        self
+    }
+
+    /// Reads the empty strings and NULLs of text columns in query results with `empty_strings`.
+    #[must_use]
+    pub fn with_empty_strings(mut self, empty_strings: EmptyStringHandling) -> Self {
+        self.empty_strings = empty_strings;
+        self
+    }
+
+    /// The connection of the open read-only transaction if there is one, or the shared connection otherwise.
+    fn active_conn(&self) -> Connection {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .unwrap_or_else(|| self.conn.clone())
+    }
+
+    /// Ends the read-only transaction with `sql`, closing its connection. Dropping the connection without ending the
+    /// transaction, i.e. when this is dropped, closes it too, which rolls the transaction back.
+    async fn end_read_only(&self, sql: &'static str) -> Result<()> {
+        let snapshot = self
+            .snapshot
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        snapshot
+            .call(move |conn| {
+                conn.execute_batch(sql)?;
+                Ok(())
+            })
+            .await
+            .context(ConnectionSnafu)?;
+        Ok(())
+    }
+}
+
+/// The state of a query, shared between its future and the connection's thread that runs it.
+#[derive(Default)]
+enum QueryState {
+    #[default]
+    Queued,
+    Running(InterruptHandle),
+    Finished,
+    Cancelled,
+}
+
+/// Cancels a query if its future is dropped before the query completes, as `tokio_rusqlite` otherwise still runs it
+/// on the connection's thread, holding up every other query of the shared connection. A queued query is skipped, and
+/// a running one is interrupted. The interrupt handle is only set while the query runs, and the query can't finish
+/// while it is being interrupted, so the interrupt never reaches a later statement.
+#[derive(Default)]
+struct CancelOnDrop(Arc<Mutex<QueryState>>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match &*state {
+            QueryState::Queued => *state = QueryState::Cancelled,
+            QueryState::Running(interrupt_handle) => {
+                tracing::debug!("Interrupting cancelled SQLite query");
+                interrupt_handle.interrupt();
+            }
+            QueryState::Finished | QueryState::Cancelled => {}
+        }
+    }
+}
+
+/// Runs a query on the connection's thread, failing with `SQLITE_INTERRUPT` like an interrupted query if it was
+/// cancelled while queued. The text columns of the result are read with `empty_strings`.
+fn query_rows_to_arrow(
+    conn: &rusqlite::Connection,
+    state: &Mutex<QueryState>,
+    sql: &str,
+    params: &[&'static (dyn ToSql + Sync)],
+    empty_strings: EmptyStringHandling,
+) -> tokio_rusqlite::Result<RecordBatch> {
+    {
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if matches!(*state, QueryState::Cancelled) {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT),
+                None,
+            )
+            .into());
+        }
+        *state = QueryState::Running(conn.get_interrupt_handle());
+    }
+
+    let rec = run_query(conn, sql, params, empty_strings);
+    *state.lock().unwrap_or_else(PoisonError::into_inner) = QueryState::Finished;
+    rec
+}
+
+fn run_query(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    params: &[&'static (dyn ToSql + Sync)],
+    empty_strings: EmptyStringHandling,
+) -> tokio_rusqlite::Result<RecordBatch> {
+    let mut stmt = conn.prepare(sql)?;
+    for (i, param) in params.iter().enumerate() {
+        stmt.raw_bind_parameter(i + 1, param)?;
+    }
+    let column_count = stmt.column_count();
+    let rows = stmt.raw_query();
+    let rec = rows_to_arrow_with_empty_strings(rows, column_count, empty_strings)
+        .context(ConversionSnafu)
+        .map_err(to_tokio_rusqlite_error)?;
+    Ok(rec)
 }
 
 fn to_tokio_rusqlite_error(e: impl Into<Error>) -> tokio_rusqlite::Error {
     tokio_rusqlite::Error::Other(Box::new(e.into()))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use arrow::{array::AsArray, datatypes::Int64Type};
+    use futures::TryStreamExt;
+
+    async fn count_rows(conn: &SqliteConnection) -> i64 {
+        let batches: Vec<_> = conn
+            .query_arrow("SELECT COUNT(*) FROM items", &[])
+            .await
+            .expect("query should succeed")
+            .try_collect()
+            .await
+            .expect("batches should be collected");
+        batches[0].column(0).as_primitive::<Int64Type>().value(0)
+    }
+
+    /// Counts to a billion, which takes far longer than the tests wait for.
+    const SLOW_QUERY: &str = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000000000) SELECT COUNT(*) FROM n";
+
+    #[tokio::test]
+    async fn test_cancelled_query_is_interrupted() {
+        let conn = SqliteConnection::new(
+            Connection::open_in_memory()
+                .await
+                .expect("connection should open"),
+        );
+
+        // The interrupted query fails promptly
+        let cancel_on_drop = CancelOnDrop::default();
+        let state = Arc::clone(&cancel_on_drop.0);
+        let slow_query = tokio::spawn({
+            let conn = conn.conn.clone();
+            async move {
+                conn.call(move |conn| {
+                    query_rows_to_arrow(
+                        conn,
+                        &state,
+                        SLOW_QUERY,
+                        &[],
+                        EmptyStringHandling::Preserve,
+                    )
+                })
+                .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(cancel_on_drop);
+        let err = tokio::time::timeout(Duration::from_secs(5), slow_query)
+            .await
+            .expect("query should be interrupted promptly")
+            .expect("query task should complete")
+            .expect_err("query should fail");
+        assert!(
+            matches!(
+                err,
+                tokio_rusqlite::Error::Rusqlite(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error {
+                        code: rusqlite::ErrorCode::OperationInterrupted,
+                        ..
+                    },
+                    _
+                ))
+            ),
+            "{err}"
+        );
+
+        // Dropping the future of a query interrupts it, so the connection's next query isn't held up
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(100),
+            conn.query_arrow(SLOW_QUERY, &[]),
+        )
+        .await;
+        assert!(cancelled.is_err(), "slow query should time out");
+        let batches: Vec<_> = tokio::time::timeout(Duration::from_secs(5), async {
+            conn.query_arrow("SELECT 1", &[])
+                .await
+                .expect("query should succeed")
+                .try_collect()
+                .await
+                .expect("batches should be collected")
+        })
+        .await
+        .expect("query should run promptly after the cancelled one");
+        assert_eq!(batches[0].column(0).as_primitive::<Int64Type>().value(0), 1);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_transaction_reads_one_snapshot() {
+        let path = std::env::temp_dir().join(format!("read_only_{}.db", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+
+        // Like the users of an `SqliteConnectionPool`, the writer and the reader share a connection
+        let shared = Connection::open(path.clone())
+            .await
+            .expect("connection should open");
+        shared
+            .call(|conn| {
+                conn.execute_batch(
+                    "PRAGMA journal_mode = WAL; CREATE TABLE items (id INTEGER); INSERT INTO items VALUES (1);",
+                )?;
+                Ok(())
+            })
+            .await
+            .expect("table should be created");
+        let writer = SqliteConnection::new(shared.clone());
+        let reader = SqliteConnection::new(shared);
+
+        reader
+            .begin_read_only()
+            .await
+            .expect("transaction should begin");
+        assert_eq!(count_rows(&reader).await, 1);
+
+        // The writer isn't blocked by the transaction, and the reader doesn't see its write
+        writer
+            .execute("INSERT INTO items VALUES (2)", &[])
+            .await
+            .expect("row should be inserted");
+        assert_eq!(count_rows(&writer).await, 2);
+        assert_eq!(count_rows(&reader).await, 1);
+
+        assert!(reader
+            .execute("INSERT INTO items VALUES (3)", &[])
+            .await
+            .is_err());
+
+        reader.commit().await.expect("transaction should commit");
+        assert_eq!(count_rows(&reader).await, 2);
+
+        drop(writer);
+        drop(reader);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{path}{suffix}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_transaction_requires_a_file() {
+        let conn = SqliteConnection::new(
+            Connection::open_in_memory()
+                .await
+                .expect("connection should open"),
+        );
+        let err = conn
+            .begin_read_only()
+            .await
+            .expect_err("transaction should be rejected");
+        assert!(matches!(
+            err.downcast_ref::<crate::dbconnection::Error>(),
+            Some(crate::dbconnection::Error::ReadOnlyTransactionNotSupported {})
+        ));
+    }
+}
@@ -22,7 +22,7 @@ use snafu::{prelude::*, ResultExt};
 
 use super::{DbConnectionPool, Result};
 use crate::{
-    dbconnection::{duckdbconn::DuckDbConnection, DbConnection, SyncDbConnection},
+    dbconnection::{duckdbconn::DuckDbConnection, DbConnection},
     JoinPushDown,
 };
 
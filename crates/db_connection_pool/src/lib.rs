@@ -17,11 +17,15 @@ limitations under the License.
 use crate::dbconnection::DbConnection;
 use async_trait::async_trait;
 
+#[cfg(feature = "circuitbreaker")]
+pub mod circuitbreakerpool;
 #[cfg(feature = "clickhouse")]
 pub mod clickhousepool;
 pub mod dbconnection;
 #[cfg(feature = "duckdb")]
 pub mod duckdbpool;
+#[cfg(feature = "mock")]
+pub mod mockpool;
 #[cfg(feature = "mysql")]
 pub mod mysqlpool;
 #[cfg(feature = "odbc")]
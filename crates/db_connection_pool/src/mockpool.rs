@@ -0,0 +1,147 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use snafu::prelude::*;
+
+use super::{DbConnectionPool, JoinPushDown, Result};
+use crate::dbconnection::{mockconn::MockDbConnection, DbConnection};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{message}"))]
+    ConnectionPoolError { message: String },
+}
+
+/// A `DbConnectionPool` that hands out clones of a single `MockDbConnection`.
+///
+/// Connection failures can be queued with `with_connect_error` and are returned before any connection is handed out.
+pub struct MockPool<P = ()> {
+    conn: MockDbConnection<P>,
+    connect_errors: Mutex<VecDeque<String>>,
+    join_push_down: JoinPushDown,
+}
+
+impl<P> MockPool<P> {
+    #[must_use]
+    pub fn new(conn: MockDbConnection<P>) -> Self {
+        Self {
+            conn,
+            connect_errors: Mutex::new(VecDeque::new()),
+            join_push_down: JoinPushDown::Disallow,
+        }
+    }
+
+    #[must_use]
+    pub fn with_connect_error(self, message: &str) -> Self {
+        if let Ok(mut connect_errors) = self.connect_errors.lock() {
+            connect_errors.push_back(message.to_string());
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn with_join_push_down(mut self, join_push_down: JoinPushDown) -> Self {
+        self.join_push_down = join_push_down;
+        self
+    }
+
+    /// Returns the connection shared by this pool, i.e. to inspect the statements it received.
+    #[must_use]
+    pub fn connection(&self) -> &MockDbConnection<P> {
+        &self.conn
+    }
+}
+
+#[async_trait]
+impl<P: Send + Sync + 'static> DbConnectionPool<(), P> for MockPool<P> {
+    async fn connect(&self) -> Result<Box<dyn DbConnection<(), P>>> {
+        let connect_error = self
+            .connect_errors
+            .lock()
+            .ok()
+            .and_then(|mut connect_errors| connect_errors.pop_front());
+        if let Some(message) = connect_error {
+            ConnectionPoolSnafu { message }.fail()?;
+        }
+
+        Ok(Box::new(self.conn.clone()))
+    }
+
+    fn join_push_down(&self) -> JoinPushDown {
+        self.join_push_down.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use futures::TryStreamExt;
+    use util::fibonacci_backoff::FibonacciBackoffBuilder;
+    use util::{retry, RetryError};
+
+    use super::*;
+
+    // The clock is paused so the backoff intervals elapse instantly
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_failure_then_success() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .expect("valid record batch");
+
+        let pool: MockPool = MockPool::new(
+            MockDbConnection::default()
+                .with_query_error("connection reset")
+                .with_query_result(Arc::clone(&schema), vec![batch.clone()]),
+        )
+        .with_connect_error("connection refused");
+
+        let retry_strategy = FibonacciBackoffBuilder::new().max_retries(Some(3)).build();
+
+        let batches: Vec<RecordBatch> = retry(retry_strategy, || async {
+            let conn = pool.connect().await.map_err(RetryError::transient)?;
+            let Some(conn) = conn.as_async() else {
+                return Err(RetryError::permanent("mock connection is async".into()));
+            };
+            let stream = conn
+                .query_arrow("SELECT * FROM test", &[])
+                .await
+                .map_err(RetryError::transient)?;
+            stream
+                .try_collect::<Vec<RecordBatch>>()
+                .await
+                .map_err(|e| RetryError::permanent(e.into()))
+        })
+        .await
+        .expect("query succeeds after retrying");
+
+        assert_eq!(batches, vec![batch]);
+        assert_eq!(
+            pool.connection().statements(),
+            vec!["SELECT * FROM test", "SELECT * FROM test"]
+        );
+    }
+}
@@ -25,7 +25,7 @@ use secrecy::{ExposeSecret, Secret, SecretString};
 use snafu::{ResultExt, Snafu};
 
 use crate::{
-    dbconnection::{mysqlconn::MySQLConnection, AsyncDbConnection, DbConnection},
+    dbconnection::{mysqlconn::MySQLConnection, DbConnection},
     JoinPushDown,
 };
 
@@ -20,60 +20,409 @@ use async_trait::async_trait;
 use odbc_api::{sys::AttrConnectionPooling, Connection, ConnectionOptions, Environment};
 use secrecy::{ExposeSecret, Secret, SecretString};
 use snafu::prelude::*;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
 
 use super::{DbConnectionPool, JoinPushDown, Result};
-use lazy_static::lazy_static;
-
-lazy_static! {
-  static ref ENV: Environment = unsafe {
-      // Enable connection pooling. Let driver decide wether the attributes of two connection
-      // are similar enough to change the attributes of a pooled one, to fit the requested
-      // connection, or if it is cheaper to create a new Connection from scratch.
-      // See <https://docs.microsoft.com/en-us/sql/odbc/reference/develop-app/driver-aware-connection-pooling>
-      if let Err(e) = Environment::set_connection_pooling(AttrConnectionPooling::DriverAware) {
-          tracing::error!("Failed to set ODBC connection pooling: {e}");
-      };
-      match Environment::new() {
-          Ok(env) => env,
-          Err(e) => {
-            panic!("Failed to create ODBC environment: {e}");
-          }
-      }
-  };
+
+/// The process wide ODBC environment, with the connection pooling mode it was created with.
+static ENV: OnceLock<(Environment, ConnectionPooling)> = OnceLock::new();
+
+/// How the ODBC driver manager pools connections, set with the `odbc_connection_pooling` parameter.
+///
+/// Connection pooling is an attribute of the driver manager for the whole process, which can only be set before
+/// the ODBC environment is created. All pools share that environment, so the first pool that is created decides
+/// the mode, and a pool asking for a different mode afterwards is rejected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionPooling {
+    /// Connections are opened from scratch, and closed when they are dropped.
+    Off,
+    /// Let the driver decide whether the attributes of two connections are similar enough to change the attributes of
+    /// a pooled one, to fit the requested connection, or if it is cheaper to create a new Connection from scratch.
+    /// See <https://docs.microsoft.com/en-us/sql/odbc/reference/develop-app/driver-aware-connection-pooling>
+    #[default]
+    DriverAware,
+    /// A single pool of connections for each driver.
+    OnePerDriver,
+}
+
+impl ConnectionPooling {
+    fn attribute(self) -> AttrConnectionPooling {
+        match self {
+            ConnectionPooling::Off => AttrConnectionPooling::Off,
+            ConnectionPooling::DriverAware => AttrConnectionPooling::DriverAware,
+            ConnectionPooling::OnePerDriver => AttrConnectionPooling::OnePerDriver,
+        }
+    }
+}
+
+impl Display for ConnectionPooling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionPooling::Off => write!(f, "off"),
+            ConnectionPooling::DriverAware => write!(f, "driver_aware"),
+            ConnectionPooling::OnePerDriver => write!(f, "one_per_driver"),
+        }
+    }
+}
+
+impl FromStr for ConnectionPooling {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(ConnectionPooling::Off),
+            "driver_aware" => Ok(ConnectionPooling::DriverAware),
+            "one_per_driver" => Ok(ConnectionPooling::OnePerDriver),
+            _ => InvalidConnectionPoolingSnafu { value }.fail(),
+        }
+    }
+}
+
+/// The ODBC environment, which is created with `pooling`, or the default mode, if it doesn't exist yet.
+fn environment(
+    pooling: Option<ConnectionPooling>,
+) -> Result<&'static (Environment, ConnectionPooling), Error> {
+    let env = ENV.get_or_init(|| {
+        let pooling = pooling.unwrap_or_default();
+        // Safety: the pooling mode is set before the only ODBC environment is created
+        if let Err(e) = unsafe { Environment::set_connection_pooling(pooling.attribute()) } {
+            tracing::error!("Failed to set ODBC connection pooling: {e}");
+        }
+        match Environment::new() {
+            Ok(env) => (env, pooling),
+            Err(e) => {
+                panic!("Failed to create ODBC environment: {e}");
+            }
+        }
+    });
+
+    match pooling {
+        Some(requested) if requested != env.1 => ConflictingConnectionPoolingSnafu {
+            requested,
+            configured: env.1,
+        }
+        .fail(),
+        _ => Ok(env),
+    }
 }
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Missing ODBC connection string parameter: odbc_connection_string"))]
+    #[snafu(display(
+        "Missing ODBC connection parameter: either odbc_connection_string or odbc_dsn is required"
+    ))]
     MissingConnectionString {},
 
+    #[snafu(display(
+        "Conflicting ODBC connection parameters: only one of odbc_connection_string or odbc_dsn can be set"
+    ))]
+    ConflictingConnectionString {},
+
     #[snafu(display("Invalid parameter: {parameter_name}"))]
     InvalidParameterError { parameter_name: String },
+
+    #[snafu(display(
+        "The ODBC connection string references the parameter {parameter_name}, which isn't set"
+    ))]
+    UnresolvedConnectionStringParameter { parameter_name: String },
+
+    #[snafu(display(
+        "The ODBC connection string has an unterminated `${{` at position {position}"
+    ))]
+    UnterminatedConnectionStringParameter { position: usize },
+
+    #[snafu(display(
+        "Invalid odbc_connection_pooling: {value}. Expected one of off, driver_aware or one_per_driver"
+    ))]
+    InvalidConnectionPooling { value: String },
+
+    #[snafu(display(
+        "ODBC connection pooling can't be {requested}, it is already {configured} for all ODBC connections"
+    ))]
+    ConflictingConnectionPooling {
+        requested: ConnectionPooling,
+        configured: ConnectionPooling,
+    },
+
+    #[snafu(display(
+        "Timed out connecting to the ODBC data source after {seconds} seconds. Verify the odbc_connection_string is correct and the data source is reachable: {message}"
+    ))]
+    LoginTimeout { seconds: u32, message: String },
+
+    #[snafu(display("Unable to connect to the ODBC data source: {message}"))]
+    UnableToConnect { message: String },
+}
+
+/// The keys of connection string attributes whose values are masked by `redact_connection_string`.
+const SECRET_KEYS: &[&str] = &[
+    "pwd",
+    "password",
+    "uid",
+    "user",
+    "user id",
+    "username",
+    "secret",
+    "client_secret",
+    "token",
+    "access_token",
+    "api_key",
+    "apikey",
+    "private_key",
+    "passphrase",
+];
+
+/// Masks the values of the credentials, i.e. `PWD=...` and `UID=...`, in `text`, so a connection string, or an
+/// error message quoting one, can be logged.
+#[must_use]
+pub fn redact_connection_string(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(position) = rest.find('=') {
+        let (attribute, value) = (&rest[..position], &rest[position + 1..]);
+        redacted.push_str(attribute);
+        redacted.push('=');
+        // The key is the part of the attribute after the previous attribute's `;`
+        let key = attribute.rsplit(';').next().unwrap_or_default();
+        if is_secret_key(key) {
+            redacted.push_str("***");
+            rest = &value[attribute_value_len(value)..];
+        } else {
+            rest = value;
+        }
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.trim().to_lowercase();
+    SECRET_KEYS
+        .iter()
+        .any(|secret| key == *secret || key.ends_with(&format!(" {secret}")))
+}
+
+/// The length of the attribute value at the start of `value`, which is either enclosed in braces, with `}}` escaping
+/// a brace, or runs until the next `;`.
+fn attribute_value_len(value: &str) -> usize {
+    if value.starts_with('{') {
+        let mut chars = value.char_indices().skip(1).peekable();
+        while let Some((index, c)) = chars.next() {
+            if c == '}' {
+                if chars.peek().is_some_and(|(_, next)| *next == '}') {
+                    chars.next();
+                } else {
+                    return index + 1;
+                }
+            }
+        }
+        value.len()
+    } else {
+        value.find([';', '\n']).unwrap_or(value.len())
+    }
+}
+
+/// A part of an ODBC connection string template, see `ODBCPool::new`.
+enum Segment<'a> {
+    Text(&'a str),
+    Parameter(&'a str),
+}
+
+fn parse_connection_string(connection_string: &str) -> Result<Vec<Segment<'_>>, Error> {
+    let mut segments = Vec::new();
+    let mut rest = connection_string;
+    while let Some(start) = rest.find("${") {
+        segments.push(Segment::Text(&rest[..start]));
+        let position = connection_string.len() - rest.len() + start;
+        rest = &rest[start + 2..];
+        let end = rest
+            .find('}')
+            .context(UnterminatedConnectionStringParameterSnafu { position })?;
+        segments.push(Segment::Parameter(&rest[..end]));
+        rest = &rest[end + 1..];
+    }
+    segments.push(Segment::Text(rest));
+    Ok(segments)
+}
+
+/// The names of the parameters referenced by the `${name}` placeholders of `connection_string`.
+///
+/// # Errors
+///
+/// Returns an error if a placeholder isn't terminated.
+pub fn connection_string_parameters(connection_string: &str) -> Result<Vec<&str>, Error> {
+    Ok(parse_connection_string(connection_string)?
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Parameter(name) => Some(name),
+            Segment::Text(_) => None,
+        })
+        .collect())
+}
+
+/// Replaces the `${name}` placeholders of `connection_string` with the values of the `name` parameters.
+///
+/// The values are escaped like any attribute value, see `attribute_value`, so a `;` in a secret can't end its
+/// attribute. A placeholder the template already encloses in braces, i.e. `PWD={${password}}`, only has its `}`
+/// escaped.
+fn expand_connection_string(
+    connection_string: &str,
+    params: &HashMap<String, SecretString>,
+) -> Result<SecretString, Error> {
+    let mut expanded = String::with_capacity(connection_string.len());
+    for segment in parse_connection_string(connection_string)? {
+        match segment {
+            Segment::Text(text) => expanded.push_str(text),
+            Segment::Parameter(parameter_name) => {
+                let value = params
+                    .get(parameter_name)
+                    .context(UnresolvedConnectionStringParameterSnafu { parameter_name })?;
+                if expanded.ends_with('{') {
+                    expanded.push_str(&value.expose_secret().replace('}', "}}"));
+                } else {
+                    expanded.push_str(&attribute_value(value.expose_secret()));
+                }
+            }
+        }
+    }
+    Ok(SecretString::new(expanded))
+}
+
+/// Where the connection string of an `ODBCPool` comes from, see `ODBCPool::new`.
+enum ConnectionString {
+    /// An `odbc_connection_string`, whose placeholders are expanded on connecting.
+    Template(SecretString),
+    /// A connection string assembled from the `odbc_dsn`, `odbc_username` and `odbc_password` parameters.
+    Dsn(SecretString),
+}
+
+impl ConnectionString {
+    fn from_params(params: &HashMap<String, SecretString>) -> Result<Self, Error> {
+        match (params.get("odbc_connection_string"), params.get("odbc_dsn")) {
+            (Some(template), None) => {
+                expand_connection_string(template.expose_secret(), params)?;
+                Ok(ConnectionString::Template(template.clone()))
+            }
+            (None, Some(dsn)) => Ok(ConnectionString::Dsn(dsn_connection_string(
+                dsn.expose_secret(),
+                params.get("odbc_username").map(Secret::expose_secret),
+                params.get("odbc_password").map(Secret::expose_secret),
+            ))),
+            (Some(_), Some(_)) => ConflictingConnectionStringSnafu.fail(),
+            (None, None) => MissingConnectionStringSnafu.fail(),
+        }
+    }
+
+    /// The connection string to connect with.
+    fn resolve(&self, params: &HashMap<String, SecretString>) -> Result<SecretString, Error> {
+        match self {
+            ConnectionString::Template(template) => {
+                expand_connection_string(template.expose_secret(), params)
+            }
+            ConnectionString::Dsn(connection_string) => Ok(connection_string.clone()),
+        }
+    }
+}
+
+/// Assembles the connection string of the data source named `dsn` in `odbc.ini`, i.e. `DSN=sales;UID=spice;PWD=...`.
+fn dsn_connection_string(
+    dsn: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> SecretString {
+    let mut attributes = vec![format!("DSN={}", attribute_value(dsn))];
+    if let Some(username) = username {
+        attributes.push(format!("UID={}", attribute_value(username)));
+    }
+    if let Some(password) = password {
+        attributes.push(format!("PWD={}", attribute_value(password)));
+    }
+    SecretString::new(attributes.join(";"))
+}
+
+/// Encloses `value` in braces, with `}` escaped as `}}`, if it can't be a plain connection string attribute value.
+fn attribute_value(value: &str) -> Cow<'_, str> {
+    if value.contains([';', '{', '}', '=']) || value.trim() != value {
+        Cow::Owned(format!("{{{}}}", value.replace('}', "}}")))
+    } else {
+        Cow::Borrowed(value)
+    }
 }
 
 pub struct ODBCPool {
     pool: &'static Environment,
+    connection_pooling: ConnectionPooling,
     params: Arc<HashMap<String, SecretString>>,
-    connection_string: String,
+    connection_string: ConnectionString,
+    join_push_down: JoinPushDown,
+    login_timeout: Option<u32>,
 }
 
 impl ODBCPool {
     // Creates a new instance of `ODBCPool`.
     ///
+    /// The `odbc_connection_string` can be a template whose `${name}` placeholders are replaced with the `name`
+    /// parameter on connecting, i.e. `Driver={PostgreSQL};UID=spice;PWD=${password}` with the `password` secret,
+    /// so secrets don't have to be written into the connection string.
+    ///
+    /// Instead of a connection string, a data source configured in `odbc.ini` can be connected to with the `odbc_dsn`
+    /// parameter, and the optional `odbc_username` and `odbc_password` parameters.
+    ///
+    /// The `odbc_connection_pooling` parameter sets the `ConnectionPooling` of the driver manager to `off`,
+    /// `driver_aware` (the default) or `one_per_driver`. It applies to the whole process, see `ConnectionPooling`.
+    ///
+    /// Joins are only pushed down to the database if an `odbc_join_context` parameter is set, which has to identify
+    /// the server the connection string connects to, i.e. `server=db.example.com,database=sales`. Tables of pools
+    /// with the same join context can be joined in a single query.
+    ///
+    /// The `odbc_login_timeout` parameter is the number of seconds to wait for a connection to be established,
+    /// instead of waiting indefinitely for a data source that doesn't respond.
+    ///
     /// # Errors
     ///
-    /// Returns an error if there is a problem creating the connection pool.
+    /// Returns an error if there is a problem creating the connection pool, if not exactly one of
+    /// `odbc_connection_string` or `odbc_dsn` is set, if a placeholder of the connection string doesn't resolve to a
+    /// parameter, if the connection pooling conflicts with the one already in use, or if
+    /// the login timeout isn't a number of seconds.
     pub fn new(params: Arc<HashMap<String, SecretString>>) -> Result<Self> {
-        let connection_string = params
-            .get("odbc_connection_string")
+        let connection_string = ConnectionString::from_params(&params)?;
+        let connection_pooling = params
+            .get("odbc_connection_pooling")
+            .map(|pooling| pooling.expose_secret().parse::<ConnectionPooling>())
+            .transpose()?;
+        let (pool, connection_pooling) = environment(connection_pooling)?;
+        let join_push_down = params
+            .get("odbc_join_context")
+            .map(Secret::expose_secret)
+            .filter(|context| !context.is_empty())
+            .map_or(JoinPushDown::Disallow, |context| {
+                JoinPushDown::AllowedFor(context.to_string())
+            });
+        let login_timeout = params
+            .get("odbc_login_timeout")
             .map(Secret::expose_secret)
-            .map(ToString::to_string)
-            .context(MissingConnectionStringSnafu)?;
+            .map(|seconds| {
+                seconds
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .context(InvalidParameterSnafu {
+                        parameter_name: "odbc_login_timeout",
+                    })
+            })
+            .transpose()?;
         Ok(Self {
             params,
             connection_string,
-            pool: &ENV,
+            pool,
+            connection_pooling: *connection_pooling,
+            join_push_down,
+            login_timeout,
         })
     }
 
@@ -81,6 +430,20 @@ impl ODBCPool {
     pub fn odbc_environment(&self) -> &'static Environment {
         self.pool
     }
+
+    #[must_use]
+    pub fn connection_pooling(&self) -> ConnectionPooling {
+        self.connection_pooling
+    }
+
+    /// The options new connections are opened with.
+    #[must_use]
+    pub fn connection_options(&self) -> ConnectionOptions {
+        ConnectionOptions {
+            login_timeout_sec: self.login_timeout,
+            ..ConnectionOptions::default()
+        }
+    }
 }
 
 #[async_trait]
@@ -89,10 +452,14 @@ where
     'a: 'static,
 {
     async fn connect(&self) -> Result<Box<ODBCDbConnection<'a>>> {
-        let cxn = self.pool.connect_with_connection_string(
-            &self.connection_string,
-            ConnectionOptions::default(),
-        )?;
+        let connection_string = self.connection_string.resolve(&self.params)?;
+        let cxn = self
+            .pool
+            .connect_with_connection_string(
+                connection_string.expose_secret(),
+                self.connection_options(),
+            )
+            .map_err(|source| connect_error(source, self.login_timeout))?;
 
         let odbc_cxn = ODBCConnection {
             conn: Arc::new(cxn.into()),
@@ -105,7 +472,232 @@ where
     fn join_push_down(&self) -> JoinPushDown {
         // It would be technically feasible to return JoinPushDown::AllowedFor(connection_string) here,
         // but we don't have a general way to strip out sensitive information from the connection string.
-        // We could solve this by asking the user to explicly provide a join context in the parameters.
-        JoinPushDown::Disallow
+        // Instead, joins are only pushed down for the join context the user explicitly provides.
+        self.join_push_down.clone()
+    }
+}
+
+/// Reports a connection that timed out as a `LoginTimeout`, which the ODBC driver signals with a `HYT00` (timeout
+/// expired) or `HYT01` (connection timeout expired) state.
+///
+/// Drivers may quote the connection string in their messages, so the credentials are redacted from the message, and
+/// the driver's error isn't kept as the source.
+fn connect_error(source: odbc_api::Error, login_timeout: Option<u32>) -> super::Error {
+    let message = redact_connection_string(&source.to_string());
+    match (&source, login_timeout) {
+        (odbc_api::Error::Diagnostics { record, .. }, Some(seconds))
+            if matches!(&record.state.0, b"HYT00" | b"HYT01") =>
+        {
+            Box::new(Error::LoginTimeout { seconds, message })
+        }
+        _ => Box::new(Error::UnableToConnect { message }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(values: &[(&str, &str)]) -> HashMap<String, SecretString> {
+        values
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), SecretString::new((*value).to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_connection_string() {
+        let template = "Driver={PostgreSQL};UID=${user};PWD=${password};";
+        let params = params(&[("user", "spice"), ("password", "p@ss;word}")]);
+
+        assert_eq!(
+            connection_string_parameters(template).expect("template is parsed"),
+            vec!["user", "password"]
+        );
+        assert_eq!(
+            expand_connection_string(template, &params)
+                .expect("template is expanded")
+                .expose_secret(),
+            "Driver={PostgreSQL};UID=spice;PWD={p@ss;word}}};"
+        );
+        assert_eq!(
+            expand_connection_string("UID=${user};PWD={${password}}", &params)
+                .expect("braced template is expanded")
+                .expose_secret(),
+            "UID=spice;PWD={p@ss;word}}}"
+        );
+        assert_eq!(
+            expand_connection_string("Driver={SQLite3};Database=spice.db", &params)
+                .expect("connection string without placeholders is unchanged")
+                .expose_secret(),
+            "Driver={SQLite3};Database=spice.db"
+        );
+    }
+
+    #[test]
+    fn test_invalid_connection_string_template() {
+        let params = params(&[("user", "spice")]);
+
+        assert!(matches!(
+            expand_connection_string("UID=${user};PWD=${password}", &params),
+            Err(Error::UnresolvedConnectionStringParameter { parameter_name }) if parameter_name == "password"
+        ));
+        assert!(matches!(
+            expand_connection_string("UID=${user};PWD=${password", &params),
+            Err(Error::UnterminatedConnectionStringParameter { position: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_parse_connection_pooling() {
+        assert_eq!(
+            "off".parse::<ConnectionPooling>().expect("off is parsed"),
+            ConnectionPooling::Off
+        );
+        assert_eq!(
+            "Driver_Aware"
+                .parse::<ConnectionPooling>()
+                .expect("driver_aware is parsed"),
+            ConnectionPooling::DriverAware
+        );
+        assert_eq!(
+            "one_per_driver"
+                .parse::<ConnectionPooling>()
+                .expect("one_per_driver is parsed"),
+            ConnectionPooling::OnePerDriver
+        );
+        assert!(matches!(
+            "one_per_env".parse::<ConnectionPooling>(),
+            Err(Error::InvalidConnectionPooling { value }) if value == "one_per_env"
+        ));
+    }
+
+    #[test]
+    fn test_join_push_down_requires_join_context() {
+        let pool = ODBCPool::new(Arc::new(params(&[(
+            "odbc_connection_string",
+            "Driver={SQLite3};Database=:memory:",
+        )])))
+        .expect("Must create ODBC pool");
+        assert!(matches!(pool.join_push_down(), JoinPushDown::Disallow));
+
+        let pool = ODBCPool::new(Arc::new(params(&[
+            (
+                "odbc_connection_string",
+                "Driver={SQLite3};Database=:memory:",
+            ),
+            ("odbc_join_context", "server=db.example.com,database=sales"),
+        ])))
+        .expect("Must create ODBC pool");
+        assert!(matches!(
+            pool.join_push_down(),
+            JoinPushDown::AllowedFor(context) if context == "server=db.example.com,database=sales"
+        ));
+    }
+
+    #[test]
+    fn test_login_timeout() {
+        let connection_string = (
+            "odbc_connection_string",
+            "Driver={SQLite3};Database=:memory:",
+        );
+
+        let pool =
+            ODBCPool::new(Arc::new(params(&[connection_string]))).expect("Must create ODBC pool");
+        assert_eq!(pool.connection_options().login_timeout_sec, None);
+
+        let pool = ODBCPool::new(Arc::new(params(&[
+            connection_string,
+            ("odbc_login_timeout", "5"),
+        ])))
+        .expect("Must create ODBC pool");
+        assert_eq!(pool.connection_options().login_timeout_sec, Some(5));
+
+        let result = ODBCPool::new(Arc::new(params(&[
+            connection_string,
+            ("odbc_login_timeout", "5s"),
+        ])));
+        assert!(result.is_err_and(|e| e.to_string() == "Invalid parameter: odbc_login_timeout"));
+    }
+
+    #[test]
+    fn test_redact_connection_string() {
+        assert_eq!(
+            redact_connection_string("Driver={PostgreSQL};Server=localhost;UID=spice;PWD=hunter2;"),
+            "Driver={PostgreSQL};Server=localhost;UID=***;PWD=***;"
+        );
+        assert_eq!(
+            redact_connection_string("Driver={SQL Server};User ID=spice;Password={hunter2;}}x}"),
+            "Driver={SQL Server};User ID=***;Password=***"
+        );
+        assert_eq!(
+            redact_connection_string("Failed to connect with pwd=hunter2\nCheck the data source"),
+            "Failed to connect with pwd=***\nCheck the data source"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_error_is_redacted() {
+        let pool = ODBCPool::new(Arc::new(params(&[(
+            "odbc_connection_string",
+            "Driver={spice_missing_driver};UID=spice;PWD=hunter2",
+        )])))
+        .expect("Must create ODBC pool");
+
+        let Err(error) = pool.connect().await else {
+            panic!("Connecting with a missing driver must fail");
+        };
+        assert!(error
+            .to_string()
+            .starts_with("Unable to connect to the ODBC data source"));
+        assert!(!error.to_string().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_dsn_connection_string() {
+        assert_eq!(
+            dsn_connection_string("sales", None, None).expose_secret(),
+            "DSN=sales"
+        );
+        assert_eq!(
+            dsn_connection_string("sales", Some("spice"), Some("hunter2")).expose_secret(),
+            "DSN=sales;UID=spice;PWD=hunter2"
+        );
+        assert_eq!(
+            dsn_connection_string("sales db", Some("spice"), Some("p;ss}${word} ")).expose_secret(),
+            "DSN=sales db;UID=spice;PWD={p;ss}}${word}} }"
+        );
+    }
+
+    #[test]
+    fn test_dsn_parameters() {
+        let pool = ODBCPool::new(Arc::new(params(&[
+            ("odbc_dsn", "sales"),
+            ("odbc_username", "spice"),
+            ("odbc_password", "${password}"),
+        ])))
+        .expect("Must create ODBC pool from a DSN");
+        assert_eq!(
+            pool.connection_string
+                .resolve(&pool.params)
+                .expect("DSN connection string is resolved")
+                .expose_secret(),
+            "DSN=sales;UID=spice;PWD=${password}"
+        );
+
+        assert!(matches!(
+            ConnectionString::from_params(&params(&[("odbc_username", "spice")])),
+            Err(Error::MissingConnectionString {})
+        ));
+        assert!(matches!(
+            ConnectionString::from_params(&params(&[
+                (
+                    "odbc_connection_string",
+                    "Driver={SQLite3};Database=:memory:"
+                ),
+                ("odbc_dsn", "sales"),
+            ])),
+            Err(Error::ConflictingConnectionString {})
+        ));
     }
 }
@@ -16,22 +16,26 @@ limitations under the License.
 
 use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
 
+use arrow_sql_gen::EmptyStringHandling;
 use async_trait::async_trait;
 use bb8::ErrorSink;
-use bb8_postgres::{
-    tokio_postgres::{config::Host, types::ToSql, Config},
-    PostgresConnectionManager,
-};
+use bb8_postgres::tokio_postgres::{config::Host, types::ToSql, Config};
+use futures::StreamExt;
 use native_tls::{Certificate, TlsConnector};
 use ns_lookup::verify_ns_lookup_and_tcp_connect;
 use postgres_native_tls::MakeTlsConnector;
 use secrecy::{ExposeSecret, Secret, SecretString};
 use snafu::{prelude::*, ResultExt};
-use tokio_postgres;
+use tokio::sync::broadcast;
+use tokio_postgres::{
+    error::{DbError, Severity},
+    tls::{MakeTlsConnect, TlsConnect},
+    AsyncMessage, Client, Socket,
+};
 
 use super::DbConnectionPool;
 use crate::{
-    dbconnection::{postgresconn::PostgresConnection, AsyncDbConnection, DbConnection},
+    dbconnection::{postgresconn::PostgresConnection, DbConnection},
     JoinPushDown,
 };
 
@@ -80,18 +84,117 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// How many notices are kept for a subscriber that hasn't received them yet. Older notices are dropped.
+const NOTICE_CHANNEL_CAPACITY: usize = 128;
+
+/// A `bb8` connection manager for Postgres, like `bb8_postgres::PostgresConnectionManager`, that doesn't discard the
+/// notices the server sends its connections, i.e. the `NOTICE` and `WARNING` messages of `RAISE` in functions and
+/// triggers. Notices are logged as tracing events and published to the subscribers of the manager.
+pub struct PostgresConnectionManager<Tls> {
+    config: Config,
+    tls: Tls,
+    notices: broadcast::Sender<DbError>,
+}
+
+impl<Tls> PostgresConnectionManager<Tls> {
+    #[must_use]
+    pub fn new(config: Config, tls: Tls) -> Self {
+        let (notices, _) = broadcast::channel(NOTICE_CHANNEL_CAPACITY);
+        Self {
+            config,
+            tls,
+            notices,
+        }
+    }
+
+    /// Subscribes to the notices received by the connections of the manager from now on.
+    #[must_use]
+    pub fn subscribe_notices(&self) -> broadcast::Receiver<DbError> {
+        self.notices.subscribe()
+    }
+}
+
+#[async_trait]
+impl<Tls> bb8::ManageConnection for PostgresConnectionManager<Tls>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    <Tls as MakeTlsConnect<Socket>>::Stream: Send + Sync,
+    <Tls as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <<Tls as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    type Connection = Client;
+    type Error = tokio_postgres::Error;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let (client, mut connection) = self.config.connect(self.tls.clone()).await?;
+        let notices = self.notices.clone();
+        tokio::spawn(async move {
+            let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+            while let Some(message) = messages.next().await {
+                match message {
+                    Ok(AsyncMessage::Notice(notice)) => publish_notice(&notices, notice),
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::debug!("Postgres connection closed: {e}");
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(client)
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        conn.simple_query("").await.map(|_| ())
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.is_closed()
+    }
+}
+
+fn publish_notice(notices: &broadcast::Sender<DbError>, notice: DbError) {
+    match notice.parsed_severity() {
+        Some(Severity::Warning) => {
+            tracing::warn!("Postgres {}: {}", notice.severity(), notice.message());
+        }
+        Some(Severity::Debug | Severity::Log) => {
+            tracing::debug!("Postgres {}: {}", notice.severity(), notice.message());
+        }
+        _ => tracing::info!("Postgres {}: {}", notice.severity(), notice.message()),
+    }
+    // Sending only fails when there are no subscribers, in which case the notice is just logged
+    let _ = notices.send(notice);
+}
+
 pub struct PostgresConnectionPool {
     pool: Arc<bb8::Pool<PostgresConnectionManager<MakeTlsConnector>>>,
     join_push_down: JoinPushDown,
+    server_version_num: Option<u32>,
+    empty_strings: EmptyStringHandling,
+    notices: broadcast::Sender<DbError>,
 }
 
 impl PostgresConnectionPool {
     /// Creates a new instance of `PostgresConnectionPool`.
     ///
+    /// The empty strings and NULLs of text columns in query results are read as the `pg_empty_string_handling`
+    /// parameter says: `preserve`, the default, `empty_as_null` or `null_as_empty`.
+    ///
     /// # Errors
     ///
     /// Returns an error if there is a problem creating the connection pool.
     pub async fn new(params: Arc<HashMap<String, SecretString>>) -> Result<Self> {
+        let empty_strings = match params
+            .get("pg_empty_string_handling")
+            .map(Secret::expose_secret)
+        {
+            Some(value) => EmptyStringHandling::parse(value).context(InvalidParameterSnafu {
+                parameter_name: "pg_empty_string_handling".to_string(),
+            })?,
+            None => EmptyStringHandling::default(),
+        };
+
         let mut connection_string = String::new();
         let mut ssl_mode = "verify-full".to_string();
         let mut ssl_rootcert_path: Option<PathBuf> = None;
@@ -177,6 +280,7 @@ impl PostgresConnectionPool {
         let join_push_down = get_join_context(&config);
 
         let manager = PostgresConnectionManager::new(config, connector);
+        let notices = manager.notices.clone();
         let error_sink = PostgresErrorSink::new();
 
         let pool = bb8::Pool::builder()
@@ -185,18 +289,40 @@ impl PostgresConnectionPool {
             .await
             .context(ConnectionPoolSnafu)?;
 
-        // Test the connection
+        // Test the connection, capturing the server version while at it
         let conn = pool.get().await.context(ConnectionPoolRunSnafu)?;
-        conn.execute("SELECT 1", &[])
+        let version: String = conn
+            .query_one("SHOW server_version_num", &[])
             .await
-            .context(ConnectionPoolSnafu)?;
+            .context(ConnectionPoolSnafu)?
+            .get(0);
+        let server_version_num = version.parse::<u32>().ok();
+        if server_version_num.is_none() {
+            tracing::debug!("Unable to parse the Postgres server_version_num {version}");
+        }
 
         Ok(PostgresConnectionPool {
             pool: Arc::new(pool.clone()),
             join_push_down,
+            server_version_num,
+            empty_strings,
+            notices,
         })
     }
 
+    /// Subscribes to the notices, i.e. the `NOTICE` and `WARNING` messages raised by functions and triggers, that the
+    /// connections of the pool receive from now on.
+    #[must_use]
+    pub fn subscribe_notices(&self) -> broadcast::Receiver<DbError> {
+        self.notices.subscribe()
+    }
+
+    /// The `server_version_num` of the server, i.e. `130004` for 13.4, captured when the pool was created.
+    #[must_use]
+    pub fn server_version_num(&self) -> Option<u32> {
+        self.server_version_num
+    }
+
     /// Returns a direct connection to the underlying database.
     ///
     /// # Errors
@@ -205,7 +331,9 @@ impl PostgresConnectionPool {
     pub async fn connect_direct(&self) -> super::Result<PostgresConnection> {
         let pool = Arc::clone(&self.pool);
         let conn = pool.get_owned().await.context(ConnectionPoolRunSnafu)?;
-        Ok(PostgresConnection::new(conn))
+        Ok(PostgresConnection::new(conn)
+            .with_server_version_num(self.server_version_num)
+            .with_empty_strings(self.empty_strings))
     }
 }
 
@@ -362,7 +490,11 @@ impl
     > {
         let pool = Arc::clone(&self.pool);
         let conn = pool.get_owned().await.context(ConnectionPoolRunSnafu)?;
-        Ok(Box::new(PostgresConnection::new(conn)))
+        Ok(Box::new(
+            PostgresConnection::new(conn)
+                .with_server_version_num(self.server_version_num)
+                .with_empty_strings(self.empty_strings),
+        ))
     }
 
     fn join_push_down(&self) -> JoinPushDown {
@@ -19,7 +19,7 @@ use pkcs8::{LineEnding, SecretDocument};
 use secrecy::{ExposeSecret, Secret, SecretString};
 use snafu::prelude::*;
 use snowflake_api::{SnowflakeApi, SnowflakeApiError};
-use std::{collections::HashMap, fs, sync::Arc};
+use std::{any::Any, collections::HashMap, fs, sync::Arc, time::Duration};
 
 use super::{DbConnectionPool, Result};
 
@@ -71,6 +71,10 @@ pub enum Error {
 pub struct SnowflakeConnectionPool {
     pub api: Arc<SnowflakeApi>,
     join_push_down: JoinPushDown,
+    keep_alive_interval: Option<Duration>,
+    cast_batch_size: Option<usize>,
+    cast_concurrency: Option<usize>,
+    preserve_order: bool,
 }
 
 impl SnowflakeConnectionPool {
@@ -101,6 +105,63 @@ impl SnowflakeConnectionPool {
             .map(Secret::expose_secret)
             .map(ToString::to_string);
 
+        let keep_alive_interval = params
+            .get("snowflake_keep_alive_interval")
+            .map(Secret::expose_secret)
+            .map(|interval| {
+                fundu::parse_duration(interval)
+                    .ok()
+                    .filter(|interval| !interval.is_zero())
+                    .context(InvalidParameterValueSnafu {
+                        param_key: "snowflake_keep_alive_interval",
+                        param_value: interval,
+                    })
+            })
+            .transpose()?;
+
+        let cast_batch_size = params
+            .get("snowflake_cast_batch_size")
+            .map(Secret::expose_secret)
+            .map(|rows| {
+                rows.parse::<usize>().ok().filter(|rows| *rows > 0).context(
+                    InvalidParameterValueSnafu {
+                        param_key: "snowflake_cast_batch_size",
+                        param_value: rows,
+                    },
+                )
+            })
+            .transpose()?;
+
+        let cast_concurrency = params
+            .get("snowflake_cast_concurrency")
+            .map(Secret::expose_secret)
+            .map(|tasks| {
+                tasks
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|tasks| *tasks > 0)
+                    .context(InvalidParameterValueSnafu {
+                        param_key: "snowflake_cast_concurrency",
+                        param_value: tasks,
+                    })
+            })
+            .transpose()?;
+
+        let preserve_order = params
+            .get("snowflake_preserve_order")
+            .map(Secret::expose_secret)
+            .map(|preserve_order| {
+                preserve_order
+                    .parse::<bool>()
+                    .ok()
+                    .context(InvalidParameterValueSnafu {
+                        param_key: "snowflake_preserve_order",
+                        param_value: preserve_order,
+                    })
+            })
+            .transpose()?
+            .unwrap_or_default();
+
         let auth_type = params
             .get("snowflake_auth_type")
             .map(Secret::expose_secret)
@@ -154,6 +215,10 @@ impl SnowflakeConnectionPool {
         Ok(Self {
             api: Arc::new(api),
             join_push_down: JoinPushDown::AllowedFor(join_push_context_str),
+            keep_alive_interval,
+            cast_batch_size,
+            cast_concurrency,
+            preserve_order,
         })
     }
 }
@@ -231,13 +296,23 @@ fn init_snowflake_api_with_keypair_auth(
 }
 
 #[async_trait]
-impl DbConnectionPool<Arc<SnowflakeApi>, &'static (dyn Sync)> for SnowflakeConnectionPool {
+impl DbConnectionPool<Arc<SnowflakeApi>, &'static (dyn Any + Sync)> for SnowflakeConnectionPool {
     async fn connect(
         &self,
-    ) -> Result<Box<dyn DbConnection<Arc<SnowflakeApi>, &'static (dyn Sync)>>> {
+    ) -> Result<Box<dyn DbConnection<Arc<SnowflakeApi>, &'static (dyn Any + Sync)>>> {
         let api = Arc::clone(&self.api);
 
-        let conn = SnowflakeConnection { api };
+        let mut conn = match self.keep_alive_interval {
+            Some(interval) => SnowflakeConnection::with_keep_alive(api, interval),
+            None => SnowflakeConnection::new(api),
+        };
+        if let Some(rows) = self.cast_batch_size {
+            conn = conn.with_cast_batch_size(rows);
+        }
+        if let Some(tasks) = self.cast_concurrency {
+            conn = conn.with_cast_concurrency(tasks);
+        }
+        conn = conn.with_preserve_order(self.preserve_order);
 
         Ok(Box::new(conn))
     }
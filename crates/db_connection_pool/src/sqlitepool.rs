@@ -13,13 +13,18 @@ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 See the License for the specific language governing permissions and
 limitations under the License.
 */
+use std::time::Duration;
+
+use arrow_sql_gen::EmptyStringHandling;
 use async_trait::async_trait;
+use rusqlite::OpenFlags;
+use secrecy::{ExposeSecret, SecretString};
 use snafu::{prelude::*, ResultExt};
 use tokio_rusqlite::{Connection, ToSql};
 
 use super::{DbConnectionPool, Result};
 use crate::{
-    dbconnection::{sqliteconn::SqliteConnection, AsyncDbConnection, DbConnection},
+    dbconnection::{sqliteconn::SqliteConnection, DbConnection},
     JoinPushDown, Mode,
 };
 
@@ -30,11 +35,128 @@ pub enum Error {
 
     #[snafu(display("No path provided for SQLite connection"))]
     NoPathError {},
+
+    #[snafu(display(
+        "Invalid SQLite page_size {page_size}: must be a power of two between 512 and 65536"
+    ))]
+    InvalidPageSize { page_size: u32 },
+
+    #[snafu(display("Invalid SQLite cache_size {cache_size}: must not be zero"))]
+    InvalidCacheSize { cache_size: i64 },
+
+    #[snafu(display("Invalid SQLite journal_mode {journal_mode}: must be one of {}", JOURNAL_MODES.join(", ")))]
+    InvalidJournalMode { journal_mode: String },
+
+    #[snafu(display("Invalid SQLite synchronous {synchronous}: must be one of {}", SYNCHRONOUS_MODES.join(", ")))]
+    InvalidSynchronous { synchronous: String },
+
+    #[snafu(display(
+        "The sqlite_encryption_key option requires a build with the sqlcipher feature"
+    ))]
+    EncryptionNotSupported {},
+
+    #[snafu(display(
+        "Unable to decrypt the SQLite database, check its sqlite_encryption_key: {source}"
+    ))]
+    InvalidEncryptionKey { source: tokio_rusqlite::Error },
 }
 
+const JOURNAL_MODES: [&str; 6] = ["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"];
+
+const SYNCHRONOUS_MODES: [&str; 4] = ["OFF", "NORMAL", "FULL", "EXTRA"];
+
+/// How long a statement waits for a lock held by another connection to the database, i.e. the writer of another
+/// table in the same file, before failing with `database is locked`.
+pub const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
 pub struct SqliteConnectionPool {
     conn: Connection,
     join_push_down: JoinPushDown,
+    encryption_key: Option<SecretString>,
+    empty_strings: EmptyStringHandling,
+}
+
+/// The settings a `SqliteConnectionPool` applies to its connection. Unset values keep the `SQLite` defaults.
+#[derive(Clone)]
+pub struct SqliteConnectionPoolOptions {
+    page_size: Option<u32>,
+    cache_size: Option<i64>,
+    journal_mode: Option<String>,
+    synchronous: Option<String>,
+    busy_timeout: Duration,
+    encryption_key: Option<SecretString>,
+    empty_strings: EmptyStringHandling,
+}
+
+impl Default for SqliteConnectionPoolOptions {
+    fn default() -> Self {
+        Self {
+            page_size: None,
+            cache_size: None,
+            journal_mode: None,
+            synchronous: None,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            encryption_key: None,
+            empty_strings: EmptyStringHandling::default(),
+        }
+    }
+}
+
+impl SqliteConnectionPoolOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The page size of a new database, which `SQLite` ignores once the database is non-empty.
+    #[must_use]
+    pub fn with_page_size(mut self, page_size: Option<u32>) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// The `SQLite` cache size: positive values are pages, negative values are KiB.
+    #[must_use]
+    pub fn with_cache_size(mut self, cache_size: Option<i64>) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// The case-insensitive journal mode, i.e. `WAL` to let readers run alongside the writer.
+    #[must_use]
+    pub fn with_journal_mode(mut self, journal_mode: Option<String>) -> Self {
+        self.journal_mode = journal_mode;
+        self
+    }
+
+    /// The case-insensitive `synchronous` setting.
+    #[must_use]
+    pub fn with_synchronous(mut self, synchronous: Option<String>) -> Self {
+        self.synchronous = synchronous;
+        self
+    }
+
+    /// How long a statement that finds the database locked by another connection retries before failing, see
+    /// [`DEFAULT_BUSY_TIMEOUT`]. A zero `busy_timeout` fails immediately.
+    #[must_use]
+    pub fn with_busy_timeout(mut self, busy_timeout: Duration) -> Self {
+        self.busy_timeout = busy_timeout;
+        self
+    }
+
+    /// The key the database is encrypted with by `SQLCipher`, which requires the `sqlcipher` feature.
+    #[must_use]
+    pub fn with_encryption_key(mut self, encryption_key: Option<SecretString>) -> Self {
+        self.encryption_key = encryption_key;
+        self
+    }
+
+    /// How the empty strings and NULLs of text columns in query results are read, see `EmptyStringHandling`.
+    #[must_use]
+    pub fn with_empty_strings(mut self, empty_strings: EmptyStringHandling) -> Self {
+        self.empty_strings = empty_strings;
+        self
+    }
 }
 
 impl SqliteConnectionPool {
@@ -43,11 +165,50 @@ impl SqliteConnectionPool {
     /// NOTE: The `SqliteConnectionPool` currently does no connection pooling, it simply creates a new connection
     /// and clones it on each call to `connect()`.
     ///
+    /// `page_size` is applied before any table is created, as `SQLite` ignores it once the database is non-empty.
+    /// An in-memory database only supports the `MEMORY` and `OFF` journal modes, and keeps `MEMORY` for any other.
+    ///
+    /// With an `encryption_key`, the key is set before anything else reads the database, and a file encrypted with
+    /// another key is rejected.
+    ///
     /// # Errors
     ///
-    /// Returns an error if there is a problem creating the connection pool.
-    #[allow(clippy::needless_pass_by_value)]
-    pub async fn new(path: &str, mode: Mode) -> Result<Self> {
+    /// Returns an error if there is a problem creating the connection pool, if `page_size`, `cache_size`,
+    /// `journal_mode` or `synchronous` are invalid, or if the database can't be decrypted with `encryption_key`.
+    pub async fn new(path: &str, mode: Mode, options: SqliteConnectionPoolOptions) -> Result<Self> {
+        let SqliteConnectionPoolOptions {
+            page_size,
+            cache_size,
+            journal_mode,
+            synchronous,
+            busy_timeout,
+            encryption_key,
+            empty_strings,
+        } = options;
+        if let Some(page_size) = page_size {
+            ensure!(
+                page_size.is_power_of_two() && (512..=65536).contains(&page_size),
+                InvalidPageSizeSnafu { page_size }
+            );
+        }
+        if let Some(cache_size) = cache_size {
+            ensure!(cache_size != 0, InvalidCacheSizeSnafu { cache_size });
+        }
+        let journal_mode = journal_mode.map(|journal_mode| journal_mode.to_uppercase());
+        if let Some(journal_mode) = &journal_mode {
+            ensure!(
+                JOURNAL_MODES.contains(&journal_mode.as_str()),
+                InvalidJournalModeSnafu { journal_mode }
+            );
+        }
+        let synchronous = synchronous.map(|synchronous| synchronous.to_uppercase());
+        if let Some(synchronous) = &synchronous {
+            ensure!(
+                SYNCHRONOUS_MODES.contains(&synchronous.as_str()),
+                InvalidSynchronousSnafu { synchronous }
+            );
+        }
+
         let (conn, join_push_down) = match mode {
             Mode::Memory => (
                 Connection::open_in_memory()
@@ -62,12 +223,95 @@ impl SqliteConnectionPool {
                 JoinPushDown::AllowedFor(path.to_string()),
             ),
         };
+        set_encryption_key(&conn, encryption_key.clone()).await?;
+
+        conn.call(move |conn| {
+            conn.busy_timeout(busy_timeout)?;
+            if let Some(page_size) = page_size {
+                conn.pragma_update(None, "page_size", page_size)?;
+            }
+            if let Some(cache_size) = cache_size {
+                conn.pragma_update(None, "cache_size", cache_size)?;
+            }
+            // After page_size, which can't be changed once the database is in WAL mode
+            if let Some(journal_mode) = journal_mode {
+                conn.pragma_update(None, "journal_mode", journal_mode)?;
+            }
+            if let Some(synchronous) = synchronous {
+                conn.pragma_update(None, "synchronous", synchronous)?;
+            }
+            Ok(())
+        })
+        .await
+        .context(ConnectionPoolSnafu)?;
 
         Ok(SqliteConnectionPool {
             conn,
             join_push_down,
+            encryption_key,
+            empty_strings,
         })
     }
+
+    /// Opens the existing database file at `path` read-only, i.e. a file produced by another application. The file
+    /// isn't created when it doesn't exist, and statements that write to it fail with `attempt to write a readonly
+    /// database`. Only the `busy_timeout`, `encryption_key` and `empty_strings` of `options` apply, as the others
+    /// change the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file doesn't exist, can't be opened, or can't be decrypted with `encryption_key`.
+    pub async fn new_read_only(path: &str, options: SqliteConnectionPoolOptions) -> Result<Self> {
+        let SqliteConnectionPoolOptions {
+            busy_timeout,
+            encryption_key,
+            empty_strings,
+            ..
+        } = options;
+        let conn = Connection::open_with_flags(
+            path.to_string(),
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .await
+        .context(ConnectionPoolSnafu)?;
+        set_encryption_key(&conn, encryption_key.clone()).await?;
+
+        conn.call(move |conn| {
+            conn.busy_timeout(busy_timeout)?;
+            Ok(())
+        })
+        .await
+        .context(ConnectionPoolSnafu)?;
+
+        Ok(SqliteConnectionPool {
+            conn,
+            join_push_down: JoinPushDown::AllowedFor(path.to_string()),
+            encryption_key,
+            empty_strings,
+        })
+    }
+}
+
+/// Sets the `SQLCipher` key of a newly opened `conn`, then reads the schema so a wrong key is reported here rather than
+/// by the first statement. The key is never logged.
+async fn set_encryption_key(conn: &Connection, encryption_key: Option<SecretString>) -> Result<()> {
+    let Some(encryption_key) = encryption_key else {
+        return Ok(());
+    };
+    ensure!(cfg!(feature = "sqlcipher"), EncryptionNotSupportedSnafu);
+
+    conn.call(move |conn| {
+        conn.pragma_update(None, "key", encryption_key.expose_secret())?;
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        Ok(())
+    })
+    .await
+    .context(InvalidEncryptionKeySnafu)?;
+    Ok(())
 }
 
 #[async_trait]
@@ -75,10 +319,275 @@ impl DbConnectionPool<Connection, &'static (dyn ToSql + Sync)> for SqliteConnect
     async fn connect(
         &self,
     ) -> Result<Box<dyn DbConnection<Connection, &'static (dyn ToSql + Sync)>>> {
-        Ok(Box::new(SqliteConnection::new(self.conn.clone())))
+        Ok(Box::new(
+            SqliteConnection::new(self.conn.clone())
+                .with_encryption_key(self.encryption_key.clone())
+                .with_empty_strings(self.empty_strings),
+        ))
     }
 
     fn join_push_down(&self) -> JoinPushDown {
         self.join_push_down.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn pragma_value<T: rusqlite::types::FromSql + Send + 'static>(
+        pool: &SqliteConnectionPool,
+        pragma: &'static str,
+    ) -> T {
+        pool.conn
+            .call(move |conn| Ok(conn.pragma_query_value(None, pragma, |row| row.get(0))?))
+            .await
+            .expect("pragma should be queried")
+    }
+
+    #[tokio::test]
+    async fn test_page_size_and_cache_size() {
+        let pool = SqliteConnectionPool::new(
+            "",
+            Mode::Memory,
+            SqliteConnectionPoolOptions::new()
+                .with_page_size(Some(8192))
+                .with_cache_size(Some(-4000)),
+        )
+        .await
+        .expect("pool should be created");
+
+        assert_eq!(pragma_value::<i64>(&pool, "page_size").await, 8192);
+        assert_eq!(pragma_value::<i64>(&pool, "cache_size").await, -4000);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_page_size() {
+        for page_size in [0, 256, 1000, 131_072] {
+            assert!(
+                SqliteConnectionPool::new(
+                    "",
+                    Mode::Memory,
+                    SqliteConnectionPoolOptions::new().with_page_size(Some(page_size))
+                )
+                .await
+                .is_err(),
+                "page_size {page_size} should be rejected"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_cache_size() {
+        assert!(SqliteConnectionPool::new(
+            "",
+            Mode::Memory,
+            SqliteConnectionPoolOptions::new().with_cache_size(Some(0))
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_journal_mode_and_synchronous() {
+        let path = std::env::temp_dir().join(format!("journal_mode_{}.db", std::process::id()));
+        let pool = SqliteConnectionPool::new(
+            path.to_str().expect("temp path is UTF-8"),
+            Mode::File,
+            SqliteConnectionPoolOptions::new()
+                .with_journal_mode(Some("wal".to_string()))
+                .with_synchronous(Some("normal".to_string())),
+        )
+        .await
+        .expect("pool should be created");
+
+        assert_eq!(pragma_value::<String>(&pool, "journal_mode").await, "wal");
+        // NORMAL
+        assert_eq!(pragma_value::<i64>(&pool, "synchronous").await, 1);
+
+        drop(pool);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_journal_mode_and_synchronous() {
+        assert!(SqliteConnectionPool::new(
+            "",
+            Mode::Memory,
+            SqliteConnectionPoolOptions::new().with_journal_mode(Some("wall".to_string()))
+        )
+        .await
+        .is_err());
+        assert!(SqliteConnectionPool::new(
+            "",
+            Mode::Memory,
+            SqliteConnectionPoolOptions::new().with_synchronous(Some("sometimes".to_string()))
+        )
+        .await
+        .is_err());
+    }
+
+    async fn file_pool(path: &str, busy_timeout: Duration) -> SqliteConnectionPool {
+        SqliteConnectionPool::new(
+            path,
+            Mode::File,
+            SqliteConnectionPoolOptions::new().with_busy_timeout(busy_timeout),
+        )
+        .await
+        .expect("pool should be created")
+    }
+
+    async fn execute(pool: &SqliteConnectionPool, sql: &'static str) -> tokio_rusqlite::Result<()> {
+        pool.conn
+            .call(move |conn| {
+                conn.execute_batch(sql)?;
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_busy_timeout_waits_for_concurrent_writer() {
+        let path = std::env::temp_dir().join(format!("busy_timeout_{}.db", std::process::id()));
+        let path = path.to_str().expect("temp path is UTF-8").to_string();
+
+        // Tables in the same file have their own pools, and so their own connections
+        let writer = file_pool(&path, DEFAULT_BUSY_TIMEOUT).await;
+        execute(&writer, "CREATE TABLE items (id INTEGER)")
+            .await
+            .expect("table should be created");
+        execute(&writer, "BEGIN IMMEDIATE; INSERT INTO items VALUES (1);")
+            .await
+            .expect("transaction should begin");
+
+        let impatient = file_pool(&path, Duration::ZERO).await;
+        let err = execute(&impatient, "INSERT INTO items VALUES (2)")
+            .await
+            .expect_err("write should fail while the database is locked");
+        assert!(err.to_string().contains("database is locked"), "{err}");
+
+        let patient = file_pool(&path, DEFAULT_BUSY_TIMEOUT).await;
+        let waiting_write =
+            tokio::spawn(async move { execute(&patient, "INSERT INTO items VALUES (3)").await });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        execute(&writer, "COMMIT")
+            .await
+            .expect("transaction should commit");
+        waiting_write
+            .await
+            .expect("write task should complete")
+            .expect("write should wait for the lock instead of failing");
+
+        drop(writer);
+        drop(impatient);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_read_only() {
+        let path = std::env::temp_dir().join(format!("read_only_{}.db", std::process::id()));
+        let path = path.to_str().expect("temp path is UTF-8").to_string();
+
+        assert!(
+            SqliteConnectionPool::new_read_only(&path, SqliteConnectionPoolOptions::new())
+                .await
+                .is_err(),
+            "a missing file should not be created"
+        );
+
+        let writer = file_pool(&path, DEFAULT_BUSY_TIMEOUT).await;
+        execute(
+            &writer,
+            "CREATE TABLE items (id INTEGER); INSERT INTO items VALUES (1);",
+        )
+        .await
+        .expect("table should be populated");
+        drop(writer);
+
+        let reader = SqliteConnectionPool::new_read_only(&path, SqliteConnectionPoolOptions::new())
+            .await
+            .expect("pool should be created");
+        let count: i64 = reader
+            .conn
+            .call(|conn| Ok(conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))?))
+            .await
+            .expect("table should be read");
+        assert_eq!(count, 1);
+        let err = execute(&reader, "INSERT INTO items VALUES (2)")
+            .await
+            .expect_err("write should fail");
+        assert!(err.to_string().contains("readonly"), "{err}");
+
+        drop(reader);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[tokio::test]
+    async fn test_encryption_requires_sqlcipher() {
+        let result = SqliteConnectionPool::new(
+            "",
+            Mode::Memory,
+            SqliteConnectionPoolOptions::new()
+                .with_encryption_key(Some(SecretString::new("key".to_string()))),
+        )
+        .await;
+        let err = result.err().expect("key should be rejected");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::EncryptionNotSupported {})
+        ));
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[tokio::test]
+    async fn test_encrypted_database() {
+        let path = std::env::temp_dir().join(format!("encrypted_{}.db", std::process::id()));
+        let path = path.to_str().expect("temp path is UTF-8").to_string();
+        let encrypted_pool = |key: Option<&str>| {
+            SqliteConnectionPool::new(
+                &path,
+                Mode::File,
+                SqliteConnectionPoolOptions::new()
+                    .with_encryption_key(key.map(|key| SecretString::new(key.to_string()))),
+            )
+        };
+
+        let writer = encrypted_pool(Some("correct horse"))
+            .await
+            .expect("pool should be created");
+        execute(
+            &writer,
+            "CREATE TABLE items (id INTEGER); INSERT INTO items VALUES (1);",
+        )
+        .await
+        .expect("table should be populated");
+        drop(writer);
+
+        let reader = encrypted_pool(Some("correct horse"))
+            .await
+            .expect("database should be reopened with the same key");
+        let count: i64 = reader
+            .conn
+            .call(|conn| Ok(conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))?))
+            .await
+            .expect("rows should be read");
+        assert_eq!(count, 1);
+        drop(reader);
+
+        assert!(
+            encrypted_pool(Some("battery staple")).await.is_err(),
+            "database should not be opened with another key"
+        );
+        // Without a key the file is opened, but can't be read
+        let unkeyed = encrypted_pool(None).await.expect("pool should be created");
+        assert!(execute(&unkeyed, "SELECT COUNT(*) FROM items")
+            .await
+            .is_err());
+        drop(unkeyed);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
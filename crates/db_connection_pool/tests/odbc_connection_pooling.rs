@@ -0,0 +1,61 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#![cfg(feature = "odbc")]
+
+use std::{collections::HashMap, sync::Arc};
+
+use db_connection_pool::odbcpool::{ConnectionPooling, ODBCPool};
+use secrecy::SecretString;
+
+fn params(values: &[(&str, &str)]) -> Arc<HashMap<String, SecretString>> {
+    Arc::new(
+        values
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), SecretString::new((*value).to_string())))
+            .collect(),
+    )
+}
+
+// The connection pooling is set for the whole process, so this is the only test creating ODBC pools in this binary
+#[test]
+fn test_pool_with_connection_pooling_off() {
+    let pool = ODBCPool::new(params(&[
+        (
+            "odbc_connection_string",
+            "Driver={SQLite3};Database=:memory:",
+        ),
+        ("odbc_connection_pooling", "off"),
+    ]))
+    .expect("Must create ODBC pool with connection pooling off");
+    assert_eq!(pool.connection_pooling(), ConnectionPooling::Off);
+
+    let pool = ODBCPool::new(params(&[(
+        "odbc_connection_string",
+        "Driver={SQLite3};Database=:memory:",
+    )]))
+    .expect("Must create ODBC pool without connection pooling parameter");
+    assert_eq!(pool.connection_pooling(), ConnectionPooling::Off);
+
+    assert!(ODBCPool::new(params(&[
+        (
+            "odbc_connection_string",
+            "Driver={SQLite3};Database=:memory:"
+        ),
+        ("odbc_connection_pooling", "driver_aware"),
+    ]))
+    .is_err_and(|e| e.to_string().contains("already off")));
+}
@@ -26,6 +26,7 @@ use flight_client::tls::new_tls_flight_channel;
 use snafu::prelude::*;
 use std::any::Any;
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::pin::Pin;
 use std::{future::Future, sync::Arc};
 
@@ -36,6 +37,11 @@ pub enum Error {
 
     #[snafu(display("Unable to construct TLS flight client: {source}"))]
     UnableToConstructTlsChannel { source: flight_client::tls::Error },
+
+    #[snafu(display(
+        "flightsql_batch_size value {value} is invalid, please use a positive number of rows"
+    ))]
+    InvalidBatchSize { value: String },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -55,6 +61,14 @@ impl DataConnectorFactory for FlightSQL {
                 .get("endpoint")
                 .cloned()
                 .context(MissingEndpointParameterSnafu)?;
+            let batch_size = params
+                .get("flightsql_batch_size")
+                .map(|rows| {
+                    rows.parse::<NonZeroUsize>()
+                        .ok()
+                        .context(InvalidBatchSizeSnafu { value: rows })
+                })
+                .transpose()?;
             let flight_channel = new_tls_flight_channel(&endpoint)
                 .await
                 .context(UnableToConstructTlsChannelSnafu)?;
@@ -68,7 +82,10 @@ impl DataConnectorFactory for FlightSQL {
                     )
                     .await;
             };
-            let flightsql_factory = FlightSQLFactory::new(client, endpoint);
+            let mut flightsql_factory = FlightSQLFactory::new(client, endpoint);
+            if let Some(rows) = batch_size {
+                flightsql_factory = flightsql_factory.with_batch_size(rows);
+            }
             Ok(Arc::new(Self { flightsql_factory }) as Arc<dyn DataConnector>)
         })
     }
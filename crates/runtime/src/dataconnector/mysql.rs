@@ -20,6 +20,8 @@ use async_trait::async_trait;
 use data_components::mysql::MySQLTableFactory;
 use data_components::Read;
 use datafusion::datasource::TableProvider;
+#[cfg(feature = "circuitbreaker")]
+use db_connection_pool::circuitbreakerpool::CircuitBreakerPool;
 use db_connection_pool::mysqlpool::MySQLConnectionPool;
 use db_connection_pool::DbConnectionPool;
 use mysql_async::prelude::ToValue;
@@ -68,6 +70,8 @@ impl DataConnectorFactory for MySQL {
                     .await
                     .context(UnableToCreateMySQLConnectionPoolSnafu)?,
             );
+            #[cfg(feature = "circuitbreaker")]
+            let pool = Arc::new(CircuitBreakerPool::new(pool));
 
             let mysql_factory = MySQLTableFactory::new(pool);
 
@@ -21,7 +21,8 @@ use data_components::odbc::ODBCTableFactory;
 use data_components::Read;
 use datafusion::datasource::TableProvider;
 use db_connection_pool::dbconnection::odbcconn::ODBCDbConnectionPool;
-use db_connection_pool::odbcpool::ODBCPool;
+use db_connection_pool::odbcpool::{connection_string_parameters, ODBCPool};
+use secrecy::ExposeSecret;
 use snafu::prelude::*;
 use std::any::Any;
 use std::pin::Pin;
@@ -60,6 +61,19 @@ where
                 "odbc_connection_string_key",
                 "odbc_connection_string",
             );
+            secret.insert_to_params(&mut params, "odbc_username_key", "odbc_username");
+            secret.insert_to_params(&mut params, "odbc_password_key", "odbc_password");
+
+            // The placeholders of a templated connection string are filled from the secret, i.e. `${password}` with
+            // the secret named by the `password_key` parameter, or the `password` secret
+            let connection_string = params
+                .get("odbc_connection_string")
+                .map(|connection_string| connection_string.expose_secret().to_string());
+            if let Some(connection_string) = connection_string {
+                for name in connection_string_parameters(&connection_string).unwrap_or_default() {
+                    secret.insert_to_params(&mut params, &format!("{name}_key"), name);
+                }
+            }
         }
 
         Box::pin(async move {
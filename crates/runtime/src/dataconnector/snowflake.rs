@@ -24,6 +24,8 @@ use crate::component::dataset::Dataset;
 use crate::secrets::Secret;
 use crate::secrets::SecretMap;
 use datafusion::datasource::TableProvider;
+#[cfg(feature = "circuitbreaker")]
+use db_connection_pool::circuitbreakerpool::CircuitBreakerPool;
 use db_connection_pool::snowflakepool::SnowflakeConnectionPool;
 use db_connection_pool::DbConnectionPool;
 use itertools::Itertools;
@@ -81,12 +83,14 @@ impl DataConnectorFactory for Snowflake {
 
         Box::pin(async move {
             let pool: Arc<
-                dyn DbConnectionPool<Arc<SnowflakeApi>, &'static (dyn Sync)> + Send + Sync,
+                dyn DbConnectionPool<Arc<SnowflakeApi>, &'static (dyn Any + Sync)> + Send + Sync,
             > = Arc::new(
                 SnowflakeConnectionPool::new(&params)
                     .await
                     .context(UnableToCreateSnowflakeConnectionPoolSnafu)?,
             );
+            #[cfg(feature = "circuitbreaker")]
+            let pool = Arc::new(CircuitBreakerPool::new(pool));
 
             let table_factory = SnowflakeTableFactory::new(pool);
 
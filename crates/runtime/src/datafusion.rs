@@ -24,9 +24,10 @@ use crate::component::dataset::{Dataset, Mode};
 use crate::dataaccelerator::{self, create_accelerator_table};
 use crate::dataconnector::{DataConnector, DataConnectorError};
 use crate::dataupdate::{DataUpdate, DataUpdateExecutionPlan, UpdateType};
+use crate::embeddings::array_distance::{ArrayDistance, DistanceMetric};
+use crate::get_dependent_table_names;
 use crate::object_store_registry::default_runtime_env;
 use crate::secrets::Secret;
-use crate::{embeddings, get_dependent_table_names};
 
 use arrow::datatypes::Schema;
 use arrow_tools::schema::verify_schema;
@@ -221,7 +222,13 @@ impl DataFusion {
             .with_query_planner(Arc::new(FederatedQueryPlanner::new()));
 
         let ctx = SessionContext::new_with_state(state);
-        ctx.register_udf(embeddings::array_distance::ArrayDistance::new().into());
+        for metric in [
+            DistanceMetric::L2,
+            DistanceMetric::Cosine,
+            DistanceMetric::InnerProduct,
+        ] {
+            ctx.register_udf(ArrayDistance::with_metric(metric).into());
+        }
         ctx.register_udf(crate::datafusion::udf::Greatest::new().into());
         ctx.register_udf(crate::datafusion::udf::Least::new().into());
         let catalog = MemoryCatalogProvider::new();
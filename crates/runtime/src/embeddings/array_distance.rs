@@ -33,9 +33,69 @@ use std::{any::Any, sync::Arc};
 // See: https://github.com/apache/datafusion/blob/888504a8da6d20f9caf3ecb6cd1a6b7d1956e23e/datafusion/expr/src/signature.rs#L36
 pub const FIXED_SIZE_LIST_WILDCARD: i32 = i32::MIN;
 
+/// The metric used to compare embeddings. For each metric a smaller value means more similar vectors, so results
+/// can always be ranked in ascending order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// The squared Euclidean distance.
+    #[default]
+    L2,
+    /// One minus the cosine similarity, from 0 for vectors pointing the same way to 2 for opposite ones. Null if
+    /// either vector is all zeros.
+    Cosine,
+    /// The negated inner product, so that the largest inner product ranks first.
+    InnerProduct,
+}
+
+impl DistanceMetric {
+    /// The name of the [`ArrayDistance`] scalar UDF computing this metric.
+    #[must_use]
+    pub fn function_name(self) -> &'static str {
+        match self {
+            DistanceMetric::L2 => "array_distance",
+            DistanceMetric::Cosine => "array_cosine_distance",
+            DistanceMetric::InnerProduct => "array_negative_inner_product",
+        }
+    }
+
+    /// The distance between the vectors `a` and `b`, which must have the same length and no null elements.
+    fn distance(self, a: &Float64Array, b: &Float64Array) -> DataFusionResult<Option<f64>> {
+        if a.len() != b.len() {
+            return Err(DataFusionError::Internal(format!(
+                "Vectors must have the same length, got {} and {}",
+                a.len(),
+                b.len()
+            )));
+        }
+        if a.null_count() > 0 || b.null_count() > 0 {
+            return Err(DataFusionError::Internal(
+                "Vector elements must be non-null".into(),
+            ));
+        }
+        let dot = || -> f64 { a.values().iter().zip(b.values()).map(|(x, y)| x * y).sum() };
+        match self {
+            DistanceMetric::L2 => {
+                let z: Float64Array = binary(a, b, |x, y| (x - y).powi(2))
+                    .map_err(|e| DataFusionError::Internal(e.to_string()))?;
+                Ok(sum(&z))
+            }
+            DistanceMetric::Cosine => {
+                let norm = |v: &Float64Array| v.values().iter().map(|x| x * x).sum::<f64>().sqrt();
+                let norms = norm(a) * norm(b);
+                if norms == 0.0 {
+                    return Ok(None);
+                }
+                Ok(Some(1.0 - dot() / norms))
+            }
+            DistanceMetric::InnerProduct => Ok(Some(-dot())),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ArrayDistance {
     signature: Signature,
+    metric: DistanceMetric,
 }
 
 impl Default for ArrayDistance {
@@ -46,7 +106,8 @@ impl Default for ArrayDistance {
 
 /// [`ArrayDistance`] is a scalar UDF that calculates the Euclidean distance between elements in
 /// [`DataType::FixedSizeList`] arrays with a numeric inner type. Limited support for
-/// [`DataType::List`] is also provided.
+/// [`DataType::List`] is also provided. The other [`DistanceMetric`]s are available from
+/// [`ArrayDistance::with_metric`], under their own [`DistanceMetric::function_name`].
 ///
 /// For two [`DataType::FixedSizeList`], the inputs must have the same length, and have compatible
 /// inner types. Compatible inner types are
@@ -66,6 +127,11 @@ impl Default for ArrayDistance {
 impl ArrayDistance {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_metric(DistanceMetric::L2)
+    }
+
+    #[must_use]
+    pub fn with_metric(metric: DistanceMetric) -> Self {
         let valid_types = [true, false]
             .iter()
             .cartesian_product([
@@ -101,6 +167,7 @@ impl ArrayDistance {
 
         Self {
             signature: Signature::one_of(valid_signatures, Volatility::Immutable),
+            metric,
         }
     }
 
@@ -222,7 +289,7 @@ impl ScalarUDFImpl for ArrayDistance {
         self
     }
     fn name(&self) -> &str {
-        "array_distance"
+        self.metric.function_name()
     }
     fn signature(&self) -> &Signature {
         &self.signature
@@ -230,7 +297,7 @@ impl ScalarUDFImpl for ArrayDistance {
 
     fn return_type(&self, args: &[DataType]) -> DataFusionResult<DataType> {
         if args.len() != 2 {
-            return plan_err!("array_distance takes exactly two arguments");
+            return plan_err!("{} takes exactly two arguments", self.name());
         }
 
         match (args[0].clone(), args[1].clone()) {
@@ -245,7 +312,7 @@ impl ScalarUDFImpl for ArrayDistance {
             | (DataType::List(f1) | DataType::LargeList(f1), DataType::FixedSizeList(f2, _)) => {
                 Self::least_precise_float_type(f1.data_type(), f2.data_type())
             }
-            _ => plan_err!("Invalid combination of input types for 'array_distance'"),
+            _ => plan_err!("Invalid combination of input types for '{}'", self.name()),
         }
     }
 
@@ -262,15 +329,10 @@ impl ScalarUDFImpl for ArrayDistance {
             .iter()
             .zip(z2.iter())
             .map(|(a, b)| match (a, b) {
-                (Some(a), Some(b)) => {
-                    let z: Float64Array = binary(
-                        &Self::cast_to_float64_array(&a)?,
-                        &Self::cast_to_float64_array(&b)?,
-                        |x, y| (x - y).powi(2),
-                    )
-                    .map_err(|e| DataFusionError::Internal(e.to_string()))?;
-                    Ok(sum(&z))
-                }
+                (Some(a), Some(b)) => self.metric.distance(
+                    &Self::cast_to_float64_array(&a)?,
+                    &Self::cast_to_float64_array(&b)?,
+                ),
                 _ => Ok(None),
             })
             .collect();
@@ -316,7 +378,7 @@ mod tests {
         logical_expr::{ColumnarValue, ScalarUDF},
     };
 
-    use super::ArrayDistance;
+    use super::{ArrayDistance, DistanceMetric};
 
     #[allow(clippy::float_cmp)]
     #[tokio::test]
@@ -482,4 +544,76 @@ mod tests {
 
         Ok(())
     }
+
+    #[allow(clippy::float_cmp)]
+    #[tokio::test]
+    async fn test_metrics() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let field = Arc::new(Field::new("item", DataType::Float64, false));
+        // [3, 4] against [3, 4], [4, -3] and [0, 0]
+        let v1 = Arc::new(FixedSizeListArray::try_new(
+            Arc::clone(&field),
+            2_i32,
+            Arc::new(Float64Array::try_new(
+                vec![3.0, 4.0, 3.0, 4.0, 3.0, 4.0].into(),
+                None,
+            )?),
+            None,
+        )?) as ArrayRef;
+        let v2 = Arc::new(FixedSizeListArray::try_new(
+            Arc::clone(&field),
+            2_i32,
+            Arc::new(Float64Array::try_new(
+                vec![3.0, 4.0, 4.0, -3.0, 0.0, 0.0].into(),
+                None,
+            )?),
+            None,
+        )?) as ArrayRef;
+
+        let distances = |metric: DistanceMetric| -> Result<Float64Array, Box<dyn std::error::Error + Send + Sync>> {
+            let udf = ScalarUDF::from(ArrayDistance::with_metric(metric));
+            assert_eq!(udf.name(), metric.function_name());
+            let result = udf.invoke(&[
+                ColumnarValue::Array(Arc::clone(&v1)),
+                ColumnarValue::Array(Arc::clone(&v2)),
+            ])?;
+            let array_vec = ColumnarValue::values_to_arrays(&[result])?;
+            Ok(array_vec[0]
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .ok_or("failed downcast of result")?
+                .clone())
+        };
+
+        let l2 = distances(DistanceMetric::L2)?;
+        assert_eq!(l2.values().to_vec(), vec![0.0, 50.0, 25.0]);
+
+        let cosine = distances(DistanceMetric::Cosine)?;
+        assert_eq!(cosine.value(0), 0.0);
+        assert_eq!(cosine.value(1), 1.0);
+        assert!(cosine.is_null(2));
+
+        let inner_product = distances(DistanceMetric::InnerProduct)?;
+        assert_eq!(inner_product.values().to_vec(), vec![-25.0, 0.0, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_metrics_validate_vectors() {
+        let a = Float64Array::from(vec![1.0, 2.0]);
+        let longer = Float64Array::from(vec![1.0, 2.0, 3.0]);
+        let with_null = Float64Array::from(vec![Some(1.0), None]);
+
+        for metric in [
+            DistanceMetric::L2,
+            DistanceMetric::Cosine,
+            DistanceMetric::InnerProduct,
+        ] {
+            assert!(metric.distance(&a, &a).is_ok());
+            assert!(metric.distance(&a, &longer).is_err(), "{metric:?}");
+            assert!(metric.distance(&longer, &a).is_err(), "{metric:?}");
+            assert!(metric.distance(&a, &with_null).is_err(), "{metric:?}");
+            assert!(metric.distance(&with_null, &a).is_err(), "{metric:?}");
+        }
+    }
 }
@@ -63,14 +63,20 @@ impl EmbeddingConnector {
             .map(|e| (e.column.clone(), e.model.clone()))
             .collect::<HashMap<_, _>>();
 
-        Ok(Arc::new(
+        let table = dataset.embeddings.iter().fold(
             EmbeddingTable::new(
                 inner_table_provider,
                 embed_columns,
                 Arc::clone(&self.embedding_models),
             )
             .await,
-        ) as Arc<dyn TableProvider>)
+            |table, e| match &e.vector_column {
+                Some(vector_column) => table.with_vector_column(&e.column, vector_column),
+                None => table,
+            },
+        );
+
+        Ok(Arc::new(table) as Arc<dyn TableProvider>)
     }
 }
 
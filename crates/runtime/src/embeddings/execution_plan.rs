@@ -45,6 +45,7 @@ pub struct EmbeddingTableExec {
     base_plan: Arc<dyn ExecutionPlan>,
 
     embedded_columns: HashMap<String, String>,
+    vector_columns: HashMap<String, String>,
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 }
 
@@ -96,6 +97,7 @@ impl ExecutionPlan for EmbeddingTableExec {
             self.limit,
             Arc::clone(&self.base_plan).with_new_children(children)?,
             self.embedded_columns.clone(),
+            self.vector_columns.clone(),
             Arc::clone(&self.embedding_models),
         )) as Arc<dyn ExecutionPlan>)
     }
@@ -112,6 +114,7 @@ impl ExecutionPlan for EmbeddingTableExec {
                 s,
                 Arc::clone(&self.projected_schema),
                 self.embedded_columns.clone(),
+                self.vector_columns.clone(),
                 Arc::clone(&self.embedding_models),
             ),
         )))
@@ -126,6 +129,7 @@ impl EmbeddingTableExec {
         limit: Option<usize>,
         base_plan: Arc<dyn ExecutionPlan>,
         embedded_columns: HashMap<String, String>,
+        vector_columns: HashMap<String, String>,
         embedding_models: Arc<RwLock<EmbeddingModelStore>>,
     ) -> Self {
         Self {
@@ -135,6 +139,7 @@ impl EmbeddingTableExec {
             properties: Self::compute_properties(&base_plan, projected_schema),
             base_plan,
             embedded_columns,
+            vector_columns,
             embedding_models,
         }
     }
@@ -154,13 +159,14 @@ fn to_sendable_stream(
     mut base_stream: SendableRecordBatchStream,
     projected_schema: SchemaRef,
     embedded_columns: HashMap<String, String>,
+    vector_columns: HashMap<String, String>,
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 ) -> impl Stream<Item = DataFusionResult<RecordBatch>> + 'static {
     stream! {
         while let Some(batch_result) = base_stream.next().await {
             match batch_result {
                 Ok(batch) => {
-                    match get_embeddings(&batch, &embedded_columns, Arc::clone(&embedding_models)).await {
+                    match get_embeddings(&batch, &embedded_columns, &vector_columns, Arc::clone(&embedding_models)).await {
                         Ok(embeddings) => {
 
                             match construct_record_batch(
@@ -205,6 +211,7 @@ fn construct_record_batch(
 async fn get_embeddings(
     rb: &RecordBatch,
     embedded_columns: &HashMap<String, String>,
+    vector_columns: &HashMap<String, String>,
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 ) -> Result<HashMap<String, ArrayRef>, Box<dyn std::error::Error + Send + Sync>> {
     let field = Arc::new(Field::new("item", DataType::Float32, false));
@@ -212,6 +219,9 @@ async fn get_embeddings(
     let mut embed_arrays: HashMap<String, ArrayRef> =
         HashMap::with_capacity(embedded_columns.len());
     for (col, model_name) in embedded_columns {
+        let Some(vector_column) = vector_columns.get(col) else {
+            continue;
+        };
         let read_guard = embedding_models.read().await;
         let model_lock_opt = read_guard.get(model_name);
 
@@ -250,7 +260,7 @@ async fn get_embeddings(
             Arc::new(values),
             None,
         )?;
-        embed_arrays.insert(format!("{col}_embedding"), Arc::new(list_array));
+        embed_arrays.insert(vector_column.clone(), Arc::new(list_array));
     }
     Ok(embed_arrays)
 }
@@ -48,6 +48,9 @@ pub struct EmbeddingTable {
     // A mapping of columns names from [`base_table`] to the embedding's `name` to use.
     embedded_columns: HashMap<String, String>,
 
+    // A mapping of columns names from [`base_table`] to the name of the column holding their embedding vectors.
+    vector_columns: HashMap<String, String>,
+
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 
     // Precompute to avoid async lock waits from `embedding_models` data structure.
@@ -62,14 +65,29 @@ impl EmbeddingTable {
         embedding_models: Arc<RwLock<EmbeddingModelStore>>,
     ) -> Self {
         let sizes = Self::precompute_embedding_sizes(&embedded_columns, &embedding_models).await;
+        let vector_columns = embedded_columns
+            .keys()
+            .map(|column| (column.clone(), embedding_column_name(column)))
+            .collect();
         Self {
             base_table,
             embedded_columns,
+            vector_columns,
             embedding_models,
             embedding_sizes: sizes,
         }
     }
 
+    /// Names the column holding the embedding vectors of `column` `vector_column`, instead of `{column}_embedding`.
+    /// Columns that aren't augmented with embeddings are ignored.
+    #[must_use]
+    pub fn with_vector_column(mut self, column: &str, vector_column: impl Into<String>) -> Self {
+        if let Some(name) = self.vector_columns.get_mut(column) {
+            *name = vector_column.into();
+        }
+        self
+    }
+
     /// Get the names of the embedding models used by this table across its columns.
     #[must_use]
     pub fn get_embedding_models_used(&self) -> Vec<String> {
@@ -82,6 +100,18 @@ impl EmbeddingTable {
         self.embedded_columns.keys().cloned().collect()
     }
 
+    /// Get the name of the column holding the embedding vectors of `column`, if `column` is augmented with embeddings.
+    #[must_use]
+    pub fn get_embedding_vector_column(&self, column: &str) -> Option<String> {
+        self.vector_columns.get(column).cloned()
+    }
+
+    /// Get the vector dimension of the embedding for `column`, as declared in the table's schema.
+    #[must_use]
+    pub fn get_embedding_dimension(&self, column: &str) -> Option<i32> {
+        embedding_dimension(&self.schema(), self.vector_columns.get(column)?)
+    }
+
     async fn precompute_embedding_sizes(
         embedded_columns: &HashMap<String, String>,
         embedding_models: &Arc<RwLock<EmbeddingModelStore>>,
@@ -137,6 +167,22 @@ impl EmbeddingTable {
     }
 }
 
+/// The default name of the column an [`EmbeddingTable`] adds for the embedding vectors of `column`.
+fn embedding_column_name(column: &str) -> String {
+    format!("{column}_embedding")
+}
+
+/// Reads the vector dimension of the embedding column `vector_column` from its `FixedSizeList(Float32, n)` type in
+/// `schema`. Returns `None` if the column isn't an embedding column, or its size is unknown (i.e. the model wasn't
+/// loaded).
+pub(crate) fn embedding_dimension(schema: &Schema, vector_column: &str) -> Option<i32> {
+    let (_, field) = schema.column_with_name(vector_column)?;
+    match field.data_type() {
+        DataType::FixedSizeList(_, size) if *size > 0 => Some(*size),
+        _ => None,
+    }
+}
+
 #[async_trait]
 impl TableProvider for EmbeddingTable {
     fn as_any(&self) -> &dyn Any {
@@ -181,6 +227,7 @@ impl TableProvider for EmbeddingTable {
             .sorted() // Important to be kept alphabetical for fast lookup
             .filter_map(|k| match base_schema.column_with_name(k) {
                 Some((_, field)) => {
+                    let vector_column = self.vector_columns.get(k)?;
                     let embedding_size = self
                         .embedding_sizes
                         .get(field.name())
@@ -195,7 +242,7 @@ impl TableProvider for EmbeddingTable {
                                 embedding_size,
                                 false,
                             ))
-                            .with_name(format!("{}_embedding", field.name())),
+                            .with_name(vector_column),
                     ))
                 }
                 None => None,
@@ -245,6 +292,12 @@ impl TableProvider for EmbeddingTable {
             .filter(|(c, _m)| columns_to_embed.contains(c))
             .map(|(c, m)| (c.clone(), m.clone()))
             .collect();
+        let scan_vector_columns: HashMap<String, String> = self
+            .vector_columns
+            .iter()
+            .filter(|(c, _v)| columns_to_embed.contains(c))
+            .map(|(c, v)| (c.clone(), v.clone()))
+            .collect();
 
         // Need to ensure base table gets the underlying column for each embedding column specified (as well as everything in the original [`projection`]).
         let projection_for_base_table: Option<Vec<usize>> = match projection.cloned() {
@@ -277,6 +330,7 @@ impl TableProvider for EmbeddingTable {
             limit,
             base_plan,
             scan_embed_columns,
+            scan_vector_columns,
             Arc::clone(&self.embedding_models),
         )) as Arc<dyn ExecutionPlan>)
     }
@@ -315,3 +369,81 @@ impl TableProvider for EmbeddingTable {
         self.base_table.insert_into(state, input, overwrite).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::Field;
+    use datafusion::datasource::MemTable;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embedding_vector_column_is_in_schema() {
+        let base_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("body", DataType::Utf8, true),
+        ]));
+        let base_table = MemTable::try_new(base_schema, vec![vec![]]).expect("table is created");
+        let table = EmbeddingTable::new(
+            Arc::new(base_table),
+            HashMap::from([("body".to_string(), "model".to_string())]),
+            Arc::new(RwLock::new(HashMap::new())),
+        )
+        .await;
+
+        let vector_column = table
+            .get_embedding_vector_column("body")
+            .expect("body is embedded");
+        assert_eq!(vector_column, "body_embedding");
+        assert!(table.schema().column_with_name(&vector_column).is_some());
+        assert_eq!(table.get_embedding_vector_column("id"), None);
+    }
+
+    #[tokio::test]
+    async fn test_configured_embedding_vector_column() {
+        let base_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("body", DataType::Utf8, true),
+        ]));
+        let base_table = MemTable::try_new(base_schema, vec![vec![]]).expect("table is created");
+        let table = EmbeddingTable::new(
+            Arc::new(base_table),
+            HashMap::from([("body".to_string(), "model".to_string())]),
+            Arc::new(RwLock::new(HashMap::new())),
+        )
+        .await
+        .with_vector_column("body", "body_vectors")
+        .with_vector_column("id", "id_vectors");
+
+        assert_eq!(
+            table.get_embedding_vector_column("body").as_deref(),
+            Some("body_vectors")
+        );
+        assert!(table.schema().column_with_name("body_vectors").is_some());
+        assert!(table.schema().column_with_name("body_embedding").is_none());
+        assert_eq!(table.get_embedding_vector_column("id"), None);
+        assert!(table.schema().column_with_name("id_vectors").is_none());
+    }
+
+    #[test]
+    fn test_embedding_dimension_from_schema() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("body", DataType::Utf8, true),
+            Field::new(
+                "body_embedding",
+                DataType::new_fixed_size_list(DataType::Float32, 384, false),
+                true,
+            ),
+            Field::new(
+                "title_embedding",
+                DataType::new_fixed_size_list(DataType::Float32, 0, false),
+                true,
+            ),
+        ]);
+
+        assert_eq!(embedding_dimension(&schema, "body_embedding"), Some(384));
+        assert_eq!(embedding_dimension(&schema, "title_embedding"), None);
+        assert_eq!(embedding_dimension(&schema, "id"), None);
+    }
+}
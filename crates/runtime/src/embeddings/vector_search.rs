@@ -15,18 +15,39 @@ limitations under the License.
 */
 #![allow(clippy::module_name_repetitions)]
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::Arc,
+};
 
 use app::App;
-use arrow::array::{RecordBatch, StringArray};
+use arrow::{
+    array::{AsArray, FixedSizeListArray, Float32Array, RecordBatch, StringArray},
+    compute::cast,
+    datatypes::{DataType, Field, Float32Type, Schema},
+};
 use async_openai::types::EmbeddingInput;
-use datafusion::{common::Constraint, datasource::TableProvider, sql::TableReference};
+use datafusion::{
+    common::{Constraint, ScalarValue},
+    dataframe::DataFrame,
+    datasource::TableProvider,
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionContext,
+    logical_expr::{col, lit, Expr, ScalarUDF},
+    sql::TableReference,
+};
 
-use tokio::sync::RwLock;
+use futures::stream::{self, BoxStream, StreamExt};
+use lru::LruCache;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::{accelerated_table::AcceleratedTable, datafusion::DataFusion, EmbeddingModelStore};
 
-use super::table::EmbeddingTable;
+use super::{
+    array_distance::{ArrayDistance, DistanceMetric},
+    table::EmbeddingTable,
+};
 use snafu::prelude::*;
 
 #[derive(Debug, Snafu)]
@@ -48,6 +69,43 @@ pub enum Error {
         num_embeddings: usize,
     },
 
+    #[snafu(display("Embedding for {data_source} has {actual} dimensions, but the embedding column has {expected}"))]
+    IncorrectEmbeddingDimension {
+        data_source: String,
+        expected: i32,
+        actual: usize,
+    },
+
+    #[snafu(display("Embedding model {model_name} returned an embedding with {actual} dimensions, but the columns it embeds have {expected}"))]
+    EmbeddingDimensionMismatch {
+        model_name: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[snafu(display(
+        "Embedding for {data_source} has {actual} dimensions, exceeding the maximum of {max}"
+    ))]
+    EmbeddingTooLarge {
+        data_source: String,
+        max: usize,
+        actual: usize,
+    },
+
+    #[snafu(display("Data source {data_source} does not have a column {column} to select"))]
+    ColumnNotFound { data_source: String, column: String },
+
+    #[snafu(display("The vector search WHERE predicate is empty"))]
+    EmptyWherePredicate,
+
+    #[snafu(display("Vector search by distance threshold is not supported, limit the results to the top N instead"))]
+    UnsupportedRetrievalLimit,
+
+    #[snafu(display(
+        "Data source {data_source} has no primary key to identify its search results"
+    ))]
+    MissingPrimaryKey { data_source: String },
+
     #[snafu(display("Embedding model {} not found", model_name))]
     EmbeddingModelNotFound { model_name: String },
 
@@ -64,16 +122,141 @@ pub struct VectorSearch {
     df: Arc<DataFusion>,
     embeddings: Arc<RwLock<EmbeddingModelStore>>,
     explicit_primary_keys: HashMap<TableReference, Vec<String>>,
+    max_embedding_dimension: Option<usize>,
+    distance_metric: DistanceMetric,
+    query_embedding_cache: Option<Mutex<LruCache<(ModelKey, String), Vec<f32>>>>,
+    missing_primary_key_policy: MissingPrimaryKeyPolicy,
 }
 
+/// How to identify the search results of a table with neither a primary key constraint nor explicit primary keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingPrimaryKeyPolicy {
+    /// Return the results without keys.
+    #[default]
+    Omit,
+    /// Fail the search with [`Error::MissingPrimaryKey`].
+    Error,
+    /// Number the rows of the table, before they are filtered, and return the number of each result as
+    /// [`ROW_NUMBER_COLUMN`]. The numbers are positions in a scan of the table, so they only identify rows until the
+    /// table changes.
+    RowNumber,
+}
+
+/// The column of the row numbers returned for tables without primary keys, see [`MissingPrimaryKeyPolicy::RowNumber`].
+pub const ROW_NUMBER_COLUMN: &str = "row_number";
+
 pub enum RetrievalLimit {
     TopN(usize),
     Threshold(f64),
 }
+
+impl RetrievalLimit {
+    /// The number of results to retrieve from each table, failing with [`Error::UnsupportedRetrievalLimit`] for limits
+    /// that can't be searched yet.
+    fn top_n(&self) -> Result<usize> {
+        match self {
+            RetrievalLimit::TopN(n) => Ok(*n),
+            RetrievalLimit::Threshold(_) => UnsupportedRetrievalLimitSnafu.fail(),
+        }
+    }
+}
+
+/// Restricts which rows of each table [`VectorSearch::search`] ranks, and which of their columns it returns.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    where_predicate: Option<String>,
+    offset: usize,
+    additional_columns: Vec<String>,
+}
+
+impl SearchOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only ranks the rows of each table matching `where_predicate`, i.e. `tenant_id = 'x'`.
+    ///
+    /// The predicate is written into the search SQL as is, so it must come from a trusted caller and never from user
+    /// input: anything accepted in a SQL `WHERE` clause, including subqueries of other tables, can be run through it.
+    /// Values from users must be escaped as SQL literals first.
+    #[must_use]
+    pub fn with_where_predicate(mut self, where_predicate: impl Into<String>) -> Self {
+        self.where_predicate = Some(where_predicate.into());
+        self
+    }
+
+    /// Skips the `offset` closest rows of each table, so the following results can be paged through.
+    #[must_use]
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Selects the `additional_columns` of each table, i.e. a `url` or `title`, after its primary keys, returning them
+    /// with the keys in `retrieved_public_keys`.
+    #[must_use]
+    pub fn with_additional_columns(mut self, additional_columns: Vec<String>) -> Self {
+        self.additional_columns = additional_columns;
+        self
+    }
+}
 pub type ModelKey = String;
 pub struct VectorSearchResult {
     pub retrieved_entries: HashMap<TableReference, Vec<String>>,
     pub retrieved_public_keys: HashMap<TableReference, Vec<RecordBatch>>,
+    pub retrieved_counts: HashMap<TableReference, RetrievalCount>,
+    /// The distance of each retrieved row from the search embedding, in the order of `retrieved_entries`.
+    pub retrieved_distances: HashMap<TableReference, Vec<f32>>,
+}
+
+/// Rows of a table streamed by [`VectorSearch::search_stream`], closest first.
+pub struct VectorSearchBatch {
+    pub table: TableReference,
+    /// The text of the embedding column of each row.
+    pub entries: Vec<String>,
+    /// The primary keys and additional columns of each row, followed by its embedding column.
+    pub public_keys: RecordBatch,
+    /// The distance of each row from the search embedding.
+    pub distances: Vec<f32>,
+}
+
+impl VectorSearchBatch {
+    fn try_new(table: TableReference, batch: RecordBatch, embedding_column: &str) -> Result<Self> {
+        let (public_keys, distances) = take_batch_distances(batch)?;
+        Ok(VectorSearchBatch {
+            table,
+            entries: embedded_entries(&public_keys, embedding_column)?,
+            public_keys,
+            distances,
+        })
+    }
+}
+
+/// The column the distance from the search embedding is selected as.
+const DISTANCE_COLUMN: &str = "dist";
+
+/// The number of results requested from a table, and the number it returned. A table returns fewer results than
+/// requested when it has fewer rows, in which case callers may want to search more tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetrievalCount {
+    pub requested: usize,
+    pub returned: usize,
+}
+
+impl RetrievalCount {
+    fn from_batches(requested: usize, batches: &[RecordBatch]) -> Self {
+        RetrievalCount {
+            requested,
+            returned: batches.iter().map(RecordBatch::num_rows).sum(),
+        }
+    }
+
+    /// The number of requested results the table could not return.
+    #[must_use]
+    pub fn shortfall(&self) -> usize {
+        self.requested.saturating_sub(self.returned)
+    }
 }
 
 impl VectorSearch {
@@ -86,24 +269,189 @@ impl VectorSearch {
             df,
             embeddings,
             explicit_primary_keys,
+            max_embedding_dimension: None,
+            distance_metric: DistanceMetric::default(),
+            query_embedding_cache: None,
+            missing_primary_key_policy: MissingPrimaryKeyPolicy::default(),
         }
     }
 
+    /// Rejects search embeddings with more than `max_embedding_dimension` dimensions before they are searched,
+    /// guarding against a misconfigured model producing huge vectors.
+    #[must_use]
+    pub fn with_max_embedding_dimension(mut self, max_embedding_dimension: usize) -> Self {
+        self.max_embedding_dimension = Some(max_embedding_dimension);
+        self
+    }
+
+    /// Ranks results by `distance_metric` instead of the default [`DistanceMetric::L2`]. [`DistanceMetric::Cosine`]
+    /// is usually preferred for normalized embeddings.
+    #[must_use]
+    pub fn with_distance_metric(mut self, distance_metric: DistanceMetric) -> Self {
+        self.distance_metric = distance_metric;
+        self
+    }
+
+    /// Caches the embeddings of up to `capacity` queries, evicting the least recently searched, so a query that is
+    /// searched repeatedly with the same embedding model is only embedded once.
+    #[must_use]
+    pub fn with_query_embedding_cache(mut self, capacity: NonZeroUsize) -> Self {
+        self.query_embedding_cache = Some(Mutex::new(LruCache::new(capacity)));
+        self
+    }
+
+    /// Identifies the results of tables without primary keys by `missing_primary_key_policy`, instead of returning them
+    /// without keys.
+    #[must_use]
+    pub fn with_missing_primary_key_policy(
+        mut self,
+        missing_primary_key_policy: MissingPrimaryKeyPolicy,
+    ) -> Self {
+        self.missing_primary_key_policy = missing_primary_key_policy;
+        self
+    }
+
+    /// Searches `tables` for the rows closest to the embedding of `query`, restricted and extended by `options`.
     pub async fn search(
         &self,
         query: String,
         tables: Vec<TableReference>,
         limit: RetrievalLimit,
+        options: SearchOptions,
     ) -> Result<VectorSearchResult> {
-        let n = match limit {
-            RetrievalLimit::TopN(n) => n,
-            RetrievalLimit::Threshold(_) => unimplemented!(),
-        };
+        check_where_predicate(options.where_predicate.as_deref())?;
+        let n = limit.top_n()?;
 
         let per_table_embeddings = self
             .calculate_embeddings_per_table(query.clone(), tables.clone())
             .await?;
 
+        self.search_embeddings(per_table_embeddings, tables, n, &options)
+            .await
+    }
+
+    /// Searches `tables` like `search`, but streams the results rather than collecting them, so callers can process
+    /// the closest rows before the rest are read. The tables are searched one after another, and the results of each
+    /// are streamed in batches closest first.
+    pub async fn search_stream(
+        &self,
+        query: String,
+        tables: Vec<TableReference>,
+        limit: RetrievalLimit,
+        options: SearchOptions,
+    ) -> Result<BoxStream<'static, Result<VectorSearchBatch>>> {
+        check_where_predicate(options.where_predicate.as_deref())?;
+        let n = limit.top_n()?;
+
+        let per_table_embeddings = self
+            .calculate_embeddings_per_table(query, tables.clone())
+            .await?;
+        let table_primary_keys = self
+            .get_primary_keys_with_overrides(&self.explicit_primary_keys, tables)
+            .await?;
+
+        let mut streams = Vec::with_capacity(per_table_embeddings.len());
+        for (tbl, search_vectors) in per_table_embeddings {
+            let (dataframe, embedding_column) = self
+                .search_table(
+                    &tbl,
+                    &search_vectors,
+                    table_primary_keys.get(&tbl).cloned().unwrap_or_default(),
+                    n,
+                    &options,
+                )
+                .await?;
+            let batches = dataframe
+                .execute_stream()
+                .await
+                .boxed()
+                .context(DataFusionSnafu)?;
+            streams.push(batches.map(move |batch| {
+                let batch = batch.boxed().context(DataFusionSnafu)?;
+                VectorSearchBatch::try_new(tbl.clone(), batch, &embedding_column)
+            }));
+        }
+        Ok(stream::iter(streams).flatten().boxed())
+    }
+
+    /// Searches `tables` for the rows closest to each of `queries`, returning a result per query in the order of
+    /// `queries`, as `search` would with the default [`SearchOptions`].
+    ///
+    /// The distinct queries are embedded together, in a single request to each embedding model, rather than one
+    /// request per query. Query embeddings aren't cached.
+    pub async fn search_many(
+        &self,
+        queries: Vec<String>,
+        tables: Vec<TableReference>,
+        limit: RetrievalLimit,
+    ) -> Result<Vec<VectorSearchResult>> {
+        let n = limit.top_n()?;
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+        let options = SearchOptions::default();
+        let embeddings_to_run = self.find_relevant_embedding_models(tables.clone()).await?;
+
+        let mut distinct_queries: Vec<String> = Vec::new();
+        for query in &queries {
+            if !distinct_queries.contains(query) {
+                distinct_queries.push(query.clone());
+            }
+        }
+
+        let mut embedded_queries: HashMap<(ModelKey, String), Vec<f32>> = HashMap::new();
+        let mut embedded_models: HashSet<&ModelKey> = HashSet::new();
+        for model in embeddings_to_run.values().flatten() {
+            // Tables embedded by the same model share its embeddings of the queries
+            if !embedded_models.insert(model) {
+                continue;
+            }
+            let embeddings = self
+                .embed_all(EmbeddingInput::StringArray(distinct_queries.clone()), model)
+                .await?;
+            ensure!(
+                embeddings.len() == distinct_queries.len(),
+                EmbeddingSnafu {
+                    source: string_to_boxed_err(format!(
+                        "Expected {} embeddings from {model}, but it returned {}",
+                        distinct_queries.len(),
+                        embeddings.len()
+                    )),
+                }
+            );
+            for (query, embedding) in distinct_queries.iter().zip(embeddings) {
+                embedded_queries.insert((model.clone(), query.clone()), embedding);
+            }
+        }
+
+        let mut results = Vec::with_capacity(queries.len());
+        for query in queries {
+            let per_table_embeddings = embeddings_to_run
+                .iter()
+                .map(|(t, model_names)| {
+                    let z: Vec<_> = model_names
+                        .iter()
+                        .filter_map(|m| embedded_queries.get(&(m.clone(), query.clone())).cloned())
+                        .collect();
+                    (t.clone(), z)
+                })
+                .collect();
+            results.push(
+                self.search_embeddings(per_table_embeddings, tables.clone(), n, &options)
+                    .await?,
+            );
+        }
+        Ok(results)
+    }
+
+    /// Searches each table for the rows closest to its embedding in `per_table_embeddings`.
+    async fn search_embeddings(
+        &self,
+        per_table_embeddings: HashMap<TableReference, Vec<Vec<f32>>>,
+        tables: Vec<TableReference>,
+        n: usize,
+        options: &SearchOptions,
+    ) -> Result<VectorSearchResult> {
         let table_primary_keys = self
             .get_primary_keys_with_overrides(&self.explicit_primary_keys, tables.clone())
             .await?;
@@ -111,74 +459,42 @@ impl VectorSearch {
         let mut response = VectorSearchResult {
             retrieved_entries: HashMap::new(),
             retrieved_public_keys: HashMap::new(),
+            retrieved_counts: HashMap::new(),
+            retrieved_distances: HashMap::new(),
         };
 
         for (tbl, search_vectors) in per_table_embeddings {
-            tracing::debug!("Running vector search for table {:#?}", tbl.clone());
+            let (dataframe, embedding_column) = self
+                .search_table(
+                    &tbl,
+                    &search_vectors,
+                    table_primary_keys.get(&tbl).cloned().unwrap_or_default(),
+                    n,
+                    options,
+                )
+                .await?;
+            let batch = dataframe.collect().await.boxed().context(DataFusionSnafu)?;
+            let (batch, distances) = take_distances(batch)?;
 
-            // Only support one embedding column per table.
-            let table_provider =
-                self.df
-                    .get_table(tbl.clone())
-                    .await
-                    .ok_or(Error::DataSourceNotFound {
-                        data_source: tbl.to_string(),
-                    })?;
-
-            let embedding_column = get_embedding_table(&table_provider)
-                .and_then(|e| e.get_embedding_columns().first().cloned())
-                .ok_or(Error::NoEmbeddingColumns {
-                    data_source: tbl.to_string(),
-                })?;
+            let entries = batch
+                .iter()
+                .map(|b| embedded_entries(b, &embedding_column))
+                .collect::<Result<Vec<_>>>()?
+                .concat();
 
-            if search_vectors.len() != 1 {
-                return Err(Error::IncorrectNumberOfEmbeddingColumns {
-                    data_source: tbl.to_string(),
-                    num_embeddings: search_vectors.len(),
-                });
+            let count = RetrievalCount::from_batches(n, &batch);
+            if count.shortfall() > 0 {
+                tracing::debug!(
+                    "Vector search for table {tbl} returned {} of {} requested results",
+                    count.returned,
+                    count.requested
+                );
             }
-            match search_vectors.first() {
-                None => unreachable!(),
-                Some(embedding) => {
-                    let mut select_keys = table_primary_keys.get(&tbl).cloned().unwrap_or(vec![]);
-                    select_keys.push(embedding_column.clone());
-
-                    let result = self
-                        .df
-                        .ctx
-                        .sql(&format!(
-                            "SELECT {} FROM {tbl} ORDER BY array_distance({embedding_column}_embedding, {embedding:?}) LIMIT {}", select_keys.join(", "), n
-                        ))
-                        .await
-                        .boxed()
-                        .context(DataFusionSnafu)?;
-                    let batch = result.collect().await.boxed().context(DataFusionSnafu)?;
-
-                    let outt: Vec<_> = batch
-                        .iter()
-                        .map(|b| {
-                            let z =
-                                b.column(b.num_columns() -1).as_any().downcast_ref::<StringArray>().ok_or(
-                                    string_to_boxed_err(
-                                        format!("Expected '{embedding_column}' to be last column of SQL query and return a String type"),
-                                    ),
-                                ).context(DataFusionSnafu);
-                            let zz = z.map(|s| {
-                                s.iter()
-                                    .map(|ss| ss.unwrap_or_default().to_string())
-                                    .collect::<Vec<String>>()
-                            });
-                            zz
-                        })
-                        .collect::<Result<Vec<_>>>()?;
-
-                    let outtt: Vec<String> =
-                        outt.iter().flat_map(std::clone::Clone::clone).collect();
-
-                    response.retrieved_entries.insert(tbl.clone(), outtt);
-                    response.retrieved_public_keys.insert(tbl, batch);
-                }
-            };
+
+            response.retrieved_entries.insert(tbl.clone(), entries);
+            response.retrieved_counts.insert(tbl.clone(), count);
+            response.retrieved_distances.insert(tbl.clone(), distances);
+            response.retrieved_public_keys.insert(tbl, batch);
         }
         tracing::debug!(
             "Relevant data from vector search: {:#?}",
@@ -187,6 +503,94 @@ impl VectorSearch {
         Ok(response)
     }
 
+    /// Plans the search of `tbl` for the rows closest to its embedding in `search_vectors`, returning the
+    /// [`DataFrame`] of the results and the embedding column whose text ends each of them.
+    async fn search_table(
+        &self,
+        tbl: &TableReference,
+        search_vectors: &[Vec<f32>],
+        primary_keys: Vec<String>,
+        n: usize,
+        options: &SearchOptions,
+    ) -> Result<(DataFrame, String)> {
+        tracing::debug!("Running vector search for table {:#?}", tbl.clone());
+
+        // Only support one embedding column per table.
+        let table_provider =
+            self.df
+                .get_table(tbl.clone())
+                .await
+                .ok_or(Error::DataSourceNotFound {
+                    data_source: tbl.to_string(),
+                })?;
+
+        let embedding_table =
+            get_embedding_table(&table_provider).ok_or(Error::NoEmbeddingColumns {
+                data_source: tbl.to_string(),
+            })?;
+        let embedding_column = embedding_table
+            .get_embedding_columns()
+            .first()
+            .cloned()
+            .ok_or(Error::NoEmbeddingColumns {
+                data_source: tbl.to_string(),
+            })?;
+        let vector_column = embedding_table
+            .get_embedding_vector_column(&embedding_column)
+            .ok_or(Error::NoEmbeddingColumns {
+                data_source: tbl.to_string(),
+            })?;
+        let embedding_dimension = embedding_table.get_embedding_dimension(&embedding_column);
+
+        let [embedding] = search_vectors else {
+            return Err(Error::IncorrectNumberOfEmbeddingColumns {
+                data_source: tbl.to_string(),
+                num_embeddings: search_vectors.len(),
+            });
+        };
+        check_embedding_size(tbl, embedding, self.max_embedding_dimension)?;
+
+        if let Some(expected) = embedding_dimension {
+            if usize::try_from(expected).ok() != Some(embedding.len()) {
+                return Err(Error::IncorrectEmbeddingDimension {
+                    data_source: tbl.to_string(),
+                    expected,
+                    actual: embedding.len(),
+                });
+            }
+        }
+
+        let number_rows = primary_keys == [ROW_NUMBER_COLUMN]
+            && table_provider
+                .schema()
+                .column_with_name(ROW_NUMBER_COLUMN)
+                .is_none();
+        let select_keys = search_columns(
+            tbl,
+            &table_provider.schema(),
+            primary_keys,
+            &options.additional_columns,
+            &embedding_column,
+        )?;
+
+        let dataframe = vector_search_dataframe(
+            &self.df.ctx,
+            tbl,
+            &select_keys,
+            &vector_column,
+            embedding,
+            self.distance_metric,
+            options.where_predicate.as_deref(),
+            number_rows,
+            n,
+            options.offset,
+        )
+        .await
+        .boxed()
+        .context(DataFusionSnafu)?;
+        Ok((dataframe, embedding_column))
+    }
+
     /// For the data sources that assumedly exist in the [`DataFusion`] instance, find the embedding models used in each data source.
     async fn find_relevant_embedding_models(
         &self,
@@ -248,7 +652,8 @@ impl VectorSearch {
 
     /// For a set of tables, get their primary keys. Attempt to determine the primary key(s) of the
     /// table from the [`TableProvider`] constraints, and if not provided, use the explicit primary
-    /// keys defined in the spicepod configuration.
+    /// keys defined in the spicepod configuration. Tables with neither are handled by the
+    /// [`MissingPrimaryKeyPolicy`].
     async fn get_primary_keys_with_overrides(
         &self,
         explicit_primary_keys: &HashMap<TableReference, Vec<String>>,
@@ -262,16 +667,46 @@ impl VectorSearch {
                 tbl_to_pks.insert(tbl.clone(), pks);
             } else if let Some(explicit_pks) = explicit_primary_keys.get(&tbl) {
                 tbl_to_pks.insert(tbl.clone(), explicit_pks.clone());
+            } else {
+                match self.missing_primary_key_policy {
+                    MissingPrimaryKeyPolicy::Omit => {}
+                    MissingPrimaryKeyPolicy::Error => {
+                        return MissingPrimaryKeySnafu {
+                            data_source: tbl.to_string(),
+                        }
+                        .fail();
+                    }
+                    MissingPrimaryKeyPolicy::RowNumber => {
+                        tbl_to_pks.insert(tbl.clone(), vec![ROW_NUMBER_COLUMN.to_string()]);
+                    }
+                }
             }
         }
         Ok(tbl_to_pks)
     }
 
-    /// Embed the input text using the specified embedding model.
+    /// Embed the input text using the specified embedding model, checking that the embedding has the model's size.
     async fn embed(&self, input: &str, embedding_model: &str) -> Result<Vec<f32>> {
-        self.embeddings
-            .read()
-            .await
+        self.embed_all(EmbeddingInput::String(input.to_string()), embedding_model)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(Error::EmbeddingError {
+                source: string_to_boxed_err(format!(
+                    "No embeddings returned for input text from {embedding_model}"
+                )),
+            })
+    }
+
+    /// Embed each text of the input using the specified embedding model, checking that the embeddings have the
+    /// model's size.
+    async fn embed_all(
+        &self,
+        input: EmbeddingInput,
+        embedding_model: &str,
+    ) -> Result<Vec<Vec<f32>>> {
+        let models = self.embeddings.read().await;
+        let mut model = models
             .iter()
             .find_map(|(name, model)| {
                 if name.clone() == embedding_model {
@@ -284,18 +719,29 @@ impl VectorSearch {
                 model_name: embedding_model.to_string(),
             })?
             .write()
-            .await
-            .embed(EmbeddingInput::String(input.to_string()))
-            .await
-            .boxed()
-            .context(EmbeddingSnafu)?
-            .first()
-            .cloned()
-            .ok_or(Error::EmbeddingError {
-                source: string_to_boxed_err(format!(
-                    "No embeddings returned for input text from {embedding_model}"
-                )),
-            })
+            .await;
+        let embeddings = model.embed(input).await.boxed().context(EmbeddingSnafu)?;
+        for embedding in &embeddings {
+            check_embedding_dimension(embedding_model, model.size(), embedding)?;
+        }
+        Ok(embeddings)
+    }
+
+    /// Embed the query text using the specified embedding model, or take its embedding from the query embedding cache.
+    async fn embed_query(&self, query: &str, embedding_model: &str) -> Result<Vec<f32>> {
+        let Some(cache) = &self.query_embedding_cache else {
+            return self.embed(query, embedding_model).await;
+        };
+
+        let key = (embedding_model.to_string(), query.to_string());
+        if let Some(embedding) = cache.lock().await.get(&key) {
+            return Ok(embedding.clone());
+        }
+
+        // Not held while embedding, so other queries aren't blocked on the model
+        let embedding = self.embed(query, embedding_model).await?;
+        cache.lock().await.put(key, embedding.clone());
+        Ok(embedding)
     }
 
     /// For each embedding column that a [`TableReference`] contains, calculate the embeddings vector between the query and the column.
@@ -312,11 +758,11 @@ impl VectorSearch {
         // Create embedding(s) for question/statement. `embedded_inputs` model_name -> embedding.
         let mut embedded_inputs: HashMap<ModelKey, Vec<f32>> = HashMap::new();
         for model in embeddings_to_run.values().flatten() {
-            let result = self
-                .embed(&query, model)
-                .await
-                .boxed()
-                .context(EmbeddingSnafu)?;
+            // Tables embedded by the same model share its embedding of the query
+            if embedded_inputs.contains_key(model) {
+                continue;
+            }
+            let result = self.embed_query(&query, model).await?;
             embedded_inputs.insert(model.clone(), result);
         }
 
@@ -351,6 +797,208 @@ fn get_embedding_table(tbl: &Arc<dyn TableProvider>) -> Option<Arc<EmbeddingTabl
     None
 }
 
+fn check_embedding_size(
+    data_source: &TableReference,
+    embedding: &[f32],
+    max_embedding_dimension: Option<usize>,
+) -> Result<()> {
+    match max_embedding_dimension {
+        Some(max) if embedding.len() > max => EmbeddingTooLargeSnafu {
+            data_source: data_source.to_string(),
+            max,
+            actual: embedding.len(),
+        }
+        .fail(),
+        _ => Ok(()),
+    }
+}
+
+/// The embedding columns of a model are lists of its `model_size`, so an embedding of any other size can't be compared
+/// with them. A `model_size` that isn't positive is unknown, and isn't checked.
+fn check_embedding_dimension(
+    embedding_model: &str,
+    model_size: i32,
+    embedding: &[f32],
+) -> Result<()> {
+    match usize::try_from(model_size) {
+        Ok(expected) if expected > 0 && embedding.len() != expected => {
+            EmbeddingDimensionMismatchSnafu {
+                model_name: embedding_model,
+                expected,
+                actual: embedding.len(),
+            }
+            .fail()
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects an empty `where_predicate` rather than ignoring it, as an unfiltered search could return rows the caller
+/// meant to exclude, i.e. another tenant's.
+fn check_where_predicate(where_predicate: Option<&str>) -> Result<()> {
+    ensure!(
+        where_predicate.map_or(true, |predicate| !predicate.trim().is_empty()),
+        EmptyWherePredicateSnafu
+    );
+    Ok(())
+}
+
+/// The columns selected from `tbl` by a vector search: its `primary_keys`, the `additional_columns` not already
+/// selected, and last the `embedding_column` the embeddings were computed from.
+fn search_columns(
+    tbl: &TableReference,
+    schema: &Schema,
+    primary_keys: Vec<String>,
+    additional_columns: &[String],
+    embedding_column: &str,
+) -> Result<Vec<String>> {
+    let mut columns = primary_keys;
+    for column in additional_columns {
+        ensure!(
+            schema.column_with_name(column).is_some(),
+            ColumnNotFoundSnafu {
+                data_source: tbl.to_string(),
+                column,
+            }
+        );
+        if column != embedding_column && !columns.contains(column) {
+            columns.push(column.clone());
+        }
+    }
+    columns.push(embedding_column.to_string());
+    Ok(columns)
+}
+
+/// The [`DataFrame`] selecting `select_keys` from the `n` rows of `tbl` whose `vector_column` is closest to
+/// `embedding` by `metric` after skipping the `offset` closest, followed by their distance from `embedding` as
+/// [`DISTANCE_COLUMN`]. Only the rows matching `where_predicate` are ranked.
+///
+/// With `number_rows`, the rows of `tbl` are numbered from 1 before they're filtered, and the numbers can be selected
+/// as [`ROW_NUMBER_COLUMN`].
+///
+/// The embedding is passed to the distance function as a literal value rather than formatted into the SQL, so it
+/// is compared exactly as computed and large embeddings don't have to be parsed.
+#[allow(clippy::too_many_arguments)]
+async fn vector_search_dataframe(
+    ctx: &SessionContext,
+    tbl: &TableReference,
+    select_keys: &[String],
+    vector_column: &str,
+    embedding: &[f32],
+    metric: DistanceMetric,
+    where_predicate: Option<&str>,
+    number_rows: bool,
+    n: usize,
+    offset: usize,
+) -> DataFusionResult<DataFrame> {
+    // Parenthesized so the predicate's own `OR`s can't escape the clause
+    let where_clause = where_predicate
+        .map(|predicate| format!(" WHERE ({predicate})"))
+        .unwrap_or_default();
+    // Aliased as the table, so the predicate can still qualify its columns with the table name
+    let source = if number_rows {
+        format!(
+            "(SELECT *, ROW_NUMBER() OVER () AS {ROW_NUMBER_COLUMN} FROM {tbl}) AS {}",
+            tbl.table()
+        )
+    } else {
+        tbl.to_string()
+    };
+    let candidates = ctx
+        .sql(&format!(
+            "SELECT {}, {vector_column} FROM {source}{where_clause}",
+            select_keys.join(", "),
+        ))
+        .await?;
+
+    // The embeddings are the last column, replaced by their distance from `embedding`
+    let mut columns: Vec<Expr> = candidates
+        .schema()
+        .columns()
+        .into_iter()
+        .map(Expr::Column)
+        .collect();
+    let embeddings = columns
+        .pop()
+        .ok_or_else(|| DataFusionError::Plan(format!("No embedding column selected from {tbl}")))?;
+    columns.push(
+        ScalarUDF::from(ArrayDistance::with_metric(metric))
+            .call(vec![embeddings, lit(vector_scalar(embedding)?)])
+            .alias(DISTANCE_COLUMN),
+    );
+
+    candidates
+        .select(columns)?
+        .sort(vec![col(DISTANCE_COLUMN).sort(true, false)])?
+        .limit(offset, Some(n))
+}
+
+/// The text of `embedding_column`, the last column of a vector search result `batch`, of each row.
+fn embedded_entries(batch: &RecordBatch, embedding_column: &str) -> Result<Vec<String>> {
+    let entries = batch
+        .column(batch.num_columns() - 1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or(string_to_boxed_err(format!(
+            "Expected '{embedding_column}' to be last column of SQL query and return a String type"
+        )))
+        .context(DataFusionSnafu)?;
+    Ok(entries
+        .iter()
+        .map(|entry| entry.unwrap_or_default().to_string())
+        .collect())
+}
+
+/// Removes [`DISTANCE_COLUMN`] from the vector search results, returning the remaining batches and the distances.
+/// Rows without an embedding have no distance, and are reported as infinitely far away.
+fn take_distances(batches: Vec<RecordBatch>) -> Result<(Vec<RecordBatch>, Vec<f32>)> {
+    let mut distances = Vec::new();
+    let batches = batches
+        .into_iter()
+        .map(|batch| {
+            let (batch, batch_distances) = take_batch_distances(batch)?;
+            distances.extend(batch_distances);
+            Ok(batch)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((batches, distances))
+}
+
+/// Like [`take_distances`], for a single batch.
+fn take_batch_distances(mut batch: RecordBatch) -> Result<(RecordBatch, Vec<f32>)> {
+    let index = batch
+        .schema()
+        .index_of(DISTANCE_COLUMN)
+        .boxed()
+        .context(DataFusionSnafu)?;
+    let column = cast(&batch.remove_column(index), &DataType::Float32)
+        .boxed()
+        .context(DataFusionSnafu)?;
+    let distances = column
+        .as_primitive::<Float32Type>()
+        .iter()
+        .map(|distance| distance.unwrap_or(f32::INFINITY))
+        .collect();
+    Ok((batch, distances))
+}
+
+/// An embedding as a value of the embedding columns' type, `FixedSizeList(Float32, dimension)`.
+fn vector_scalar(vector: &[f32]) -> DataFusionResult<ScalarValue> {
+    let dimension = i32::try_from(vector.len()).map_err(|_| {
+        DataFusionError::Plan(format!(
+            "Embedding with {} dimensions is too large",
+            vector.len()
+        ))
+    })?;
+    let list = FixedSizeListArray::try_new(
+        Arc::new(Field::new("item", DataType::Float32, true)),
+        dimension,
+        Arc::new(Float32Array::from(vector.to_vec())),
+        None,
+    )?;
+    Ok(ScalarValue::FixedSizeList(Arc::new(list)))
+}
+
 fn string_to_boxed_err(s: String) -> Box<dyn std::error::Error + Send + Sync> {
     Box::<dyn std::error::Error + Send + Sync>::from(s)
 }
@@ -371,3 +1019,772 @@ pub async fn compute_primary_keys(
             .collect::<HashMap<TableReference, Vec<_>>>()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use arrow::{
+        array::{ArrayRef, Int64Array},
+        datatypes::{Int64Type, UInt64Type},
+    };
+    use async_trait::async_trait;
+    use datafusion::datasource::MemTable;
+    use llms::embeddings::Embed;
+
+    use super::*;
+
+    /// A table of `rows` documents whose embeddings are `[rows - 1 - id, 0.0]`, so the last document is the closest
+    /// to the origin.
+    fn documents_context(rows: u16) -> SessionContext {
+        documents_context_with_embeddings(
+            &(0..rows)
+                .map(|i| [f32::from(rows - 1 - i), 0.0])
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// A table with a document per embedding, whose `id` is the index of its embedding and `url` is
+    /// `https://example.com/{id}`.
+    fn documents_context_with_embeddings(embeddings: &[[f32; 2]]) -> SessionContext {
+        let rows = i64::try_from(embeddings.len()).expect("few embeddings");
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "id",
+                Arc::new(Int64Array::from_iter_values(0..rows)) as ArrayRef,
+            ),
+            (
+                "content",
+                Arc::new(StringArray::from_iter_values(
+                    (0..rows).map(|i| format!("document {i}")),
+                )) as ArrayRef,
+            ),
+            (
+                "url",
+                Arc::new(StringArray::from_iter_values(
+                    (0..rows).map(|i| format!("https://example.com/{i}")),
+                )) as ArrayRef,
+            ),
+            (
+                "content_embedding",
+                Arc::new(
+                    FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                        embeddings
+                            .iter()
+                            .map(|embedding| Some(embedding.map(Some).to_vec())),
+                        2,
+                    ),
+                ) as ArrayRef,
+            ),
+        ])
+        .expect("valid record batch");
+
+        let ctx = SessionContext::new();
+        ctx.register_batch("docs", batch)
+            .expect("table is registered");
+        ctx
+    }
+
+    fn ids(batches: &[RecordBatch]) -> Vec<i64> {
+        batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+            .collect()
+    }
+
+    async fn search_documents(ctx: &SessionContext, n: usize) -> Vec<RecordBatch> {
+        search_documents_by(ctx, &[0.0, 0.0], DistanceMetric::L2, None, n).await
+    }
+
+    async fn search_documents_by(
+        ctx: &SessionContext,
+        embedding: &[f32],
+        metric: DistanceMetric,
+        where_predicate: Option<&str>,
+        n: usize,
+    ) -> Vec<RecordBatch> {
+        search_documents_page(ctx, embedding, metric, where_predicate, n, 0).await
+    }
+
+    async fn search_documents_page(
+        ctx: &SessionContext,
+        embedding: &[f32],
+        metric: DistanceMetric,
+        where_predicate: Option<&str>,
+        n: usize,
+        offset: usize,
+    ) -> Vec<RecordBatch> {
+        vector_search_dataframe(
+            ctx,
+            &TableReference::bare("docs"),
+            &["id".to_string(), "content".to_string()],
+            "content_embedding",
+            embedding,
+            metric,
+            where_predicate,
+            false,
+            n,
+            offset,
+        )
+        .await
+        .expect("search is planned")
+        .collect()
+        .await
+        .expect("search is executed")
+    }
+
+    #[tokio::test]
+    async fn test_offset_pages_through_results() {
+        let ctx = documents_context(5);
+
+        let first_page =
+            search_documents_page(&ctx, &[0.0, 0.0], DistanceMetric::L2, None, 2, 0).await;
+        let second_page =
+            search_documents_page(&ctx, &[0.0, 0.0], DistanceMetric::L2, None, 2, 2).await;
+        assert_eq!(ids(&first_page), vec![4, 3]);
+        assert_eq!(ids(&second_page), vec![2, 1]);
+
+        let (_, distances) = take_distances(second_page).expect("distances are read");
+        assert_eq!(distances, vec![4.0, 9.0]);
+
+        // The last page is short
+        let last_page =
+            search_documents_page(&ctx, &[0.0, 0.0], DistanceMetric::L2, None, 2, 4).await;
+        assert_eq!(ids(&last_page), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_additional_columns_are_selected() {
+        let ctx = documents_context(3);
+        let tbl = TableReference::bare("docs");
+        let schema = ctx
+            .table_provider(tbl.clone())
+            .await
+            .expect("table is registered")
+            .schema();
+
+        // Columns that are already selected aren't selected twice
+        let columns = search_columns(
+            &tbl,
+            &schema,
+            vec!["id".to_string()],
+            &["url".to_string(), "id".to_string(), "content".to_string()],
+            "content",
+        )
+        .expect("columns are selected");
+        assert_eq!(columns, vec!["id", "url", "content"]);
+
+        let batches = vector_search_dataframe(
+            &ctx,
+            &tbl,
+            &columns,
+            "content_embedding",
+            &[0.0, 0.0],
+            DistanceMetric::L2,
+            None,
+            false,
+            2,
+            0,
+        )
+        .await
+        .expect("search is planned")
+        .collect()
+        .await
+        .expect("search is executed");
+        // The batches returned as `retrieved_public_keys`
+        let (batches, _) = take_distances(batches).expect("distances are read");
+        let urls: Vec<_> = batches
+            .iter()
+            .flat_map(|batch| {
+                let urls = batch.column_by_name("url").expect("url is selected");
+                urls.as_string::<i32>()
+                    .iter()
+                    .map(|url| url.unwrap_or_default().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(urls, vec!["https://example.com/2", "https://example.com/1"]);
+
+        let err = search_columns(&tbl, &schema, vec![], &["title".to_string()], "content")
+            .expect_err("missing column is rejected");
+        assert!(matches!(err, Error::ColumnNotFound { ref column, .. } if column == "title"));
+        assert_eq!(
+            err.to_string(),
+            "Data source docs does not have a column title to select"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_vector_column_with_any_name() {
+        let ctx = documents_context(3);
+        ctx.sql("CREATE VIEW vectors AS SELECT id, content, content_embedding AS vector FROM docs")
+            .await
+            .expect("view is created");
+
+        let batches = vector_search_dataframe(
+            &ctx,
+            &TableReference::bare("vectors"),
+            &["id".to_string(), "content".to_string()],
+            "vector",
+            &[0.0, 0.0],
+            DistanceMetric::L2,
+            None,
+            false,
+            2,
+            0,
+        )
+        .await
+        .expect("search is planned")
+        .collect()
+        .await
+        .expect("search is executed");
+        assert_eq!(ids(&batches), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_vector_scalar_is_exact() {
+        let vector = vec![
+            0.1,
+            1.0 / 3.0,
+            -2.5,
+            -0.0,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            1e-40,
+            123_456.79,
+        ];
+
+        let ScalarValue::FixedSizeList(list) = vector_scalar(&vector).expect("vector is converted")
+        else {
+            panic!("vector is a FixedSizeList");
+        };
+        assert_eq!(
+            list.data_type(),
+            &DataType::new_fixed_size_list(DataType::Float32, 8, true)
+        );
+        let values = list.value(0);
+        let values = values.as_primitive::<Float32Type>();
+        for (value, expected) in values.values().iter().zip(vector) {
+            assert_eq!(value.to_bits(), expected.to_bits(), "{expected}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_extreme_magnitudes() {
+        let ctx =
+            documents_context_with_embeddings(&[[1e-40, 0.0], [2e-40, 1e-45], [f32::MAX, 0.0]]);
+
+        // Subnormal embeddings are compared exactly, so the identical one is ranked first
+        let batches = search_documents_by(&ctx, &[2e-40, 1e-45], DistanceMetric::L2, None, 3).await;
+        assert_eq!(ids(&batches), vec![1, 0, 2]);
+
+        let batches =
+            search_documents_by(&ctx, &[f32::MAX, 0.0], DistanceMetric::L2, None, 1).await;
+        let (batches, distances) = take_distances(batches).expect("distances are read");
+        assert_eq!(ids(&batches), vec![2]);
+        assert_eq!(distances, vec![0.0]);
+    }
+
+    /// An embedding model that counts the texts it embeds, embedding every text as `[id]`.
+    struct CountingEmbed {
+        id: f32,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Embed for CountingEmbed {
+        async fn embed(
+            &mut self,
+            _input: EmbeddingInput,
+        ) -> llms::embeddings::Result<Vec<Vec<f32>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![vec![self.id]])
+        }
+
+        fn size(&self) -> i32 {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_embeddings_are_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let models: EmbeddingModelStore = [("a", 1.0), ("b", 2.0)]
+            .into_iter()
+            .map(|(name, id)| {
+                let model: Box<dyn Embed> = Box::new(CountingEmbed {
+                    id,
+                    calls: Arc::clone(&calls),
+                });
+                (name.to_string(), RwLock::new(model))
+            })
+            .collect();
+        let vector_search = VectorSearch::new(
+            Arc::new(DataFusion::new()),
+            Arc::new(RwLock::new(models)),
+            HashMap::new(),
+        )
+        .with_query_embedding_cache(NonZeroUsize::new(2).expect("capacity is non-zero"));
+
+        let embed = |query: &'static str, model: &'static str| {
+            let vector_search = &vector_search;
+            async move {
+                vector_search
+                    .embed_query(query, model)
+                    .await
+                    .expect("query is embedded")
+            }
+        };
+
+        assert_eq!(embed("hello", "a").await, vec![1.0]);
+        assert_eq!(embed("hello", "a").await, vec![1.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // The same query is embedded again by another model
+        assert_eq!(embed("hello", "b").await, vec![2.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The least recently searched embedding is evicted once the cache is full
+        embed("bye", "a").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        embed("hello", "b").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        embed("hello", "a").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    /// An embedding model that embeds every text as a vector of `width` zeros, whatever its declared `size`.
+    struct FixedWidthEmbed {
+        size: i32,
+        width: usize,
+    }
+
+    #[async_trait]
+    impl Embed for FixedWidthEmbed {
+        async fn embed(
+            &mut self,
+            _input: EmbeddingInput,
+        ) -> llms::embeddings::Result<Vec<Vec<f32>>> {
+            Ok(vec![vec![0.0; self.width]])
+        }
+
+        fn size(&self) -> i32 {
+            self.size
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedding_dimension_mismatch() {
+        let models: EmbeddingModelStore = [("wrong", 2, 3), ("right", 2, 2), ("unsized", 0, 3)]
+            .into_iter()
+            .map(|(name, size, width)| {
+                let model: Box<dyn Embed> = Box::new(FixedWidthEmbed { size, width });
+                (name.to_string(), RwLock::new(model))
+            })
+            .collect();
+        let vector_search = VectorSearch::new(
+            Arc::new(DataFusion::new()),
+            Arc::new(RwLock::new(models)),
+            HashMap::new(),
+        );
+
+        let err = vector_search
+            .embed("hello", "wrong")
+            .await
+            .expect_err("wrong width embedding is rejected");
+        assert!(matches!(
+            err,
+            Error::EmbeddingDimensionMismatch {
+                expected: 2,
+                actual: 3,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Embedding model wrong returned an embedding with 3 dimensions, but the columns it embeds have 2"
+        );
+
+        assert_eq!(
+            vector_search
+                .embed("hello", "right")
+                .await
+                .expect("embedding has the model's size"),
+            vec![0.0; 2]
+        );
+        assert_eq!(
+            vector_search
+                .embed("hello", "unsized")
+                .await
+                .expect("embedding of a model without a size isn't checked"),
+            vec![0.0; 3]
+        );
+    }
+
+    /// An embedding model that embeds every text as `[length, 0.0]`, recording the texts of each request.
+    struct LengthEmbed {
+        requests: Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+    }
+
+    #[async_trait]
+    impl Embed for LengthEmbed {
+        async fn embed(
+            &mut self,
+            input: EmbeddingInput,
+        ) -> llms::embeddings::Result<Vec<Vec<f32>>> {
+            let texts = match input {
+                EmbeddingInput::String(text) => vec![text],
+                EmbeddingInput::StringArray(texts) => texts,
+                _ => unimplemented!("only texts are embedded"),
+            };
+            let embeddings = texts
+                .iter()
+                .map(|text| {
+                    vec![
+                        f32::from(u16::try_from(text.len()).expect("short text")),
+                        0.0,
+                    ]
+                })
+                .collect();
+            self.requests
+                .lock()
+                .expect("requests are recorded")
+                .push(texts);
+            Ok(embeddings)
+        }
+
+        fn size(&self) -> i32 {
+            2
+        }
+    }
+
+    /// A search over a `docs` table whose `content` column holds `contents`, embedded by `LengthEmbed` into
+    /// `vector_column`, if it is set. Also returns the texts of each embedding request.
+    async fn length_embedded_docs(
+        contents: Vec<&str>,
+        vector_column: Option<&str>,
+    ) -> (VectorSearch, Arc<std::sync::Mutex<Vec<Vec<String>>>>) {
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model: Box<dyn Embed> = Box::new(LengthEmbed {
+            requests: Arc::clone(&requests),
+        });
+        let models: EmbeddingModelStore =
+            HashMap::from([("length".to_string(), RwLock::new(model))]);
+        let models = Arc::new(RwLock::new(models));
+
+        let batch = RecordBatch::try_from_iter(vec![(
+            "content",
+            Arc::new(StringArray::from(contents)) as ArrayRef,
+        )])
+        .expect("valid record batch");
+        let docs = MemTable::try_new(batch.schema(), vec![vec![batch]]).expect("table is created");
+        let mut docs = EmbeddingTable::new(
+            Arc::new(docs),
+            HashMap::from([("content".to_string(), "length".to_string())]),
+            Arc::clone(&models),
+        )
+        .await;
+        if let Some(vector_column) = vector_column {
+            docs = docs.with_vector_column("content", vector_column);
+        }
+        let df = Arc::new(DataFusion::new());
+        df.ctx
+            .register_table("docs", Arc::new(docs))
+            .expect("table is registered");
+
+        (VectorSearch::new(df, models, HashMap::new()), requests)
+    }
+
+    #[tokio::test]
+    async fn test_search_many() {
+        let (vector_search, requests) = length_embedded_docs(vec!["a", "bb", "cccc"], None).await;
+        let results = vector_search
+            .search_many(
+                vec!["z".to_string(), "zzzz".to_string(), "z".to_string()],
+                vec![TableReference::bare("docs")],
+                RetrievalLimit::TopN(1),
+            )
+            .await
+            .expect("queries are searched");
+
+        let closest: Vec<_> = results
+            .iter()
+            .map(|result| result.retrieved_entries[&TableReference::bare("docs")].clone())
+            .collect();
+        assert_eq!(closest, vec![vec!["a"], vec!["cccc"], vec!["a"]]);
+
+        // The repeated query is embedded once, together with the other one
+        let requests = requests.lock().expect("requests are recorded");
+        let query_requests: Vec<_> = requests
+            .iter()
+            .filter(|texts| texts.iter().any(|text| text.starts_with('z')))
+            .collect();
+        assert_eq!(
+            query_requests,
+            vec![&vec!["z".to_string(), "zzzz".to_string()]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_stream_yields_ranked_batches() {
+        let (vector_search, _) =
+            length_embedded_docs(vec!["aaaaaa", "a", "aaaa", "aaaaa", "aa", "aaa"], None).await;
+        // Small batches, so the results are streamed in several of them
+        vector_search
+            .df
+            .ctx
+            .sql("SET datafusion.execution.batch_size = 2")
+            .await
+            .expect("batch size is set");
+
+        let mut results = vector_search
+            .search_stream(
+                "z".to_string(),
+                vec![TableReference::bare("docs")],
+                RetrievalLimit::TopN(5),
+                SearchOptions::default(),
+            )
+            .await
+            .expect("search is planned");
+
+        let mut entries = Vec::new();
+        let mut distances = Vec::new();
+        while let Some(result) = results.next().await {
+            let result = result.expect("results are streamed");
+            assert_eq!(result.table, TableReference::bare("docs"));
+            assert!(result.entries.len() <= 2);
+            assert_eq!(result.entries.len(), result.distances.len());
+            entries.push(result.entries);
+            distances.extend(result.distances);
+        }
+        assert!(entries.len() > 1);
+        assert_eq!(entries.concat(), vec!["a", "aa", "aaa", "aaaa", "aaaaa"]);
+        assert_eq!(distances, vec![0.0, 1.0, 4.0, 9.0, 16.0]);
+    }
+
+    #[tokio::test]
+    async fn test_search_embedding_table_with_configured_vector_column() {
+        let (vector_search, _) =
+            length_embedded_docs(vec!["aaa", "a", "aa"], Some("content_vectors")).await;
+        let result = vector_search
+            .search(
+                "z".to_string(),
+                vec![TableReference::bare("docs")],
+                RetrievalLimit::TopN(2),
+                SearchOptions::default(),
+            )
+            .await
+            .expect("table is searched");
+        assert_eq!(
+            result.retrieved_entries[&TableReference::bare("docs")],
+            vec!["a", "aa"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_threshold_limit_is_rejected() {
+        let vector_search = VectorSearch::new(
+            Arc::new(DataFusion::new()),
+            Arc::new(RwLock::new(HashMap::new())),
+            HashMap::new(),
+        );
+        let tables = vec![TableReference::bare("docs")];
+
+        let err = vector_search
+            .search(
+                "z".to_string(),
+                tables.clone(),
+                RetrievalLimit::Threshold(0.5),
+                SearchOptions::default(),
+            )
+            .await
+            .err();
+        assert!(matches!(err, Some(Error::UnsupportedRetrievalLimit)));
+
+        let err = vector_search
+            .search_stream(
+                "z".to_string(),
+                tables,
+                RetrievalLimit::Threshold(0.5),
+                SearchOptions::default(),
+            )
+            .await
+            .err();
+        assert!(matches!(err, Some(Error::UnsupportedRetrievalLimit)));
+    }
+
+    #[test]
+    fn test_check_embedding_size() {
+        let table = TableReference::bare("docs");
+        let embedding = vec![0.5; 8];
+
+        assert!(check_embedding_size(&table, &embedding, None).is_ok());
+        assert!(check_embedding_size(&table, &embedding, Some(8)).is_ok());
+
+        let err = check_embedding_size(&table, &embedding, Some(4))
+            .expect_err("oversized embedding is rejected");
+        assert!(matches!(
+            err,
+            Error::EmbeddingTooLarge {
+                max: 4,
+                actual: 8,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Embedding for docs has 8 dimensions, exceeding the maximum of 4"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_count_shortfall() {
+        let ctx = documents_context(3);
+
+        let batches = search_documents(&ctx, 10).await;
+        let count = RetrievalCount::from_batches(10, &batches);
+
+        assert_eq!(
+            count,
+            RetrievalCount {
+                requested: 10,
+                returned: 3
+            }
+        );
+        assert_eq!(count.shortfall(), 7);
+
+        let batches = search_documents(&ctx, 2).await;
+        assert_eq!(RetrievalCount::from_batches(2, &batches).shortfall(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_distances_are_sorted() {
+        let ctx = documents_context(5);
+
+        let batches = search_documents(&ctx, 5).await;
+        let (batches, distances) = take_distances(batches).expect("distances are read");
+
+        // `array_distance` is the squared Euclidean distance
+        assert_eq!(distances, vec![0.0, 1.0, 4.0, 9.0, 16.0]);
+        assert!(distances.windows(2).all(|d| d[0] <= d[1]));
+        for batch in &batches {
+            assert!(batch.schema().index_of(DISTANCE_COLUMN).is_err());
+            assert_eq!(batch.num_columns(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_distance_metric_ordering() {
+        // Document 0 points the same way as the search embedding but is far from it, document 1 is close to it
+        // but at 45 degrees
+        let ctx = documents_context_with_embeddings(&[[10.0, 0.0], [0.5, 0.5]]);
+
+        let l2 = ids(&search_documents_by(&ctx, &[1.0, 0.0], DistanceMetric::L2, None, 2).await);
+        let cosine =
+            ids(&search_documents_by(&ctx, &[1.0, 0.0], DistanceMetric::Cosine, None, 2).await);
+        let inner_product =
+            ids(
+                &search_documents_by(&ctx, &[1.0, 0.0], DistanceMetric::InnerProduct, None, 2)
+                    .await,
+            );
+
+        assert_eq!(l2, vec![1, 0]);
+        assert_eq!(cosine, vec![0, 1]);
+        assert_eq!(inner_product, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_where_predicate_filters_before_ranking() {
+        let ctx = documents_context(5);
+
+        assert_eq!(ids(&search_documents(&ctx, 2).await), vec![4, 3]);
+
+        // The closest documents are excluded, so the next closest are ranked instead of fewer results returned
+        let batches = search_documents_by(
+            &ctx,
+            &[0.0, 0.0],
+            DistanceMetric::L2,
+            Some("id < 3 OR id > 10"),
+            2,
+        )
+        .await;
+        assert_eq!(ids(&batches), vec![2, 1]);
+        assert_eq!(RetrievalCount::from_batches(2, &batches).shortfall(), 0);
+
+        assert!(check_where_predicate(None).is_ok());
+        assert!(check_where_predicate(Some("id < 3")).is_ok());
+        assert!(matches!(
+            check_where_predicate(Some("  ")),
+            Err(Error::EmptyWherePredicate)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_missing_primary_key_policy() {
+        let df = Arc::new(DataFusion::new());
+        let docs = documents_context(5)
+            .table_provider(TableReference::bare("docs"))
+            .await
+            .expect("table is registered");
+        df.ctx
+            .register_table("docs", docs)
+            .expect("table is registered");
+        let tables = vec![TableReference::bare("docs")];
+        let vector_search = |policy| {
+            VectorSearch::new(
+                Arc::clone(&df),
+                Arc::new(RwLock::new(EmbeddingModelStore::new())),
+                HashMap::new(),
+            )
+            .with_missing_primary_key_policy(policy)
+        };
+
+        let primary_keys = vector_search(MissingPrimaryKeyPolicy::Omit)
+            .get_primary_keys_with_overrides(&HashMap::new(), tables.clone())
+            .await
+            .expect("primary keys are omitted");
+        assert!(primary_keys.is_empty());
+
+        let err = vector_search(MissingPrimaryKeyPolicy::Error)
+            .get_primary_keys_with_overrides(&HashMap::new(), tables.clone())
+            .await
+            .expect_err("table without primary keys is rejected");
+        assert_eq!(
+            err.to_string(),
+            "Data source docs has no primary key to identify its search results"
+        );
+
+        let primary_keys = vector_search(MissingPrimaryKeyPolicy::RowNumber)
+            .get_primary_keys_with_overrides(&HashMap::new(), tables)
+            .await
+            .expect("row numbers are the primary keys");
+        assert_eq!(
+            primary_keys[&TableReference::bare("docs")],
+            vec![ROW_NUMBER_COLUMN]
+        );
+
+        // Rows are numbered before they're filtered, so each result has its row's position in the table
+        let batches = vector_search_dataframe(
+            &df.ctx,
+            &TableReference::bare("docs"),
+            &[ROW_NUMBER_COLUMN.to_string(), "content".to_string()],
+            "content_embedding",
+            &[0.0, 0.0],
+            DistanceMetric::L2,
+            Some("docs.id < 3"),
+            true,
+            2,
+            0,
+        )
+        .await
+        .expect("search is planned")
+        .collect()
+        .await
+        .expect("search is executed");
+        let row_numbers: Vec<_> = batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<UInt64Type>().values().to_vec())
+            .collect();
+        assert_eq!(row_numbers, vec![3, 2]);
+    }
+}
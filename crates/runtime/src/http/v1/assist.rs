@@ -36,7 +36,7 @@ use tokio::sync::RwLock;
 use futures::StreamExt;
 
 use crate::{
-    embeddings::vector_search::{RetrievalLimit, VectorSearch, VectorSearchResult},
+    embeddings::vector_search::{RetrievalLimit, SearchOptions, VectorSearch, VectorSearchResult},
     model::LLMModelStore,
 };
 
@@ -231,7 +231,12 @@ pub(crate) async fn post(
         .collect();
 
     let relevant_data = match vs
-        .search(payload.text.clone(), input_tables, RetrievalLimit::TopN(3))
+        .search(
+            payload.text.clone(),
+            input_tables,
+            RetrievalLimit::TopN(3),
+            SearchOptions::default(),
+        )
         .await
     {
         Ok(relevant_data) => relevant_data,
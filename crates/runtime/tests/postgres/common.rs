@@ -85,7 +85,18 @@ pub(super) async fn start_postgres_docker_container(
 #[instrument]
 pub(super) async fn get_postgres_connection_pool() -> Result<PostgresConnectionPool, anyhow::Error>
 {
-    let pool = PostgresConnectionPool::new(Arc::new(get_pg_params())).await?;
+    get_postgres_connection_pool_with_params(&[]).await
+}
+
+/// Like `get_postgres_connection_pool`, with the parameters `extra_params` added to the connection ones.
+pub(super) async fn get_postgres_connection_pool_with_params(
+    extra_params: &[(&str, &str)],
+) -> Result<PostgresConnectionPool, anyhow::Error> {
+    let mut params = get_pg_params();
+    for (key, value) in extra_params {
+        params.insert((*key).to_string(), SecretString::from((*value).to_string()));
+    }
+    let pool = PostgresConnectionPool::new(Arc::new(params)).await?;
 
     Ok(pool)
 }
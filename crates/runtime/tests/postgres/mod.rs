@@ -17,17 +17,38 @@ limitations under the License.
 use std::sync::Arc;
 
 use arrow::{
-    array::TimestampMillisecondArray,
-    datatypes::{DataType, TimeUnit},
+    array::{Array, ArrayAccessor, AsArray, StringArray, TimestampMillisecondArray},
+    datatypes::{
+        DataType, Date32Type, Decimal128Type, Int32Type, Int64Type, TimeUnit,
+        TimestampMillisecondType, UInt32Type, UInt64Type,
+    },
+};
+use arrow_sql_gen::postgres::{
+    columns_to_schema_with_options, rows_to_arrow, rows_to_arrow_parallel,
+    rows_to_arrow_with_options, ArrowConversionOptions, PG_OID_METADATA_KEY, PG_TYPE_METADATA_KEY,
+    PG_TYPMOD_METADATA_KEY,
 };
 use data_components::postgres::DynPostgresConnectionPool;
-use datafusion::execution::context::SessionContext;
+use datafusion::{datasource::TableProvider, execution::context::SessionContext};
+use db_connection_pool::dbconnection::{postgresconn::PostgresConnection, AsyncDbConnection};
+use futures::TryStreamExt;
 use sql_provider_datafusion::SqlTable;
 
 use crate::init_tracing;
 
 mod common;
 
+async fn count_rows(conn: &PostgresConnection, table: &str) -> i64 {
+    let batches = conn
+        .query_arrow(&format!("SELECT COUNT(*) FROM {table}"), &[])
+        .await
+        .expect("query should succeed")
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("batches should be collected");
+    batches[0].column(0).as_primitive::<Int64Type>().value(0)
+}
+
 #[tokio::test]
 async fn test_postgres_types() -> Result<(), anyhow::Error> {
     let _tracing = init_tracing(Some("integration=debug,info"));
@@ -58,10 +79,52 @@ CREATE TABLE test (
             &[],
         )
         .await.expect("inserted data");
+
+    // The server version is captured when the pool connects, xid8 exists since Postgres 13
+    let server_version_num = pool
+        .server_version_num()
+        .expect("server version is captured");
+    assert!(server_version_num >= 130_000);
+    assert_eq!(db_conn.server_version_num(), Some(server_version_num));
+    let record_batch = db_conn
+        .query_arrow("SELECT '42'::xid8 AS xid", &[])
+        .await
+        .expect("xid8 is queried")
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("batches are collected")
+        .remove(0);
+    assert_eq!(
+        record_batch.columns()[0]
+            .as_primitive::<UInt64Type>()
+            .value(0),
+        42
+    );
+
+    // A type the server doesn't have is rejected with a clear error, i.e. xid8 before Postgres 13
+    let old_server_conn = pool
+        .connect_direct()
+        .await
+        .expect("connection can be established")
+        .with_server_version_num(Some(120_000));
+    let Err(err) = old_server_conn
+        .query_arrow("SELECT '42'::xid8 AS xid", &[])
+        .await
+    else {
+        panic!("xid8 is rejected before Postgres 13");
+    };
+    assert!(err
+        .to_string()
+        .contains("Postgres type xid8 requires server version 130000 or later"));
+
     let sqltable_pool: Arc<DynPostgresConnectionPool> = Arc::new(pool);
     let table = SqlTable::new("postgres", &sqltable_pool, "test", None)
         .await
         .expect("table can be created");
+    // The primary key is NOT NULL
+    let schema = table.schema();
+    assert!(!schema.field(0).is_nullable());
+    assert!(schema.field(1).is_nullable());
     ctx.register_table("test_datafusion", Arc::new(table))
         .expect("Table should be registered");
     let sql = "SELECT id, created_at FROM test_datafusion";
@@ -75,13 +138,31 @@ CREATE TABLE test (
         .first()
         .expect("At least 1 record batch is returned");
     assert_eq!(record_batch.num_rows(), 1);
+    // The batches have the nullability of the table schema
+    assert!(!record_batch.schema().field(0).is_nullable());
+    assert!(record_batch.schema().field(1).is_nullable());
+
+    // A NOT NULL column on the nullable side of an outer join holds NULLs, so it stays nullable
+    let record_batch = db_conn
+        .query_arrow(
+            "SELECT test.id FROM (SELECT 1) AS one LEFT JOIN test ON false",
+            &[],
+        )
+        .await
+        .expect("outer join is queried")
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("batches are collected")
+        .remove(0);
+    assert!(record_batch.schema().field(0).is_nullable());
+    assert!(record_batch.column(0).is_null(0));
 
     assert_eq!(
         DataType::Utf8,
         *record_batch.schema().fields()[0].data_type()
     );
     assert_eq!(
-        DataType::Timestamp(TimeUnit::Millisecond, None),
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
         *record_batch.schema().fields()[1].data_type()
     );
 
@@ -102,6 +183,458 @@ CREATE TABLE test (
             .value(0)
     );
 
+    db_conn
+        .conn
+        .execute(
+            "
+CREATE TABLE test_arrays (
+    ids UUID[],
+    dates DATE[],
+    timestamps TIMESTAMP[],
+    names VARCHAR[],
+    codes CHAR(3)[]
+);",
+            &[],
+        )
+        .await
+        .expect("table is created");
+    db_conn
+        .conn
+        .execute(
+            "INSERT INTO test_arrays (ids, dates, timestamps, names, codes) VALUES ('{5ea5a3ac-07a0-4d4d-b201-faff68d8356c, 4c2a8f0e-2f4b-4a31-9c56-0ab1e0b2e4a1}', '{2023-05-02, NULL}', '{2023-05-02 14:30:00}', ARRAY['a','b']::varchar[], ARRAY['x', NULL]::char(3)[]);",
+            &[],
+        )
+        .await
+        .expect("inserted data");
+    let table = SqlTable::new("postgres", &sqltable_pool, "test_arrays", None)
+        .await
+        .expect("table can be created");
+    ctx.register_table("test_arrays_datafusion", Arc::new(table))
+        .expect("Table should be registered");
+    let record_batch = ctx
+        .sql("SELECT ids, dates, timestamps, names, codes FROM test_arrays_datafusion")
+        .await
+        .expect("DataFrame can be created from query")
+        .collect()
+        .await
+        .expect("RecordBatch can be collected");
+    let record_batch = record_batch
+        .first()
+        .expect("At least 1 record batch is returned");
+    assert_eq!(record_batch.num_rows(), 1);
+
+    let ids = record_batch.columns()[0].as_list::<i32>().value(0);
+    let ids = ids.as_string::<i32>();
+    assert_eq!(ids.len(), 2);
+    assert_eq!(ids.value(0), "5ea5a3ac-07a0-4d4d-b201-faff68d8356c");
+    assert_eq!(ids.value(1), "4c2a8f0e-2f4b-4a31-9c56-0ab1e0b2e4a1");
+
+    let dates = record_batch.columns()[1].as_list::<i32>().value(0);
+    let dates = dates.as_primitive::<Date32Type>();
+    assert_eq!(dates.value(0), 19_479);
+    assert!(dates.is_null(1));
+
+    let timestamps = record_batch.columns()[2].as_list::<i32>().value(0);
+    assert_eq!(
+        timestamps
+            .as_primitive::<TimestampMillisecondType>()
+            .value(0),
+        1_683_037_800_000
+    );
+
+    let names = record_batch.columns()[3].as_list::<i32>().value(0);
+    let names = names.as_string::<i32>();
+    assert_eq!(names.len(), 2);
+    assert_eq!(names.value(0), "a");
+    assert_eq!(names.value(1), "b");
+
+    // CHAR(n) elements are blank-padded by Postgres, like scalar BPCHAR values they are trimmed
+    let codes = record_batch.columns()[4].as_list::<i32>().value(0);
+    let codes = codes.as_string::<i32>();
+    assert_eq!(codes.value(0), "x");
+    assert!(codes.is_null(1));
+
+    db_conn
+        .conn
+        .batch_execute(
+            "
+CREATE TYPE mood AS ENUM ('sad', 'happy');
+CREATE TABLE test_enums (current_mood mood);
+INSERT INTO test_enums (current_mood) VALUES ('happy'), ('sad'), (NULL), ('happy');",
+        )
+        .await
+        .expect("enum table is created");
+    let table = SqlTable::new("postgres", &sqltable_pool, "test_enums", None)
+        .await
+        .expect("table can be created");
+    ctx.register_table("test_enums_datafusion", Arc::new(table))
+        .expect("Table should be registered");
+    let record_batch = ctx
+        .sql("SELECT current_mood FROM test_enums_datafusion")
+        .await
+        .expect("DataFrame can be created from query")
+        .collect()
+        .await
+        .expect("RecordBatch can be collected");
+    let record_batch = record_batch
+        .first()
+        .expect("At least 1 record batch is returned");
+    assert_eq!(
+        DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+        *record_batch.schema().fields()[0].data_type()
+    );
+    let moods = record_batch.columns()[0].as_dictionary::<Int32Type>();
+    let moods = moods
+        .downcast_dict::<StringArray>()
+        .expect("string dictionary");
+    assert_eq!(moods.value(0), "happy");
+    assert_eq!(moods.value(1), "sad");
+    assert!(moods.is_null(2));
+
+    db_conn
+        .conn
+        .batch_execute(
+            "
+CREATE TABLE test_money (price money);
+INSERT INTO test_money (price) VALUES ('$12.34'::money), (NULL);",
+        )
+        .await
+        .expect("money table is created");
+    let table = SqlTable::new("postgres", &sqltable_pool, "test_money", None)
+        .await
+        .expect("table can be created");
+    ctx.register_table("test_money_datafusion", Arc::new(table))
+        .expect("Table should be registered");
+    let record_batch = ctx
+        .sql("SELECT price FROM test_money_datafusion")
+        .await
+        .expect("DataFrame can be created from query")
+        .collect()
+        .await
+        .expect("RecordBatch can be collected");
+    let record_batch = record_batch
+        .first()
+        .expect("At least 1 record batch is returned");
+    assert_eq!(
+        DataType::Decimal128(38, 2),
+        *record_batch.schema().fields()[0].data_type()
+    );
+    let prices = record_batch.columns()[0].as_primitive::<Decimal128Type>();
+    assert_eq!(prices.value_as_string(0), "12.34");
+    assert!(prices.is_null(1));
+
+    // `money` and `numeric(10, 2)` are read with the same scale, even when the first numeric value is NULL
+    db_conn
+        .conn
+        .batch_execute(
+            "
+CREATE TABLE test_money_numeric (price money, amount numeric(10, 2));
+INSERT INTO test_money_numeric (price, amount) VALUES ('$12.34'::money, NULL), ('$1.00'::money, 1.5);",
+        )
+        .await
+        .expect("money and numeric table is created");
+    let record_batch = db_conn
+        .query_arrow("SELECT price, amount FROM test_money_numeric", &[])
+        .await
+        .expect("money and numeric are queried")
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("batches are collected")
+        .remove(0);
+    let schema = record_batch.schema();
+    assert_eq!(DataType::Decimal128(38, 2), *schema.field(0).data_type());
+    assert_eq!(DataType::Decimal128(10, 2), *schema.field(1).data_type());
+    let amounts = record_batch.columns()[1].as_primitive::<Decimal128Type>();
+    assert!(amounts.is_null(0));
+    assert_eq!(amounts.value_as_string(1), "1.50");
+
+    db_conn
+        .conn
+        .batch_execute(
+            "
+CREATE TABLE test_network (network cidr, host inet, mac macaddr);
+INSERT INTO test_network (network, host, mac) VALUES ('192.168.0.0/24'::cidr, '192.168.0.1/24'::inet, '08:00:2b:01:02:03'::macaddr), (NULL, NULL, NULL);",
+        )
+        .await
+        .expect("network table is created");
+    let table = SqlTable::new("postgres", &sqltable_pool, "test_network", None)
+        .await
+        .expect("table can be created");
+    ctx.register_table("test_network_datafusion", Arc::new(table))
+        .expect("Table should be registered");
+    let record_batch = ctx
+        .sql("SELECT network, host, mac FROM test_network_datafusion")
+        .await
+        .expect("DataFrame can be created from query")
+        .collect()
+        .await
+        .expect("RecordBatch can be collected");
+    let record_batch = record_batch
+        .first()
+        .expect("At least 1 record batch is returned");
+    for field in record_batch.schema().fields() {
+        assert_eq!(DataType::Utf8, *field.data_type());
+    }
+    let network = record_batch.columns()[0].as_string::<i32>();
+    assert_eq!(network.value(0), "192.168.0.0/24");
+    assert!(network.is_null(1));
+    // cidr rejects host bits to the right of the mask, an inet keeps them
+    assert_eq!(
+        record_batch.columns()[1].as_string::<i32>().value(0),
+        "192.168.0.1/24"
+    );
+    assert_eq!(
+        record_batch.columns()[2].as_string::<i32>().value(0),
+        "08:00:2b:01:02:03"
+    );
+
+    // System catalogs identify rows by OID
+    let rows = db_conn
+        .conn
+        .query(
+            "SELECT oid, typname FROM pg_catalog.pg_type WHERE typname = 'int4'",
+            &[],
+        )
+        .await
+        .expect("pg_type is queried");
+    let record_batch = rows_to_arrow(&rows).expect("rows are converted");
+    assert_eq!(
+        DataType::UInt32,
+        *record_batch.schema().fields()[0].data_type()
+    );
+    assert_eq!(
+        record_batch.columns()[0]
+            .as_primitive::<UInt32Type>()
+            .value(0),
+        23
+    );
+
+    // The UTC offset of timestamptz columns is reported in the session time zone
+    db_conn
+        .conn
+        .batch_execute("SET TIME ZONE 'America/New_York'")
+        .await
+        .expect("time zone is set");
+    let rows = db_conn
+        .conn
+        .query(
+            "SELECT '2024-01-15 12:00:00+00'::timestamptz AS created_at
+            UNION ALL SELECT '2024-07-15 12:00:00+00'::timestamptz",
+            &[],
+        )
+        .await
+        .expect("timestamps are queried");
+    let record_batch = rows_to_arrow_with_options(
+        &rows,
+        &ArrowConversionOptions::new()
+            .with_timestamptz_offset_columns(chrono_tz::America::New_York),
+    )
+    .expect("rows are converted");
+    assert_eq!(
+        "created_at_offset_seconds",
+        record_batch.schema().fields()[1].name()
+    );
+    let offsets = record_batch.columns()[1].as_primitive::<Int32Type>();
+    assert_eq!(offsets.value(0), -5 * 3600);
+    assert_eq!(offsets.value(1), -4 * 3600);
+
+    // Multi-dimensional arrays are read as nested lists
+    db_conn
+        .conn
+        .batch_execute(
+            "CREATE TABLE test_matrix (matrix int[][]);
+            INSERT INTO test_matrix VALUES ('{{1,2,3},{4,5,6}}');",
+        )
+        .await
+        .expect("matrix is inserted");
+    let rows = db_conn
+        .conn
+        .query("SELECT matrix FROM test_matrix", &[])
+        .await
+        .expect("matrix is queried");
+    let record_batch = rows_to_arrow_with_options(
+        &rows,
+        &ArrowConversionOptions::new().with_array_dimensions("matrix", 2),
+    )
+    .expect("rows are converted");
+    let DataType::List(row_field) = record_batch.schema().fields()[0].data_type().clone() else {
+        panic!("matrix is read as a list");
+    };
+    let DataType::List(element_field) = row_field.data_type() else {
+        panic!("matrix rows are read as lists");
+    };
+    assert_eq!(DataType::Int32, *element_field.data_type());
+    let matrix = record_batch.columns()[0].as_list::<i32>().value(0);
+    let matrix_rows = matrix
+        .as_list::<i32>()
+        .iter()
+        .map(|row| {
+            let row = row.expect("matrix row is not null");
+            row.as_primitive::<Int32Type>().values().to_vec()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(matrix_rows, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+
+    // Empty strings of text columns are read as NULL with the pg_empty_string_handling parameter
+    let empty_as_null_pool = common::get_postgres_connection_pool_with_params(&[(
+        "pg_empty_string_handling",
+        "empty_as_null",
+    )])
+    .await?;
+    let record_batch = empty_as_null_pool
+        .connect_direct()
+        .await
+        .expect("connection can be established")
+        .query_arrow("SELECT 'a'::text AS s UNION ALL SELECT ''::text", &[])
+        .await
+        .expect("strings are queried")
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("batches are collected")
+        .remove(0);
+    let strings = record_batch.columns()[0].as_string::<i32>();
+    assert_eq!(strings.iter().collect::<Vec<_>>(), vec![Some("a"), None]);
+
+    // The parallel conversion of a wide result set matches the serial one
+    let columns = (0..50)
+        .map(|i| match i % 5 {
+            0 => format!("(g * {i})::int AS c{i}"),
+            1 => format!("'value_' || g || '_{i}' AS c{i}"),
+            2 => format!("CASE WHEN g % 7 = 0 THEN NULL ELSE g::numeric / 8 END AS c{i}"),
+            3 => format!("(g % 2 = 0) AS c{i}"),
+            _ => format!("TIMESTAMP '2024-01-01' + g * INTERVAL '1 second' AS c{i}"),
+        })
+        .collect::<Vec<_>>();
+    let rows = db_conn
+        .conn
+        .query(
+            &format!(
+                "SELECT {} FROM generate_series(1, 10000) g",
+                columns.join(", ")
+            ),
+            &[],
+        )
+        .await
+        .expect("wide rows are queried");
+    let start = std::time::Instant::now();
+    let serial = rows_to_arrow(&rows).expect("rows are converted");
+    let serial_duration = start.elapsed();
+    let start = std::time::Instant::now();
+    let parallel = rows_to_arrow_parallel(&rows).expect("rows are converted in parallel");
+    let parallel_duration = start.elapsed();
+    tracing::info!(
+        "Converted 50 columns of 10000 rows serially in {serial_duration:?}, in parallel in {parallel_duration:?}"
+    );
+    assert_eq!(serial.num_columns(), 50);
+    assert_eq!(serial, parallel);
+
+    // Both reads of a read-only transaction see the same snapshot, even with a write in between
+    let reader = common::get_postgres_connection_pool()
+        .await?
+        .connect_direct()
+        .await
+        .expect("connection can be established");
+    reader
+        .begin_read_only()
+        .await
+        .expect("transaction should begin");
+    assert_eq!(count_rows(&reader, "test_money").await, 2);
+    db_conn
+        .conn
+        .execute(
+            "INSERT INTO test_money (price) VALUES ('$1.00'::money)",
+            &[],
+        )
+        .await
+        .expect("row is inserted");
+    assert_eq!(count_rows(&reader, "test_money").await, 2);
+    reader.commit().await.expect("transaction should commit");
+    assert_eq!(count_rows(&reader, "test_money").await, 3);
+
+    // Unconstrained numerics get the configured default type in the schema, without reading any row, and in the rows
+    let options = ArrowConversionOptions::new()
+        .with_default_numeric_precision(20)
+        .with_default_numeric_scale(4);
+    let sql = "SELECT n, n::numeric(10, 2) AS d FROM (VALUES (NULL::numeric), (1.23456)) v(n)";
+    let statement = db_conn
+        .conn
+        .prepare(sql)
+        .await
+        .expect("statement is prepared");
+    let schema =
+        columns_to_schema_with_options(statement.columns(), &options).expect("schema is resolved");
+    assert_eq!(*schema.field(0).data_type(), DataType::Decimal128(20, 4));
+    assert_eq!(*schema.field(1).data_type(), DataType::Decimal128(10, 2));
+    let rows = db_conn
+        .conn
+        .query(sql, &[])
+        .await
+        .expect("rows are queried");
+    let record_batch = rows_to_arrow_with_options(&rows, &options).expect("rows are converted");
+    assert_eq!(
+        record_batch.schema().fields()[0].data_type(),
+        schema.field(0).data_type()
+    );
+    assert_eq!(
+        record_batch.columns()[0]
+            .as_primitive::<Decimal128Type>()
+            .value_as_string(1),
+        "1.2345"
+    );
+
+    // The Postgres type of each column is attached to the metadata of its field, both in the schema and in the rows
+    let options = ArrowConversionOptions::new().with_type_metadata(true);
+    let sql = "SELECT 'a'::varchar(64) AS name, 1.5::numeric(10, 2) AS price";
+    let statement = db_conn
+        .conn
+        .prepare(sql)
+        .await
+        .expect("statement is prepared");
+    let schema =
+        columns_to_schema_with_options(statement.columns(), &options).expect("schema is resolved");
+    let rows = db_conn
+        .conn
+        .query(sql, &[])
+        .await
+        .expect("rows are queried");
+    let record_batch = rows_to_arrow_with_options(&rows, &options).expect("rows are converted");
+    for schema in [schema, record_batch.schema()] {
+        let name = schema.field(0).metadata();
+        assert_eq!(name[PG_TYPE_METADATA_KEY], "varchar");
+        assert_eq!(name[PG_OID_METADATA_KEY], "1043");
+        assert_eq!(name[PG_TYPMOD_METADATA_KEY], "68");
+        let price = schema.field(1).metadata();
+        assert_eq!(price[PG_TYPE_METADATA_KEY], "numeric");
+        assert_eq!(price[PG_OID_METADATA_KEY], "1700");
+        assert_eq!(price[PG_TYPMOD_METADATA_KEY], "655366");
+    }
+
+    // Notices raised by a function are published to the subscribers of the pool
+    let notice_pool = common::get_postgres_connection_pool().await?;
+    let mut notices = notice_pool.subscribe_notices();
+    let notice_conn = notice_pool
+        .connect_direct()
+        .await
+        .expect("connection can be established");
+    notice_conn
+        .conn
+        .batch_execute(
+            "CREATE FUNCTION raise_notices() RETURNS void LANGUAGE plpgsql AS $$
+BEGIN
+    RAISE NOTICE 'refreshing %', 'test_money';
+    RAISE WARNING 'almost out of space';
+END $$;
+SELECT raise_notices();",
+        )
+        .await
+        .expect("notices are raised");
+    let notice = notices.recv().await.expect("notice is captured");
+    assert_eq!(notice.severity(), "NOTICE");
+    assert_eq!(notice.message(), "refreshing test_money");
+    let warning = notices.recv().await.expect("warning is captured");
+    assert_eq!(warning.severity(), "WARNING");
+    assert_eq!(warning.message(), "almost out of space");
+
     running_container.remove().await?;
 
     Ok(())
@@ -107,4 +107,8 @@ pub struct ColumnEmbeddingConfig {
 
     #[serde(rename = "column_pk", skip_serializing_if = "Option::is_none")]
     pub primary_keys: Option<Vec<String>>,
+
+    /// The name of the column holding the embedding vectors of `column`, `{column}_embedding` by default.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub vector_column: Option<String>,
 }
@@ -15,12 +15,16 @@ limitations under the License.
 */
 
 use datafusion::{
-    logical_expr::{Cast, Expr},
+    logical_expr::{expr::ScalarFunction, Cast, Expr},
     scalar::ScalarValue,
 };
 
 pub const SECONDS_IN_DAY: i32 = 86_400;
 
+/// The name of the function that filters a `SQLite` full-text (FTS5) table, written as `fts_match(column, query)`
+/// and translated to `column MATCH query`.
+pub const FTS_MATCH: &str = "fts_match";
+
 #[derive(Debug, snafu::Snafu)]
 pub enum Error {
     #[snafu(display("Expression not supported {expr}"))]
@@ -42,10 +46,32 @@ pub enum Engine {
 }
 
 pub fn to_sql_with_engine(expr: &Expr, engine: Option<Engine>) -> Result<String> {
+    expr_to_sql(expr, engine, None)
+}
+
+/// Like `to_sql_with_engine`, but writes literal values as `?` placeholders and returns the values to bind to them,
+/// in placeholder order, so they are never quoted or escaped into the SQL.
+///
+/// Dates, timestamps and `NULL` are still written inline, as are `Float32` values, which then compare equal to the
+/// value they were inserted as rather than to their widened `f64` representation.
+pub fn to_parameterized_sql_with_engine(
+    expr: &Expr,
+    engine: Option<Engine>,
+) -> Result<(String, Vec<ScalarValue>)> {
+    let mut params = Vec::new();
+    let sql = expr_to_sql(expr, engine, Some(&mut params))?;
+    Ok((sql, params))
+}
+
+fn expr_to_sql(
+    expr: &Expr,
+    engine: Option<Engine>,
+    mut params: Option<&mut Vec<ScalarValue>>,
+) -> Result<String> {
     match expr {
         Expr::BinaryExpr(binary_expr) => {
-            let left = to_sql_with_engine(&binary_expr.left, engine)?;
-            let right = to_sql_with_engine(&binary_expr.right, engine)?;
+            let left = expr_to_sql(&binary_expr.left, engine, params.as_deref_mut())?;
+            let right = expr_to_sql(&binary_expr.right, engine, params.as_deref_mut())?;
 
             if let Some(Engine::DuckDB) = engine {
                 // TODO: DuckDB doesn't support comparison between timestamp_s /timestamp_ms with timestampz as of v1
@@ -64,7 +90,27 @@ pub fn to_sql_with_engine(expr: &Expr, engine: Option<Engine>) -> Result<String>
             Some(Engine::Spark | Engine::ODBC) => Ok(format!("{name}")),
             _ => Ok(format!("\"{name}\"")),
         },
-        Expr::Cast(cast) => handle_cast(cast, engine, expr),
+        Expr::Cast(cast) => handle_cast(cast, engine, expr, params),
+        Expr::ScalarFunction(ScalarFunction { func, args }) if func.name() == FTS_MATCH => {
+            match (engine, args.as_slice()) {
+                (Some(Engine::SQLite), [column @ Expr::Column(_), query]) => Ok(format!(
+                    "{} MATCH {}",
+                    expr_to_sql(column, engine, params.as_deref_mut())?,
+                    expr_to_sql(query, engine, params)?
+                )),
+                _ => EngineNotSupportedForExpressionSnafu {
+                    engine: engine.map_or_else(|| "default".to_string(), |e| format!("{e:?}")),
+                    expr: format!("{expr}"),
+                }
+                .fail(),
+            }
+        }
+        Expr::Literal(value) if is_bindable(value) && params.is_some() => {
+            if let Some(params) = params {
+                params.push(value.clone());
+            }
+            Ok("?".to_string())
+        }
         Expr::Literal(value) => match value {
             ScalarValue::Date32(Some(value)) => match engine {
                 Some(Engine::SQLite) => {
@@ -129,21 +175,44 @@ pub fn to_sql(expr: &Expr) -> Result<String> {
     to_sql_with_engine(expr, None)
 }
 
-fn handle_cast(cast: &Cast, engine: Option<Engine>, expr: &Expr) -> Result<String> {
+fn is_bindable(value: &ScalarValue) -> bool {
+    matches!(
+        value,
+        ScalarValue::Boolean(Some(_))
+            | ScalarValue::Int8(Some(_))
+            | ScalarValue::Int16(Some(_))
+            | ScalarValue::Int32(Some(_))
+            | ScalarValue::Int64(Some(_))
+            | ScalarValue::UInt8(Some(_))
+            | ScalarValue::UInt16(Some(_))
+            | ScalarValue::UInt32(Some(_))
+            | ScalarValue::UInt64(Some(_))
+            | ScalarValue::Float64(Some(_))
+            | ScalarValue::Utf8(Some(_))
+            | ScalarValue::LargeUtf8(Some(_))
+    )
+}
+
+fn handle_cast(
+    cast: &Cast,
+    engine: Option<Engine>,
+    expr: &Expr,
+    params: Option<&mut Vec<ScalarValue>>,
+) -> Result<String> {
     match cast.data_type {
         arrow::datatypes::DataType::Timestamp(_, Some(_) | None) => match engine {
             Some(Engine::ODBC) => Ok(format!(
                 "CAST({} AS TIMESTAMP)",
-                to_sql_with_engine(&cast.expr, engine)?,
+                expr_to_sql(&cast.expr, engine, params)?,
             )),
             // This needs to match the timestamp conversion below
             Some(Engine::DuckDB) => Ok(format!(
                 "TO_TIMESTAMP(EPOCH(CAST({} AS TIMESTAMP)))",
-                to_sql_with_engine(&cast.expr, engine)?,
+                expr_to_sql(&cast.expr, engine, params)?,
             )),
             Some(Engine::SQLite) => Ok(format!(
                 "datetime({}, 'subsec', 'utc')",
-                to_sql_with_engine(&cast.expr, engine)?,
+                expr_to_sql(&cast.expr, engine, params)?,
             )),
             Some(Engine::Spark) => EngineNotSupportedForExpressionSnafu {
                 engine: "Spark".to_string(),
@@ -152,7 +221,7 @@ fn handle_cast(cast: &Cast, engine: Option<Engine>, expr: &Expr) -> Result<Strin
             .fail()?,
             _ => Ok(format!(
                 "CAST({} AS TIMESTAMPTZ)",
-                to_sql_with_engine(&cast.expr, engine)?,
+                expr_to_sql(&cast.expr, engine, params)?,
             )),
         },
         _ => Err(Error::UnsupportedFilterExpr {
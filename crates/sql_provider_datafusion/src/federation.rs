@@ -6,7 +6,7 @@ use futures::TryStreamExt;
 use snafu::prelude::*;
 use std::sync::Arc;
 
-use crate::{get_stream, to_execution_error, SqlTable, UnableToGetSchemaSnafu};
+use crate::{get_stream, to_execution_error, SqlTable, UnableToGetSchemaSnafu, SQL_TRACING_TARGET};
 use datafusion::{
     arrow::datatypes::SchemaRef,
     error::{DataFusionError, Result as DataFusionResult},
@@ -69,6 +69,7 @@ impl<T, P> SQLExecutor for SqlTable<T, P> {
         query: &str,
         schema: SchemaRef,
     ) -> DataFusionResult<SendableRecordBatchStream> {
+        tracing::debug!(target: SQL_TRACING_TARGET, "{} sql: {query}", self.name);
         let fut = get_stream(Arc::clone(&self.pool), query.to_string());
 
         let stream = futures::stream::once(fut).try_flatten();
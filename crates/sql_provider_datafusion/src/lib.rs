@@ -16,6 +16,8 @@ limitations under the License.
 
 #![allow(clippy::missing_errors_doc)]
 
+use arrow::compute::CastOptions;
+use arrow_tools::record_batch::try_cast_to_with_options;
 use async_trait::async_trait;
 use datafusion::sql::unparser::dialect::Dialect;
 use db_connection_pool::dbconnection::{get_schema, query_arrow};
@@ -59,6 +61,11 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// The tracing target the SQL pushed down to the backend of a [`SqlTable`] is logged to, at debug level. Enabling
+/// it on its own, i.e. with `RUST_LOG=sql_provider_datafusion::sql=debug`, shows the queries run against the backend
+/// without the rest of the debug logs.
+pub const SQL_TRACING_TARGET: &str = "sql_provider_datafusion::sql";
+
 pub struct SqlTable<T: 'static, P: 'static> {
     name: &'static str,
     pool: Arc<dyn DbConnectionPool<T, P> + Send + Sync>,
@@ -346,12 +353,27 @@ impl<T: 'static, P: 'static> ExecutionPlan for SqlExec<T, P> {
         _context: Arc<TaskContext>,
     ) -> DataFusionResult<SendableRecordBatchStream> {
         let sql = self.sql().map_err(to_execution_error)?;
-        tracing::debug!("SqlExec sql: {sql}");
+        tracing::debug!(target: SQL_TRACING_TARGET, "SqlExec sql: {sql}");
 
         let fut = get_stream(Arc::clone(&self.pool), sql);
 
-        let stream = futures::stream::once(fut).try_flatten();
+        // The types of the rows can differ from the schema of the table, i.e. the scale of an unconstrained Postgres
+        // `numeric` column is picked from its rows, so every batch is cast to the schema the plan reports. Values that
+        // don't fit the schema fail the query rather than being read as NULL.
         let schema = Arc::clone(&self.schema());
+        let cast_schema = Arc::clone(&schema);
+        let cast_options = CastOptions {
+            safe: false,
+            ..CastOptions::default()
+        };
+        let stream = futures::stream::once(fut)
+            .try_flatten()
+            .and_then(move |batch| {
+                futures::future::ready(
+                    try_cast_to_with_options(batch, Arc::clone(&cast_schema), &cast_options)
+                        .map_err(to_execution_error),
+                )
+            });
         Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
     }
 }
@@ -376,14 +398,20 @@ pub fn to_execution_error(
 mod tests {
     use std::{error::Error, sync::Arc};
 
+    use arrow::array::{AsArray, Decimal128Array, RecordBatch};
+    use arrow::datatypes::{DataType, Decimal128Type, Field, Schema};
     use datafusion::execution::context::SessionContext;
     use datafusion::sql::TableReference;
-    use db_connection_pool::dbconnection::duckdbconn::DuckDbConnection;
+    use db_connection_pool::dbconnection::{
+        duckdbconn::DuckDbConnection, mockconn::MockDbConnection,
+    };
+    use db_connection_pool::mockpool::MockPool;
     use db_connection_pool::{duckdbpool::DuckDbConnectionPool, DbConnectionPool};
     use duckdb::{AccessMode, DuckdbConnectionManager, ToSql};
     use tracing::{level_filters::LevelFilter, subscriber::DefaultGuard, Dispatch};
+    use tracing_subscriber::EnvFilter;
 
-    use crate::SqlTable;
+    use crate::{SqlTable, SQL_TRACING_TARGET};
 
     fn setup_tracing() -> DefaultGuard {
         let subscriber: tracing_subscriber::FmtSubscriber = tracing_subscriber::fmt()
@@ -394,6 +422,30 @@ mod tests {
         tracing::dispatcher::set_default(&dispatch)
     }
 
+    /// A log writer keeping everything written to it, shared between its clones.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().expect("logs are readable")).into_owned()
+        }
+    }
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0
+                .lock()
+                .expect("logs are writable")
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
     fn test_references() {
         let table_ref = TableReference::bare("test");
@@ -451,4 +503,120 @@ mod tests {
         drop(t);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_pushed_down_sql_is_logged() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new(format!("{SQL_TRACING_TARGET}=debug")))
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let t = tracing::subscriber::set_default(subscriber);
+
+        let ctx = SessionContext::new();
+        let pool: Arc<
+            dyn DbConnectionPool<r2d2::PooledConnection<DuckdbConnectionManager>, &dyn ToSql>
+                + Send
+                + Sync,
+        > = Arc::new(DuckDbConnectionPool::new_memory(&AccessMode::ReadWrite)?);
+        let conn = pool.connect().await?;
+        let db_conn = conn
+            .as_any()
+            .downcast_ref::<DuckDbConnection>()
+            .expect("Unable to downcast to DuckDbConnection");
+        db_conn.conn.execute_batch(
+            "CREATE TABLE test (a INTEGER, b VARCHAR); INSERT INTO test VALUES (3, 'bar');",
+        )?;
+        let duckdb_table = SqlTable::new("duckdb", &pool, "test", None).await?;
+        ctx.register_table("test_datafusion", Arc::new(duckdb_table))?;
+        ctx.sql("SELECT a FROM test_datafusion WHERE b = 'bar'")
+            .await?
+            .collect()
+            .await?;
+        drop(t);
+
+        let logs = logs.contents();
+        assert!(
+            logs.contains(r#"SqlExec sql: SELECT "a" FROM test WHERE "b" = 'bar'"#),
+            "{logs}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_cast_to_table_schema() -> Result<(), Box<dyn Error + Send + Sync>> {
+        // The rows have the scale of their first value, while the table declares the default NUMERIC scale
+        let row_schema = Arc::new(Schema::new(vec![Field::new(
+            "amount",
+            DataType::Decimal128(38, 2),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&row_schema),
+            vec![Arc::new(
+                Decimal128Array::from(vec![Some(150), None]).with_precision_and_scale(38, 2)?,
+            )],
+        )?;
+        let table_schema = Arc::new(Schema::new(vec![Field::new(
+            "amount",
+            DataType::Decimal128(38, 10),
+            true,
+        )]));
+
+        let pool: Arc<dyn DbConnectionPool<(), ()> + Send + Sync> = Arc::new(MockPool::new(
+            MockDbConnection::default().with_query_result(row_schema, vec![batch]),
+        ));
+        let table = SqlTable::new_with_schema("mock", &pool, table_schema, "test", None);
+
+        let ctx = SessionContext::new();
+        ctx.register_table("test", Arc::new(table))?;
+        let batches = ctx.sql("SELECT amount FROM test").await?.collect().await?;
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            *batches[0].schema().field(0).data_type(),
+            DataType::Decimal128(38, 10)
+        );
+        let amounts = batches[0].column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(amounts.value(0), 15_000_000_000);
+        assert!(amounts.is_null(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batches_that_overflow_table_schema_fail(
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // 30 integer digits don't fit in the 28 that the default NUMERIC scale leaves
+        let row_schema = Arc::new(Schema::new(vec![Field::new(
+            "amount",
+            DataType::Decimal128(38, 2),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&row_schema),
+            vec![Arc::new(
+                Decimal128Array::from(vec![Some(10_i128.pow(31))])
+                    .with_precision_and_scale(38, 2)?,
+            )],
+        )?;
+        let table_schema = Arc::new(Schema::new(vec![Field::new(
+            "amount",
+            DataType::Decimal128(38, 10),
+            true,
+        )]));
+
+        let pool: Arc<dyn DbConnectionPool<(), ()> + Send + Sync> = Arc::new(MockPool::new(
+            MockDbConnection::default().with_query_result(row_schema, vec![batch]),
+        ));
+        let table = SqlTable::new_with_schema("mock", &pool, table_schema, "test", None);
+
+        let ctx = SessionContext::new();
+        ctx.register_table("test", Arc::new(table))?;
+        let result = ctx.sql("SELECT amount FROM test").await?.collect().await;
+
+        assert!(result.is_err(), "the value should not be read as NULL");
+        Ok(())
+    }
 }
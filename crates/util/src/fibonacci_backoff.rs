@@ -17,6 +17,7 @@ limitations under the License.
 use std::time::Duration;
 
 use backoff::backoff::Backoff;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 // Fibonacci-based backoff delay intervals capped at 5 mins
 const BACKOFF_INTERVALS_MS: [u64; 14] = [
@@ -24,11 +25,17 @@ const BACKOFF_INTERVALS_MS: [u64; 14] = [
     300_000,
 ];
 
+// With jitter enabled each interval is scaled by a random factor in this range ("equal jitter")
+const JITTER_MIN_FACTOR: f64 = 0.5;
+const JITTER_MAX_FACTOR: f64 = 1.0;
+
 #[derive(Debug)]
 pub struct FibonacciBackoff {
     num_retries: usize,
     pub randomization_factor: f64,
     pub max_retries: Option<usize>,
+    pub jitter: bool,
+    rng: StdRng,
 }
 
 impl Default for FibonacciBackoff {
@@ -37,6 +44,8 @@ impl Default for FibonacciBackoff {
             num_retries: 0,
             randomization_factor: 0.3,
             max_retries: None,
+            jitter: false,
+            rng: StdRng::from_entropy(),
         }
     }
 }
@@ -67,6 +76,11 @@ impl Backoff for FibonacciBackoff {
             interval,
         );
 
+        if self.jitter {
+            let factor = self.rng.gen_range(JITTER_MIN_FACTOR..=JITTER_MAX_FACTOR);
+            return Some(apply_jitter(factor, randomized_interval));
+        }
+
         Some(randomized_interval)
     }
 }
@@ -89,10 +103,16 @@ fn get_random_value_from_interval(
     nanos_to_duration(nanos)
 }
 
+fn apply_jitter(factor: f64, interval: Duration) -> Duration {
+    nanos_to_duration(duration_to_nanos(interval) * factor)
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub struct FibonacciBackoffBuilder {
     randomization_factor: f64,
     max_retries: Option<usize>,
+    jitter: bool,
+    seed: Option<u64>,
 }
 
 impl FibonacciBackoffBuilder {
@@ -101,6 +121,8 @@ impl FibonacciBackoffBuilder {
         Self {
             randomization_factor: 0.3,
             max_retries: None,
+            jitter: false,
+            seed: None,
         }
     }
 
@@ -116,11 +138,33 @@ impl FibonacciBackoffBuilder {
         self
     }
 
+    /// Scales every interval by a random factor between 0.5 and 1.0 so that many clients
+    /// retrying at the same time don't hit the backend in lockstep.
+    #[must_use]
+    pub fn with_jitter(mut self, value: bool) -> Self {
+        self.jitter = value;
+        self
+    }
+
+    /// Seeds the random generator used for jitter, making the sequence of intervals reproducible.
+    #[must_use]
+    pub fn seed(mut self, value: u64) -> Self {
+        self.seed = Some(value);
+        self
+    }
+
     #[must_use]
     pub fn build(self) -> FibonacciBackoff {
+        let rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
         FibonacciBackoff {
             randomization_factor: self.randomization_factor,
             max_retries: self.max_retries,
+            jitter: self.jitter,
+            rng,
             ..FibonacciBackoff::default()
         }
     }
@@ -210,4 +254,37 @@ mod tests {
             assert!(backoff.next_backoff().is_some());
         }
     }
+
+    #[test]
+    fn test_jitter_within_range() {
+        let mut backoff = FibonacciBackoffBuilder::new()
+            .randomization_factor(0.0)
+            .with_jitter(true)
+            .build();
+
+        for interval_ms in &BACKOFF_INTERVALS_MS[1..] {
+            let interval = Duration::from_millis(*interval_ms);
+            let result = backoff.next_backoff().expect("backoff should be returned");
+
+            assert!(
+                result >= interval / 2 && result <= interval,
+                "Jittered interval out of range for {interval:?}: got {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_jitter_differs_across_seeds() {
+        let intervals = |seed: u64| {
+            let mut backoff = FibonacciBackoffBuilder::new()
+                .randomization_factor(0.0)
+                .with_jitter(true)
+                .seed(seed)
+                .build();
+            (0..10).map(|_| backoff.next_backoff()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(intervals(1), intervals(1));
+        assert_ne!(intervals(1), intervals(2));
+    }
 }
@@ -25,15 +25,25 @@ pub mod fibonacci_backoff;
 pub use backoff::future::retry;
 pub use backoff::Error as RetryError;
 
+#[must_use]
+pub fn human_readable_bytes(num: usize) -> String {
+    if num < 1 {
+        return format!("{num} B");
+    }
+    let (value, unit) = human_readable_bytes_parts(num);
+    format!("{value:.2} {unit}")
+}
+
+/// Returns `num` scaled to the largest fitting binary unit, along with the unit label.
 #[allow(clippy::cast_precision_loss)]
 #[allow(clippy::cast_sign_loss)]
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::cast_possible_wrap)]
 #[must_use]
-pub fn human_readable_bytes(num: usize) -> String {
+pub fn human_readable_bytes_parts(num: usize) -> (f64, &'static str) {
     let units = ["B", "kiB", "MiB", "GiB"];
     if num < 1 {
-        return format!("{num} B");
+        return (0.0, units[0]);
     }
     let delimiter = 1024_f64;
     let num = num as f64;
@@ -41,8 +51,7 @@ pub fn human_readable_bytes(num: usize) -> String {
         (num.ln() / delimiter.ln()).floor() as usize,
         units.len() - 1,
     );
-    let unit = units[exponent];
-    format!("{:.2} {unit}", num / delimiter.powi(exponent as i32))
+    (num / delimiter.powi(exponent as i32), units[exponent])
 }
 
 #[must_use]
@@ -101,6 +110,19 @@ mod tests {
         assert_eq!(super::human_readable_bytes(1024 * 1024 * 1024), "1.00 GiB");
     }
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_human_readable_bytes_parts() {
+        assert_eq!(super::human_readable_bytes_parts(0), (0.0, "B"));
+        assert_eq!(super::human_readable_bytes_parts(512), (512.0, "B"));
+        assert_eq!(super::human_readable_bytes_parts(1536), (1.5, "kiB"));
+        assert_eq!(super::human_readable_bytes_parts(1024 * 1024), (1.0, "MiB"));
+        assert_eq!(
+            super::human_readable_bytes_parts(2 * 1024 * 1024 * 1024),
+            (2.0, "GiB")
+        );
+    }
+
     #[test]
     fn test_print_number() {
         assert_eq!(super::pretty_print_number(123), "123");